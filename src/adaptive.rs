@@ -0,0 +1,203 @@
+//! Adaptive per-key capacity.
+//!
+//! When enabled, each key's effective capacity drifts within configured
+//! bounds based on how often it has recently been throttled: well-behaved
+//! clients earn a larger burst, abusive ones lose theirs.
+//!
+//! To avoid oscillation the adjustment is itself rate limited: a key is
+//! re-evaluated at most once per interval, only once it has made enough
+//! requests since the last evaluation, and each step moves capacity by a
+//! bounded fraction.
+
+use crate::error::ThrottlerError;
+use crate::metrics::MetricsCollector;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Throttled fraction at or below which a key's capacity grows
+pub const GROW_BELOW_THROTTLE_RATIO: f64 = 0.05;
+
+/// Throttled fraction at or above which a key's capacity shrinks
+pub const SHRINK_ABOVE_THROTTLE_RATIO: f64 = 0.25;
+
+/// Fraction of the current capacity added or removed per adjustment
+pub const ADJUST_STEP: f64 = 0.1;
+
+/// Requests a key must make between adjustments before its ratio is trusted
+pub const MIN_SAMPLES: u64 = 10;
+
+/// Where a key stood at its last adjustment
+#[derive(Debug, Clone)]
+struct KeyState {
+    /// Current effective capacity
+    capacity: u64,
+    /// When capacity was last evaluated (ms since UNIX epoch)
+    evaluated_at: u64,
+    /// Request counters at the last evaluation, to diff against
+    total_requests: u64,
+    throttled_requests: u64,
+}
+
+/// Tracks an effective capacity per key, adjusted from its metrics.
+#[derive(Debug)]
+pub struct AdaptiveCapacity {
+    /// Capacity a key starts at, clamped to the bounds
+    initial: u64,
+    min: u64,
+    max: u64,
+    /// Minimum time between adjustments of one key
+    interval_ms: u64,
+    keys: Mutex<HashMap<String, KeyState>>,
+}
+
+impl AdaptiveCapacity {
+    /// Creates a tracker with capacities bounded to `min..=max`, adjusted at
+    /// most once per `interval_ms` per key
+    pub fn new(initial: u64, min: u64, max: u64, interval_ms: u64) -> Self {
+        Self {
+            initial: initial.clamp(min, max),
+            min,
+            max,
+            interval_ms,
+            keys: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The key's effective capacity, re-evaluated from `metrics` if it is due
+    pub async fn capacity_for(&self, key: &str, metrics: &MetricsCollector) -> Result<u64, ThrottlerError> {
+        self.capacity_at(key, metrics, now_ms()).await
+    }
+
+    async fn capacity_at(
+        &self,
+        key: &str,
+        metrics: &MetricsCollector,
+        now: u64,
+    ) -> Result<u64, ThrottlerError> {
+        let counters = metrics.get_client_metrics(key).await
+            .map(|m| (m.total_requests, m.throttled_requests))
+            .unwrap_or((0, 0));
+
+        let mut keys = self.keys.lock()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire lock on adaptive capacities".to_string()))?;
+        let state = keys.entry(key.to_string()).or_insert_with(|| KeyState {
+            capacity: self.initial,
+            evaluated_at: now,
+            total_requests: counters.0,
+            throttled_requests: counters.1,
+        });
+
+        let requests = counters.0.saturating_sub(state.total_requests);
+        if now.saturating_sub(state.evaluated_at) < self.interval_ms || requests < MIN_SAMPLES {
+            return Ok(state.capacity);
+        }
+
+        let throttled = counters.1.saturating_sub(state.throttled_requests);
+        let ratio = throttled as f64 / requests as f64;
+        let step = ((state.capacity as f64 * ADJUST_STEP).ceil() as u64).max(1);
+        if ratio <= GROW_BELOW_THROTTLE_RATIO {
+            state.capacity = state.capacity.saturating_add(step).min(self.max);
+        } else if ratio >= SHRINK_ABOVE_THROTTLE_RATIO {
+            state.capacity = state.capacity.saturating_sub(step).max(self.min);
+        }
+
+        state.evaluated_at = now;
+        state.total_requests = counters.0;
+        state.throttled_requests = counters.1;
+
+        Ok(state.capacity)
+    }
+
+    /// Forget a key's learned capacity, e.g. when its bucket is reset
+    pub fn forget(&self, key: &str) -> Result<(), ThrottlerError> {
+        self.keys.lock()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire lock on adaptive capacities".to_string()))?
+            .remove(key);
+        Ok(())
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn record(metrics: &MetricsCollector, key: &str, allowed: u64, throttled: u64) {
+        for _ in 0..allowed {
+            metrics.record_request(key, true).await;
+        }
+        for _ in 0..throttled {
+            metrics.record_request(key, false).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_capacity_grows_for_low_throttle_client() {
+        let adaptive = AdaptiveCapacity::new(100, 50, 150, 1_000);
+        let metrics = MetricsCollector::new();
+        assert_eq!(adaptive.capacity_at("good", &metrics, 0).await.unwrap(), 100);
+
+        let mut now = 0;
+        let mut last = 100;
+        for _ in 0..10 {
+            record(&metrics, "good", 20, 0).await;
+            now += 1_000;
+            let capacity = adaptive.capacity_at("good", &metrics, now).await.unwrap();
+            assert!(capacity >= last);
+            last = capacity;
+        }
+        assert_eq!(last, 150);
+    }
+
+    #[tokio::test]
+    async fn test_capacity_shrinks_for_high_throttle_client() {
+        let adaptive = AdaptiveCapacity::new(100, 50, 150, 1_000);
+        let metrics = MetricsCollector::new();
+        adaptive.capacity_at("abuser", &metrics, 0).await.unwrap();
+
+        record(&metrics, "abuser", 10, 10).await;
+        assert_eq!(adaptive.capacity_at("abuser", &metrics, 1_000).await.unwrap(), 90);
+
+        for i in 2..20 {
+            record(&metrics, "abuser", 10, 10).await;
+            adaptive.capacity_at("abuser", &metrics, i * 1_000).await.unwrap();
+        }
+        assert_eq!(adaptive.capacity_at("abuser", &metrics, 20_000).await.unwrap(), 50);
+    }
+
+    #[tokio::test]
+    async fn test_adjustment_is_rate_limited() {
+        let adaptive = AdaptiveCapacity::new(100, 50, 150, 1_000);
+        let metrics = MetricsCollector::new();
+        adaptive.capacity_at("key", &metrics, 0).await.unwrap();
+
+        // Inside the interval nothing moves, however much traffic arrives
+        record(&metrics, "key", 100, 0).await;
+        assert_eq!(adaptive.capacity_at("key", &metrics, 999).await.unwrap(), 100);
+
+        // One bounded step once the interval has passed
+        assert_eq!(adaptive.capacity_at("key", &metrics, 1_000).await.unwrap(), 110);
+        assert_eq!(adaptive.capacity_at("key", &metrics, 1_500).await.unwrap(), 110);
+
+        // Too few requests since the last step to judge
+        record(&metrics, "key", MIN_SAMPLES - 1, 0).await;
+        assert_eq!(adaptive.capacity_at("key", &metrics, 5_000).await.unwrap(), 110);
+    }
+
+    #[tokio::test]
+    async fn test_middling_ratio_holds_capacity() {
+        let adaptive = AdaptiveCapacity::new(100, 50, 150, 1_000);
+        let metrics = MetricsCollector::new();
+        adaptive.capacity_at("key", &metrics, 0).await.unwrap();
+
+        record(&metrics, "key", 90, 10).await;
+        assert_eq!(adaptive.capacity_at("key", &metrics, 1_000).await.unwrap(), 100);
+    }
+}
@@ -1,10 +1,12 @@
 //! Rate limiting algorithms module
 //!
 //! This module contains different rate limiting algorithm implementations
-//! that can be used by the throttler service.
+//! that can be used by the throttler service. Custom implementations of
+//! [`RateLimitAlgorithm`] are plugged in through the [`registry`].
 
 // Note: sliding_window requires Redis async features not currently configured
 // pub mod sliding_window;
+pub mod registry;
 
 use crate::error::ThrottlerError;
 use serde::{Deserialize, Serialize};
@@ -12,16 +14,46 @@ use std::time::Duration;
 
 // Re-export the token bucket from the crate root
 pub use crate::token_bucket::TokenBucket;
+pub use registry::{AlgorithmFactory, RateLimitAlgorithmRegistry};
 
 /// Configuration for rate limiting algorithms
+///
+/// For the token bucket, `window_size` is how long a bucket may sit idle
+/// before it expires; it must cover the time to refill `capacity`, or an
+/// expired bucket would come back full sooner than refill allows.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AlgorithmConfig {
     pub capacity: u64,
+    /// Tokens added per second
     pub refill_rate: u64,
     #[serde(with = "humantime_serde")]
     pub window_size: Duration,
 }
 
+impl AlgorithmConfig {
+    /// Check that the window is long enough for an empty bucket to refill
+    pub fn validate(&self) -> Result<(), ThrottlerError> {
+        if self.refill_rate == 0 || self.window_size.is_zero() {
+            return Err(ThrottlerError::ValidationError(
+                "Refill rate and window size must be greater than 0".to_string()
+            ));
+        }
+        if self.window_size < self.refill_duration() {
+            return Err(ThrottlerError::ValidationError(format!(
+                "Window size must be at least {:?}, the time to refill the capacity",
+                self.refill_duration()
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Time for an empty bucket to refill to `capacity`
+    pub fn refill_duration(&self) -> Duration {
+        Duration::from_secs_f64(self.capacity as f64 / self.refill_rate.max(1) as f64)
+    }
+}
+
 impl Default for AlgorithmConfig {
     fn default() -> Self {
         Self {
@@ -51,3 +83,21 @@ pub struct AlgorithmState {
     pub last_refill: u64,
     pub requests_in_window: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_must_cover_refill_time() {
+        let config = AlgorithmConfig::default();
+        assert_eq!(config.refill_duration(), Duration::from_secs(10));
+        assert!(config.validate().is_ok());
+
+        let short = AlgorithmConfig { window_size: Duration::from_secs(5), ..config.clone() };
+        assert!(short.validate().is_err());
+
+        let exact = AlgorithmConfig { window_size: Duration::from_secs(10), ..config };
+        assert!(exact.validate().is_ok());
+    }
+}
@@ -0,0 +1,201 @@
+//! # Algorithm Registry
+//!
+//! The token bucket is built in. Other algorithms can be plugged in without
+//! forking: register a named factory for a [`RateLimitAlgorithm`] at
+//! startup, before any limiter is created, and select it by setting
+//! `Config::algorithm` (`RATE_LIMIT_ALGORITHM`) to that name.
+//!
+//! ```rust
+//! use throttler::algorithms::{AlgorithmState, RateLimitAlgorithm, RateLimitAlgorithmRegistry};
+//! use throttler::{Config, RateLimiter, ThrottlerError};
+//!
+//! /// Lets every request through
+//! struct AllowAll;
+//!
+//! impl RateLimitAlgorithm for AllowAll {
+//!     fn is_allowed(&self, _key: &str, _tokens: u64) -> Result<bool, ThrottlerError> {
+//!         Ok(true)
+//!     }
+//!
+//!     fn get_state(&self, _key: &str) -> Result<AlgorithmState, ThrottlerError> {
+//!         Ok(AlgorithmState { available_tokens: u64::MAX, last_refill: 0, requests_in_window: 0 })
+//!     }
+//!
+//!     fn reset(&self, _key: &str) -> Result<(), ThrottlerError> {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! RateLimitAlgorithmRegistry::register("allow_all", |_config| Ok(Box::new(AllowAll))).unwrap();
+//!
+//! let config = Config { algorithm: "allow_all".to_string(), ..Config::default() };
+//! let limiter = RateLimiter::new(config).unwrap();
+//! assert!(limiter.check_rate_limit("any-key").unwrap().0);
+//! ```
+//!
+//! ## Scope
+//!
+//! A registered algorithm replaces the token bucket for every key of the
+//! limiter it was selected for, and keeps its own state: it is handed the
+//! [`Config`] once, and is not given per-key rules' capacity or refill
+//! rate, nor the Redis store. The global limit, quotas and route limits
+//! still apply around it.
+
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::config::Config;
+use crate::error::ThrottlerError;
+
+use super::RateLimitAlgorithm;
+
+/// Name of the built-in token bucket, which cannot be registered over
+pub const TOKEN_BUCKET_ALGORITHM: &str = "token_bucket";
+
+/// Builds an algorithm for a limiter from its configuration
+pub type AlgorithmFactory =
+    Arc<dyn Fn(&Config) -> Result<Box<dyn RateLimitAlgorithm>, ThrottlerError> + Send + Sync>;
+
+/// Process-wide table of algorithm factories by name.
+pub struct RateLimitAlgorithmRegistry;
+
+/// Registered factories, by name
+fn factories() -> &'static RwLock<HashMap<String, AlgorithmFactory>> {
+    static FACTORIES: OnceLock<RwLock<HashMap<String, AlgorithmFactory>>> = OnceLock::new();
+    FACTORIES.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+impl RateLimitAlgorithmRegistry {
+    /// Registers `factory` under `name`, replacing any factory registered
+    /// under it before. Limiters created earlier keep their algorithm.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ConfigError` if `name` is empty or the built-in
+    /// [`TOKEN_BUCKET_ALGORITHM`].
+    pub fn register<F>(name: &str, factory: F) -> Result<(), ThrottlerError>
+    where
+        F: Fn(&Config) -> Result<Box<dyn RateLimitAlgorithm>, ThrottlerError> + Send + Sync + 'static,
+    {
+        if name.is_empty() || name == TOKEN_BUCKET_ALGORITHM {
+            return Err(ThrottlerError::ConfigError(format!(
+                "Cannot register an algorithm named '{}'", name
+            )));
+        }
+
+        let mut factories = factories().write()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire lock on algorithm registry".to_string()))?;
+        factories.insert(name.to_string(), Arc::new(factory));
+        Ok(())
+    }
+
+    /// Builds the algorithm registered under `name` for `config`, or `None`
+    /// for the built-in token bucket.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ConfigError` if nothing is registered under `name`, or
+    /// whatever error the factory returns.
+    pub fn create(name: &str, config: &Config) -> Result<Option<Box<dyn RateLimitAlgorithm>>, ThrottlerError> {
+        if name.is_empty() || name == TOKEN_BUCKET_ALGORITHM {
+            return Ok(None);
+        }
+
+        let factory = factories().read()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire lock on algorithm registry".to_string()))?
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ThrottlerError::ConfigError(format!(
+                "Unknown rate limit algorithm '{}'; register it before creating the limiter", name
+            )))?;
+        factory(config).map(Some)
+    }
+
+    /// Names of the registered algorithms, sorted
+    pub fn registered() -> Vec<String> {
+        let mut names: Vec<String> = factories().read()
+            .map(|factories| factories.keys().cloned().collect())
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::AlgorithmState;
+    use crate::rate_limiter::RateLimiter;
+    use std::sync::Mutex;
+
+    /// Allows `Config::default_capacity` requests per key, ever
+    struct FixedAllowance {
+        allowance: u64,
+        used: Mutex<HashMap<String, u64>>,
+    }
+
+    impl RateLimitAlgorithm for FixedAllowance {
+        fn is_allowed(&self, key: &str, tokens: u64) -> Result<bool, ThrottlerError> {
+            let mut used = self.used.lock().unwrap();
+            let used = used.entry(key.to_string()).or_default();
+            if *used + tokens > self.allowance {
+                return Ok(false);
+            }
+            *used += tokens;
+            Ok(true)
+        }
+
+        fn get_state(&self, key: &str) -> Result<AlgorithmState, ThrottlerError> {
+            let used = self.used.lock().unwrap().get(key).copied().unwrap_or(0);
+            Ok(AlgorithmState {
+                available_tokens: self.allowance - used,
+                last_refill: 0,
+                requests_in_window: used,
+            })
+        }
+
+        fn reset(&self, key: &str) -> Result<(), ThrottlerError> {
+            self.used.lock().unwrap().remove(key);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registered_algorithm_drives_the_limiter() {
+        RateLimitAlgorithmRegistry::register("test_fixed_allowance", |config| {
+            Ok(Box::new(FixedAllowance {
+                allowance: config.default_capacity,
+                used: Mutex::new(HashMap::new()),
+            }))
+        }).unwrap();
+        assert!(RateLimitAlgorithmRegistry::registered().contains(&"test_fixed_allowance".to_string()));
+
+        let limiter = RateLimiter::new(Config {
+            algorithm: "test_fixed_allowance".to_string(),
+            default_capacity: 2,
+            // The token bucket would refill well within the test
+            default_refill_rate: 1000.0,
+            ..Config::default()
+        }).unwrap();
+
+        assert_eq!(limiter.consume_tokens_shared("k", 2, 1000.0, 1, None).await.unwrap(), (true, 1.0));
+        assert_eq!(limiter.check_rate_limit("k").unwrap(), (true, 0));
+        assert_eq!(limiter.consume_tokens_shared("k", 2, 1000.0, 1, None).await.unwrap(), (false, 0.0));
+
+        limiter.reset("k").unwrap();
+        assert!(limiter.check_rate_limit("k").unwrap().0);
+
+        // Status names the algorithm actually limiting the key
+        let throttler = crate::throttler::Throttler::with_rate_limiter(limiter).unwrap();
+        let status = throttler.get_rate_limit_status("k").await.unwrap();
+        assert_eq!(status.algorithm, "test_fixed_allowance");
+    }
+
+    #[test]
+    fn test_unknown_and_reserved_names_are_rejected() {
+        let config = Config { algorithm: "test_never_registered".to_string(), ..Config::default() };
+        assert!(matches!(RateLimiter::new(config), Err(ThrottlerError::ConfigError(_))));
+        assert!(RateLimitAlgorithmRegistry::create(TOKEN_BUCKET_ALGORITHM, &Config::default()).unwrap().is_none());
+        assert!(RateLimitAlgorithmRegistry::register(TOKEN_BUCKET_ALGORITHM, |_| unreachable!()).is_err());
+    }
+}
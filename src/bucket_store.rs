@@ -0,0 +1,587 @@
+//! # Bucket Storage Backends
+//!
+//! The distributed code path in [`RateLimiter`](crate::rate_limiter::RateLimiter)
+//! talks to shared bucket state through the [`BucketStore`] trait rather than
+//! to Redis directly.
+//!
+//! ```text
+//! ┌──────────────┐      ┌──────────────────┐
+//! │ RateLimiter  │─────▶│ dyn BucketStore  │
+//! └──────────────┘      └────────┬─────────┘
+//!                       ┌────────┴─────────┐
+//!                       ▼                  ▼
+//!                ┌─────────────┐    ┌─────────────┐
+//!                │ RedisClient │    │ MemoryStore │
+//!                │ (production)│    │   (tests)   │
+//!                └─────────────┘    └─────────────┘
+//! ```
+//!
+//! ## MemoryStore
+//!
+//! [`MemoryStore`] keeps buckets in a `HashMap` and reproduces the behavior
+//! of the Redis Lua scripts in Rust: the race check on writes, key expiry,
+//! and the refill math of [`BucketStore::atomic_consume_tokens`]. Its clock
+//! can be pinned and advanced, so tests of the distributed logic run
+//! deterministically without a Redis server:
+//!
+//! ```rust
+//! use std::sync::Arc;
+//! use throttler::bucket_store::MemoryStore;
+//! use throttler::{Config, RateLimiter};
+//!
+//! let store = Arc::new(MemoryStore::new());
+//! let limiter = RateLimiter::with_store(Config::default(), store).unwrap();
+//! ```
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::concurrency::SlotTable;
+use crate::error::ThrottlerError;
+use crate::quota::QuotaTable;
+use crate::rate_limit_config::RateLimitRule;
+use crate::token_bucket::TokenBucket;
+
+/// The result of consuming under a disabled rule: allowed, with a full
+/// bucket that is never stored.
+///
+/// [`RateLimitRule::disabled`] has no capacity and never refills, so
+/// consuming against it as usual would deny the key forever.
+pub fn disabled_rule_consume(rule: &RateLimitRule) -> (bool, TokenBucket) {
+    (true, TokenBucket::new(rule.burst_capacity as u64, rule.refill_per_second()))
+}
+
+/// Shared storage for token buckets, keyed by the full store key
+/// (e.g. `throttler:api-key-123`).
+///
+/// Implementations are called from blocking worker threads and must be
+/// safe to share between them.
+pub trait BucketStore: Send + Sync {
+    /// Reads a bucket, or `None` if the key does not exist or has expired
+    fn get_token_bucket(&self, key: &str) -> Result<Option<TokenBucket>, ThrottlerError>;
+
+    /// Writes a bucket with a TTL in seconds unless a concurrent writer
+    /// stored a newer one first. Returns `Ok(false)` when the write lost
+    /// that race.
+    fn try_set_token_bucket(&self, key: &str, bucket: &TokenBucket, ttl: usize) -> Result<bool, ThrottlerError>;
+
+    /// Like [`Self::try_set_token_bucket`], but a lost race is an error
+    fn set_token_bucket(&self, key: &str, bucket: &TokenBucket, ttl: usize) -> Result<(), ThrottlerError> {
+        if !self.try_set_token_bucket(key, bucket, ttl)? {
+            return Err(ThrottlerError::RedisError("Token bucket update was rejected due to race condition".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Refills and consumes from a bucket in one atomic step, creating it
+    /// full from `rule` if missing. Returns whether the consume succeeded
+    /// and the bucket as stored afterwards.
+    ///
+    /// A disabled rule never limits: the consume succeeds without touching
+    /// the store (see [`disabled_rule_consume`]).
+    fn atomic_consume_tokens(&self, key: &str, tokens_to_consume: u32, rule: &RateLimitRule) -> Result<(bool, TokenBucket), ThrottlerError>;
+
+    /// Refills two buckets and moves `tokens` from one to the other in one
+    /// atomic step. Missing buckets start full from `capacity` and
+    /// `refill_rate`; existing ones keep their own.
+    ///
+    /// Returns `None`, changing nothing, when `from` holds fewer than
+    /// `tokens` beyond the `reserved` ones. Otherwise returns the tokens
+    /// moved: all of them, or as many as fit below `to`'s capacity, which
+    /// is all `from` is charged.
+    fn transfer_tokens(
+        &self,
+        from: &str,
+        to: &str,
+        tokens: f64,
+        reserved: f64,
+        capacity: u64,
+        refill_rate: f64,
+    ) -> Result<Option<f64>, ThrottlerError>;
+
+    fn delete_token_bucket(&self, key: &str) -> Result<(), ThrottlerError>;
+
+    /// Deletes several buckets; backends may do this in one round trip
+    fn delete_token_buckets(&self, keys: &[String]) -> Result<(), ThrottlerError> {
+        for key in keys {
+            self.delete_token_bucket(key)?;
+        }
+        Ok(())
+    }
+
+    /// Health check; answers `PONG` when the store is reachable
+    fn ping(&self) -> Result<String, ThrottlerError>;
+
+    /// Stored buckets found unreadable or inconsistent and replaced with
+    /// fresh ones since this store was created
+    fn corrupt_buckets(&self) -> u64 {
+        0
+    }
+
+    /// Lists stored buckets whose key starts with `prefix`, one page at a
+    /// time. Pass `None` to start and the returned cursor to continue; a
+    /// page holds about `count` buckets (Redis treats it as a hint).
+    fn scan_buckets(&self, prefix: &str, cursor: Option<&str>, count: usize) -> Result<BucketPage, ThrottlerError>;
+
+    /// Takes concurrency slot `slot_id` for `key`, held for `ttl_ms` unless
+    /// released first, after dropping slots past their TTL. Returns the
+    /// slots held afterwards, or `None` if `limit` were already held.
+    fn acquire_slot(&self, key: &str, slot_id: &str, limit: u64, ttl_ms: u64) -> Result<Option<u64>, ThrottlerError>;
+
+    /// Releases a concurrency slot, returning whether it was still held
+    fn release_slot(&self, key: &str, slot_id: &str) -> Result<bool, ThrottlerError>;
+
+    /// Charges `cost` against the quota of `limit` at `key` for the period
+    /// ending at `reset_at_ms`, when the usage lapses. Returns whether the
+    /// charge fit, changing nothing when it did not, and the tokens used
+    /// in the period afterwards.
+    fn consume_quota(&self, key: &str, cost: u64, limit: u64, reset_at_ms: u64) -> Result<(bool, u64), ThrottlerError>;
+
+    /// Tokens used of the quota at `key` in the period ending at
+    /// `reset_at_ms`, read without charging or extending it
+    fn quota_used(&self, key: &str, reset_at_ms: u64) -> Result<u64, ThrottlerError>;
+}
+
+/// One page of [`BucketStore::scan_buckets`]
+#[derive(Debug, Clone, Default)]
+pub struct BucketPage {
+    /// `(store key, bucket)` pairs
+    pub buckets: Vec<(String, TokenBucket)>,
+    /// Where the next page starts, or `None` once every key has been seen
+    pub cursor: Option<String>,
+}
+
+/// A stored bucket and when it expires (milliseconds since the UNIX epoch)
+struct StoredBucket {
+    bucket: TokenBucket,
+    expires_at: u64,
+}
+
+/// In-memory [`BucketStore`] with the semantics of the Redis scripts.
+///
+/// Intended for tests; state is not shared between processes.
+pub struct MemoryStore {
+    buckets: Mutex<HashMap<String, StoredBucket>>,
+    /// Concurrency slots, expiring on the store's clock
+    slots: SlotTable,
+    /// Quota usage per key and period
+    quotas: QuotaTable,
+    /// Pinned clock in milliseconds, or `None` to follow the system clock
+    clock: Mutex<Option<u64>>,
+    /// How far a stored `last_refill` may lead the clock before it is distrusted
+    max_clock_skew_ms: u64,
+}
+
+impl Default for MemoryStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore {
+            buckets: Mutex::new(HashMap::new()),
+            slots: SlotTable::new(),
+            quotas: QuotaTable::new(),
+            clock: Mutex::new(None),
+            max_clock_skew_ms: 1000,
+        }
+    }
+
+    /// Pins the store's clock, in milliseconds since the UNIX epoch
+    pub fn set_time(&self, now_ms: u64) -> Result<(), ThrottlerError> {
+        *self.lock_clock()? = Some(now_ms);
+        Ok(())
+    }
+
+    /// Moves the store's clock forward, pinning it first if needed
+    pub fn advance(&self, ms: u64) -> Result<(), ThrottlerError> {
+        let mut clock = self.lock_clock()?;
+        *clock = Some(clock.unwrap_or_else(system_now_ms) + ms);
+        Ok(())
+    }
+
+    /// Number of unexpired buckets held
+    pub fn len(&self) -> Result<usize, ThrottlerError> {
+        let now = self.now_ms()?;
+        Ok(self.lock_buckets()?.values().filter(|stored| stored.expires_at > now).count())
+    }
+
+    pub fn is_empty(&self) -> Result<bool, ThrottlerError> {
+        Ok(self.len()? == 0)
+    }
+
+    fn now_ms(&self) -> Result<u64, ThrottlerError> {
+        Ok(self.lock_clock()?.unwrap_or_else(system_now_ms))
+    }
+
+    fn lock_clock(&self) -> Result<std::sync::MutexGuard<'_, Option<u64>>, ThrottlerError> {
+        self.clock.lock()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire lock on store clock".to_string()))
+    }
+
+    fn lock_buckets(&self) -> Result<std::sync::MutexGuard<'_, HashMap<String, StoredBucket>>, ThrottlerError> {
+        self.buckets.lock()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire lock on stored buckets".to_string()))
+    }
+}
+
+/// Time for a bucket to refill from empty, after which it may expire as a
+/// fresh one would be identical (an hour when it never refills)
+fn refill_window_ms(bucket: &TokenBucket) -> u64 {
+    if bucket.refill_rate <= 0.0 {
+        return 3_600_000;
+    }
+    ((bucket.capacity as f64 / bucket.refill_rate).ceil() as u64).max(1) * 1000
+}
+
+fn system_now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+impl BucketStore for MemoryStore {
+    fn get_token_bucket(&self, key: &str) -> Result<Option<TokenBucket>, ThrottlerError> {
+        let now = self.now_ms()?;
+        Ok(self.lock_buckets()?
+            .get(key)
+            .filter(|stored| stored.expires_at > now)
+            .map(|stored| stored.bucket.clone()))
+    }
+
+    fn try_set_token_bucket(&self, key: &str, bucket: &TokenBucket, ttl: usize) -> Result<bool, ThrottlerError> {
+        let now = self.now_ms()?;
+        let mut buckets = self.lock_buckets()?;
+
+        // Same rule as the Redis script: keep a newer bucket unless it is
+        // older than the skew tolerance
+        if let Some(existing) = buckets.get(key).filter(|stored| stored.expires_at > now) {
+            let existing_refill = existing.bucket.last_refill;
+            if bucket.last_refill < existing_refill
+                && now.saturating_sub(existing_refill) <= self.max_clock_skew_ms
+            {
+                return Ok(false);
+            }
+        }
+
+        buckets.insert(key.to_string(), StoredBucket {
+            bucket: bucket.clone(),
+            expires_at: now + ttl as u64 * 1000,
+        });
+        Ok(true)
+    }
+
+    fn atomic_consume_tokens(&self, key: &str, tokens_to_consume: u32, rule: &RateLimitRule) -> Result<(bool, TokenBucket), ThrottlerError> {
+        if !rule.enabled {
+            return Ok(disabled_rule_consume(rule));
+        }
+        let now = self.now_ms()?;
+        let capacity = rule.burst_capacity as u64;
+        let refill_per_ms = rule.refill_rate_ms();
+        let window_ms = rule.window_size.as_millis() as u64;
+
+        let mut buckets = self.lock_buckets()?;
+
+        let mut bucket = match buckets.get(key).filter(|stored| stored.expires_at > now) {
+            Some(stored) => {
+                let mut bucket = stored.bucket.clone();
+
+                if bucket.last_refill > now + self.max_clock_skew_ms {
+                    bucket.last_refill = now;
+                }
+
+                let elapsed = now.saturating_sub(bucket.last_refill);
+                if elapsed > 0 {
+                    let tokens_to_add = elapsed as f64 * refill_per_ms;
+                    bucket.tokens = (bucket.tokens + tokens_to_add).min(capacity as f64);
+                    bucket.last_refill = now;
+                }
+                bucket
+            }
+            None => {
+                let mut bucket = TokenBucket::new(capacity, refill_per_ms * 1000.0);
+                bucket.last_refill = now;
+                bucket
+            }
+        };
+
+        let success = bucket.tokens >= tokens_to_consume as f64;
+        if success {
+            bucket.tokens -= tokens_to_consume as f64;
+        }
+
+        buckets.insert(key.to_string(), StoredBucket {
+            bucket: bucket.clone(),
+            expires_at: now + window_ms,
+        });
+
+        Ok((success, bucket))
+    }
+
+    fn transfer_tokens(
+        &self,
+        from: &str,
+        to: &str,
+        tokens: f64,
+        reserved: f64,
+        capacity: u64,
+        refill_rate: f64,
+    ) -> Result<Option<f64>, ThrottlerError> {
+        let now = self.now_ms()?;
+        let mut buckets = self.lock_buckets()?;
+
+        let load = |key: &str| {
+            match buckets.get(key).filter(|stored| stored.expires_at > now) {
+                Some(stored) => {
+                    let mut bucket = stored.bucket.clone();
+                    if bucket.last_refill > now + self.max_clock_skew_ms {
+                        bucket.last_refill = now;
+                    }
+                    let elapsed = now.saturating_sub(bucket.last_refill);
+                    bucket.tokens = (bucket.tokens + elapsed as f64 * bucket.refill_rate / 1000.0)
+                        .min(bucket.capacity as f64);
+                    bucket.last_refill = now;
+                    bucket
+                }
+                None => {
+                    let mut bucket = TokenBucket::new(capacity, refill_rate);
+                    bucket.last_refill = now;
+                    bucket
+                }
+            }
+        };
+
+        let mut source = load(from);
+        if source.tokens - reserved < tokens {
+            return Ok(None);
+        }
+        let mut destination = load(to);
+
+        let moved = tokens.min(destination.capacity as f64 - destination.tokens).max(0.0);
+        source.tokens -= moved;
+        destination.tokens += moved;
+
+        for (key, bucket) in [(from, source), (to, destination)] {
+            let expires_at = now + refill_window_ms(&bucket);
+            buckets.insert(key.to_string(), StoredBucket { bucket, expires_at });
+        }
+
+        Ok(Some(moved))
+    }
+
+    fn delete_token_bucket(&self, key: &str) -> Result<(), ThrottlerError> {
+        self.lock_buckets()?.remove(key);
+        Ok(())
+    }
+
+    fn ping(&self) -> Result<String, ThrottlerError> {
+        Ok("PONG".to_string())
+    }
+
+    /// Pages through keys in sorted order; the cursor is the last key returned
+    fn scan_buckets(&self, prefix: &str, cursor: Option<&str>, count: usize) -> Result<BucketPage, ThrottlerError> {
+        let now = self.now_ms()?;
+        let buckets = self.lock_buckets()?;
+
+        let mut keys: Vec<&String> = buckets.iter()
+            .filter(|(key, stored)| {
+                key.starts_with(prefix)
+                    && stored.expires_at > now
+                    && cursor.is_none_or(|after| key.as_str() > after)
+            })
+            .map(|(key, _)| key)
+            .collect();
+        keys.sort();
+
+        let more = keys.len() > count;
+        let page: Vec<(String, TokenBucket)> = keys.into_iter()
+            .take(count)
+            .map(|key| (key.clone(), buckets[key].bucket.clone()))
+            .collect();
+        let cursor = if more { page.last().map(|(key, _)| key.clone()) } else { None };
+
+        Ok(BucketPage { buckets: page, cursor })
+    }
+
+    fn acquire_slot(&self, key: &str, slot_id: &str, limit: u64, ttl_ms: u64) -> Result<Option<u64>, ThrottlerError> {
+        self.slots.acquire(key, slot_id, limit, ttl_ms, self.now_ms()?)
+    }
+
+    fn release_slot(&self, key: &str, slot_id: &str) -> Result<bool, ThrottlerError> {
+        self.slots.release(key, slot_id, self.now_ms()?)
+    }
+
+    fn consume_quota(&self, key: &str, cost: u64, limit: u64, reset_at_ms: u64) -> Result<(bool, u64), ThrottlerError> {
+        self.quotas.consume(key, cost, limit, reset_at_ms)
+    }
+
+    fn quota_used(&self, key: &str, reset_at_ms: u64) -> Result<u64, ThrottlerError> {
+        self.quotas.used(key, reset_at_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const T0: u64 = 1_700_000_000_000;
+
+    fn pinned_store() -> MemoryStore {
+        let store = MemoryStore::new();
+        store.set_time(T0).unwrap();
+        store
+    }
+
+    #[test]
+    fn test_atomic_consume_creates_full_bucket() {
+        let store = pinned_store();
+        let rule = RateLimitRule::new(10, 5, Duration::from_secs(60));
+
+        let (allowed, bucket) = store.atomic_consume_tokens("k", 2, &rule).unwrap();
+        assert!(allowed);
+        assert_eq!(bucket.tokens, 3.0);
+        assert_eq!(bucket.last_refill, T0);
+
+        let (allowed, bucket) = store.atomic_consume_tokens("k", 4, &rule).unwrap();
+        assert!(!allowed);
+        assert_eq!(bucket.tokens, 3.0);
+    }
+
+    #[test]
+    fn test_atomic_consume_refills_like_the_lua_script() {
+        let store = pinned_store();
+        // 10 tokens per second, i.e. one token every 100ms
+        let rule = RateLimitRule::new(10, 10, Duration::from_secs(1));
+
+        let (_, bucket) = store.atomic_consume_tokens("k", 10, &rule).unwrap();
+        assert_eq!(bucket.tokens, 0.0);
+
+        // 250ms earns 2.5 tokens; the fraction is kept
+        store.advance(250).unwrap();
+        let (allowed, bucket) = store.atomic_consume_tokens("k", 1, &rule).unwrap();
+        assert!(allowed);
+        assert!((bucket.tokens - 1.5).abs() < 1e-9);
+
+        // Refill never exceeds capacity
+        let small = RateLimitRule::new(10, 4, Duration::from_secs(1));
+        store.atomic_consume_tokens("small", 4, &small).unwrap();
+        store.advance(900).unwrap();
+        let (_, bucket) = store.atomic_consume_tokens("small", 0, &small).unwrap();
+        assert_eq!(bucket.tokens, 4.0);
+
+        // The bucket expires one window after its last write
+        store.advance(1_000).unwrap();
+        assert!(store.get_token_bucket("small").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_disabled_rule_allows_instead_of_locking_out() {
+        let store = pinned_store();
+        let rule = RateLimitRule::disabled();
+
+        // Zero capacity and refill would deny forever; disabled never limits
+        for _ in 0..5 {
+            assert!(store.atomic_consume_tokens("paused", 1, &rule).unwrap().0);
+            store.advance(1_000).unwrap();
+        }
+        assert!(store.get_token_bucket("paused").unwrap().is_none());
+
+        // A limit paused on an existing rule does not drain its bucket either
+        let paused = RateLimitRule { enabled: false, ..RateLimitRule::new(1, 1, Duration::from_secs(60)) };
+        for _ in 0..3 {
+            assert!(store.atomic_consume_tokens("limited", 1, &paused).unwrap().0);
+        }
+        let active = RateLimitRule::new(1, 1, Duration::from_secs(60));
+        assert!(store.atomic_consume_tokens("limited", 1, &active).unwrap().0);
+    }
+
+    #[test]
+    fn test_sub_second_window_refills_and_expires_in_ms() {
+        let store = pinned_store();
+        // 10 tokens per second with a 200ms window
+        let rule = RateLimitRule::new(10, 2, Duration::from_millis(200));
+
+        let (_, bucket) = store.atomic_consume_tokens("fast", 2, &rule).unwrap();
+        assert_eq!(bucket.tokens, 0.0);
+
+        // 50ms is half a token: not enough yet, but not lost either
+        store.advance(50).unwrap();
+        let (allowed, bucket) = store.atomic_consume_tokens("fast", 1, &rule).unwrap();
+        assert!(!allowed);
+        assert!((bucket.tokens - 0.5).abs() < 1e-9);
+        store.advance(50).unwrap();
+        assert!(store.atomic_consume_tokens("fast", 1, &rule).unwrap().0);
+
+        // The TTL is the 200ms window, not a rounded-up second
+        store.advance(199).unwrap();
+        assert!(store.get_token_bucket("fast").unwrap().is_some());
+        store.advance(2).unwrap();
+        assert!(store.get_token_bucket("fast").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_atomic_consume_distrusts_future_timestamps() {
+        let store = pinned_store();
+        let rule = RateLimitRule::new(10, 10, Duration::from_secs(60));
+
+        let mut ahead = TokenBucket::new(10, 10.0);
+        ahead.tokens = 0.0;
+        ahead.last_refill = T0 + 60_000;
+        assert!(store.try_set_token_bucket("k", &ahead, 60).unwrap());
+
+        let (_, bucket) = store.atomic_consume_tokens("k", 0, &rule).unwrap();
+        assert_eq!(bucket.last_refill, T0);
+    }
+
+    #[test]
+    fn test_try_set_rejects_stale_write() {
+        let store = pinned_store();
+        let mut newer = TokenBucket::new(10, 1.0);
+        newer.last_refill = T0;
+        let mut older = newer.clone();
+        older.last_refill = T0 - 10;
+
+        assert!(store.try_set_token_bucket("k", &newer, 60).unwrap());
+        assert!(!store.try_set_token_bucket("k", &older, 60).unwrap());
+        assert!(store.set_token_bucket("k", &older, 60).is_err());
+
+        // Past the skew tolerance the stored bucket no longer wins
+        store.advance(2_000).unwrap();
+        assert!(store.try_set_token_bucket("k", &older, 60).unwrap());
+    }
+
+    #[test]
+    fn test_buckets_expire_after_ttl() {
+        let store = pinned_store();
+        let bucket = TokenBucket::new(10, 1.0);
+        store.set_token_bucket("k", &bucket, 2).unwrap();
+        assert_eq!(store.len().unwrap(), 1);
+
+        store.advance(2_000).unwrap();
+        assert!(store.get_token_bucket("k").unwrap().is_none());
+        assert!(store.is_empty().unwrap());
+    }
+
+    #[test]
+    fn test_scan_buckets_pages_through_prefix() {
+        let store = pinned_store();
+        let bucket = TokenBucket::new(10, 1.0);
+        for key in ["throttler:c", "throttler:a", "other:x", "throttler:b"] {
+            store.set_token_bucket(key, &bucket, 60).unwrap();
+        }
+
+        let first = store.scan_buckets("throttler:", None, 2).unwrap();
+        let keys: Vec<_> = first.buckets.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, vec!["throttler:a", "throttler:b"]);
+
+        let second = store.scan_buckets("throttler:", first.cursor.as_deref(), 2).unwrap();
+        let keys: Vec<_> = second.buckets.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, vec!["throttler:c"]);
+        assert!(second.cursor.is_none());
+    }
+}
@@ -0,0 +1,158 @@
+//! # Concurrency Slots
+//!
+//! Rate limits bound how often a client may start requests; a rule's
+//! `concurrency_limit` bounds how many it may have running at once. Each
+//! request takes a slot with `POST /concurrency/:key/acquire` and hands it
+//! back with `POST /concurrency/:key/release` once it finishes.
+//!
+//! ## Leaked Slots
+//!
+//! A client that crashes or drops its connection never releases its slot,
+//! so every slot is only held for `Config::concurrency_slot_ttl_ms`. Slots
+//! past their TTL are dropped on the next acquire for the key and no longer
+//! count against its limit; local slots of keys never acquired again are
+//! dropped by the periodic cleanup. The TTL should comfortably exceed the longest
+//! request it guards: a slot that lapses while its request is still running
+//! lets one more request in.
+//!
+//! ## Storage
+//!
+//! With Redis configured, a key's slots are a sorted set scored by expiry
+//! time, acquired and pruned by one Lua script, so the limit holds across
+//! instances. Otherwise (or when Redis fails, in lenient mode) they are kept
+//! in a [`SlotTable`] in the process.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::error::ThrottlerError;
+
+/// A concurrency slot taken for a request
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConcurrencySlot {
+    /// Identifies the slot when releasing it
+    pub id: String,
+    /// Slots the key holds, including this one
+    pub held: u64,
+}
+
+/// Expiry time (ms since epoch) of each slot a key holds, by slot id
+type HeldSlots = HashMap<String, u64>;
+
+/// Concurrency slots held per key, each with its expiry time.
+#[derive(Debug, Default)]
+pub struct SlotTable {
+    slots: Mutex<HashMap<String, HeldSlots>>,
+}
+
+impl SlotTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, HashMap<String, HeldSlots>>, ThrottlerError> {
+        self.slots.lock()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire lock on concurrency slots".to_string()))
+    }
+
+    /// Takes slot `slot_id` for `key` until `now_ms + ttl_ms`, unless
+    /// `limit` unexpired slots are already held. Returns the slots held
+    /// afterwards, or `None` if the key was at its limit.
+    pub fn acquire(
+        &self,
+        key: &str,
+        slot_id: &str,
+        limit: u64,
+        ttl_ms: u64,
+        now_ms: u64,
+    ) -> Result<Option<u64>, ThrottlerError> {
+        let mut slots = self.lock()?;
+        let held = slots.entry(key.to_string()).or_default();
+        held.retain(|_, expires_at| *expires_at > now_ms);
+
+        if held.len() as u64 >= limit {
+            if held.is_empty() {
+                slots.remove(key);
+            }
+            return Ok(None);
+        }
+        held.insert(slot_id.to_string(), now_ms.saturating_add(ttl_ms));
+        Ok(Some(held.len() as u64))
+    }
+
+    /// Releases a slot, returning whether it was still held
+    pub fn release(&self, key: &str, slot_id: &str, now_ms: u64) -> Result<bool, ThrottlerError> {
+        let mut slots = self.lock()?;
+        let Some(held) = slots.get_mut(key) else {
+            return Ok(false);
+        };
+
+        let released = held.remove(slot_id).is_some_and(|expires_at| expires_at > now_ms);
+        held.retain(|_, expires_at| *expires_at > now_ms);
+        if held.is_empty() {
+            slots.remove(key);
+        }
+        Ok(released)
+    }
+
+    /// Drops slots expired as of `now_ms`, and keys left holding none,
+    /// returning how many slots were dropped
+    pub fn sweep(&self, now_ms: u64) -> Result<usize, ThrottlerError> {
+        let mut slots = self.lock()?;
+        let mut dropped = 0;
+        slots.retain(|_, held| {
+            let before = held.len();
+            held.retain(|_, expires_at| *expires_at > now_ms);
+            dropped += before - held.len();
+            !held.is_empty()
+        });
+        Ok(dropped)
+    }
+
+    /// Keys holding slots, including expired ones not yet dropped
+    pub fn key_count(&self) -> Result<usize, ThrottlerError> {
+        Ok(self.lock()?.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slots_are_capped_released_and_expire() {
+        let table = SlotTable::new();
+
+        assert_eq!(table.acquire("k", "a", 2, 1000, 0).unwrap(), Some(1));
+        assert_eq!(table.acquire("k", "b", 2, 1000, 0).unwrap(), Some(2));
+        assert_eq!(table.acquire("k", "c", 2, 1000, 0).unwrap(), None);
+
+        // Releasing frees a slot, once
+        assert!(table.release("k", "a", 10).unwrap());
+        assert!(!table.release("k", "a", 10).unwrap());
+        assert_eq!(table.acquire("k", "c", 2, 1000, 10).unwrap(), Some(2));
+
+        // Leaked slots stop counting once their TTL passes
+        assert_eq!(table.acquire("k", "d", 2, 1000, 500).unwrap(), None);
+        assert_eq!(table.acquire("k", "d", 2, 1000, 1000).unwrap(), Some(2));
+        assert!(!table.release("k", "c", 2000).unwrap());
+    }
+
+    #[test]
+    fn test_empty_and_expired_keys_are_dropped() {
+        let table = SlotTable::new();
+
+        // A denied acquire leaves nothing behind
+        assert_eq!(table.acquire("zero", "a", 0, 1000, 0).unwrap(), None);
+        assert_eq!(table.key_count().unwrap(), 0);
+
+        // Slots never released are swept once expired
+        table.acquire("leaked", "a", 2, 1000, 0).unwrap();
+        table.acquire("leaked", "b", 2, 1000, 500).unwrap();
+        table.acquire("live", "a", 2, 5000, 0).unwrap();
+        assert_eq!(table.sweep(1200).unwrap(), 1);
+        assert_eq!(table.key_count().unwrap(), 2);
+        assert_eq!(table.sweep(2000).unwrap(), 1);
+        assert_eq!(table.key_count().unwrap(), 1);
+    }
+}
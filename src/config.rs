@@ -1,26 +1,595 @@
+use axum::http::StatusCode;
 use crate::error::ThrottlerError;
 use crate::config_validator::ConfigValidator;
+use crate::rate_limit_config::{RateLimitRule, RateUnit};
+use crate::redis::SerializationFormat;
+use crate::token_bucket::DEFAULT_MAX_REFILL_ELAPSED_SECS;
+use crate::validation::{DEFAULT_MAX_JSON_DEPTH, DEFAULT_MAX_JSON_FIELDS};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
 use std::env;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Default bind address for local runs
+pub const LOOPBACK_BIND_ADDRESS: &str = "127.0.0.1:8080";
+
+/// Default bind address in production and containers
+pub const ALL_INTERFACES_BIND_ADDRESS: &str = "0.0.0.0:8080";
+
+/// Whether reported `remaining` counts include the token consumed by the
+/// current request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RemainingSemantics {
+    /// Remaining after this request was counted (default)
+    #[default]
+    After,
+    /// Remaining before this request was counted
+    Before,
+}
+
+/// How keys without an explicitly configured rule are treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownKeyPolicy {
+    /// Unknown keys are limited by the default rule (default)
+    #[default]
+    AllowWithDefault,
+    /// Only keys with a configured rule are allowed
+    Deny,
+}
+
+/// Rate limit headers governed by [`ResponseHeaderPolicy`]. Other response
+/// headers (content type, CORS, ...) are never touched.
+pub const RATE_LIMIT_HEADERS: &[&str] = &[
+    "X-RateLimit-Limit",
+    "X-RateLimit-Remaining",
+    "X-RateLimit-Window",
+    "X-RateLimit-Scope",
+    "X-RateLimit-Retry-Budget",
+    "X-RateLimit-Utilization",
+    "X-RateLimit-Retry-After-Ms",
+    "X-RateLimit-Source",
+    "X-RateLimit-Refund-Id",
+    "X-Quota-Remaining",
+    "X-Quota-Reset",
+    "Retry-After",
+    "Warning",
+];
+
+/// Which rate limit headers responses may carry, for proxies that strip or
+/// choke on some of them.
+///
+/// Parsed from `RESPONSE_HEADERS` as `all`, `allow:<h1>,<h2>` or
+/// `deny:<h1>,<h2>`; header names are case-insensitive.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ResponseHeaderPolicy {
+    /// Emit every rate limit header (default)
+    #[default]
+    All,
+    /// Emit only the listed rate limit headers
+    Allow(Vec<String>),
+    /// Emit every rate limit header except the listed ones
+    Deny(Vec<String>),
+}
+
+impl ResponseHeaderPolicy {
+    /// Whether a response header may be emitted
+    pub fn permits(&self, name: &str) -> bool {
+        let governed = RATE_LIMIT_HEADERS.iter().any(|h| h.eq_ignore_ascii_case(name));
+        let listed = |names: &[String]| names.iter().any(|n| n.eq_ignore_ascii_case(name));
+
+        match self {
+            ResponseHeaderPolicy::All => true,
+            _ if !governed => true,
+            ResponseHeaderPolicy::Allow(names) => listed(names),
+            ResponseHeaderPolicy::Deny(names) => !listed(names),
+        }
+    }
+}
+
+impl FromStr for ResponseHeaderPolicy {
+    type Err = ThrottlerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let names = |list: &str| -> Vec<String> {
+            list.split(',')
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .collect()
+        };
+
+        if s.eq_ignore_ascii_case("all") {
+            Ok(ResponseHeaderPolicy::All)
+        } else if let Some(list) = s.strip_prefix("allow:") {
+            Ok(ResponseHeaderPolicy::Allow(names(list)))
+        } else if let Some(list) = s.strip_prefix("deny:") {
+            Ok(ResponseHeaderPolicy::Deny(names(list)))
+        } else {
+            Err(ThrottlerError::ConfigError(format!(
+                "Invalid RESPONSE_HEADERS value '{}'. Must be 'all', 'allow:<headers>' or 'deny:<headers>'",
+                s
+            )))
+        }
+    }
+}
+
+impl FromStr for UnknownKeyPolicy {
+    type Err = ThrottlerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "allow_with_default" => Ok(UnknownKeyPolicy::AllowWithDefault),
+            "deny" => Ok(UnknownKeyPolicy::Deny),
+            other => Err(ThrottlerError::ConfigError(format!(
+                "Invalid UNKNOWN_KEY_POLICY value '{}'. Must be 'allow_with_default' or 'deny'",
+                other
+            ))),
+        }
+    }
+}
+
+impl FromStr for RemainingSemantics {
+    type Err = ThrottlerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "after" => Ok(RemainingSemantics::After),
+            "before" => Ok(RemainingSemantics::Before),
+            other => Err(ThrottlerError::ConfigError(format!(
+                "Invalid REMAINING_SEMANTICS value '{}'. Must be 'after' or 'before'",
+                other
+            ))),
+        }
+    }
+}
+
+/// What to do when the authoritative Redis store cannot be reached.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConsistencyMode {
+    /// Fall back to local buckets, staying available (default)
+    #[default]
+    Lenient,
+    /// Fail with 503 rather than decide from possibly divergent local state
+    Strict,
+}
+
+impl FromStr for ConsistencyMode {
+    type Err = ThrottlerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "lenient" => Ok(ConsistencyMode::Lenient),
+            "strict" => Ok(ConsistencyMode::Strict),
+            other => Err(ThrottlerError::ConfigError(format!(
+                "Invalid CONSISTENCY_MODE value '{}'. Must be 'lenient' or 'strict'",
+                other
+            ))),
+        }
+    }
+}
+
+/// When a rule change becomes visible to checks of the keys it governs.
+///
+/// Either way a check reads a rule's capacity and refill rate together from
+/// one version of the rule, never one from each.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RuleUpdateOrdering {
+    /// A change applies from the next check that starts after it is stored;
+    /// checks already in flight finish with the rule they resolved (default)
+    #[default]
+    NextConsume,
+    /// A change waits for in-flight checks to finish, so once it returns no
+    /// check is still using the old rule
+    Serialized,
+}
+
+impl FromStr for RuleUpdateOrdering {
+    type Err = ThrottlerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "next_consume" => Ok(RuleUpdateOrdering::NextConsume),
+            "serialized" => Ok(RuleUpdateOrdering::Serialized),
+            other => Err(ThrottlerError::ConfigError(format!(
+                "Invalid RULE_UPDATE_ORDERING value '{}'. Must be 'next_consume' or 'serialized'",
+                other
+            ))),
+        }
+    }
+}
+
+/// How rate limit keys are canonicalized before use, so that e.g. `User123`
+/// and `user123` can share a bucket.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyCase {
+    /// Keys are used exactly as given (default)
+    #[default]
+    Sensitive,
+    /// Keys are lowercased
+    Lower,
+    /// Keys are uppercased
+    Upper,
+}
+
+impl FromStr for KeyCase {
+    type Err = ThrottlerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "sensitive" => Ok(KeyCase::Sensitive),
+            "lower" => Ok(KeyCase::Lower),
+            "upper" => Ok(KeyCase::Upper),
+            other => Err(ThrottlerError::ConfigError(format!(
+                "Invalid KEY_CASE value '{}'. Must be 'sensitive', 'lower' or 'upper'",
+                other
+            ))),
+        }
+    }
+}
+
+/// How a `/` in a key is handled. Path keys are decoded before use, so a
+/// client can send one URL-encoded, e.g. `/rate-limit/a%2Fb/check`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeySlashes {
+    /// Keys containing `/` are rejected (default)
+    #[default]
+    Reject,
+    /// Each `/` is replaced with `_`, so `a/b` and `a_b` share a bucket
+    Replace,
+}
+
+impl FromStr for KeySlashes {
+    type Err = ThrottlerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "reject" => Ok(KeySlashes::Reject),
+            "replace" => Ok(KeySlashes::Replace),
+            other => Err(ThrottlerError::ConfigError(format!(
+                "Invalid KEY_SLASHES value '{}'. Must be 'reject' or 'replace'",
+                other
+            ))),
+        }
+    }
+}
+
+/// Body of an allowed check; denials always carry the full body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CheckResponseMode {
+    /// `{"allowed", "remaining", "limit"}` (default)
+    #[default]
+    Full,
+    /// An empty JSON object, `{}`
+    Minimal,
+    /// No body; the outcome is in the status and headers only
+    HeadersOnly,
+}
+
+impl FromStr for CheckResponseMode {
+    type Err = ThrottlerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "full" => Ok(CheckResponseMode::Full),
+            "minimal" => Ok(CheckResponseMode::Minimal),
+            "headers_only" => Ok(CheckResponseMode::HeadersOnly),
+            other => Err(ThrottlerError::ConfigError(format!(
+                "Invalid CHECK_RESPONSE_MODE value '{}'. Must be 'full', 'minimal' or 'headers_only'",
+                other
+            ))),
+        }
+    }
+}
+
+/// Lowest TLS protocol version accepted from a `rediss://` Redis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TlsVersion {
+    /// TLS 1.2 or later
+    Tls12,
+    /// TLS 1.3 only
+    Tls13,
+}
+
+impl FromStr for TlsVersion {
+    type Err = ThrottlerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().trim_start_matches("tls").trim_start_matches('v') {
+            "1.2" => Ok(TlsVersion::Tls12),
+            "1.3" => Ok(TlsVersion::Tls13),
+            "1.0" | "1.1" => Err(ThrottlerError::ConfigError(format!(
+                "REDIS_MIN_TLS_VERSION '{}' is not supported; TLS 1.0 and 1.1 are never negotiated",
+                s
+            ))),
+            _ => Err(ThrottlerError::ConfigError(format!(
+                "Invalid REDIS_MIN_TLS_VERSION value '{}'. Must be '1.2' or '1.3'",
+                s
+            ))),
+        }
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub redis_url: String,
+    /// Optional read replica for status and dry-run reads (empty = use
+    /// primary); one replica cannot serve several shards, so it may not be
+    /// combined with more than one `redis_urls` entry
+    pub redis_replica_url: String,
+    /// Independent Redis nodes to shard keys across by consistent hashing;
+    /// when set, used instead of `redis_url`
+    pub redis_urls: Vec<String>,
+    /// Lowest TLS version Redis connections may negotiate; when set every
+    /// Redis URL must be `rediss://` (None = the TLS connector's default)
+    pub redis_min_tls_version: Option<TlsVersion>,
+    /// Listen for bucket keys expiring in Redis (needs `notify-keyspace-events Ex`)
+    pub expiry_events: bool,
     pub bind_address: String,
     pub default_capacity: u64,
-    pub default_refill_rate: u64,
+    /// Tokens added per second; fractional rates such as 0.5 are allowed
+    pub default_refill_rate: f64,
     pub environment: String,
     pub log_level: String,
+    /// Encoding used for token buckets stored in Redis
+    pub redis_serialization: SerializationFormat,
+    /// Upper bound on a single Redis operation in milliseconds (0 = unbounded)
+    pub redis_op_timeout_ms: u64,
+    /// Whether reported remaining counts are taken after or before consuming
+    pub remaining_semantics: RemainingSemantics,
+    /// Whether keys without a configured rule are allowed or denied
+    pub unknown_key_policy: UnknownKeyPolicy,
+    /// How far a bucket timestamp may lead the Redis clock before it is distrusted
+    pub max_clock_skew_ms: u64,
+    /// Time allowed for shutdown work such as flushing buckets to Redis
+    pub shutdown_timeout_ms: u64,
+    /// Include internal error details in 500 responses (development aid)
+    pub verbose_errors: bool,
+    /// Service-wide requests per second across all keys (0 = disabled)
+    pub global_rate_limit: u64,
+    /// Minimum time between Redis writes of one bucket (0 = write every consume)
+    pub min_redis_write_interval_ms: u64,
+    /// Store SHA-256 hashes of keys in Redis instead of raw identifiers
+    pub hash_keys: bool,
+    /// Add `X-RateLimit-Retry-Budget` to denials so clients know when to stop retrying
+    pub retry_budget: bool,
+    /// Which rate limit headers are emitted on responses
+    pub response_headers: ResponseHeaderPolicy,
+    /// Serve waiters on a hot key in arrival order instead of lock order
+    pub fair_queueing: bool,
+    /// Most requests that may wait on one key when fair queueing is on
+    pub fair_queue_depth: usize,
+    /// Most per-key rules the throttler will hold (0 = unlimited)
+    pub max_rules: usize,
+    /// Extra attempts when a Redis bucket write loses a race to another writer
+    pub redis_race_retries: u32,
+    /// Decimal places in `X-RateLimit-Remaining` (0 = whole tokens, floored)
+    pub remaining_precision: u32,
+    /// Largest `Retry-After` ever sent, including for buckets that never refill
+    pub max_retry_after_secs: u64,
+    /// Scope every key to the tenant in the `X-Tenant-Id` request header
+    pub tenant_isolation: bool,
+    /// Fraction of requests recorded in per-key metrics (0.0-1.0); counts are scaled up
+    pub metrics_sample_rate: f64,
+    /// Window sizes rules may use, in ms (empty = any window within range)
+    pub allowed_windows_ms: Vec<u64>,
+    /// IPv6 prefix length per-IP buckets are keyed on (IPv4 stays per-address)
+    pub ipv6_aggregate_prefix: u8,
+    /// Send `X-RateLimit-Utilization` on checks and `utilization` in status
+    pub emit_utilization: bool,
+    /// Send a `Server-Timing` latency breakdown on checks
+    pub emit_server_timing: bool,
+    /// Let each key's capacity adapt to how often it is throttled
+    pub adaptive_capacity: bool,
+    /// Lowest capacity adaptive mode may shrink a key to
+    pub adaptive_min_capacity: u64,
+    /// Highest capacity adaptive mode may grow a key to
+    pub adaptive_max_capacity: u64,
+    /// Minimum time between capacity adjustments of one key, in ms
+    pub adaptive_interval_ms: u64,
+    /// Whether an unreachable Redis falls back to local state or fails the request
+    pub consistency_mode: ConsistencyMode,
+    /// Deepest object/array nesting accepted in a rule body
+    pub max_json_depth: usize,
+    /// Most object fields accepted in a rule body
+    pub max_json_fields: usize,
+    /// Whether rule changes wait for in-flight checks
+    pub rule_update_ordering: RuleUpdateOrdering,
+    /// Parse bodies sent without a `Content-Type` as JSON
+    pub lenient_content_type: bool,
+    /// Reject `GET` and `DELETE` requests that carry a body with `400`
+    pub strict_get_bodies: bool,
+    /// Replicas a Redis write must reach (`WAIT`) before it is acknowledged
+    /// (0 = don't wait)
+    pub redis_wait_replicas: usize,
+    /// How long `WAIT` may block for the replicas, in ms
+    pub redis_wait_timeout_ms: u64,
+    /// Case normalization applied to keys named in requests
+    pub key_case: KeyCase,
+    /// Bound requests by the gateway's `X-Request-Deadline`
+    pub honor_request_deadline: bool,
+    /// Status of a check denied by the key's own limit (200 lets clients
+    /// read `allowed` instead of handling an error)
+    pub deny_status_code: u16,
+    /// Rules in effect from startup, before any are set through the API
+    pub seed_rules: Vec<(String, RateLimitRule)>,
+    /// Proxies in front of the service that append to `X-Forwarded-For`;
+    /// the client is that many entries from the right (0 = trust the
+    /// first entry)
+    pub trusted_proxy_hops: usize,
+    /// Body sent for allowed checks, trimmed for high-QPS clients
+    pub check_response_mode: CheckResponseMode,
+    /// Honor `?debug=true` on checks, exposing the bucket state behind the
+    /// decision (development aid)
+    pub allow_debug: bool,
+    /// Most Redis operations in flight at once (0 = unbounded)
+    pub max_redis_concurrency: usize,
+    /// How long a Redis operation may wait for a free slot when
+    /// `max_redis_concurrency` is reached, in ms (0 = fail at once)
+    pub redis_queue_timeout_ms: u64,
+    /// Percent of keys (chosen by key hash) whose denials are enforced;
+    /// the rest are shadowed, counted but let through (100 = all)
+    pub enforcement_rollout_pct: u8,
+    /// Tokens each instance leases from a Redis bucket to serve locally
+    /// (0 = every consume goes to Redis)
+    pub hybrid_local_burst: u64,
+    /// How long a local lease is used before resyncing with Redis, in ms
+    pub hybrid_sync_interval_ms: u64,
+    /// How long a concurrency slot is held unless released, in ms; bounds
+    /// how long a slot leaked by a dropped client blocks the key
+    pub concurrency_slot_ttl_ms: u64,
+    /// Log the source of the line a failing Lua script stopped at, next to
+    /// the error class and line number that are always logged
+    pub log_script_source: bool,
+    /// Recent Redis write races kept for `/admin/stats`, newest last; 0
+    /// keeps only the running count
+    pub race_log_size: usize,
+    /// Send a `Warning` header on every response while Redis is configured
+    /// but unreachable and limits are enforced per instance
+    pub warn_on_degraded: bool,
+    /// Shortest time, in ms, in which the default or a rule's refill rate
+    /// may refill the full capacity; faster is rejected as a likely typo
+    /// (0 = no check)
+    pub min_full_refill_ms: u64,
+    /// How often, in ms, each instance adds its request counts to Redis so
+    /// `/metrics` reports totals for the whole fleet (0 = per-instance
+    /// metrics; needs Redis)
+    pub metrics_flush_interval_ms: u64,
+    /// Send `X-RateLimit-Retry-After-Ms`, the wait for a denied request in
+    /// milliseconds, alongside the whole-second `Retry-After`
+    pub emit_retry_after_ms: bool,
+    /// Algorithm every key is limited with: `token_bucket`, or the name of
+    /// one registered with [`crate::algorithms::RateLimitAlgorithmRegistry`]
+    pub algorithm: String,
+    /// Send `X-RateLimit-Source` on every denial, naming the constraint
+    /// that fired: `key`, `global`, `quota`, `route` or `concurrency`
+    pub emit_limit_source: bool,
+    /// How often, in ms, to delete Redis buckets idle for longer than
+    /// `redis_sweep_max_age_ms` (0 = rely on TTL expiry alone)
+    pub redis_sweep_interval_ms: u64,
+    /// How long, in ms, a Redis bucket may go without a refill before the
+    /// sweeper deletes it
+    pub redis_sweep_max_age_ms: u64,
+    /// Whether keys containing `/` (sent as `%2F`) are rejected or have it
+    /// replaced
+    pub key_slashes: KeySlashes,
+    /// Upper bounds of the `throttler_remaining_tokens` histogram buckets,
+    /// recording the tokens left after each check (empty = not recorded)
+    pub remaining_histogram_buckets: Vec<f64>,
+    /// How long, in ms, the tokens an allowed check consumed may be partly
+    /// refunded with its refund id (0 = refunds disabled)
+    pub refund_window_ms: u64,
+    /// Most elapsed time, in seconds, a stored bucket's refill counts,
+    /// unless the bucket needs longer to fill; guards the refill arithmetic
+    /// without stopping a slow bucket from returning to full
+    pub max_refill_elapsed_secs: u64,
+    /// Operator token that unlocks the fleet-wide `/admin` endpoints while
+    /// `tenant_isolation` is on, sent as `X-Admin-Token` (empty = refused)
+    pub admin_token: String,
+    /// Longest a request may wait in a key's fair queue, in ms; one that
+    /// would wait longer is denied instead (0 = never wait)
+    pub fair_queue_max_wait_ms: u64,
+}
+
+/// One entry of `RULES_FILE`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SeedRule {
+    requests: u64,
+    window_ms: u64,
+    #[serde(default)]
+    metadata: HashMap<String, String>,
+}
+
+impl SeedRule {
+    /// `requests` is the burst capacity, refilled over the window, as for
+    /// rules set through the API
+    fn into_rule(self) -> RateLimitRule {
+        let requests = self.requests.min(u32::MAX as u64) as u32;
+        RateLimitRule::new(requests, requests, Duration::from_millis(self.window_ms))
+            .with_rate_unit(RateUnit::PerWindow)
+            .with_metadata(self.metadata)
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             redis_url: String::new(),
-            bind_address: "127.0.0.1:8080".to_string(),
+            redis_replica_url: String::new(),
+            redis_urls: Vec::new(),
+            redis_min_tls_version: None,
+            expiry_events: false,
+            bind_address: LOOPBACK_BIND_ADDRESS.to_string(),
             default_capacity: 100,
-            default_refill_rate: 10,
+            default_refill_rate: 10.0,
             environment: "development".to_string(),
             log_level: "info".to_string(),
+            redis_serialization: SerializationFormat::Json,
+            redis_op_timeout_ms: 250,
+            remaining_semantics: RemainingSemantics::After,
+            unknown_key_policy: UnknownKeyPolicy::AllowWithDefault,
+            max_clock_skew_ms: 1000,
+            shutdown_timeout_ms: 5000,
+            verbose_errors: false,
+            global_rate_limit: 0,
+            min_redis_write_interval_ms: 0,
+            hash_keys: false,
+            retry_budget: false,
+            response_headers: ResponseHeaderPolicy::All,
+            fair_queueing: false,
+            fair_queue_depth: 64,
+            max_rules: 10_000,
+            redis_race_retries: 3,
+            remaining_precision: 0,
+            max_retry_after_secs: 86_400,
+            tenant_isolation: false,
+            metrics_sample_rate: 1.0,
+            allowed_windows_ms: Vec::new(),
+            ipv6_aggregate_prefix: 64,
+            emit_utilization: false,
+            emit_server_timing: false,
+            adaptive_capacity: false,
+            adaptive_min_capacity: 10,
+            adaptive_max_capacity: 1000,
+            adaptive_interval_ms: 10_000,
+            consistency_mode: ConsistencyMode::Lenient,
+            max_json_depth: DEFAULT_MAX_JSON_DEPTH,
+            max_json_fields: DEFAULT_MAX_JSON_FIELDS,
+            rule_update_ordering: RuleUpdateOrdering::NextConsume,
+            lenient_content_type: false,
+            strict_get_bodies: false,
+            redis_wait_replicas: 0,
+            redis_wait_timeout_ms: 100,
+            key_case: KeyCase::Sensitive,
+            honor_request_deadline: false,
+            deny_status_code: 429,
+            seed_rules: Vec::new(),
+            trusted_proxy_hops: 0,
+            check_response_mode: CheckResponseMode::Full,
+            allow_debug: false,
+            max_redis_concurrency: 0,
+            redis_queue_timeout_ms: 50,
+            enforcement_rollout_pct: 100,
+            hybrid_local_burst: 0,
+            hybrid_sync_interval_ms: 1000,
+            concurrency_slot_ttl_ms: 30_000,
+            log_script_source: false,
+            race_log_size: 0,
+            warn_on_degraded: false,
+            min_full_refill_ms: 100,
+            metrics_flush_interval_ms: 0,
+            emit_retry_after_ms: false,
+            algorithm: "token_bucket".to_string(),
+            emit_limit_source: false,
+            redis_sweep_interval_ms: 0,
+            redis_sweep_max_age_ms: 86_400_000,
+            key_slashes: KeySlashes::Reject,
+            remaining_histogram_buckets: Vec::new(),
+            refund_window_ms: 0,
+            max_refill_elapsed_secs: DEFAULT_MAX_REFILL_ELAPSED_SECS,
+            admin_token: String::new(),
+            fair_queue_max_wait_ms: 1000,
         }
     }
 }
@@ -30,8 +599,27 @@ impl Config {
         let redis_url = env::var("REDIS_URL")
             .unwrap_or_else(|_| "redis://localhost:6379".to_string());
         
-        let bind_address = env::var("BIND_ADDRESS")
-            .unwrap_or_else(|_| "127.0.0.1:8080".to_string());
+        let redis_replica_url = env::var("REDIS_REPLICA_URL").unwrap_or_default();
+        
+        let redis_urls = env::var("REDIS_URLS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(str::to_string)
+            .collect();
+        
+        let redis_min_tls_version = match env::var("REDIS_MIN_TLS_VERSION") {
+            Ok(version) if !version.is_empty() => Some(version.parse()?),
+            _ => None,
+        };
+        
+        let expiry_events = env::var("EXPIRY_EVENTS")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid EXPIRY_EVENTS value".to_string()
+            ))?;
         
         let default_capacity = env::var("DEFAULT_CAPACITY")
             .unwrap_or_else(|_| "100".to_string())
@@ -50,16 +638,518 @@ impl Config {
         let environment = env::var("ENVIRONMENT")
             .unwrap_or_else(|_| "development".to_string());
         
+        // Loopback is unreachable from outside a container, so listen on all
+        // interfaces there and in production unless told otherwise
+        let bind_address = env::var("BIND_ADDRESS").unwrap_or_else(|_| {
+            Self::default_bind_address(&environment, Self::running_in_container()).to_string()
+        });
+        
         let log_level = env::var("LOG_LEVEL")
             .unwrap_or_else(|_| "info".to_string());
+
+        let redis_serialization = env::var("REDIS_SERIALIZATION")
+            .unwrap_or_else(|_| "json".to_string())
+            .parse()?;
+
+        let redis_op_timeout_ms = env::var("REDIS_OP_TIMEOUT_MS")
+            .unwrap_or_else(|_| "250".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid REDIS_OP_TIMEOUT_MS value".to_string()
+            ))?;
+
+        let remaining_semantics = env::var("REMAINING_SEMANTICS")
+            .unwrap_or_else(|_| "after".to_string())
+            .parse()?;
+
+        let unknown_key_policy = env::var("UNKNOWN_KEY_POLICY")
+            .unwrap_or_else(|_| "allow_with_default".to_string())
+            .parse()?;
+
+        let max_clock_skew_ms = env::var("MAX_CLOCK_SKEW_MS")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid MAX_CLOCK_SKEW_MS value".to_string()
+            ))?;
+
+        let shutdown_timeout_ms = env::var("SHUTDOWN_TIMEOUT_MS")
+            .unwrap_or_else(|_| "5000".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid SHUTDOWN_TIMEOUT_MS value".to_string()
+            ))?;
+        
+        // Detailed errors default on only for development
+        let verbose_errors = match env::var("VERBOSE_ERRORS") {
+            Ok(value) => value.parse().map_err(|_| ThrottlerError::ConfigError(
+                "Invalid VERBOSE_ERRORS value".to_string()
+            ))?,
+            Err(_) => environment.eq_ignore_ascii_case("development"),
+        };
+        
+        let global_rate_limit = env::var("GLOBAL_RATE_LIMIT")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid GLOBAL_RATE_LIMIT value".to_string()
+            ))?;
+        
+        let min_redis_write_interval_ms = env::var("MIN_REDIS_WRITE_INTERVAL_MS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid MIN_REDIS_WRITE_INTERVAL_MS value".to_string()
+            ))?;
+        
+        let hash_keys = env::var("HASH_KEYS")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid HASH_KEYS value".to_string()
+            ))?;
+        
+        let retry_budget = env::var("RETRY_BUDGET")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid RETRY_BUDGET value".to_string()
+            ))?;
+        
+        let response_headers = env::var("RESPONSE_HEADERS")
+            .unwrap_or_else(|_| "all".to_string())
+            .parse()?;
+        
+        let fair_queueing = env::var("FAIR_QUEUEING")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid FAIR_QUEUEING value".to_string()
+            ))?;
+        
+        let fair_queue_depth = env::var("FAIR_QUEUE_DEPTH")
+            .unwrap_or_else(|_| "64".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid FAIR_QUEUE_DEPTH value".to_string()
+            ))?;
+        
+        let max_rules = env::var("MAX_RULES")
+            .unwrap_or_else(|_| "10000".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid MAX_RULES value".to_string()
+            ))?;
+        
+        let redis_race_retries = env::var("REDIS_RACE_RETRIES")
+            .unwrap_or_else(|_| "3".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid REDIS_RACE_RETRIES value".to_string()
+            ))?;
+        
+        let remaining_precision = env::var("REMAINING_PRECISION")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid REMAINING_PRECISION value".to_string()
+            ))?;
+        
+        let max_retry_after_secs = env::var("MAX_RETRY_AFTER_SECS")
+            .unwrap_or_else(|_| "86400".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid MAX_RETRY_AFTER_SECS value".to_string()
+            ))?;
+        
+        let tenant_isolation = env::var("TENANT_ISOLATION")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid TENANT_ISOLATION value".to_string()
+            ))?;
+        
+        let metrics_sample_rate = env::var("METRICS_SAMPLE_RATE")
+            .unwrap_or_else(|_| "1.0".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid METRICS_SAMPLE_RATE value".to_string()
+            ))?;
+        
+        let allowed_windows_ms = env::var("ALLOWED_WINDOWS_MS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|window| !window.is_empty())
+            .map(|window| window.parse())
+            .collect::<Result<Vec<u64>, _>>()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid ALLOWED_WINDOWS_MS value".to_string()
+            ))?;
+        
+        let ipv6_aggregate_prefix = env::var("IPV6_AGGREGATE_PREFIX")
+            .unwrap_or_else(|_| "64".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid IPV6_AGGREGATE_PREFIX value".to_string()
+            ))?;
+        
+        let emit_utilization = env::var("EMIT_UTILIZATION")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid EMIT_UTILIZATION value".to_string()
+            ))?;
+        
+        let emit_server_timing = env::var("EMIT_SERVER_TIMING")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid EMIT_SERVER_TIMING value".to_string()
+            ))?;
+        
+        let adaptive_capacity = env::var("ADAPTIVE_CAPACITY")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid ADAPTIVE_CAPACITY value".to_string()
+            ))?;
+        
+        let adaptive_min_capacity = env::var("ADAPTIVE_MIN_CAPACITY")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid ADAPTIVE_MIN_CAPACITY value".to_string()
+            ))?;
+        
+        let adaptive_max_capacity = env::var("ADAPTIVE_MAX_CAPACITY")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid ADAPTIVE_MAX_CAPACITY value".to_string()
+            ))?;
+        
+        let adaptive_interval_ms = env::var("ADAPTIVE_INTERVAL_MS")
+            .unwrap_or_else(|_| "10000".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid ADAPTIVE_INTERVAL_MS value".to_string()
+            ))?;
+        
+        let consistency_mode = env::var("CONSISTENCY_MODE")
+            .unwrap_or_else(|_| "lenient".to_string())
+            .parse()?;
+        
+        let max_json_depth = env::var("MAX_JSON_DEPTH")
+            .unwrap_or_else(|_| "8".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid MAX_JSON_DEPTH value".to_string()
+            ))?;
+        
+        let max_json_fields = env::var("MAX_JSON_FIELDS")
+            .unwrap_or_else(|_| "64".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid MAX_JSON_FIELDS value".to_string()
+            ))?;
+        
+        let rule_update_ordering = env::var("RULE_UPDATE_ORDERING")
+            .unwrap_or_else(|_| "next_consume".to_string())
+            .parse()?;
+        
+        let lenient_content_type = env::var("LENIENT_CONTENT_TYPE")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid LENIENT_CONTENT_TYPE value".to_string()
+            ))?;
+        
+        let strict_get_bodies = env::var("STRICT_GET_BODIES")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid STRICT_GET_BODIES value".to_string()
+            ))?;
+        
+        let redis_wait_replicas = env::var("REDIS_WAIT_REPLICAS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid REDIS_WAIT_REPLICAS value".to_string()
+            ))?;
+        
+        let redis_wait_timeout_ms = env::var("REDIS_WAIT_TIMEOUT_MS")
+            .unwrap_or_else(|_| "100".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid REDIS_WAIT_TIMEOUT_MS value".to_string()
+            ))?;
+        
+        let key_case = env::var("KEY_CASE")
+            .unwrap_or_else(|_| "sensitive".to_string())
+            .parse()?;
+        
+        let honor_request_deadline = env::var("HONOR_REQUEST_DEADLINE")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid HONOR_REQUEST_DEADLINE value".to_string()
+            ))?;
+        
+        let deny_status_code = env::var("DENY_STATUS_CODE")
+            .unwrap_or_else(|_| "429".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid DENY_STATUS_CODE value".to_string()
+            ))?;
+        
+        let seed_rules = match env::var("RULES_FILE") {
+            Ok(path) if !path.is_empty() => Self::load_seed_rules(&path)?,
+            _ => Vec::new(),
+        };
+        
+        let trusted_proxy_hops = env::var("TRUSTED_PROXY_HOPS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid TRUSTED_PROXY_HOPS value".to_string()
+            ))?;
+        
+        let check_response_mode = env::var("CHECK_RESPONSE_MODE")
+            .unwrap_or_else(|_| "full".to_string())
+            .parse()?;
+        
+        // Like detailed errors, debug state defaults on only for development
+        let allow_debug = match env::var("ALLOW_DEBUG") {
+            Ok(value) => value.parse().map_err(|_| ThrottlerError::ConfigError(
+                "Invalid ALLOW_DEBUG value".to_string()
+            ))?,
+            Err(_) => environment.eq_ignore_ascii_case("development"),
+        };
+        
+        let max_redis_concurrency = env::var("MAX_REDIS_CONCURRENCY")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid MAX_REDIS_CONCURRENCY value".to_string()
+            ))?;
+        
+        let redis_queue_timeout_ms = env::var("REDIS_QUEUE_TIMEOUT_MS")
+            .unwrap_or_else(|_| "50".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid REDIS_QUEUE_TIMEOUT_MS value".to_string()
+            ))?;
+        
+        let enforcement_rollout_pct = env::var("ENFORCEMENT_ROLLOUT_PCT")
+            .unwrap_or_else(|_| "100".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid ENFORCEMENT_ROLLOUT_PCT value".to_string()
+            ))?;
+        
+        let hybrid_local_burst = env::var("HYBRID_LOCAL_BURST")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid HYBRID_LOCAL_BURST value".to_string()
+            ))?;
+        
+        let hybrid_sync_interval_ms = env::var("HYBRID_SYNC_INTERVAL_MS")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid HYBRID_SYNC_INTERVAL_MS value".to_string()
+            ))?;
+        
+        let concurrency_slot_ttl_ms = env::var("CONCURRENCY_SLOT_TTL_MS")
+            .unwrap_or_else(|_| "30000".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid CONCURRENCY_SLOT_TTL_MS value".to_string()
+            ))?;
+        
+        let log_script_source = env::var("LOG_SCRIPT_SOURCE")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid LOG_SCRIPT_SOURCE value".to_string()
+            ))?;
+        
+        let race_log_size = env::var("RACE_LOG_SIZE")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid RACE_LOG_SIZE value".to_string()
+            ))?;
+        
+        let warn_on_degraded = env::var("WARN_ON_DEGRADED")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid WARN_ON_DEGRADED value".to_string()
+            ))?;
+        
+        let min_full_refill_ms = env::var("MIN_FULL_REFILL_MS")
+            .unwrap_or_else(|_| "100".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid MIN_FULL_REFILL_MS value".to_string()
+            ))?;
+        
+        let metrics_flush_interval_ms = env::var("METRICS_FLUSH_INTERVAL_MS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid METRICS_FLUSH_INTERVAL_MS value".to_string()
+            ))?;
+        
+        let emit_retry_after_ms = env::var("EMIT_RETRY_AFTER_MS")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid EMIT_RETRY_AFTER_MS value".to_string()
+            ))?;
+        
+        let algorithm = env::var("RATE_LIMIT_ALGORITHM")
+            .unwrap_or_else(|_| "token_bucket".to_string());
+        
+        let emit_limit_source = env::var("EMIT_LIMIT_SOURCE")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid EMIT_LIMIT_SOURCE value".to_string()
+            ))?;
+        
+        let redis_sweep_interval_ms = env::var("REDIS_SWEEP_INTERVAL_MS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid REDIS_SWEEP_INTERVAL_MS value".to_string()
+            ))?;
+        
+        let redis_sweep_max_age_ms = env::var("REDIS_SWEEP_MAX_AGE_MS")
+            .unwrap_or_else(|_| "86400000".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid REDIS_SWEEP_MAX_AGE_MS value".to_string()
+            ))?;
+        
+        let key_slashes = env::var("KEY_SLASHES")
+            .unwrap_or_else(|_| "reject".to_string())
+            .parse()?;
+        
+        let remaining_histogram_buckets = env::var("REMAINING_HISTOGRAM_BUCKETS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|bound| !bound.is_empty())
+            .map(|bound| bound.parse())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid REMAINING_HISTOGRAM_BUCKETS value".to_string()
+            ))?;
+        
+        let refund_window_ms = env::var("REFUND_WINDOW_MS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid REFUND_WINDOW_MS value".to_string()
+            ))?;
+        
+        let max_refill_elapsed_secs = env::var("MAX_REFILL_ELAPSED_SECS")
+            .unwrap_or_else(|_| DEFAULT_MAX_REFILL_ELAPSED_SECS.to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid MAX_REFILL_ELAPSED_SECS value".to_string()
+            ))?;
+        
+        let admin_token = env::var("ADMIN_TOKEN").unwrap_or_default();
+        
+        let fair_queue_max_wait_ms = env::var("FAIR_QUEUE_MAX_WAIT_MS")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse()
+            .map_err(|_| ThrottlerError::ConfigError(
+                "Invalid FAIR_QUEUE_MAX_WAIT_MS value".to_string()
+            ))?;
         
         let config = Config {
             redis_url,
+            redis_replica_url,
+            redis_urls,
+            redis_min_tls_version,
+            expiry_events,
             bind_address,
             default_capacity,
             default_refill_rate,
             environment,
             log_level,
+            redis_serialization,
+            redis_op_timeout_ms,
+            remaining_semantics,
+            unknown_key_policy,
+            max_clock_skew_ms,
+            shutdown_timeout_ms,
+            verbose_errors,
+            global_rate_limit,
+            min_redis_write_interval_ms,
+            hash_keys,
+            retry_budget,
+            response_headers,
+            fair_queueing,
+            fair_queue_depth,
+            max_rules,
+            redis_race_retries,
+            remaining_precision,
+            max_retry_after_secs,
+            tenant_isolation,
+            metrics_sample_rate,
+            allowed_windows_ms,
+            ipv6_aggregate_prefix,
+            emit_utilization,
+            emit_server_timing,
+            adaptive_capacity,
+            adaptive_min_capacity,
+            adaptive_max_capacity,
+            adaptive_interval_ms,
+            consistency_mode,
+            max_json_depth,
+            max_json_fields,
+            rule_update_ordering,
+            lenient_content_type,
+            strict_get_bodies,
+            redis_wait_replicas,
+            redis_wait_timeout_ms,
+            key_case,
+            honor_request_deadline,
+            deny_status_code,
+            seed_rules,
+            trusted_proxy_hops,
+            check_response_mode,
+            allow_debug,
+            max_redis_concurrency,
+            redis_queue_timeout_ms,
+            enforcement_rollout_pct,
+            hybrid_local_burst,
+            hybrid_sync_interval_ms,
+            concurrency_slot_ttl_ms,
+            log_script_source,
+            race_log_size,
+            warn_on_degraded,
+            min_full_refill_ms,
+            metrics_flush_interval_ms,
+            emit_retry_after_ms,
+            algorithm,
+            emit_limit_source,
+            redis_sweep_interval_ms,
+            redis_sweep_max_age_ms,
+            key_slashes,
+            remaining_histogram_buckets,
+            refund_window_ms,
+            max_refill_elapsed_secs,
+            admin_token,
+            fair_queue_max_wait_ms,
         };
         
         config.validate()?;
@@ -69,13 +1159,116 @@ impl Config {
     /// Validates all configuration values
     pub fn validate(&self) -> Result<(), ThrottlerError> {
         ConfigValidator::validate_redis_url(&self.redis_url)?;
+        if !self.redis_replica_url.is_empty() {
+            ConfigValidator::validate_redis_url(&self.redis_replica_url)?;
+        }
+        for url in &self.redis_urls {
+            ConfigValidator::validate_redis_url(url)?;
+        }
+        if let Some(version) = self.redis_min_tls_version {
+            let mut urls = self.redis_nodes();
+            if !self.redis_replica_url.is_empty() {
+                urls.push(self.redis_replica_url.clone());
+            }
+            for url in &urls {
+                ConfigValidator::validate_redis_tls_url(url, version)?;
+            }
+        }
+        ConfigValidator::validate_replica_sharding(&self.redis_replica_url, self.redis_urls.len())?;
         ConfigValidator::validate_bind_address(&self.bind_address)?;
         ConfigValidator::validate_rate_limit(self.default_capacity, self.default_refill_rate)?;
+        ConfigValidator::validate_full_refill_interval(
+            self.default_capacity,
+            self.default_refill_rate,
+            self.min_full_refill_ms,
+        )?;
         ConfigValidator::validate_environment(&self.environment)?;
+        ConfigValidator::validate_remaining_precision(self.remaining_precision)?;
+        ConfigValidator::validate_metrics_sample_rate(self.metrics_sample_rate)?;
+        ConfigValidator::validate_ipv6_aggregate_prefix(self.ipv6_aggregate_prefix)?;
+        ConfigValidator::validate_deny_status_code(self.deny_status_code)?;
+        ConfigValidator::validate_enforcement_rollout_pct(self.enforcement_rollout_pct)?;
+        ConfigValidator::validate_concurrency_slot_ttl(self.concurrency_slot_ttl_ms)?;
+        ConfigValidator::validate_max_refill_elapsed(self.max_refill_elapsed_secs)?;
+        if self.redis_wait_replicas > 0 {
+            ConfigValidator::validate_redis_wait(self.redis_wait_timeout_ms, self.redis_op_timeout_ms)?;
+        }
+        if self.adaptive_capacity {
+            ConfigValidator::validate_adaptive_bounds(self.adaptive_min_capacity, self.adaptive_max_capacity)?;
+        }
         
         Ok(())
     }
     
+    /// Reads rules to seed at startup from a JSON file mapping each key to
+    /// a rule in the shape `POST /rate-limit/:key` accepts:
+    ///
+    /// ```json
+    /// {"api-client-123": {"requests": 500, "window_ms": 60000}}
+    /// ```
+    pub fn load_seed_rules(path: &str) -> Result<Vec<(String, RateLimitRule)>, ThrottlerError> {
+        let contents = std::fs::read_to_string(path).map_err(|e| ThrottlerError::ConfigError(
+            format!("Failed to read RULES_FILE {}: {}", path, e)
+        ))?;
+        let rules: BTreeMap<String, SeedRule> = serde_json::from_str(&contents).map_err(|e| {
+            ThrottlerError::ConfigError(format!("Invalid RULES_FILE {}: {}", path, e))
+        })?;
+
+        Ok(rules.into_iter().map(|(key, rule)| (key, rule.into_rule())).collect())
+    }
+    
+    /// Status a check denied by the key's own limit answers with
+    pub fn deny_status(&self) -> StatusCode {
+        StatusCode::from_u16(self.deny_status_code).unwrap_or(StatusCode::TOO_MANY_REQUESTS)
+    }
+    
+    /// The Redis nodes buckets are stored on: `redis_urls` when set, else
+    /// `redis_url` alone, else none (local-only)
+    pub fn redis_nodes(&self) -> Vec<String> {
+        if !self.redis_urls.is_empty() {
+            self.redis_urls.clone()
+        } else if !self.redis_url.is_empty() {
+            vec![self.redis_url.clone()]
+        } else {
+            Vec::new()
+        }
+    }
+    
+    /// Formats a remaining count for `X-RateLimit-Remaining` with
+    /// `remaining_precision` decimal places.
+    ///
+    /// The value is truncated rather than rounded so clients are never told
+    /// they have more than they do; with precision 0 it is the usual integer.
+    pub fn format_remaining(&self, remaining: f64) -> String {
+        let precision = self.remaining_precision;
+        if precision == 0 {
+            return (remaining.floor() as u64).to_string();
+        }
+
+        // The epsilon keeps e.g. 2.3 from truncating to 2.2999...
+        let scale = 10f64.powi(precision as i32);
+        let truncated = ((remaining * scale) + 1e-9).floor() / scale;
+        format!("{:.*}", precision as usize, truncated.max(0.0))
+    }
+
+    /// Bind address used when `BIND_ADDRESS` is unset: loopback for local
+    /// runs, all interfaces in production or inside a container
+    pub fn default_bind_address(environment: &str, in_container: bool) -> &'static str {
+        if in_container || environment.eq_ignore_ascii_case("production") {
+            ALL_INTERFACES_BIND_ADDRESS
+        } else {
+            LOOPBACK_BIND_ADDRESS
+        }
+    }
+    
+    /// Whether the process appears to run in a container (Docker, Podman
+    /// or a Kubernetes pod)
+    pub fn running_in_container() -> bool {
+        Path::new("/.dockerenv").exists()
+            || Path::new("/run/.containerenv").exists()
+            || env::var_os("KUBERNETES_SERVICE_HOST").is_some()
+    }
+    
     /// Returns true if running in production environment
     pub fn is_production(&self) -> bool {
         self.environment.to_lowercase() == "production"
@@ -85,4 +1278,89 @@ impl Config {
     pub fn is_development(&self) -> bool {
         self.environment.to_lowercase() == "development"
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_bind_address_per_environment() {
+        assert_eq!(Config::default_bind_address("development", false), LOOPBACK_BIND_ADDRESS);
+        assert_eq!(Config::default_bind_address("staging", false), LOOPBACK_BIND_ADDRESS);
+        assert_eq!(Config::default_bind_address("test", false), LOOPBACK_BIND_ADDRESS);
+        assert_eq!(Config::default_bind_address("production", false), ALL_INTERFACES_BIND_ADDRESS);
+        assert_eq!(Config::default_bind_address("Production", false), ALL_INTERFACES_BIND_ADDRESS);
+    }
+
+    #[test]
+    fn test_default_bind_address_in_container() {
+        for environment in ["development", "staging", "test", "production"] {
+            assert_eq!(Config::default_bind_address(environment, true), ALL_INTERFACES_BIND_ADDRESS);
+        }
+    }
+
+    #[test]
+    fn test_min_tls_version_requires_tls_urls() {
+        assert_eq!("1.3".parse::<TlsVersion>().unwrap(), TlsVersion::Tls13);
+        assert_eq!("TLSv1.2".parse::<TlsVersion>().unwrap(), TlsVersion::Tls12);
+        assert!("1.1".parse::<TlsVersion>().is_err());
+        assert!("1.0".parse::<TlsVersion>().is_err());
+
+        let config = Config {
+            redis_url: "rediss://redis:6380".to_string(),
+            redis_min_tls_version: Some(TlsVersion::Tls13),
+            ..Config::default()
+        };
+        assert!(config.validate().is_ok());
+
+        let plaintext_replica = Config { redis_replica_url: "redis://replica:6379".to_string(), ..config.clone() };
+        assert!(plaintext_replica.validate().is_err());
+
+        let plaintext_shard = Config {
+            redis_urls: vec!["rediss://a:6380".to_string(), "redis://b:6379".to_string()],
+            ..config
+        };
+        assert!(plaintext_shard.validate().is_err());
+    }
+
+    #[test]
+    fn test_replica_is_rejected_with_sharding() {
+        let sharded = Config {
+            redis_url: "redis://redis:6379".to_string(),
+            redis_urls: vec!["redis://a:6379".to_string(), "redis://b:6379".to_string()],
+            ..Config::default()
+        };
+        assert!(sharded.validate().is_ok());
+
+        let with_replica = Config { redis_replica_url: "redis://replica:6379".to_string(), ..sharded.clone() };
+        assert!(with_replica.validate().is_err());
+
+        // A single node is not sharded
+        let single = Config { redis_urls: vec!["redis://a:6379".to_string()], ..with_replica };
+        assert!(single.validate().is_ok());
+    }
+
+    #[test]
+    fn test_load_seed_rules_from_file() {
+        let path = std::env::temp_dir().join(format!("throttler-rules-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, r#"{
+            "partner-b": {"requests": 50, "window_ms": 1000},
+            "partner-a": {"requests": 500, "window_ms": 60000, "metadata": {"plan": "gold"}}
+        }"#).unwrap();
+
+        let rules = Config::load_seed_rules(path.to_str().unwrap()).unwrap();
+        assert_eq!(rules.len(), 2);
+        let (key, rule) = &rules[0];
+        assert_eq!(key, "partner-a");
+        assert_eq!(rule.burst_capacity, 500);
+        assert_eq!(rule.window_size, Duration::from_secs(60));
+        assert_eq!(rule.metadata["plan"], "gold");
+
+        std::fs::write(&path, r#"{"partner-a": {"requests": 500}}"#).unwrap();
+        assert!(Config::load_seed_rules(path.to_str().unwrap()).is_err());
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(Config::load_seed_rules("/nonexistent/rules.json").is_err());
+    }
+}
@@ -1,5 +1,16 @@
+use crate::config::TlsVersion;
 use crate::error::ThrottlerError;
 
+/// Most decimal places `X-RateLimit-Remaining` may be emitted with
+pub const MAX_REMAINING_PRECISION: u32 = 6;
+
+/// Largest accepted bucket capacity.
+///
+/// Token counts are `f64`, which hold whole numbers exactly only up to 2^53
+/// and lose fractional resolution well before that. At this ceiling a
+/// count still resolves to better than a thousandth of a token.
+pub const MAX_CAPACITY: u64 = 1_000_000_000_000;
+
 /// Validates configuration objects for consistency and correctness
 pub struct ConfigValidator;
 
@@ -21,6 +32,26 @@ impl ConfigValidator {
         Ok(())
     }
 
+    /// Validates that a Redis URL can honor a minimum TLS version: it must
+    /// use TLS, and with certificate checks on, since the version is
+    /// confirmed by a verified handshake
+    pub fn validate_redis_tls_url(url: &str, min_version: TlsVersion) -> Result<(), ThrottlerError> {
+        if !url.starts_with("rediss://") {
+            return Err(ThrottlerError::ValidationError(format!(
+                "Redis URL must start with 'rediss://' when a minimum TLS version ({:?}) is set",
+                min_version
+            )));
+        }
+
+        if url.ends_with("#insecure") {
+            return Err(ThrottlerError::ValidationError(
+                "Redis URL cannot use '#insecure' when a minimum TLS version is set".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
     /// Validates a bind address
     pub fn validate_bind_address(address: &str) -> Result<(), ThrottlerError> {
         if address.is_empty() {
@@ -40,22 +71,186 @@ impl ConfigValidator {
     }
 
     /// Validates rate limit parameters
-    pub fn validate_rate_limit(capacity: u64, refill_rate: u64) -> Result<(), ThrottlerError> {
+    pub fn validate_rate_limit(capacity: u64, refill_rate: f64) -> Result<(), ThrottlerError> {
         if capacity == 0 {
             return Err(ThrottlerError::ValidationError(
                 "Rate limit capacity must be greater than 0".to_string(),
             ));
         }
 
-        if refill_rate == 0 {
+        if capacity > MAX_CAPACITY {
+            return Err(ThrottlerError::ValidationError(format!(
+                "Rate limit capacity must be at most {}",
+                MAX_CAPACITY
+            )));
+        }
+
+        if !refill_rate.is_finite() || refill_rate <= 0.0 {
+            return Err(ThrottlerError::ValidationError(
+                "Refill rate must be a finite number greater than 0".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Validates the number of decimal places used for remaining counts
+    pub fn validate_remaining_precision(precision: u32) -> Result<(), ThrottlerError> {
+        if precision > MAX_REMAINING_PRECISION {
+            return Err(ThrottlerError::ValidationError(format!(
+                "Remaining precision must be at most {} decimal places",
+                MAX_REMAINING_PRECISION
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Validates the fraction of requests recorded in metrics
+    pub fn validate_metrics_sample_rate(rate: f64) -> Result<(), ThrottlerError> {
+        if !(0.0..=1.0).contains(&rate) {
             return Err(ThrottlerError::ValidationError(
-                "Refill rate must be greater than 0".to_string(),
+                "Metrics sample rate must be between 0.0 and 1.0".to_string()
             ));
         }
 
         Ok(())
     }
 
+    /// Validates the IPv6 prefix length used to aggregate per-IP keys
+    pub fn validate_ipv6_aggregate_prefix(prefix: u8) -> Result<(), ThrottlerError> {
+        if !(1..=128).contains(&prefix) {
+            return Err(ThrottlerError::ValidationError(
+                "IPv6 aggregate prefix must be between 1 and 128".to_string()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Validates the bounds adaptive capacity may move a key within
+    pub fn validate_adaptive_bounds(min: u64, max: u64) -> Result<(), ThrottlerError> {
+        if min == 0 {
+            return Err(ThrottlerError::ValidationError(
+                "Adaptive minimum capacity must be greater than 0".to_string()
+            ));
+        }
+        if min > max {
+            return Err(ThrottlerError::ValidationError(
+                "Adaptive minimum capacity cannot exceed the maximum".to_string()
+            ));
+        }
+        if max > MAX_CAPACITY {
+            return Err(ThrottlerError::ValidationError(
+                format!("Adaptive maximum capacity cannot exceed {}", MAX_CAPACITY)
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Validates that a read replica is not combined with sharding: the
+    /// replica follows one primary, so reads for keys on every other shard
+    /// would go to the wrong node
+    pub fn validate_replica_sharding(replica_url: &str, shards: usize) -> Result<(), ThrottlerError> {
+        if !replica_url.is_empty() && shards > 1 {
+            return Err(ThrottlerError::ValidationError(
+                "REDIS_REPLICA_URL cannot be used with more than one REDIS_URLS node".to_string()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Validates that a `WAIT` for replicas can finish within the per-operation
+    /// socket timeout (0 = no timeout), so a slow replica is reported rather
+    /// than failing the write it follows
+    pub fn validate_redis_wait(wait_timeout_ms: u64, op_timeout_ms: u64) -> Result<(), ThrottlerError> {
+        if wait_timeout_ms == 0 {
+            return Err(ThrottlerError::ValidationError(
+                "Redis WAIT timeout must be greater than 0".to_string()
+            ));
+        }
+        if op_timeout_ms > 0 && wait_timeout_ms >= op_timeout_ms {
+            return Err(ThrottlerError::ValidationError(
+                format!("Redis WAIT timeout must be below the {}ms operation timeout", op_timeout_ms)
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Validates the status a denied check answers with: a success (for
+    /// clients that read `allowed` themselves) or an error status
+    pub fn validate_deny_status_code(code: u16) -> Result<(), ThrottlerError> {
+        if !(200..=299).contains(&code) && !(400..=599).contains(&code) {
+            return Err(ThrottlerError::ValidationError(
+                format!("Deny status code {} must be 2xx, 4xx or 5xx", code)
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Validates the percentage of keys under enforcement
+    pub fn validate_enforcement_rollout_pct(pct: u8) -> Result<(), ThrottlerError> {
+        if pct > 100 {
+            return Err(ThrottlerError::ValidationError(
+                format!("Enforcement rollout {}% must be between 0 and 100", pct)
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Validates the concurrency slot TTL: slots that lapse at once would
+    /// never count against a limit
+    pub fn validate_concurrency_slot_ttl(ttl_ms: u64) -> Result<(), ThrottlerError> {
+        if ttl_ms == 0 {
+            return Err(ThrottlerError::ValidationError(
+                "Concurrency slot TTL must be greater than 0".to_string()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Validates the refill elapsed cap, which must be positive
+    pub fn validate_max_refill_elapsed(max_elapsed_secs: u64) -> Result<(), ThrottlerError> {
+        if max_elapsed_secs == 0 {
+            return Err(ThrottlerError::ValidationError(
+                "Max refill elapsed time must be greater than 0".to_string()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Flags a refill rate that refills the whole `capacity` in under
+    /// `min_full_refill_ms`: such a bucket is full again between almost any
+    /// two requests, so it limits nothing and is most likely a typo (e.g. a
+    /// per-minute rate entered per second). 0 disables the check.
+    pub fn validate_full_refill_interval(
+        capacity: u64,
+        refill_rate: f64,
+        min_full_refill_ms: u64,
+    ) -> Result<(), ThrottlerError> {
+        if min_full_refill_ms == 0 || refill_rate <= 0.0 {
+            return Ok(());
+        }
+
+        let full_refill_ms = capacity as f64 / refill_rate * 1000.0;
+        if full_refill_ms < min_full_refill_ms as f64 {
+            return Err(ThrottlerError::ValidationError(format!(
+                "Refill rate {}/s refills the full capacity of {} in {:.1}ms, under the {}ms minimum; \
+                 the limit would be ineffective",
+                refill_rate, capacity, full_refill_ms, min_full_refill_ms
+            )));
+        }
+
+        Ok(())
+    }
+
     /// Validates environment name
     pub fn validate_environment(env: &str) -> Result<(), ThrottlerError> {
         let valid_envs = ["development", "staging", "production", "test"];
@@ -108,6 +303,13 @@ mod tests {
         assert!(ConfigValidator::validate_redis_url("http://localhost:6379").is_err());
     }
 
+    #[test]
+    fn test_redis_tls_url_for_minimum_version() {
+        assert!(ConfigValidator::validate_redis_tls_url("rediss://redis:6380", TlsVersion::Tls13).is_ok());
+        assert!(ConfigValidator::validate_redis_tls_url("redis://redis:6379", TlsVersion::Tls12).is_err());
+        assert!(ConfigValidator::validate_redis_tls_url("rediss://redis:6380/#insecure", TlsVersion::Tls12).is_err());
+    }
+
     #[test]
     fn test_valid_bind_address() {
         assert!(ConfigValidator::validate_bind_address("127.0.0.1:8080").is_ok());
@@ -122,13 +324,107 @@ mod tests {
 
     #[test]
     fn test_valid_rate_limit() {
-        assert!(ConfigValidator::validate_rate_limit(100, 10).is_ok());
+        assert!(ConfigValidator::validate_rate_limit(100, 10.0).is_ok());
+        assert!(ConfigValidator::validate_rate_limit(100, 0.5).is_ok());
+        assert!(ConfigValidator::validate_rate_limit(MAX_CAPACITY, 10.0).is_ok());
     }
 
     #[test]
     fn test_invalid_rate_limit() {
-        assert!(ConfigValidator::validate_rate_limit(0, 10).is_err());
-        assert!(ConfigValidator::validate_rate_limit(100, 0).is_err());
+        assert!(ConfigValidator::validate_rate_limit(0, 10.0).is_err());
+        assert!(ConfigValidator::validate_rate_limit(100, 0.0).is_err());
+        assert!(ConfigValidator::validate_rate_limit(100, -1.0).is_err());
+        assert!(ConfigValidator::validate_rate_limit(100, f64::NAN).is_err());
+        assert!(ConfigValidator::validate_rate_limit(100, f64::INFINITY).is_err());
+        assert!(ConfigValidator::validate_rate_limit(MAX_CAPACITY + 1, 10.0).is_err());
+        assert!(ConfigValidator::validate_rate_limit(u64::MAX, 10.0).is_err());
+    }
+
+    #[test]
+    fn test_remaining_precision_bounds() {
+        assert!(ConfigValidator::validate_remaining_precision(0).is_ok());
+        assert!(ConfigValidator::validate_remaining_precision(MAX_REMAINING_PRECISION).is_ok());
+        assert!(ConfigValidator::validate_remaining_precision(MAX_REMAINING_PRECISION + 1).is_err());
+    }
+
+    #[test]
+    fn test_metrics_sample_rate_bounds() {
+        assert!(ConfigValidator::validate_metrics_sample_rate(0.0).is_ok());
+        assert!(ConfigValidator::validate_metrics_sample_rate(0.25).is_ok());
+        assert!(ConfigValidator::validate_metrics_sample_rate(1.0).is_ok());
+        assert!(ConfigValidator::validate_metrics_sample_rate(1.5).is_err());
+        assert!(ConfigValidator::validate_metrics_sample_rate(-0.1).is_err());
+        assert!(ConfigValidator::validate_metrics_sample_rate(f64::NAN).is_err());
+    }
+
+    #[test]
+    fn test_adaptive_bounds() {
+        assert!(ConfigValidator::validate_adaptive_bounds(10, 1000).is_ok());
+        assert!(ConfigValidator::validate_adaptive_bounds(50, 50).is_ok());
+        assert!(ConfigValidator::validate_adaptive_bounds(0, 1000).is_err());
+        assert!(ConfigValidator::validate_adaptive_bounds(100, 10).is_err());
+        assert!(ConfigValidator::validate_adaptive_bounds(1, MAX_CAPACITY + 1).is_err());
+    }
+
+    #[test]
+    fn test_redis_wait_fits_operation_timeout() {
+        assert!(ConfigValidator::validate_redis_wait(100, 250).is_ok());
+        assert!(ConfigValidator::validate_redis_wait(5000, 0).is_ok());
+        assert!(ConfigValidator::validate_redis_wait(0, 250).is_err());
+        assert!(ConfigValidator::validate_redis_wait(250, 250).is_err());
+    }
+
+    #[test]
+    fn test_deny_status_code_classes() {
+        assert!(ConfigValidator::validate_deny_status_code(429).is_ok());
+        assert!(ConfigValidator::validate_deny_status_code(200).is_ok());
+        assert!(ConfigValidator::validate_deny_status_code(503).is_ok());
+        assert!(ConfigValidator::validate_deny_status_code(302).is_err());
+        assert!(ConfigValidator::validate_deny_status_code(99).is_err());
+        assert!(ConfigValidator::validate_deny_status_code(600).is_err());
+    }
+
+    #[test]
+    fn test_enforcement_rollout_pct_bounds() {
+        assert!(ConfigValidator::validate_enforcement_rollout_pct(0).is_ok());
+        assert!(ConfigValidator::validate_enforcement_rollout_pct(100).is_ok());
+        assert!(ConfigValidator::validate_enforcement_rollout_pct(101).is_err());
+    }
+
+    #[test]
+    fn test_concurrency_slot_ttl_must_be_positive() {
+        assert!(ConfigValidator::validate_concurrency_slot_ttl(1).is_ok());
+        assert!(ConfigValidator::validate_concurrency_slot_ttl(0).is_err());
+    }
+
+    #[test]
+    fn test_max_refill_elapsed_must_be_positive() {
+        assert!(ConfigValidator::validate_max_refill_elapsed(1).is_ok());
+        assert!(ConfigValidator::validate_max_refill_elapsed(0).is_err());
+    }
+
+    #[test]
+    fn test_refill_that_outpaces_capacity_is_flagged() {
+        // 100 tokens refilled at 10/s: 10 seconds to refill, fine
+        assert!(ConfigValidator::validate_full_refill_interval(100, 10.0, 100).is_ok());
+        // Exactly at the minimum passes
+        assert!(ConfigValidator::validate_full_refill_interval(10, 100.0, 100).is_ok());
+
+        // 10 tokens at 1,000,000/s refill in 10µs
+        let err = ConfigValidator::validate_full_refill_interval(10, 1_000_000.0, 100).unwrap_err();
+        assert!(err.to_string().contains("under the 100ms minimum"), "{}", err);
+
+        // Disabled with 0
+        assert!(ConfigValidator::validate_full_refill_interval(10, 1_000_000.0, 0).is_ok());
+    }
+
+    #[test]
+    fn test_ipv6_aggregate_prefix_bounds() {
+        assert!(ConfigValidator::validate_ipv6_aggregate_prefix(1).is_ok());
+        assert!(ConfigValidator::validate_ipv6_aggregate_prefix(64).is_ok());
+        assert!(ConfigValidator::validate_ipv6_aggregate_prefix(128).is_ok());
+        assert!(ConfigValidator::validate_ipv6_aggregate_prefix(0).is_err());
+        assert!(ConfigValidator::validate_ipv6_aggregate_prefix(129).is_err());
     }
 
     #[test]
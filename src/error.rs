@@ -14,8 +14,15 @@
 //! │  ────────────────────────────┼─────────────────────┼───────────────────│
 //! │  RateLimitExceeded           │  429 Too Many Reqs  │  + Retry-After    │
 //! │  ValidationError             │  400 Bad Request    │  JSON error       │
+//! │  LimitOutOfRange             │  400 Bad Request    │  + code           │
 //! │  InvalidKey                  │  400 Bad Request    │  JSON error       │
 //! │  ConfigError                 │  400 Bad Request    │  JSON error       │
+//! │  UnknownKey                  │  403 Forbidden      │  JSON error       │
+//! │  Forbidden                   │  403 Forbidden      │  JSON error       │
+//! │  RuleNotFound                │  404 Not Found      │  JSON error       │
+//! │  VersionConflict             │  409 Conflict       │  + current_version│
+//! │  StoreUnavailable            │  503 Unavailable    │  + Retry-After    │
+//! │  DeadlineExceeded            │  504 Gateway Timeout│  JSON error       │
 //! │  RedisError                  │  500 Internal Error │  Generic error    │
 //! │  SerializationError          │  500 Internal Error │  Generic error    │
 //! │  InternalError               │  500 Internal Error │  Generic error    │
@@ -23,6 +30,14 @@
 //! └─────────────────────────────────────────────────────────────────────────┘
 //! ```
 //!
+//! ## Internal Error Details
+//!
+//! 500 responses never include the underlying error text. Instead they carry
+//! an `error_id` that is logged together with the full error so operators
+//! can correlate a client report with the server logs. The detail is also
+//! attached to the response as an [`ErrorDetail`] extension, which the
+//! `verbose_errors` middleware uses to expose it in development.
+//!
 //! ## Automatic Conversions
 //!
 //! The error type implements `From` for automatic conversion:
@@ -82,6 +97,15 @@ pub enum ThrottlerError {
     /// Maps to: 400 Bad Request
     ValidationError(String),
 
+    /// A rule's request count or window broke one of the configured bounds
+    /// Maps to: 400 Bad Request (with a `code` naming the constraint)
+    LimitOutOfRange {
+        /// The constraint that was broken
+        constraint: LimitConstraint,
+        /// Human-readable description, including the bound
+        message: String,
+    },
+
     /// Rate limit was exceeded for the requested key
     /// Maps to: 429 Too Many Requests (with Retry-After header)
     RateLimitExceeded {
@@ -104,16 +128,88 @@ pub enum ThrottlerError {
     /// JSON serialization/deserialization failed
     /// Maps to: 500 Internal Server Error
     SerializationError(String),
+
+    /// Key has no configured rule and unknown keys are denied
+    /// Maps to: 403 Forbidden
+    UnknownKey(String),
+
+    /// The caller may not use this operation, e.g. a fleet-wide admin
+    /// endpoint under tenant isolation without the operator token
+    /// Maps to: 403 Forbidden
+    Forbidden(String),
+
+    /// Operation requires an existing rule but the key has none
+    /// Maps to: 404 Not Found
+    RuleNotFound(String),
+
+    /// The authoritative bucket store is unreachable and strict consistency
+    /// forbids falling back to local state
+    /// Maps to: 503 Service Unavailable (with Retry-After header)
+    StoreUnavailable(String),
+
+    /// The caller's `X-Request-Deadline` passed before the work finished
+    /// Maps to: 504 Gateway Timeout
+    DeadlineExceeded(String),
+
+    /// A conditional rule update expected a version the rule no longer has
+    /// Maps to: 409 Conflict
+    VersionConflict {
+        /// The rule's key
+        key: String,
+        /// Version currently stored, or `None` if the key has no rule
+        current: Option<u64>,
+    },
 }
 
 impl std::error::Error for ThrottlerError {}
 
+/// Bound on a rule's `requests` or `window_ms` that a
+/// [`ThrottlerError::LimitOutOfRange`] reports, so clients can react to each
+/// one without parsing the message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitConstraint {
+    /// `requests` was 0
+    ZeroRequests,
+    /// `requests` was above the per-window maximum
+    MaxRequests,
+    /// `window_ms` was below the minimum
+    MinWindow,
+    /// `window_ms` was above the maximum
+    MaxWindow,
+    /// `window_ms` was not one of the allowed window tiers
+    WindowNotAllowed,
+}
+
+impl LimitConstraint {
+    /// Machine-readable code sent as the error body's `code` field
+    pub fn code(&self) -> &'static str {
+        match self {
+            LimitConstraint::ZeroRequests => "requests_zero",
+            LimitConstraint::MaxRequests => "requests_above_max",
+            LimitConstraint::MinWindow => "window_below_min",
+            LimitConstraint::MaxWindow => "window_above_max",
+            LimitConstraint::WindowNotAllowed => "window_not_allowed",
+        }
+    }
+}
+
+/// Full description of an internal error, attached to 500 responses as an
+/// extension so middleware can decide whether to expose it.
+#[derive(Debug, Clone)]
+pub struct ErrorDetail {
+    /// Correlation id included in the response body and the error log
+    pub error_id: String,
+    /// The detailed error message
+    pub message: String,
+}
+
 impl fmt::Display for ThrottlerError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ThrottlerError::RedisError(msg) => write!(f, "Redis error: {}", msg),
             ThrottlerError::ConfigError(msg) => write!(f, "Configuration error: {}", msg),
             ThrottlerError::ValidationError(msg) => write!(f, "Validation error: {}", msg),
+            ThrottlerError::LimitOutOfRange { message, .. } => write!(f, "Validation error: {}", message),
             ThrottlerError::RateLimitExceeded { retry_after, limit, window_ms } => {
                 write!(f, "Rate limit exceeded: {} requests per {}ms window. Retry after {}s",
                        limit, window_ms, retry_after)
@@ -121,12 +217,24 @@ impl fmt::Display for ThrottlerError {
             ThrottlerError::InternalError(msg) => write!(f, "Internal error: {}", msg),
             ThrottlerError::InvalidKey(key) => write!(f, "Invalid key format: {}", key),
             ThrottlerError::SerializationError(msg) => write!(f, "Serialization error: {}", msg),
+            ThrottlerError::UnknownKey(key) => write!(f, "No rate limit rule configured for key: {}", key),
+            ThrottlerError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
+            ThrottlerError::RuleNotFound(key) => write!(f, "No configuration found for key: {}", key),
+            ThrottlerError::StoreUnavailable(msg) => write!(f, "Rate limit store unavailable: {}", msg),
+            ThrottlerError::DeadlineExceeded(msg) => write!(f, "Deadline exceeded: {}", msg),
+            ThrottlerError::VersionConflict { key, current: Some(current) } => {
+                write!(f, "Rule for key {} has changed; current version is {}", key, current)
+            }
+            ThrottlerError::VersionConflict { key, current: None } => {
+                write!(f, "Rule for key {} does not exist", key)
+            }
         }
     }
 }
 
 impl IntoResponse for ThrottlerError {
     fn into_response(self) -> Response {
+        let mut detail = None;
         let (status, body) = match &self {
             ThrottlerError::RateLimitExceeded { retry_after, limit, window_ms } => {
                 (
@@ -149,6 +257,16 @@ impl IntoResponse for ThrottlerError {
                     })
                 )
             },
+            ThrottlerError::LimitOutOfRange { constraint, .. } => {
+                (
+                    StatusCode::BAD_REQUEST,
+                    serde_json::json!({
+                        "error": "validation_error",
+                        "code": constraint.code(),
+                        "message": self.to_string()
+                    })
+                )
+            },
             ThrottlerError::ConfigError(_) => {
                 (
                     StatusCode::BAD_REQUEST,
@@ -158,18 +276,83 @@ impl IntoResponse for ThrottlerError {
                     })
                 )
             },
-            _ => {
+            ThrottlerError::UnknownKey(_) => {
                 (
-                    StatusCode::INTERNAL_SERVER_ERROR,
+                    StatusCode::FORBIDDEN,
                     serde_json::json!({
-                        "error": "internal_error",
-                        "message": "An unexpected error occurred"
+                        "error": "unknown_key",
+                        "message": self.to_string()
                     })
                 )
+            },
+            ThrottlerError::Forbidden(_) => {
+                (
+                    StatusCode::FORBIDDEN,
+                    serde_json::json!({
+                        "error": "forbidden",
+                        "message": self.to_string()
+                    })
+                )
+            },
+            ThrottlerError::RuleNotFound(_) => {
+                (
+                    StatusCode::NOT_FOUND,
+                    serde_json::json!({
+                        "error": "not_found",
+                        "message": self.to_string()
+                    })
+                )
+            },
+            ThrottlerError::StoreUnavailable(_) => {
+                tracing::error!(error = %self, "Rejecting request in strict consistency mode");
+                (
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    serde_json::json!({
+                        "error": "store_unavailable",
+                        "message": "Rate limit state is temporarily unavailable"
+                    })
+                )
+            },
+            ThrottlerError::DeadlineExceeded(_) => {
+                (
+                    StatusCode::GATEWAY_TIMEOUT,
+                    serde_json::json!({
+                        "error": "deadline_exceeded",
+                        "message": self.to_string()
+                    })
+                )
+            },
+            ThrottlerError::VersionConflict { current, .. } => {
+                (
+                    StatusCode::CONFLICT,
+                    serde_json::json!({
+                        "error": "version_conflict",
+                        "message": self.to_string(),
+                        "current_version": current
+                    })
+                )
+            },
+            _ => {
+                let error_id = uuid::Uuid::new_v4().to_string();
+                tracing::error!(error_id = %error_id, error = %self, "Internal error");
+                let body = serde_json::json!({
+                    "error": "internal_error",
+                    "message": "An unexpected error occurred",
+                    "error_id": error_id
+                });
+                detail = Some(ErrorDetail { error_id, message: self.to_string() });
+                (StatusCode::INTERNAL_SERVER_ERROR, body)
             }
         };
 
         let mut response = (status, Json(body)).into_response();
+        if let Some(detail) = detail {
+            response.extensions_mut().insert(detail);
+        }
+
+        if let ThrottlerError::StoreUnavailable(_) = &self {
+            response.headers_mut().insert("Retry-After", axum::http::HeaderValue::from_static("1"));
+        }
 
         // Add Retry-After header for rate limit errors
         if let ThrottlerError::RateLimitExceeded { retry_after, limit, window_ms } = &self {
@@ -0,0 +1,178 @@
+//! Notifications when bucket keys expire in Redis.
+//!
+//! Redis publishes `__keyevent@<db>__:expired` with the key name whenever a
+//! key's TTL runs out, but only if the server has keyspace notifications
+//! enabled for expiry events:
+//!
+//! ```text
+//! CONFIG SET notify-keyspace-events Ex
+//! ```
+//!
+//! (or `notify-keyspace-events Ex` in `redis.conf`). Without it the watcher
+//! subscribes successfully but never hears anything.
+//!
+//! [`ExpiryWatcher`] subscribes on every configured Redis node from a
+//! background thread, keeps only keys in the `throttler:` namespace, and
+//! rebroadcasts them with the prefix stripped. Events are best-effort: Redis
+//! pub/sub does not buffer, so expiries while the watcher is reconnecting
+//! are lost.
+
+use redis::Client;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::error::ThrottlerError;
+
+/// Namespace of bucket keys in Redis
+pub const BUCKET_KEY_PREFIX: &str = "throttler:";
+
+/// Expiry events held for slow subscribers before the oldest are dropped
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// How often the listener wakes to check whether it should stop
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Wait before resubscribing after the connection fails
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Channel Redis publishes expired keys of database `db` on
+pub fn expired_channel(db: i64) -> String {
+    format!("__keyevent@{}__:expired", db)
+}
+
+/// Listens for bucket keys expiring on one or more Redis nodes.
+///
+/// Stops listening when dropped.
+#[derive(Debug)]
+pub struct ExpiryWatcher {
+    sender: broadcast::Sender<String>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl ExpiryWatcher {
+    /// Starts a listener thread per node in `urls`
+    pub fn start<S: AsRef<str>>(urls: &[S]) -> Result<Self, ThrottlerError> {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        for url in urls {
+            let client = Client::open(url.as_ref())
+                .map_err(|e| ThrottlerError::RedisError(format!("Failed to create Redis client: {}", e)))?;
+            let sender = sender.clone();
+            let stopped = stopped.clone();
+
+            thread::Builder::new()
+                .name("throttler-expiry".to_string())
+                .spawn(move || listen(client, sender, stopped))
+                .map_err(|e| ThrottlerError::InternalError(format!("Failed to start expiry listener: {}", e)))?;
+        }
+
+        Ok(Self { sender, stopped })
+    }
+
+    /// Receives the rate limit key (without the `throttler:` prefix) of
+    /// each bucket that expires from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<String> {
+        self.sender.subscribe()
+    }
+}
+
+impl Drop for ExpiryWatcher {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Subscribes to `client`'s expiry channel until `stopped`, reconnecting on failure
+fn listen(client: Client, sender: broadcast::Sender<String>, stopped: Arc<AtomicBool>) {
+    let channel = expired_channel(client.get_connection_info().redis.db);
+
+    while !stopped.load(Ordering::Relaxed) {
+        if let Err(e) = listen_once(&client, &channel, &sender, &stopped) {
+            tracing::warn!("Expiry notifications interrupted: {}", e);
+            thread::sleep(RECONNECT_DELAY);
+        }
+    }
+}
+
+fn listen_once(
+    client: &Client,
+    channel: &str,
+    sender: &broadcast::Sender<String>,
+    stopped: &AtomicBool,
+) -> redis::RedisResult<()> {
+    let mut conn = client.get_connection()?;
+    let mut pubsub = conn.as_pubsub();
+    pubsub.subscribe(channel)?;
+    pubsub.set_read_timeout(Some(POLL_INTERVAL))?;
+
+    while !stopped.load(Ordering::Relaxed) {
+        let message = match pubsub.get_message() {
+            Ok(message) => message,
+            Err(e) if e.is_timeout() => continue,
+            Err(e) => return Err(e),
+        };
+
+        let expired: String = message.get_payload()?;
+        if let Some(key) = expired.strip_prefix(BUCKET_KEY_PREFIX) {
+            tracing::debug!(key = %key, "Rate limit bucket expired");
+            // No subscribers is fine: the event is simply dropped
+            let _ = sender.send(key.to_string());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expired_channel_name() {
+        assert_eq!(expired_channel(0), "__keyevent@0__:expired");
+        assert_eq!(expired_channel(3), "__keyevent@3__:expired");
+    }
+}
+
+/// Tests against a live Redis at `REDIS_URL` (default `redis://127.0.0.1:6379`).
+/// Run with `cargo test --features redis-tests`.
+#[cfg(all(test, feature = "redis-tests"))]
+mod redis_tests {
+    use super::*;
+    use redis::Commands;
+
+    #[tokio::test]
+    async fn test_expired_bucket_is_reported() {
+        let url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+        let mut conn = Client::open(url.as_str()).unwrap().get_connection().unwrap();
+        let _: () = redis::cmd("CONFIG").arg("SET").arg("notify-keyspace-events").arg("Ex")
+            .query(&mut conn)
+            .unwrap();
+
+        let watcher = ExpiryWatcher::start(&[url.as_str()]).unwrap();
+        let mut events = watcher.subscribe();
+        // Give the listener time to subscribe
+        tokio::time::sleep(Duration::from_millis(300)).await;
+
+        let key = format!("expiry-test-{}", uuid::Uuid::new_v4());
+        let _: () = conn.pset_ex(format!("{}{}", BUCKET_KEY_PREFIX, key), "{}", 50).unwrap();
+        // Keys outside the namespace are ignored
+        let _: () = conn.pset_ex(format!("other:{}", key), "{}", 50).unwrap();
+
+        let expired = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let expired = events.recv().await.unwrap();
+                if expired.ends_with(&key) {
+                    return expired;
+                }
+            }
+        })
+        .await
+        .expect("no expiry event within 5s");
+        assert_eq!(expired, key);
+    }
+}
@@ -14,17 +14,59 @@
 //! │  ┌──────────────────────────────────────────────────────────────────┐  │
 //! │  │ POST /rate-limit/:key/check  →  check_rate_limit()              │  │
 //! │  │   • Validates key format                                         │  │
-//! │  │   • Consumes token from bucket                                   │  │
+//! │  │   • Consumes token from bucket (not with ?dry=true)              │  │
 //! │  │   • Returns allowed/denied with headers                          │  │
+//! │  │ HEAD /rate-limit/:key/check  →  check_rate_limit_head()         │  │
+//! │  │   • Same consume and headers, no body                            │  │
+//! │  ├──────────────────────────────────────────────────────────────────┤  │
+//! │  │ POST /rate-limit/:key/commit →  commit_rate_limit()             │  │
+//! │  │   • Consumes tokens after a dry check's downstream succeeded     │  │
+//! │  │ POST /rate-limit/:key/refund →  refund_rate_limit()             │  │
+//! │  │   • Hands back some of a check's tokens after a partial failure  │  │
 //! │  ├──────────────────────────────────────────────────────────────────┤  │
 //! │  │ GET  /rate-limit/:key        →  get_rate_limit()                │  │
 //! │  │   • Returns current token count and limit                        │  │
 //! │  ├──────────────────────────────────────────────────────────────────┤  │
+//! │  │ GET  /rate-limit/:key/explain →  explain_rate_limit()           │  │
+//! │  │   • Shows which rule applies to the key, and why                 │  │
+//! │  ├──────────────────────────────────────────────────────────────────┤  │
 //! │  │ POST /rate-limit/:key        →  set_rate_limit()                │  │
 //! │  │   • Creates or updates rate limit configuration                  │  │
 //! │  ├──────────────────────────────────────────────────────────────────┤  │
+//! │  │ POST /rate-limit/:key/enable  →  enable_rate_limit()            │  │
+//! │  │ POST /rate-limit/:key/disable →  disable_rate_limit()           │  │
+//! │  │   • Pauses/resumes limiting on an existing rule                  │  │
+//! │  ├──────────────────────────────────────────────────────────────────┤  │
 //! │  │ DELETE /rate-limit/:key      →  delete_rate_limit()             │  │
 //! │  │   • Removes rate limit and resets bucket                         │  │
+//! │  ├──────────────────────────────────────────────────────────────────┤  │
+//! │  │ DELETE /rate-limit?keys=a,b  →  delete_rate_limits()            │  │
+//! │  │   • Validates every key, then resets them all                    │  │
+//! │  ├──────────────────────────────────────────────────────────────────┤  │
+//! │  │ PUT    /route-limit          →  set_route_limit()               │  │
+//! │  │ DELETE /route-limit?route=.. →  delete_route_limit()            │  │
+//! │  │   • Limits shared by every request to a `METHOD PATH` route      │  │
+//! │  ├──────────────────────────────────────────────────────────────────┤  │
+//! │  │ PUT    /pattern-limit        →  set_pattern_limit()             │  │
+//! │  │ DELETE /pattern-limit?pattern=.. → delete_pattern_limit()       │  │
+//! │  │   • Rules for every key starting with a prefix (`tenant-*`)      │  │
+//! │  └──────────────────────────────────────────────────────────────────┘  │
+//! │                                                                        │
+//! │  Compatibility Endpoints:                                              │
+//! │  ┌──────────────────────────────────────────────────────────────────┐  │
+//! │  │ POST /nginx/limit  →  nginx_limit()  (nginx limit_req semantics) │  │
+//! │  └──────────────────────────────────────────────────────────────────┘  │
+//! │                                                                        │
+//! │  Admin Endpoints:                                                      │
+//! │  ┌──────────────────────────────────────────────────────────────────┐  │
+//! │  │ GET /admin/state  →  export_state()  (Snapshot local buckets)   │  │
+//! │  │ PUT /admin/state  →  import_state()  (Load a snapshot)          │  │
+//! │  │ GET /admin/stats  →  admin_stats()   (Counts and memory use)    │  │
+//! │  └──────────────────────────────────────────────────────────────────┘  │
+//! │                                                                        │
+//! │  Metrics Endpoints:                                                    │
+//! │  ┌──────────────────────────────────────────────────────────────────┐  │
+//! │  │ GET /metrics →  metrics()          (Prometheus text format)     │  │
 //! │  └──────────────────────────────────────────────────────────────────┘  │
 //! │                                                                        │
 //! │  Health Endpoints:                                                     │
@@ -44,7 +86,23 @@
 //! |-------------------------|--------------------------------------|
 //! | `X-RateLimit-Limit`     | Maximum requests allowed             |
 //! | `X-RateLimit-Remaining` | Remaining requests in current window |
-//! | `Retry-After`           | Seconds until tokens refill (429)    |
+//! | `Retry-After`           | Seconds until the next token (429/503)|
+//! | `X-RateLimit-Scope`     | Which limit denied: `key`, `quota`, `route` or `global`|
+//! | `X-RateLimit-Retry-Budget` | Retries still advisable (429, opt-in) |
+//! | `X-RateLimit-Retry-After-Ms` | `Retry-After` in milliseconds, unrounded (opt-in) |
+//! | `X-RateLimit-Source`    | Constraint that denied, incl. `concurrency` (opt-in) |
+//! | `X-RateLimit-Refund-Id` | Names an allowed check in a refund (opt-in) |
+//! | `X-Quota-Remaining`     | Quota left this period (rules with a quota) |
+//! | `X-Quota-Reset`         | When the quota resets (UNIX seconds) |
+//!
+//! A denial by the client's own key returns `429 Too Many Requests`, or
+//! `Config::deny_status_code` if set (e.g. `200` for clients that check
+//! `allowed` themselves); the headers are the same either way. So does a
+//! denial by the key's quota, whose `Retry-After` counts down to the reset,
+//! and by a route rule, whose limit headers describe the route's bucket.
+//! A denial
+//! by the service-wide safeguard (`Config::global_rate_limit`) is a capacity
+//! problem on our side, so it returns `503 Service Unavailable` instead.
 //!
 //! ## Error Handling
 //!
@@ -52,19 +110,87 @@
 //! `ThrottlerError` automatically converts to appropriate HTTP status codes.
 
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
-    response::IntoResponse,
+    body::{Body, Bytes},
+    extract::{rejection::JsonRejection, FromRequest, Path, Query, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
 
+use crate::config::CheckResponseMode;
+use crate::config_validator::ConfigValidator;
 use crate::error::ThrottlerError;
-use crate::rate_limiter::RateLimiter;
+use crate::metrics::{render_prometheus, MetricsCollector};
+use crate::nginx::NginxLimitRequest;
+use crate::quota::QuotaPeriod;
+use crate::rate_limit_config::{RateLimitRule, RateUnit};
+use crate::rate_limiter::{now_ms, RateLimiter, SerializableState, BUCKET_ENTRY_BYTES};
+use crate::route_rules::route_of;
+use crate::throttler::{DenialScope, RequestOutcome, Throttler};
 use crate::validation::RequestValidator;
 
+/// Header carrying the authenticated tenant when `TENANT_ISOLATION` is on
+pub const TENANT_HEADER: &str = "X-Tenant-Id";
+
+/// Canonicalizes a validated key's case (per `Config::key_case`) and scopes
+/// it to the caller's tenant, as `<tenant>:<key>`.
+///
+/// Keys may not contain `:`, so a client cannot name another tenant's
+/// space. The tenant id itself is used as given. Without tenant isolation
+/// only the case is normalized.
+fn tenant_key(state: &AppState, headers: &HeaderMap, key: String) -> Result<String, ThrottlerError> {
+    let key = state.validator.normalize_key(key);
+    if !state.rate_limiter.config().tenant_isolation {
+        return Ok(key);
+    }
+
+    let tenant = headers.get(TENANT_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| ThrottlerError::ValidationError(
+            format!("Missing or unreadable {} header", TENANT_HEADER)
+        ))?;
+    state.validator.validate_tenant_id(tenant)?;
+
+    Ok(format!("{}:{}", tenant, key))
+}
+
+/// Header carrying `Config::admin_token` on `/admin` requests
+pub const ADMIN_TOKEN_HEADER: &str = "X-Admin-Token";
+
+/// Admits a request to a fleet-wide `/admin` endpoint.
+///
+/// Those endpoints see or replace every tenant's buckets, so with tenant
+/// isolation on they need the operator's `X-Admin-Token`; with no
+/// `Config::admin_token` set they are refused outright.
+fn require_operator(state: &AppState, headers: &HeaderMap) -> Result<(), ThrottlerError> {
+    let config = state.rate_limiter.config();
+    if !config.tenant_isolation {
+        return Ok(());
+    }
+
+    let expected = config.admin_token.as_bytes();
+    let presented = headers.get(ADMIN_TOKEN_HEADER)
+        .map(|value| value.as_bytes())
+        .unwrap_or_default();
+    // No early exit, so response time says nothing about how much matched
+    let matches = !expected.is_empty()
+        && presented.len() == expected.len()
+        && presented.iter().zip(expected).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0;
+
+    if matches {
+        Ok(())
+    } else {
+        Err(ThrottlerError::Forbidden(format!(
+            "admin endpoints span every tenant and need a valid {} header", ADMIN_TOKEN_HEADER
+        )))
+    }
+}
+
 /// Thread-safe shared application state.
 ///
 /// Uses `Arc` for shared ownership across async tasks and `RwLock` for
@@ -81,6 +207,8 @@ pub type SharedState = Arc<RwLock<AppState>>;
 /// This struct holds all stateful components needed by request handlers:
 /// - `rate_limiter`: Core rate limiting engine
 /// - `validator`: Request input validation
+/// - `throttler`: Per-key rules, sharing buckets with `rate_limiter`
+/// - `metrics`: Per-key request counters
 ///
 /// # Thread Safety
 ///
@@ -91,6 +219,10 @@ pub struct AppState {
     pub rate_limiter: RateLimiter,
     /// Request input validator (key format, parameter ranges)
     pub validator: RequestValidator,
+    /// Rule store and orchestrator over the same rate limiter
+    pub throttler: Throttler,
+    /// Per-key allowed/throttled counters
+    pub metrics: MetricsCollector,
 }
 
 /// Request body for rate limit check endpoint.
@@ -105,13 +237,130 @@ pub struct AppState {
 /// {"tokens": 1}
 /// ```
 ///
-/// Or simply `{}` to use the default of 1 token.
+/// Or simply `{}` to use the default of 1 token. Negative, fractional or
+/// non-numeric `tokens` are rejected with a `400` naming the value.
+///
+/// A gateway may also name the request it is checking, so route rules
+/// (see [`crate::route_rules`]) apply on top of the key's own limit:
+///
+/// ```json
+/// {"tokens": 1, "method": "POST", "path": "/upload"}
+/// ```
 #[derive(Debug, Deserialize)]
 pub struct CheckRequest {
     /// Number of tokens to consume from the bucket.
     /// Defaults to 1 if not specified.
-    #[serde(default)]
+    #[serde(default, deserialize_with = "deserialize_tokens")]
     pub tokens: Option<u64>,
+    /// HTTP method of the request being checked; requires `path`
+    #[serde(default)]
+    pub method: Option<String>,
+    /// Path of the request being checked; requires `method`
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+impl CheckRequest {
+    /// The route the check is for, if it names one
+    fn route(&self) -> Result<Option<String>, ThrottlerError> {
+        match (&self.method, &self.path) {
+            (Some(method), Some(path)) => Ok(Some(route_of(method, path))),
+            (None, None) => Ok(None),
+            _ => Err(ThrottlerError::ValidationError(
+                "method and path must be given together".to_string(),
+            )),
+        }
+    }
+}
+
+/// Accepts a non-negative integer or null, with an error naming anything else
+fn deserialize_tokens<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = Option::<serde_json::Value>::deserialize(deserializer)?;
+    match value {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(value) => value.as_u64().map(Some).ok_or_else(|| {
+            serde::de::Error::custom(format!("must be a non-negative integer, got {}", value))
+        }),
+    }
+}
+
+/// JSON body extractor whose invalid-field errors are `ValidationError`s.
+///
+/// Axum's `Json` answers a body that parses but does not fit the type with
+/// a plain-text `422`; this turns that into the usual JSON `400`. Other
+/// rejections (bad syntax, wrong content type) are left as they are.
+pub struct ValidJson<T>(pub T);
+
+#[axum::async_trait]
+impl<T, S> FromRequest<S> for ValidJson<T>
+where
+    T: serde::de::DeserializeOwned,
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match Json::<T>::from_request(req, state).await {
+            Ok(Json(value)) => Ok(ValidJson(value)),
+            Err(JsonRejection::JsonDataError(e)) => {
+                let message = std::error::Error::source(&e)
+                    .map(|source| source.to_string())
+                    .unwrap_or_else(|| e.body_text());
+                Err(ThrottlerError::ValidationError(message).into_response())
+            }
+            Err(rejection) => Err(rejection.into_response()),
+        }
+    }
+}
+
+/// `ValidJson` that first checks the raw body against the validator's JSON
+/// nesting depth and field count limits.
+///
+/// Pathological bodies (deeply nested arrays, thousands of keys) are
+/// rejected with a `400` before serde builds anything from them.
+pub struct BoundedJson<T>(pub T);
+
+#[axum::async_trait]
+impl<T> FromRequest<SharedState> for BoundedJson<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    type Rejection = Response;
+
+    async fn from_request(req: Request, state: &SharedState) -> Result<Self, Self::Rejection> {
+        let headers = req.headers().clone();
+        let body = Bytes::from_request(req, state).await.map_err(IntoResponse::into_response)?;
+
+        state.read().await.validator
+            .validate_json_shape(&body)
+            .map_err(IntoResponse::into_response)?;
+
+        let mut req = Request::new(Body::from(body));
+        *req.headers_mut() = headers;
+        let ValidJson(value) = ValidJson::<T>::from_request(req, state).await?;
+        Ok(BoundedJson(value))
+    }
+}
+
+/// Query parameters for the check endpoint.
+///
+/// # Example
+///
+/// ```text
+/// POST /rate-limit/client-123/check?dry=true
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct CheckQuery {
+    /// Report whether the request would be allowed without consuming a token
+    #[serde(default)]
+    pub dry: bool,
+    /// Add the bucket state behind the decision as `_debug`, when
+    /// `Config::allow_debug` is on
+    #[serde(default)]
+    pub debug: bool,
 }
 
 /// Response body for rate limit check endpoint.
@@ -143,6 +392,29 @@ pub struct CheckResponse {
     pub limit: u64,
 }
 
+/// Bucket state behind a check decision, sent as `_debug` by
+/// `POST /rate-limit/:key/check?debug=true`.
+#[derive(Debug, Serialize)]
+pub struct CheckDebug {
+    /// Tokens available before the request, after refill
+    pub tokens_before: f64,
+    /// Tokens available once the request was counted
+    pub tokens_after: f64,
+    /// Tokens the refill before the request added
+    pub refilled: f64,
+    /// Time since the bucket was last refilled, in ms
+    pub elapsed_ms: u64,
+}
+
+/// [`CheckResponse`] with the [`CheckDebug`] state behind it
+#[derive(Debug, Serialize)]
+struct CheckDebugResponse {
+    #[serde(flatten)]
+    check: CheckResponse,
+    #[serde(rename = "_debug")]
+    debug: CheckDebug,
+}
+
 /// Request body for rate limit configuration endpoint.
 ///
 /// # Fields
@@ -157,12 +429,120 @@ pub struct CheckResponse {
 /// ```
 ///
 /// This configures 100 requests per 60 seconds (1 minute).
+///
+/// Optional `metadata` labels (e.g. `{"tenant": "acme", "plan": "gold"}`)
+/// are stored with the rule, returned in status, and exported as metric labels.
+///
+/// With `expires_in_secs` the rule is temporary: once that many seconds have
+/// passed it is removed and the key reverts to the defaults.
+///
+/// `concurrency_limit` additionally caps the requests the key may have in
+/// flight, through `/concurrency/:key/acquire` and `/release`.
+///
+/// `quota` caps the tokens the key may spend per calendar `quota_period`
+/// (`"monthly"` by default, or `"daily"`), reported in `X-Quota-Remaining`
+/// and `X-Quota-Reset`.
 #[derive(Debug, Deserialize)]
 pub struct ConfigRequest {
     /// Maximum number of requests allowed in the window
     pub requests: u64,
     /// Window size in milliseconds (e.g., 60000 = 1 minute)
     pub window_ms: u64,
+    /// Operator-defined labels for reporting and metrics
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// Seconds until the rule lapses; permanent when absent
+    #[serde(default)]
+    pub expires_in_secs: Option<u64>,
+    /// Most requests in flight at once; unbounded when absent
+    #[serde(default)]
+    pub concurrency_limit: Option<u32>,
+    /// Most tokens spent per quota period; no quota when absent
+    #[serde(default)]
+    pub quota: Option<u64>,
+    /// Calendar period the quota resets on
+    #[serde(default)]
+    pub quota_period: QuotaPeriod,
+}
+
+impl ConfigRequest {
+    /// Convert to a rule: `requests` is the burst capacity, refilled over the window
+    fn to_rule(&self) -> RateLimitRule {
+        let requests = self.requests.min(u32::MAX as u64) as u32;
+        let mut rule = RateLimitRule::new(requests, requests, Duration::from_millis(self.window_ms))
+            .with_rate_unit(RateUnit::PerWindow)
+            .with_metadata(self.metadata.clone());
+        if let Some(concurrency_limit) = self.concurrency_limit {
+            rule = rule.with_concurrency_limit(concurrency_limit);
+        }
+        if let Some(quota) = self.quota {
+            rule = rule.with_quota(quota, self.quota_period);
+        }
+        match self.expires_in_secs {
+            Some(secs) => rule.with_expires_at(now_ms().saturating_add(secs.saturating_mul(1000))),
+            None => rule,
+        }
+    }
+}
+
+/// Request body for the route limit endpoint: a route pattern plus the
+/// fields of a [`ConfigRequest`].
+///
+/// # Example JSON
+///
+/// ```json
+/// {"route": "POST /upload", "requests": 100, "window_ms": 60000}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct RouteConfigRequest {
+    /// `METHOD PATH` pattern, e.g. `POST /upload` or `GET /reports/*`
+    pub route: String,
+    /// The route's limit
+    #[serde(flatten)]
+    pub rule: ConfigRequest,
+}
+
+/// Query parameters for deleting a route limit.
+///
+/// # Example
+///
+/// ```text
+/// DELETE /route-limit?route=POST%20/upload
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct RouteQuery {
+    /// `METHOD PATH` pattern the rule was set under
+    pub route: String,
+}
+
+/// Request body for the pattern limit endpoint: a key prefix pattern plus
+/// the fields of a [`ConfigRequest`].
+///
+/// # Example JSON
+///
+/// ```json
+/// {"pattern": "tenant-*", "requests": 100, "window_ms": 60000}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct PatternConfigRequest {
+    /// Literal key prefix, optionally followed by a single trailing `*`
+    pub pattern: String,
+    /// The rule for every matching key
+    #[serde(flatten)]
+    pub rule: ConfigRequest,
+}
+
+/// Query parameters for deleting a pattern limit.
+///
+/// # Example
+///
+/// ```text
+/// DELETE /pattern-limit?pattern=tenant-*
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct PatternQuery {
+    /// Pattern the rule was set under
+    pub pattern: String,
 }
 
 /// Response body for configuration update operations.
@@ -186,6 +566,117 @@ pub struct ConfigResponse {
     pub key: String,
 }
 
+/// Maximum number of keys accepted by a single multi-key delete
+pub const MAX_DELETE_KEYS: usize = 1000;
+
+/// Keys listed per ranking by `GET /admin/stats` unless `top` is given
+pub const DEFAULT_STATS_TOP_KEYS: usize = 10;
+
+/// Query parameters for the stats endpoint.
+///
+/// # Example
+///
+/// ```text
+/// GET /admin/stats?top=5
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct StatsQuery {
+    /// How many keys to list in each ranking (default: 10)
+    pub top: Option<usize>,
+}
+
+/// Keys per page of `GET /admin/keys/detailed` unless `limit` is given
+pub const DEFAULT_LIST_KEYS_LIMIT: usize = 100;
+
+/// Query parameters for the detailed key listing.
+///
+/// # Example
+///
+/// ```text
+/// GET /admin/keys/detailed?limit=50&cursor=0:17408
+/// ```
+#[derive(Debug, Default, Deserialize)]
+pub struct ListKeysQuery {
+    /// Keys per page (default: 100, at most 1000)
+    pub limit: Option<usize>,
+    /// `next_cursor` from the previous page
+    pub cursor: Option<String>,
+}
+
+/// Query parameters for multi-key deletion.
+///
+/// # Example
+///
+/// ```text
+/// DELETE /rate-limit?keys=client-a,client-b,client-c
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct DeleteManyQuery {
+    /// Comma-separated list of keys to delete
+    pub keys: String,
+}
+
+/// Response body for concurrency slot acquisition.
+///
+/// # Example JSON
+///
+/// ```json
+/// {"acquired": true, "slot_id": "6f1c...", "in_flight": 3, "limit": 10}
+/// ```
+#[derive(Debug, Serialize)]
+pub struct ConcurrencyResponse {
+    /// Whether a slot was taken
+    pub acquired: bool,
+    /// The slot to release once the request ends, when one was taken
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slot_id: Option<String>,
+    /// Requests in flight for the key, including this one if acquired
+    pub in_flight: u64,
+    /// The key's concurrency limit; absent when unbounded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+}
+
+/// Request body for releasing a concurrency slot.
+///
+/// # Example JSON
+///
+/// ```json
+/// {"slot_id": "6f1c..."}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct ReleaseRequest {
+    /// `slot_id` returned when the slot was acquired
+    pub slot_id: String,
+}
+
+/// Longest slot id accepted on release (acquired ids are UUIDs)
+const MAX_SLOT_ID_LEN: usize = 64;
+
+/// Request body for returning some of a check's tokens.
+///
+/// # Example
+///
+/// ```json
+/// {"refund_id": "0b9e...", "tokens": 3}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct RefundRequest {
+    /// `X-RateLimit-Refund-Id` of the check that consumed the tokens
+    pub refund_id: String,
+    /// Tokens to return, at most those the check has left to refund
+    pub tokens: u64,
+}
+
+/// Response body for a refund
+#[derive(Debug, Serialize)]
+pub struct RefundResponse {
+    /// Tokens returned
+    pub refunded: u64,
+    /// Tokens in the key's bucket afterwards
+    pub remaining: u64,
+}
+
 /// Response body for health check endpoints.
 ///
 /// # Example JSON
@@ -208,6 +699,10 @@ pub struct HealthResponse {
 /// 2. Attempts to consume tokens from the bucket
 /// 3. Returns the result with standard rate limit headers
 ///
+/// With `?dry=true` nothing is consumed: the response reports whether the
+/// request would be allowed. Pair it with `POST /rate-limit/:key/commit`
+/// once the downstream request has succeeded.
+///
 /// # Request
 ///
 /// ```text
@@ -228,13 +723,36 @@ pub struct HealthResponse {
 /// {"allowed": true, "remaining": 99, "limit": 100}
 /// ```
 ///
+/// With `Config::check_response_mode` set to `minimal` the body is `{}`, and
+/// with `headers_only` there is none; the headers are unchanged. Denials
+/// and dry runs always carry the full body.
+///
+/// With `?debug=true` and `Config::allow_debug` on, the full body also
+/// carries the bucket state behind the decision:
+///
+/// ```json
+/// {"allowed": true, "remaining": 41, "limit": 100,
+///  "_debug": {"tokens_before": 42.5, "tokens_after": 41.5, "refilled": 2.5, "elapsed_ms": 250}}
+/// ```
+///
 /// # Response (429 Too Many Requests - Denied)
 ///
 /// ```text
 /// HTTP/1.1 429 Too Many Requests
 /// X-RateLimit-Limit: 100
 /// X-RateLimit-Remaining: 0
-/// Retry-After: 60
+/// Retry-After: 1
+/// Content-Type: application/json
+///
+/// {"allowed": false, "remaining": 0, "limit": 100}
+/// ```
+///
+/// # Response (503 Service Unavailable - Global Limit)
+///
+/// ```text
+/// HTTP/1.1 503 Service Unavailable
+/// X-RateLimit-Scope: global
+/// Retry-After: 1
 /// Content-Type: application/json
 ///
 /// {"allowed": false, "remaining": 0, "limit": 100}
@@ -246,130 +764,630 @@ pub struct HealthResponse {
 /// - `500 Internal Server Error` - Redis or internal error
 pub async fn check_rate_limit(
     State(state): State<SharedState>,
+    headers: HeaderMap,
     Path(key): Path<String>,
-    Json(_payload): Json<CheckRequest>,
+    Query(query): Query<CheckQuery>,
+    ValidJson(payload): ValidJson<CheckRequest>,
 ) -> Result<impl IntoResponse, ThrottlerError> {
     // Acquire read lock - allows concurrent rate limit checks
     let state = state.read().await;
+    let mut timing = ServerTiming::default();
 
     // Validate key format (alphanumeric, -, _, :, .)
+    let started = Instant::now();
     state.validator.validate_key(&key)?;
+    let key = tenant_key(&state, &headers, key)?;
+    timing.record("validate", started);
 
-    // Check rate limit - consumes 1 token if available
-    let (allowed, remaining) = state.rate_limiter.check_rate_limit(&key)?;
+    let route = payload.route()?;
+    let tokens = payload.tokens.unwrap_or(1);
 
-    // Build response body
-    let response = CheckResponse {
-        allowed,
-        remaining,
-        limit: 100, // TODO: Get from config
+    // Dry run: report the would-be outcome, consuming nothing (not even global)
+    if query.dry {
+        let outcome = state.throttler.peek_request_on_route(&key, tokens, route.as_deref()).await?;
+        let resp = Json(CheckResponse {
+            allowed: outcome.allowed,
+            remaining: outcome.remaining.floor() as u64,
+            limit: outcome.limit,
+        }).into_response();
+        return Ok(with_outcome_headers(&state, &outcome, resp));
+    }
+
+    let before = if query.debug && state.rate_limiter.config().allow_debug {
+        Some(state.throttler.bucket_snapshot(&key).await?)
+    } else {
+        None
     };
 
-    let mut resp = Json(response).into_response();
+    // Global limit, then the route's and the key's buckets (Redis first,
+    // then local); records metrics
+    let started = Instant::now();
+    let outcome = state.throttler
+        .process_request_on_route(&key, tokens, route.as_deref())
+        .await?;
+    timing.record_store(&state, started);
 
-    // Add standard rate limit headers
-    resp.headers_mut().insert("X-RateLimit-Limit", "100".parse().unwrap());
-    resp.headers_mut().insert("X-RateLimit-Remaining", remaining.to_string().parse().unwrap());
+    let debug = match before {
+        Some(before) => Some(CheckDebug {
+            tokens_before: before.tokens,
+            tokens_after: state.throttler.bucket_snapshot(&key).await?.tokens,
+            refilled: before.refilled,
+            elapsed_ms: before.elapsed_ms,
+        }),
+        None => None,
+    };
 
-    // If rate limited, set 429 status and Retry-After header
-    if !allowed {
-        *resp.status_mut() = StatusCode::TOO_MANY_REQUESTS;
-        resp.headers_mut().insert("Retry-After", "60".parse().unwrap());
-    }
+    let started = Instant::now();
+    let check = CheckResponse {
+        allowed: outcome.allowed,
+        remaining: outcome.remaining.floor() as u64,
+        limit: outcome.limit,
+    };
+    let mode = if outcome.allowed && debug.is_none() {
+        state.rate_limiter.config().check_response_mode
+    } else {
+        CheckResponseMode::Full
+    };
+    let resp = match mode {
+        CheckResponseMode::Full => match debug {
+            Some(debug) => Json(CheckDebugResponse { check, debug }).into_response(),
+            None => Json(check).into_response(),
+        },
+        CheckResponseMode::Minimal => ([(header::CONTENT_TYPE, "application/json")], "{}").into_response(),
+        CheckResponseMode::HeadersOnly => StatusCode::OK.into_response(),
+    };
+    timing.record("serialize", started);
 
-    Ok(resp)
+    Ok(timing.apply(&state, with_outcome_headers(&state, &outcome, resp)))
 }
 
-/// Gets current rate limit status for a key.
+/// Header-only variant of the check for monitoring tools that probe with
+/// HEAD.
 ///
-/// Returns the current token count and limit configuration for the specified key.
-/// Does not consume any tokens.
+/// Consumes one token exactly like `POST /rate-limit/:key/check` and
+/// answers with the same status and rate limit headers, but no body. A HEAD
+/// is one request: it is counted once, and only against its own key.
 ///
 /// # Request
 ///
 /// ```text
-/// GET /rate-limit/:key
+/// HEAD /rate-limit/:key/check
 /// ```
 ///
-/// # Response (200 OK)
+/// # Response (200 OK - Allowed)
 ///
-/// ```json
-/// {"key": "api-client-123", "remaining": 85, "limit": 100}
+/// ```text
+/// HTTP/1.1 200 OK
+/// X-RateLimit-Limit: 100
+/// X-RateLimit-Remaining: 99
 /// ```
 ///
 /// # Errors
 ///
 /// - `400 Bad Request` - Invalid key format
 /// - `500 Internal Server Error` - Redis or internal error
-pub async fn get_rate_limit(
+pub async fn check_rate_limit_head(
     State(state): State<SharedState>,
+    headers: HeaderMap,
     Path(key): Path<String>,
 ) -> Result<impl IntoResponse, ThrottlerError> {
-    // Acquire read lock for concurrent access
     let state = state.read().await;
+    let mut timing = ServerTiming::default();
 
-    // Validate key format
+    let started = Instant::now();
     state.validator.validate_key(&key)?;
+    let key = tenant_key(&state, &headers, key)?;
+    timing.record("validate", started);
 
-    // Get remaining tokens without consuming any
-    let remaining = state.rate_limiter.get_remaining_tokens(&key)?;
+    let started = Instant::now();
+    let outcome = state.throttler.process_request(&key, 1).await?;
+    timing.record_store(&state, started);
 
-    Ok(Json(serde_json::json!({
-        "key": key,
-        "remaining": remaining,
-        "limit": 100
-    })))
+    let resp = with_outcome_headers(&state, &outcome, StatusCode::OK.into_response());
+    Ok(timing.apply(&state, resp))
 }
 
-/// Creates or updates rate limit configuration for a key.
+/// Phase durations of one request, sent as `Server-Timing` when
+/// `Config::emit_server_timing` is on.
+///
+/// Renders as e.g.
+/// `validate;dur=0.012, store;desc="redis";dur=1.204, serialize;dur=0.008`
+/// with durations in milliseconds.
+#[derive(Debug, Default)]
+struct ServerTiming {
+    phases: Vec<(&'static str, Option<&'static str>, Duration)>,
+}
+
+impl ServerTiming {
+    /// Records a phase that began at `started` and ends now
+    fn record(&mut self, phase: &'static str, started: Instant) {
+        self.phases.push((phase, None, started.elapsed()));
+    }
+
+    /// Records the bucket store phase, noting whether a Redis store is in use
+    fn record_store(&mut self, state: &AppState, started: Instant) {
+        let store = if state.rate_limiter.has_shared_store() { "redis" } else { "local" };
+        self.phases.push(("store", Some(store), started.elapsed()));
+    }
+
+    fn header_value(&self) -> String {
+        self.phases.iter()
+            .map(|(phase, desc, elapsed)| {
+                let ms = elapsed.as_secs_f64() * 1000.0;
+                match desc {
+                    Some(desc) => format!("{};desc=\"{}\";dur={:.3}", phase, desc, ms),
+                    None => format!("{};dur={:.3}", phase, ms),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Adds the `Server-Timing` header to `resp` if enabled
+    fn apply(&self, state: &AppState, mut resp: Response) -> Response {
+        if state.rate_limiter.config().emit_server_timing {
+            resp.headers_mut().insert("Server-Timing", self.header_value().parse().unwrap());
+        }
+        resp
+    }
+}
+
+/// Names the constraint that denied a request in `X-RateLimit-Source`, if
+/// enabled
+fn with_limit_source(state: &AppState, source: &str, resp: &mut Response) {
+    if state.rate_limiter.config().emit_limit_source {
+        resp.headers_mut().insert("X-RateLimit-Source", source.parse().unwrap());
+    }
+}
+
+/// Sets the status and rate limit headers for a check outcome on `resp`
+fn with_outcome_headers(state: &AppState, outcome: &RequestOutcome, mut resp: Response) -> Response {
+    match outcome.denied_by {
+        // Service-wide safeguard: the key's own tokens were not spent
+        Some(DenialScope::Global) => {
+            *resp.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+            resp.headers_mut().insert("X-RateLimit-Scope", "global".parse().unwrap());
+        }
+        scope => {
+            // Add standard rate limit headers
+            resp.headers_mut().insert("X-RateLimit-Limit", outcome.limit.to_string().parse().unwrap());
+            let remaining_header = state.rate_limiter.config().format_remaining(outcome.remaining);
+            resp.headers_mut().insert("X-RateLimit-Remaining", remaining_header.parse().unwrap());
+            if state.rate_limiter.config().emit_utilization {
+                let utilization = format!("{:.2}", outcome.utilization);
+                resp.headers_mut().insert("X-RateLimit-Utilization", utilization.parse().unwrap());
+            }
+
+            match scope {
+                Some(DenialScope::Key) => {
+                    *resp.status_mut() = state.rate_limiter.config().deny_status();
+                    resp.headers_mut().insert("X-RateLimit-Scope", "key".parse().unwrap());
+                }
+                Some(DenialScope::Quota) => {
+                    *resp.status_mut() = state.rate_limiter.config().deny_status();
+                    resp.headers_mut().insert("X-RateLimit-Scope", "quota".parse().unwrap());
+                }
+                Some(DenialScope::Route) => {
+                    *resp.status_mut() = state.rate_limiter.config().deny_status();
+                    resp.headers_mut().insert("X-RateLimit-Scope", "route".parse().unwrap());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(scope) = outcome.denied_by {
+        with_limit_source(state, scope.as_str(), &mut resp);
+    }
+
+    if let Some(refund_id) = &outcome.refund_id {
+        resp.headers_mut().insert("X-RateLimit-Refund-Id", refund_id.parse().unwrap());
+    }
+
+    // Quota left this period, and when it resets (UNIX seconds)
+    if let Some(quota) = outcome.quota {
+        resp.headers_mut().insert("X-Quota-Remaining", quota.remaining.to_string().parse().unwrap());
+        let reset = (quota.reset_at_ms / 1000).to_string();
+        resp.headers_mut().insert("X-Quota-Reset", reset.parse().unwrap());
+    }
+
+    // Tell well-behaved clients when to retry, and when to give up retrying
+    if let Some(retry_after) = outcome.retry_after_secs {
+        resp.headers_mut().insert("Retry-After", retry_after.to_string().parse().unwrap());
+    }
+    if let Some(retry_after_ms) = outcome.retry_after_ms.filter(|_| state.rate_limiter.config().emit_retry_after_ms) {
+        resp.headers_mut().insert("X-RateLimit-Retry-After-Ms", retry_after_ms.to_string().parse().unwrap());
+    }
+    if let Some(budget) = outcome.retry_budget {
+        resp.headers_mut().insert("X-RateLimit-Retry-Budget", budget.to_string().parse().unwrap());
+    }
+
+    resp
+}
+
+/// Consumes tokens for a request that passed a dry check and succeeded
+/// downstream.
 ///
-/// Sets the rate limit parameters for a specific key. If the key already exists,
-/// its configuration is updated; otherwise, a new rate limit is created.
+/// Takes the same body as the check, and is charged exactly as a real check
+/// would be: the key's rule sizes its bucket, and the global and route
+/// limits apply. The request has already happened, so this never answers
+/// 429: `allowed` is `false` only when a limit ran out between the dry
+/// check and the commit, in which case nothing is consumed.
 ///
 /// # Request
 ///
 /// ```text
-/// POST /rate-limit/:key
+/// POST /rate-limit/:key/commit
 /// Content-Type: application/json
 ///
-/// {"requests": 100, "window_ms": 60000}
+/// {"tokens": 1}
 /// ```
 ///
 /// # Response (200 OK)
 ///
 /// ```json
-/// {
-///   "status": "success",
-///   "message": "Rate limit configuration updated",
-///   "key": "api-client-123"
-/// }
+/// {"allowed": true, "remaining": 41, "limit": 100}
 /// ```
 ///
-/// # Validation
-///
-/// - `requests`: 1 to 10,000
-/// - `window_ms`: 1,000 (1 second) to 86,400,000 (24 hours)
-///
 /// # Errors
 ///
-/// - `400 Bad Request` - Invalid key format or parameters out of range
+/// - `400 Bad Request` - Invalid key format or tokens
 /// - `500 Internal Server Error` - Redis or internal error
-pub async fn set_rate_limit(
+pub async fn commit_rate_limit(
     State(state): State<SharedState>,
+    headers: HeaderMap,
     Path(key): Path<String>,
-    Json(payload): Json<ConfigRequest>,
+    ValidJson(payload): ValidJson<CheckRequest>,
 ) -> Result<impl IntoResponse, ThrottlerError> {
-    // Acquire read lock (config update is idempotent)
     let state = state.read().await;
 
-    // Validate key format and rate limit parameters
     state.validator.validate_key(&key)?;
-    state.validator.validate_rate_limit(payload.requests, payload.window_ms)?;
+    let key = tenant_key(&state, &headers, key)?;
 
-    Ok(Json(ConfigResponse {
-        status: "success".to_string(),
+    let route = payload.route()?;
+    let outcome = state.throttler
+        .process_request_on_route(&key, payload.tokens.unwrap_or(1), route.as_deref())
+        .await?;
+
+    let resp = Json(CheckResponse {
+        allowed: outcome.allowed,
+        remaining: outcome.remaining.floor() as u64,
+        limit: outcome.limit,
+    }).into_response();
+    // Already served downstream: report the outcome, never reject it
+    let mut resp = with_outcome_headers(&state, &outcome, resp);
+    *resp.status_mut() = StatusCode::OK;
+    Ok(resp)
+}
+
+/// Returns some of the tokens a check consumed, for work that partly
+/// failed downstream.
+///
+/// Needs `Config::refund_window_ms`: allowed checks then answer with an
+/// `X-RateLimit-Refund-Id`, which a refund must name within the window. A
+/// check can never be refunded more tokens than it consumed, in one refund
+/// or several (see [`crate::refund`]).
+///
+/// # Request
+///
+/// ```text
+/// POST /rate-limit/:key/refund
+/// Content-Type: application/json
+///
+/// {"refund_id": "0b9e...", "tokens": 3}
+/// ```
+///
+/// # Response (200 OK)
+///
+/// ```json
+/// {"refunded": 3, "remaining": 45}
+/// ```
+///
+/// # Errors
+///
+/// - `400 Bad Request` - Invalid key, refunds disabled, an unknown or
+///   expired refund id, or more tokens than the check has left to refund
+/// - `500 Internal Server Error` - Redis or internal error
+pub async fn refund_rate_limit(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Path(key): Path<String>,
+    BoundedJson(payload): BoundedJson<RefundRequest>,
+) -> Result<impl IntoResponse, ThrottlerError> {
+    let state = state.read().await;
+
+    state.validator.validate_key(&key)?;
+    let key = tenant_key(&state, &headers, key)?;
+    if payload.tokens == 0 {
+        return Err(ThrottlerError::ValidationError("tokens must be at least 1".to_string()));
+    }
+
+    let remaining = state.throttler.refund(&key, &payload.refund_id, payload.tokens).await?;
+    Ok(Json(RefundResponse { refunded: payload.tokens, remaining: remaining.floor() as u64 }))
+}
+
+/// Gets current rate limit status for a key.
+///
+/// Returns the current token count and limit configuration for the specified key.
+/// Does not consume any tokens.
+///
+/// # Request
+///
+/// ```text
+/// GET /rate-limit/:key
+/// ```
+///
+/// # Response (200 OK)
+///
+/// ```json
+/// {"key": "api-client-123", "remaining": 85, "limit": 100, "metadata": {"tenant": "acme"},
+///  "algorithm": "token_bucket", "refill_per_sec": 1.6666666666666667}
+/// ```
+///
+/// A key with a rule of its own also gets the rule's version as `ETag`, to
+/// send as `If-Match` when updating it.
+///
+/// `refill_per_sec` is the rate the key's bucket actually refills at, after
+/// converting the governing rule's unit, so clients can predict recovery.
+///
+/// # Errors
+///
+/// - `400 Bad Request` - Invalid key format
+/// - `500 Internal Server Error` - Redis or internal error
+pub async fn get_rate_limit(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Path(key): Path<String>,
+) -> Result<impl IntoResponse, ThrottlerError> {
+    // Acquire read lock for concurrent access
+    let state = state.read().await;
+
+    // Validate key format
+    state.validator.validate_key(&key)?;
+    let key = tenant_key(&state, &headers, key)?;
+
+    // Get remaining tokens without consuming any, sized by the key's rule
+    let status = state.throttler.get_rate_limit_status(&key).await?;
+
+    let mut body = serde_json::json!({
+        "key": key,
+        "remaining": status.remaining,
+        "limit": status.limit,
+        "metadata": status.metadata,
+        "algorithm": status.algorithm,
+        "refill_per_sec": status.refill_per_sec
+    });
+    if state.rate_limiter.config().emit_utilization {
+        body["utilization"] = status.utilization.into();
+    }
+    if let Some(expires_at) = status.expires_at {
+        body["expires_at"] = expires_at.into();
+    }
+
+    // The version a conditional update of the key's own rule must match
+    let mut resp = Json(body).into_response();
+    if let Some(rule) = state.throttler.get_rule(&key).await {
+        resp.headers_mut().insert(header::ETAG, rule_etag(rule.version));
+    }
+    Ok(resp)
+}
+
+/// Explains which rule governs a key and how it was resolved.
+///
+/// A debugging aid for keys covered by exact rules, prefix patterns and
+/// defaults at once. Does not consume any tokens.
+///
+/// # Request
+///
+/// ```text
+/// GET /rate-limit/:key/explain
+/// ```
+///
+/// # Response (200 OK)
+///
+/// ```json
+/// {
+///   "key": "tenant-acme-42",
+///   "source": "pattern",
+///   "matched": "tenant-*",
+///   "explanation": "matched prefix pattern `tenant-*`",
+///   "rule": {"capacity": 20, "refill_per_second": 10.0, "enabled": true}
+/// }
+/// ```
+///
+/// # Errors
+///
+/// - `400 Bad Request` - Invalid key format
+pub async fn explain_rate_limit(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Path(key): Path<String>,
+) -> Result<impl IntoResponse, ThrottlerError> {
+    let state = state.read().await;
+
+    state.validator.validate_key(&key)?;
+    let key = tenant_key(&state, &headers, key)?;
+
+    Ok(Json(state.throttler.explain(&key).await))
+}
+
+/// Creates or updates rate limit configuration for a key.
+///
+/// Sets the rate limit parameters for a specific key. If the key already exists,
+/// its configuration is updated; otherwise, a new rate limit is created.
+///
+/// # Request
+///
+/// ```text
+/// POST /rate-limit/:key
+/// Content-Type: application/json
+///
+/// {"requests": 100, "window_ms": 60000}
+/// ```
+///
+/// # Response (200 OK)
+///
+/// ```json
+/// {
+///   "status": "success",
+///   "message": "Rate limit configuration updated",
+///   "key": "api-client-123"
+/// }
+/// ```
+///
+/// The response's `ETag` is the stored rule's version (`"3"`). Sending it
+/// back as `If-Match` makes the next update conditional: it is refused with
+/// `409 Conflict` if the rule has changed (or been removed) since.
+///
+/// # Validation
+///
+/// - `requests`: 1 to 10,000
+/// - `window_ms`: 1,000 (1 second) to 86,400,000 (24 hours)
+/// - `metadata`: at most 16 labels; names are metric-safe identifiers up to
+///   64 characters, values up to 256 characters
+/// - `expires_in_secs`: greater than 0 when present
+/// - body: nested at most `MAX_JSON_DEPTH` levels, at most `MAX_JSON_FIELDS`
+///   fields in total
+///
+/// # Errors
+///
+/// - `400 Bad Request` - Invalid key format, parameters out of range, an
+///   over-nested or oversized body, or a malformed `If-Match`
+/// - `409 Conflict` - `If-Match` does not match the stored rule's version
+/// - `500 Internal Server Error` - Redis or internal error
+pub async fn set_rate_limit(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Path(key): Path<String>,
+    BoundedJson(payload): BoundedJson<ConfigRequest>,
+) -> Result<impl IntoResponse, ThrottlerError> {
+    // Acquire read lock (config update is idempotent)
+    let state = state.read().await;
+
+    // Validate key format and rate limit parameters
+    state.validator.validate_key(&key)?;
+    let key = tenant_key(&state, &headers, key)?;
+    state.validator.validate_rate_limit(payload.requests, payload.window_ms)?;
+    if payload.expires_in_secs == Some(0) {
+        return Err(ThrottlerError::ValidationError(
+            "expires_in_secs must be greater than 0".to_string(),
+        ));
+    }
+
+    // Store the rule (validates metadata bounds), if still at the version
+    // the client last saw
+    let expected_version = if_match_version(&headers)?;
+    let version = state.throttler.set_rule_if(key.clone(), payload.to_rule(), expected_version).await?;
+
+    let mut resp = Json(ConfigResponse {
+        status: "success".to_string(),
         message: "Rate limit configuration updated".to_string(),
         key,
+    }).into_response();
+    resp.headers_mut().insert(header::ETAG, rule_etag(version));
+    Ok(resp)
+}
+
+/// The rule version a conditional update expects, from `If-Match`: an
+/// entity tag such as `"3"`, as sent in the rule's `ETag`
+fn if_match_version(headers: &HeaderMap) -> Result<Option<u64>, ThrottlerError> {
+    let Some(value) = headers.get(header::IF_MATCH) else {
+        return Ok(None);
+    };
+    value.to_str().ok()
+        .map(|tag| tag.trim().trim_matches('"'))
+        .and_then(|version| version.parse().ok())
+        .map(Some)
+        .ok_or_else(|| ThrottlerError::ValidationError(
+            "If-Match must be a rule version such as \"3\"".to_string()
+        ))
+}
+
+/// A rule version as an `ETag` value
+fn rule_etag(version: u64) -> HeaderValue {
+    format!("\"{}\"", version).parse().unwrap()
+}
+
+/// Enables limiting for a key whose rule was disabled.
+///
+/// # Request
+///
+/// ```text
+/// POST /rate-limit/:key/enable
+/// ```
+///
+/// # Response (200 OK)
+///
+/// ```json
+/// {
+///   "status": "success",
+///   "message": "Rate limiting enabled",
+///   "key": "api-client-123"
+/// }
+/// ```
+///
+/// # Errors
+///
+/// - `400 Bad Request` - Invalid key format
+/// - `404 Not Found` - The key has no rule
+pub async fn enable_rate_limit(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Path(key): Path<String>,
+) -> Result<impl IntoResponse, ThrottlerError> {
+    set_rule_enabled(state, headers, key, true).await
+}
+
+/// Disables limiting for a key without removing its rule.
+///
+/// Requests for the key are allowed without consuming tokens until it is
+/// enabled again, e.g. to pause limiting for a noisy but trusted client.
+///
+/// # Request
+///
+/// ```text
+/// POST /rate-limit/:key/disable
+/// ```
+///
+/// # Response (200 OK)
+///
+/// ```json
+/// {
+///   "status": "success",
+///   "message": "Rate limiting disabled",
+///   "key": "api-client-123"
+/// }
+/// ```
+///
+/// # Errors
+///
+/// - `400 Bad Request` - Invalid key format
+/// - `404 Not Found` - The key has no rule
+pub async fn disable_rate_limit(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Path(key): Path<String>,
+) -> Result<impl IntoResponse, ThrottlerError> {
+    set_rule_enabled(state, headers, key, false).await
+}
+
+async fn set_rule_enabled(
+    state: SharedState,
+    headers: HeaderMap,
+    key: String,
+    enabled: bool,
+) -> Result<Json<ConfigResponse>, ThrottlerError> {
+    let state = state.read().await;
+
+    state.validator.validate_key(&key)?;
+    let key = tenant_key(&state, &headers, key)?;
+    state.throttler.set_enabled(&key, enabled).await?;
+
+    let message = if enabled { "Rate limiting enabled" } else { "Rate limiting disabled" };
+    Ok(Json(ConfigResponse {
+        status: "success".to_string(),
+        message: message.to_string(),
+        key,
     }))
 }
 
@@ -404,6 +1422,7 @@ pub async fn set_rate_limit(
 /// - `500 Internal Server Error` - Redis or internal error
 pub async fn delete_rate_limit(
     State(state): State<SharedState>,
+    headers: HeaderMap,
     Path(key): Path<String>,
 ) -> Result<impl IntoResponse, ThrottlerError> {
     // Acquire write lock - delete requires exclusive access
@@ -411,9 +1430,11 @@ pub async fn delete_rate_limit(
 
     // Validate key format
     state.validator.validate_key(&key)?;
+    let key = tenant_key(&state, &headers, key)?;
 
-    // Reset the rate limit bucket
+    // Reset the rate limit bucket and drop any configured rule
     state.rate_limiter.reset(&key)?;
+    state.throttler.remove_rule(&key).await?;
 
     Ok(Json(ConfigResponse {
         status: "success".to_string(),
@@ -422,6 +1443,665 @@ pub async fn delete_rate_limit(
     }))
 }
 
+/// Creates or updates the limit shared by every request to a route,
+/// whichever key it is checked under.
+///
+/// # Request
+///
+/// ```text
+/// PUT /route-limit
+/// Content-Type: application/json
+///
+/// {"route": "POST /upload", "requests": 100, "window_ms": 60000}
+/// ```
+///
+/// # Response (200 OK)
+///
+/// ```json
+/// {
+///   "status": "success",
+///   "message": "Route limit configuration updated",
+///   "key": "POST /upload"
+/// }
+/// ```
+///
+/// # Errors
+///
+/// - `400 Bad Request` - Invalid route pattern or rate limit parameters
+pub async fn set_route_limit(
+    State(state): State<SharedState>,
+    BoundedJson(payload): BoundedJson<RouteConfigRequest>,
+) -> Result<impl IntoResponse, ThrottlerError> {
+    let state = state.read().await;
+
+    state.validator.validate_rate_limit(payload.rule.requests, payload.rule.window_ms)?;
+    if payload.rule.expires_in_secs == Some(0) {
+        return Err(ThrottlerError::ValidationError(
+            "expires_in_secs must be greater than 0".to_string(),
+        ));
+    }
+    state.throttler.set_route_rule(payload.route.clone(), payload.rule.to_rule()).await?;
+
+    Ok(Json(ConfigResponse {
+        status: "success".to_string(),
+        message: "Route limit configuration updated".to_string(),
+        key: payload.route,
+    }))
+}
+
+/// Removes a route's limit and resets its bucket.
+///
+/// # Request
+///
+/// ```text
+/// DELETE /route-limit?route=POST%20/upload
+/// ```
+///
+/// # Response (200 OK)
+///
+/// ```json
+/// {
+///   "status": "success",
+///   "message": "Route limit configuration deleted",
+///   "key": "POST /upload"
+/// }
+/// ```
+///
+/// # Errors
+///
+/// - `404 Not Found` - No rule is set for the route
+/// - `500 Internal Server Error` - Redis or internal error
+pub async fn delete_route_limit(
+    State(state): State<SharedState>,
+    Query(query): Query<RouteQuery>,
+) -> Result<impl IntoResponse, ThrottlerError> {
+    let state = state.write().await;
+
+    if state.throttler.remove_route_rule(&query.route).await?.is_none() {
+        return Err(ThrottlerError::RuleNotFound(query.route));
+    }
+
+    Ok(Json(ConfigResponse {
+        status: "success".to_string(),
+        message: "Route limit configuration deleted".to_string(),
+        key: query.route,
+    }))
+}
+
+/// Creates or updates the rule for every key starting with a prefix.
+///
+/// Exact key rules take precedence. Among patterns the longest prefix wins,
+/// then the earliest registered, so `tenant-` set before `tenant-*` keeps
+/// governing `tenant-acme`. With tenant isolation the pattern is scoped to
+/// the caller's tenant, like a key.
+///
+/// # Request
+///
+/// ```text
+/// PUT /pattern-limit
+/// Content-Type: application/json
+///
+/// {"pattern": "tenant-*", "requests": 100, "window_ms": 60000}
+/// ```
+///
+/// # Response (200 OK)
+///
+/// ```json
+/// {
+///   "status": "success",
+///   "message": "Pattern limit configuration updated",
+///   "key": "tenant-*"
+/// }
+/// ```
+///
+/// # Errors
+///
+/// - `400 Bad Request` - Invalid pattern or rate limit parameters
+pub async fn set_pattern_limit(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    BoundedJson(payload): BoundedJson<PatternConfigRequest>,
+) -> Result<impl IntoResponse, ThrottlerError> {
+    let state = state.read().await;
+
+    // The prefix must itself be a valid key, so patterns stay in key space
+    state.validator.validate_key(payload.pattern.strip_suffix('*').unwrap_or(&payload.pattern))?;
+    let pattern = tenant_key(&state, &headers, payload.pattern)?;
+    state.validator.validate_rate_limit(payload.rule.requests, payload.rule.window_ms)?;
+    if payload.rule.expires_in_secs == Some(0) {
+        return Err(ThrottlerError::ValidationError(
+            "expires_in_secs must be greater than 0".to_string(),
+        ));
+    }
+    state.throttler.set_pattern_rule(pattern.clone(), payload.rule.to_rule()).await?;
+
+    Ok(Json(ConfigResponse {
+        status: "success".to_string(),
+        message: "Pattern limit configuration updated".to_string(),
+        key: pattern,
+    }))
+}
+
+/// Removes a prefix pattern's rule. Buckets of matching keys are kept and
+/// fall back to their next rule.
+///
+/// # Request
+///
+/// ```text
+/// DELETE /pattern-limit?pattern=tenant-*
+/// ```
+///
+/// # Response (200 OK)
+///
+/// ```json
+/// {
+///   "status": "success",
+///   "message": "Pattern limit configuration deleted",
+///   "key": "tenant-*"
+/// }
+/// ```
+///
+/// # Errors
+///
+/// - `404 Not Found` - No rule is set for the pattern
+pub async fn delete_pattern_limit(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<PatternQuery>,
+) -> Result<impl IntoResponse, ThrottlerError> {
+    let state = state.write().await;
+
+    let pattern = tenant_key(&state, &headers, query.pattern)?;
+    if state.throttler.remove_pattern_rule(&pattern).await.is_none() {
+        return Err(ThrottlerError::RuleNotFound(pattern));
+    }
+
+    Ok(Json(ConfigResponse {
+        status: "success".to_string(),
+        message: "Pattern limit configuration deleted".to_string(),
+        key: pattern,
+    }))
+}
+
+/// Checks a request against an nginx-style `limit_req` zone.
+///
+/// Drop-in for teams migrating from nginx: the zone's `rate` and `burst` are
+/// translated into a bucket (see [`crate::nginx`]) and one token is consumed.
+/// Rejections use `limit_req_status` (503 by default, as in nginx).
+///
+/// # Request
+///
+/// ```text
+/// POST /nginx/limit
+/// Content-Type: application/json
+///
+/// {"zone": "api", "rate": "10r/s", "burst": 5, "key": "203.0.113.7"}
+/// ```
+///
+/// # Response (200 OK - Passed)
+///
+/// ```json
+/// {"allowed": true, "remaining": 5, "limit": 6}
+/// ```
+///
+/// # Response (503 Service Unavailable - Rejected)
+///
+/// ```json
+/// {"allowed": false, "remaining": 0, "limit": 6}
+/// ```
+///
+/// # Errors
+///
+/// - `400 Bad Request` - Invalid zone, key, rate, burst or `limit_req_status`
+/// - `500 Internal Server Error` - Redis or internal error
+pub async fn nginx_limit(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    ValidJson(payload): ValidJson<NginxLimitRequest>,
+) -> Result<impl IntoResponse, ThrottlerError> {
+    let state = state.read().await;
+
+    state.validator.validate_key(&payload.zone)?;
+    state.validator.validate_key(&payload.key)?;
+    let limit = payload.capacity();
+    let refill_rate = payload.refill_rate()?;
+    ConfigValidator::validate_rate_limit(limit, refill_rate)?;
+    let rejection_status = payload.rejection_status()?;
+
+    let key = tenant_key(&state, &headers, payload.bucket_key())?;
+    let (allowed, remaining) = state.rate_limiter
+        .check_rate_limit_shared_with_params(&key, limit, refill_rate)
+        .await?;
+    state.metrics.record_request(&key, allowed).await;
+    state.metrics.record_remaining(remaining as f64);
+
+    let mut resp = Json(CheckResponse { allowed, remaining, limit }).into_response();
+    resp.headers_mut().insert("X-RateLimit-Limit", limit.to_string().parse().unwrap());
+    resp.headers_mut().insert("X-RateLimit-Remaining", remaining.to_string().parse().unwrap());
+
+    if !allowed {
+        *resp.status_mut() = StatusCode::from_u16(rejection_status)
+            .map_err(|e| ThrottlerError::InternalError(e.to_string()))?;
+        let retry_after = state.rate_limiter.retry_after_secs(refill_rate, remaining as f64, 1)?;
+        resp.headers_mut().insert("Retry-After", retry_after.to_string().parse().unwrap());
+    }
+
+    Ok(resp)
+}
+
+/// Takes a concurrency slot for a request that is about to start.
+///
+/// The key's rule caps how many of its requests may be in flight at once
+/// (`concurrency_limit`); keys without a cap, or whose limiting is paused,
+/// always get a slot. Release it with `POST /concurrency/:key/release` when
+/// the request ends. Slots that are never released, e.g. because the client
+/// went away, lapse after `Config::concurrency_slot_ttl_ms`.
+///
+/// # Request
+///
+/// ```text
+/// POST /concurrency/:key/acquire
+/// ```
+///
+/// # Response (200 OK)
+///
+/// ```json
+/// {"acquired": true, "slot_id": "6f1c...", "in_flight": 3, "limit": 10}
+/// ```
+///
+/// # Response (429 Too Many Requests)
+///
+/// ```json
+/// {"acquired": false, "in_flight": 10, "limit": 10}
+/// ```
+///
+/// # Errors
+///
+/// - `400 Bad Request` - Invalid key format
+/// - `500 Internal Server Error` - Redis or internal error
+pub async fn acquire_concurrency_slot(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Path(key): Path<String>,
+) -> Result<impl IntoResponse, ThrottlerError> {
+    let state = state.read().await;
+
+    state.validator.validate_key(&key)?;
+    let key = tenant_key(&state, &headers, key)?;
+
+    let limit = state.throttler.resolve_rule(&key).await
+        .filter(|resolved| resolved.rule.enabled)
+        .and_then(|resolved| resolved.rule.concurrency_limit);
+
+    let resp = match state.rate_limiter.acquire_concurrency_slot(&key, limit).await? {
+        Some(slot) => Json(ConcurrencyResponse {
+            acquired: true,
+            slot_id: Some(slot.id),
+            in_flight: slot.held,
+            limit,
+        }).into_response(),
+        None => {
+            let mut resp = (StatusCode::TOO_MANY_REQUESTS, Json(ConcurrencyResponse {
+                acquired: false,
+                slot_id: None,
+                in_flight: limit.map_or(0, u64::from),
+                limit,
+            })).into_response();
+            with_limit_source(&state, "concurrency", &mut resp);
+            resp
+        }
+    };
+    Ok(resp)
+}
+
+/// Releases a concurrency slot once its request has ended.
+///
+/// Releasing a slot that already lapsed or was released before is not an
+/// error; `released` is then `false`.
+///
+/// # Request
+///
+/// ```text
+/// POST /concurrency/:key/release
+/// Content-Type: application/json
+///
+/// {"slot_id": "6f1c..."}
+/// ```
+///
+/// # Response (200 OK)
+///
+/// ```json
+/// {"released": true}
+/// ```
+///
+/// # Errors
+///
+/// - `400 Bad Request` - Invalid key format or slot id
+/// - `500 Internal Server Error` - Redis or internal error
+pub async fn release_concurrency_slot(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Path(key): Path<String>,
+    BoundedJson(payload): BoundedJson<ReleaseRequest>,
+) -> Result<impl IntoResponse, ThrottlerError> {
+    let state = state.read().await;
+
+    state.validator.validate_key(&key)?;
+    let key = tenant_key(&state, &headers, key)?;
+    if payload.slot_id.is_empty() || payload.slot_id.len() > MAX_SLOT_ID_LEN {
+        return Err(ThrottlerError::ValidationError(
+            format!("slot_id must be 1-{} characters", MAX_SLOT_ID_LEN)
+        ));
+    }
+
+    let released = state.rate_limiter.release_concurrency_slot(&key, &payload.slot_id).await?;
+    Ok(Json(serde_json::json!({ "released": released })))
+}
+
+/// Exports all local bucket state for migration to another instance.
+///
+/// # Request
+///
+/// ```text
+/// GET /admin/state
+/// ```
+///
+/// # Response (200 OK)
+///
+/// ```json
+/// {
+///   "exported_at": 1705312260000,
+///   "buckets": {
+///     "api-client-123": {"tokens": 42.5, "capacity": 100, "refill_rate": 10.0, "last_refill": 1705312260000}
+///   }
+/// }
+/// ```
+///
+/// # Errors
+///
+/// - `403 Forbidden` - Tenant isolation is on and `X-Admin-Token` is missing or wrong
+pub async fn export_state(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse, ThrottlerError> {
+    let state = state.read().await;
+    require_operator(&state, &headers)?;
+    Ok(Json(state.rate_limiter.export_state()?))
+}
+
+/// Imports bucket state produced by `GET /admin/state`.
+///
+/// Imported buckets replace any local state for the same keys. Refill
+/// restarts at import time so the transfer gap grants no tokens.
+///
+/// # Request
+///
+/// ```text
+/// PUT /admin/state
+/// Content-Type: application/json
+///
+/// {"exported_at": 1705312260000, "buckets": {...}}
+/// ```
+///
+/// # Response (200 OK)
+///
+/// ```json
+/// {"status": "success", "imported": 1}
+/// ```
+///
+/// # Errors
+///
+/// - `400 Bad Request` - Invalid key or bucket state
+/// - `403 Forbidden` - Tenant isolation is on and `X-Admin-Token` is missing or wrong
+pub async fn import_state(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Json(payload): Json<SerializableState>,
+) -> Result<impl IntoResponse, ThrottlerError> {
+    // Acquire write lock - import replaces bucket state
+    let state = state.write().await;
+    require_operator(&state, &headers)?;
+
+    for key in payload.buckets.keys() {
+        state.validator.validate_key(key)?;
+    }
+
+    let imported = state.rate_limiter.import_state(payload)?;
+
+    Ok(Json(serde_json::json!({
+        "status": "success",
+        "imported": imported
+    })))
+}
+
+/// Reports limiter statistics with memory estimates for capacity planning.
+///
+/// `estimated_memory_bytes` covers local buckets only: a fixed
+/// `bytes_per_bucket` plus each key's length. `largest_keys` ranks buckets
+/// by that estimate and `most_active_keys` by recorded requests, which
+/// helps spot key-cardinality blowups.
+///
+/// `write_races` counts Redis bucket writes that lost a race with another
+/// instance. With `Config::race_log_size` set, `recent_races` lists the
+/// latest of them, oldest first, to tell hot keys from occasional races.
+///
+/// # Request
+///
+/// ```text
+/// GET /admin/stats?top=10
+/// ```
+///
+/// # Response (200 OK)
+///
+/// ```json
+/// {
+///   "local_buckets": 2,
+///   "estimated_memory_bytes": 170,
+///   "bytes_per_bucket": 80,
+///   "redis_enabled": 0,
+///   "redis_writes": 0,
+///   "fair_queued": 0,
+///   "write_races": 3,
+///   "largest_keys": [{"key": "api-client-123", "bytes": 94}],
+///   "most_active_keys": [{"key": "api-client-123", "total_requests": 42, "throttled_requests": 3}],
+///   "recent_races": [{"key": "throttler:api-client-123", "read_at_ms": 1700000000000, "rejected_at_ms": 1700000000002}]
+/// }
+/// ```
+///
+/// # Errors
+///
+/// - `403 Forbidden` - Tenant isolation is on and `X-Admin-Token` is missing or wrong
+pub async fn admin_stats(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<StatsQuery>,
+) -> Result<impl IntoResponse, ThrottlerError> {
+    let state = state.read().await;
+    require_operator(&state, &headers)?;
+    let top = query.top.unwrap_or(DEFAULT_STATS_TOP_KEYS);
+
+    let mut body = serde_json::Map::new();
+    for (name, value) in state.rate_limiter.get_stats()? {
+        body.insert(name, value.into());
+    }
+    body.insert("bytes_per_bucket".to_string(), BUCKET_ENTRY_BYTES.into());
+
+    let largest: Vec<_> = state.rate_limiter.largest_keys(top)?
+        .into_iter()
+        .map(|(key, bytes)| serde_json::json!({"key": key, "bytes": bytes}))
+        .collect();
+    body.insert("largest_keys".to_string(), largest.into());
+
+    let most_active: Vec<_> = state.metrics.most_active(top).await
+        .into_iter()
+        .map(|(key, metrics)| serde_json::json!({
+            "key": key,
+            "total_requests": metrics.total_requests,
+            "throttled_requests": metrics.throttled_requests
+        }))
+        .collect();
+    body.insert("most_active_keys".to_string(), most_active.into());
+
+    if state.rate_limiter.config().race_log_size > 0 {
+        let races = serde_json::to_value(state.rate_limiter.recent_races()?)?;
+        body.insert("recent_races".to_string(), races);
+    }
+
+    Ok(Json(serde_json::Value::Object(body)))
+}
+
+/// Lists buckets with their remaining tokens, capacity and last activity.
+///
+/// Paginated: each page holds up to `limit` keys (capped at
+/// `MAX_LIST_BUCKETS`) and a `next_cursor` to fetch the next one, `null` on
+/// the last page. With Redis the listing comes from `SCAN`, so a page may
+/// be short (or empty) while `next_cursor` is still set, and keys changing
+/// during the walk may be missed or repeated.
+///
+/// # Request
+///
+/// ```text
+/// GET /admin/keys/detailed?limit=2
+/// ```
+///
+/// # Response (200 OK)
+///
+/// ```json
+/// {
+///   "keys": [
+///     {"key": "api-client-1", "remaining": 97, "capacity": 100, "last_activity_ms": 1700000000000},
+///     {"key": "api-client-2", "remaining": 100, "capacity": 100, "last_activity_ms": 1700000000500}
+///   ],
+///   "next_cursor": "api-client-2"
+/// }
+/// ```
+///
+/// # Errors
+///
+/// - `400 Bad Request` - Malformed cursor
+/// - `403 Forbidden` - Tenant isolation is on and `X-Admin-Token` is missing or wrong
+/// - `500 Internal Server Error` - Redis or internal error
+pub async fn list_keys_detailed(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<ListKeysQuery>,
+) -> Result<impl IntoResponse, ThrottlerError> {
+    let state = state.read().await;
+    require_operator(&state, &headers)?;
+    let limit = query.limit.unwrap_or(DEFAULT_LIST_KEYS_LIMIT);
+
+    Ok(Json(state.rate_limiter.list_buckets(limit, query.cursor).await?))
+}
+
+/// Per-key request counters in the Prometheus text exposition format.
+///
+/// Series are labelled with the key plus any metadata labels set on its rule.
+/// With `Config::metrics_flush_interval_ms` set the request counts are the
+/// whole fleet's, read from Redis (see [`crate::metrics`]); if Redis cannot
+/// be reached this instance's own counts are served instead.
+/// `throttler_corrupt_buckets_total` counts stored buckets that could not be
+/// read and were started over.
+/// `throttler_remaining_tokens` is a histogram of the tokens left after each
+/// check, when `Config::remaining_histogram_buckets` is set.
+///
+/// # Request
+///
+/// ```text
+/// GET /metrics
+/// ```
+///
+/// # Response (200 OK)
+///
+/// ```text
+/// # TYPE throttler_requests_total counter
+/// throttler_requests_total{key="api-client-123",tenant="acme",result="allowed"} 42
+/// throttler_requests_total{key="api-client-123",tenant="acme",result="throttled"} 3
+/// ```
+pub async fn metrics(
+    State(state): State<SharedState>,
+) -> impl IntoResponse {
+    let state = state.read().await;
+    let labels = state.throttler.get_metadata_labels().await;
+    let mut body = match state.throttler.fleet_metrics().await {
+        Ok(Some(fleet)) => render_prometheus(&fleet, &labels),
+        Ok(None) => state.metrics.render_prometheus(&labels).await,
+        Err(e) => {
+            tracing::warn!("Serving this instance's metrics, fleet totals unavailable: {}", e);
+            state.metrics.render_prometheus(&labels).await
+        }
+    };
+    body.push_str(&state.metrics.render_remaining_histogram());
+    body.push_str("# HELP throttler_corrupt_buckets_total Stored buckets found corrupt and started over\n");
+    body.push_str("# TYPE throttler_corrupt_buckets_total counter\n");
+    body.push_str(&format!("throttler_corrupt_buckets_total {}\n", state.rate_limiter.corrupt_buckets()));
+
+    ([("content-type", "text/plain; version=0.0.4")], body)
+}
+
+/// Deletes rate limit configuration and resets buckets for several keys.
+///
+/// All keys are validated first; if any is invalid the whole request is
+/// rejected and nothing is deleted.
+///
+/// # Request
+///
+/// ```text
+/// DELETE /rate-limit?keys=client-a,client-b
+/// ```
+///
+/// # Response (200 OK)
+///
+/// ```json
+/// {
+///   "status": "success",
+///   "results": {"client-a": true, "client-b": true}
+/// }
+/// ```
+///
+/// # Errors
+///
+/// - `400 Bad Request` - Empty key list, too many keys, or any invalid key
+/// - `500 Internal Server Error` - Redis or internal error
+pub async fn delete_rate_limits(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(query): Query<DeleteManyQuery>,
+) -> Result<impl IntoResponse, ThrottlerError> {
+    // Acquire write lock - delete requires exclusive access
+    let state = state.write().await;
+
+    let mut keys: Vec<String> = query.keys
+        .split(',')
+        .map(|key| key.trim().to_string())
+        .collect();
+    keys.sort();
+    keys.dedup();
+
+    if keys.len() > MAX_DELETE_KEYS {
+        return Err(ThrottlerError::ValidationError(
+            format!("At most {} keys can be deleted at once", MAX_DELETE_KEYS)
+        ));
+    }
+
+    // Validate everything before touching any state
+    for key in &keys {
+        state.validator.validate_key(key)?;
+    }
+    let keys: Vec<String> = keys.into_iter()
+        .map(|key| tenant_key(&state, &headers, key))
+        .collect::<Result<_, _>>()?;
+
+    let results = state.rate_limiter.reset_many(&keys)?;
+    for key in &keys {
+        state.throttler.remove_rule(key).await?;
+    }
+
+    Ok(Json(serde_json::json!({
+        "status": "success",
+        "results": results
+    })))
+}
+
 /// Liveness probe endpoint for Kubernetes health checks.
 ///
 /// Returns the current health status of the service. Always returns 200 OK
@@ -0,0 +1,124 @@
+//! Consistent-hash ring for sharding keys across Redis nodes.
+//!
+//! Each node is placed on the ring at many points (virtual nodes) derived
+//! from its name; a key belongs to the first node point at or after the
+//! key's own hash, wrapping around. Adding a node only takes over the arcs
+//! in front of its points, so roughly `1/n` of keys move and all of them
+//! move to the new node.
+//!
+//! Hashes come from SHA-256 rather than `std`'s hasher so that every
+//! instance, whatever its Rust version, routes a key to the same node.
+
+use sha2::{Digest, Sha256};
+
+/// Points each node occupies on the ring; more points even out the spread
+pub const RING_REPLICAS: usize = 160;
+
+/// Maps keys to node indexes with minimal remapping when nodes change.
+#[derive(Debug, Clone)]
+pub struct HashRing {
+    /// `(point, node index)`, sorted by point
+    points: Vec<(u64, usize)>,
+}
+
+impl HashRing {
+    /// Builds a ring over `nodes`, identified by name (e.g. their URLs).
+    ///
+    /// A node's placement depends only on its name, not its position in
+    /// the list, so reordering nodes does not move keys.
+    pub fn new<S: AsRef<str>>(nodes: &[S]) -> Self {
+        let mut points: Vec<(u64, usize)> = nodes.iter()
+            .enumerate()
+            .flat_map(|(index, node)| {
+                (0..RING_REPLICAS).map(move |replica| {
+                    (ring_hash(&format!("{}#{}", node.as_ref(), replica)), index)
+                })
+            })
+            .collect();
+        points.sort_unstable();
+
+        Self { points }
+    }
+
+    /// Index of the node owning `key`, or 0 for an empty ring
+    pub fn node_for(&self, key: &str) -> usize {
+        if self.points.is_empty() {
+            return 0;
+        }
+
+        let hash = ring_hash(key);
+        let position = self.points.partition_point(|&(point, _)| point < hash);
+        self.points[position % self.points.len()].1
+    }
+}
+
+/// First eight bytes of the key's SHA-256, as a ring position
+fn ring_hash(value: &str) -> u64 {
+    let digest = Sha256::digest(value.as_bytes());
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NODES: [&str; 3] = ["redis://a:6379", "redis://b:6379", "redis://c:6379"];
+
+    fn keys() -> Vec<String> {
+        (0..3000).map(|i| format!("throttler:client-{}", i)).collect()
+    }
+
+    #[test]
+    fn test_routing_is_deterministic() {
+        let ring = HashRing::new(&NODES);
+        let again = HashRing::new(&NODES);
+        for key in keys() {
+            assert_eq!(ring.node_for(&key), again.node_for(&key));
+            assert_eq!(ring.node_for(&key), ring.node_for(&key));
+        }
+
+        // Placement follows the node name, not its position in the list
+        let reordered = HashRing::new(&[NODES[2], NODES[0], NODES[1]]);
+        for key in keys() {
+            assert_eq!(NODES[ring.node_for(&key)], [NODES[2], NODES[0], NODES[1]][reordered.node_for(&key)]);
+        }
+    }
+
+    #[test]
+    fn test_keys_spread_across_nodes() {
+        let ring = HashRing::new(&NODES);
+        let mut counts = [0usize; 3];
+        for key in keys() {
+            counts[ring.node_for(&key)] += 1;
+        }
+
+        for count in counts {
+            assert!((700..=1300).contains(&count), "uneven spread: {:?}", counts);
+        }
+    }
+
+    #[test]
+    fn test_adding_a_node_moves_few_keys() {
+        let before = HashRing::new(&NODES);
+        let after = HashRing::new(&[NODES[0], NODES[1], NODES[2], "redis://d:6379"]);
+
+        let mut moved = 0;
+        for key in keys() {
+            let (old, new) = (before.node_for(&key), after.node_for(&key));
+            if old != new {
+                // Keys only ever move to the new node
+                assert_eq!(new, 3);
+                moved += 1;
+            }
+        }
+        assert!((450..=1050).contains(&moved), "moved {} of 3000", moved);
+    }
+
+    #[test]
+    fn test_single_and_empty_rings() {
+        assert_eq!(HashRing::new(&["redis://only:6379"]).node_for("any"), 0);
+        assert_eq!(HashRing::new::<&str>(&[]).node_for("any"), 0);
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
 use std::time::SystemTime;
 use serde::{Deserialize, Serialize};
 
@@ -12,27 +14,84 @@ pub struct HealthStatus {
     pub dependencies: DependencyStatus,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct DependencyStatus {
-    pub redis: ServiceStatus,
-}
+/// Status of each registered dependency, keyed by name
+pub type DependencyStatus = BTreeMap<String, ServiceStatus>;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ServiceStatus {
     pub status: String,
     pub response_time_ms: u64,
     pub error: Option<String>,
+    /// Whether this dependency being down makes the service unhealthy
+    #[serde(default)]
+    pub critical: bool,
+}
+
+impl ServiceStatus {
+    pub fn healthy() -> Self {
+        Self {
+            status: "healthy".to_string(),
+            response_time_ms: 0,
+            error: None,
+            critical: false,
+        }
+    }
+
+    pub fn unavailable(error: impl Into<String>) -> Self {
+        Self {
+            status: "unavailable".to_string(),
+            response_time_ms: 0,
+            error: Some(error.into()),
+            critical: false,
+        }
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.status == "healthy"
+    }
+}
+
+/// A health probe for a single dependency
+pub type HealthProbe = Arc<dyn Fn() -> ServiceStatus + Send + Sync>;
+
+struct RegisteredProbe {
+    critical: bool,
+    probe: HealthProbe,
 }
 
 static START_TIME: std::sync::LazyLock<SystemTime> = std::sync::LazyLock::new(SystemTime::now);
 
+/// Aggregates named dependency probes into an overall health status.
+///
+/// The overall status is `unhealthy` if any critical dependency is down,
+/// `degraded` if only non-critical ones are, and `healthy` otherwise.
+/// Redis is registered as a non-critical dependency since the service keeps
+/// working in local-only mode without it.
 pub struct HealthChecker {
-    rate_limiter: RateLimiter,
+    probes: BTreeMap<String, RegisteredProbe>,
 }
 
 impl HealthChecker {
     pub fn new(rate_limiter: RateLimiter) -> Self {
-        Self { rate_limiter }
+        let mut checker = Self::empty();
+        checker.register("redis", false, move || check_redis(&rate_limiter));
+        checker
+    }
+
+    /// Create a checker with no registered dependencies
+    pub fn empty() -> Self {
+        Self { probes: BTreeMap::new() }
+    }
+
+    /// Register (or replace) a named dependency probe
+    pub fn register<F>(&mut self, name: impl Into<String>, critical: bool, probe: F)
+    where
+        F: Fn() -> ServiceStatus + Send + Sync + 'static,
+    {
+        self.probes.insert(name.into(), RegisteredProbe {
+            critical,
+            probe: Arc::new(probe),
+        });
     }
 
     pub fn check_health(&self) -> HealthStatus {
@@ -41,12 +100,36 @@ impl HealthChecker {
             .unwrap_or_default()
             .as_secs();
 
-        let redis_status = self.check_redis();
+        let mut dependencies = DependencyStatus::new();
+        let mut critical_down = false;
+        let mut non_critical_down = false;
 
-        let overall_status = if redis_status.status == "healthy" {
-            "healthy"
+        for (name, registered) in &self.probes {
+            let start = SystemTime::now();
+            let mut status = (registered.probe)();
+            if status.response_time_ms == 0 {
+                status.response_time_ms = start.elapsed()
+                    .unwrap_or_default()
+                    .as_millis() as u64;
+            }
+            status.critical = registered.critical;
+
+            if !status.is_healthy() {
+                if registered.critical {
+                    critical_down = true;
+                } else {
+                    non_critical_down = true;
+                }
+            }
+            dependencies.insert(name.clone(), status);
+        }
+
+        let overall_status = if critical_down {
+            "unhealthy"
+        } else if non_critical_down {
+            "degraded"
         } else {
-            "degraded" // Not unhealthy, just running without Redis
+            "healthy"
         };
 
         HealthStatus {
@@ -56,34 +139,16 @@ impl HealthChecker {
                 .as_secs(),
             version: env!("CARGO_PKG_VERSION").to_string(),
             uptime_seconds: uptime,
-            dependencies: DependencyStatus {
-                redis: redis_status,
-            },
+            dependencies,
         }
     }
+}
 
-    fn check_redis(&self) -> ServiceStatus {
-        let start = SystemTime::now();
-
-        if self.rate_limiter.is_redis_available() {
-            let response_time = start.elapsed()
-                .unwrap_or_default()
-                .as_millis() as u64;
-
-            ServiceStatus {
-                status: "healthy".to_string(),
-                response_time_ms: response_time,
-                error: None,
-            }
-        } else {
-            ServiceStatus {
-                status: "unavailable".to_string(),
-                response_time_ms: start.elapsed()
-                    .unwrap_or_default()
-                    .as_millis() as u64,
-                error: Some("Redis not configured or not reachable".to_string()),
-            }
-        }
+fn check_redis(rate_limiter: &RateLimiter) -> ServiceStatus {
+    if rate_limiter.is_redis_available() {
+        ServiceStatus::healthy()
+    } else {
+        ServiceStatus::unavailable("Redis not configured or not reachable")
     }
 }
 
@@ -98,17 +163,57 @@ mod tests {
             timestamp: 1234567890,
             version: "1.0.0".to_string(),
             uptime_seconds: 3600,
-            dependencies: DependencyStatus {
-                redis: ServiceStatus {
-                    status: "healthy".to_string(),
-                    response_time_ms: 5,
-                    error: None,
-                },
-            },
+            dependencies: DependencyStatus::from([
+                ("redis".to_string(), ServiceStatus::healthy()),
+            ]),
         };
 
         let json = serde_json::to_string(&status).unwrap();
         assert!(json.contains("healthy"));
         assert!(json.contains("1234567890"));
+        assert!(json.contains("\"redis\""));
+    }
+
+    #[test]
+    fn test_all_dependencies_healthy() {
+        let mut checker = HealthChecker::empty();
+        checker.register("store", true, ServiceStatus::healthy);
+        checker.register("webhook", false, ServiceStatus::healthy);
+
+        let health = checker.check_health();
+        assert_eq!(health.status, "healthy");
+        assert_eq!(health.dependencies.len(), 2);
+    }
+
+    #[test]
+    fn test_non_critical_failure_degrades() {
+        let mut checker = HealthChecker::empty();
+        checker.register("store", true, ServiceStatus::healthy);
+        checker.register("webhook", false, || ServiceStatus::unavailable("timeout"));
+
+        let health = checker.check_health();
+        assert_eq!(health.status, "degraded");
+        assert!(!health.dependencies["webhook"].critical);
+    }
+
+    #[test]
+    fn test_critical_failure_is_unhealthy() {
+        let mut checker = HealthChecker::empty();
+        checker.register("store", true, || ServiceStatus::unavailable("connection refused"));
+        checker.register("webhook", false, ServiceStatus::healthy);
+
+        let health = checker.check_health();
+        assert_eq!(health.status, "unhealthy");
+        assert!(health.dependencies["store"].critical);
+        assert_eq!(health.dependencies["store"].error.as_deref(), Some("connection refused"));
+    }
+
+    #[test]
+    fn test_redis_registered_as_non_critical() {
+        let checker = HealthChecker::new(RateLimiter::new(crate::config::Config::default()).unwrap());
+
+        let health = checker.check_health();
+        assert_eq!(health.status, "degraded");
+        assert!(!health.dependencies["redis"].critical);
     }
 }
@@ -1,7 +1,34 @@
 //! Key generation utilities for rate limiting.
 
+use crate::config::Config;
 use crate::error::ThrottlerError;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::net::{IpAddr, Ipv6Addr};
+
+/// Key prefixes produced by [`KeyGenerator`] that carry no client data
+const GENERATED_PREFIXES: &[&str] = &[
+    "throttle:ip:",
+    "throttle:api:",
+    "throttle:user:",
+    "throttle:composite:",
+];
+
+/// Key type prefixes [`KeyGenerator::hash_key`] leaves readable; any other
+/// leading segment may be the identifier itself and is hashed
+const CLEAR_TYPE_PREFIXES: &[&str] = &["ip:", "api:", "user:", "composite:"];
+
+/// Default maximum length, in characters, of the path component of a key
+pub const DEFAULT_MAX_PATH_LEN: usize = 256;
+
+/// Hex digits of the path digest kept when a long path is truncated
+const PATH_DIGEST_LEN: usize = 16;
+
+/// Default maximum number of sub-strategies in a composite key
+pub const DEFAULT_MAX_COMPOSITE_PARTS: usize = 4;
+
+/// Default IPv6 prefix length that per-IP keys are aggregated to
+pub const DEFAULT_IPV6_AGGREGATE_PREFIX: u8 = 64;
 
 /// Strategy for generating rate limit keys
 #[derive(Debug, Clone, PartialEq)]
@@ -19,15 +46,65 @@ pub enum KeyStrategy {
 /// Generates rate limiting keys based on request context
 pub struct KeyGenerator {
     default_strategy: KeyStrategy,
+    max_path_len: usize,
+    max_composite_parts: usize,
+    ipv6_aggregate_prefix: u8,
+    trusted_proxy_hops: usize,
 }
 
 impl KeyGenerator {
     pub fn new(strategy: KeyStrategy) -> Self {
         Self {
             default_strategy: strategy,
+            max_path_len: DEFAULT_MAX_PATH_LEN,
+            max_composite_parts: DEFAULT_MAX_COMPOSITE_PARTS,
+            ipv6_aggregate_prefix: DEFAULT_IPV6_AGGREGATE_PREFIX,
+            trusted_proxy_hops: 0,
         }
     }
 
+    /// Create a generator using the key settings from `config`
+    pub fn from_config(strategy: KeyStrategy, config: &Config) -> Self {
+        Self::new(strategy)
+            .with_ipv6_aggregate_prefix(config.ipv6_aggregate_prefix)
+            .with_trusted_proxy_hops(config.trusted_proxy_hops)
+    }
+
+    /// Set the maximum length of the path component of generated keys.
+    ///
+    /// Longer paths are truncated and suffixed with a digest of the full
+    /// path, so distinct long paths still map to distinct keys.
+    pub fn with_max_path_len(mut self, max_path_len: usize) -> Self {
+        self.max_path_len = max_path_len;
+        self
+    }
+
+    /// Set the maximum number of sub-strategies a composite key may combine.
+    ///
+    /// Each part adds a client-supplied component to the key, so a long
+    /// composite list makes for long keys; larger lists are rejected.
+    pub fn with_max_composite_parts(mut self, max_composite_parts: usize) -> Self {
+        self.max_composite_parts = max_composite_parts;
+        self
+    }
+
+    /// Set the IPv6 prefix length that per-IP keys are aggregated to.
+    ///
+    /// A single IPv6 client usually holds a whole /64, so keying on the full
+    /// address lets it rotate through fresh buckets. Values above 128 are
+    /// treated as 128 (no aggregation).
+    pub fn with_ipv6_aggregate_prefix(mut self, prefix: u8) -> Self {
+        self.ipv6_aggregate_prefix = prefix.min(128);
+        self
+    }
+
+    /// Set how many proxies in front of the service append to
+    /// `X-Forwarded-For`; see [`forwarded_client_ip`]
+    pub fn with_trusted_proxy_hops(mut self, hops: usize) -> Self {
+        self.trusted_proxy_hops = hops;
+        self
+    }
+
     /// Generate a rate limit key from request headers and metadata
     pub fn generate_key(
         &self,
@@ -46,8 +123,11 @@ impl KeyGenerator {
         client_ip: &str,
         path: &str,
     ) -> Result<String, ThrottlerError> {
+        let path = self.path_component(path);
         match strategy {
-            KeyStrategy::IpAddress => Ok(format!("throttle:ip:{}:{}", client_ip, path)),
+            KeyStrategy::IpAddress => {
+                Ok(format!("throttle:ip:{}:{}", self.ip_component(client_ip), path))
+            }
             KeyStrategy::ApiKey => {
                 let api_key = headers
                     .get("x-api-key")
@@ -62,10 +142,23 @@ impl KeyGenerator {
                 Ok(format!("throttle:user:{}:{}", user_id, path))
             }
             KeyStrategy::Composite(strategies) => {
+                if strategies.is_empty() {
+                    return Err(ThrottlerError::ValidationError(
+                        "Composite key needs at least one strategy".to_string(),
+                    ));
+                }
+                if strategies.len() > self.max_composite_parts {
+                    return Err(ThrottlerError::ValidationError(format!(
+                        "Composite key has {} strategies; at most {} are allowed",
+                        strategies.len(),
+                        self.max_composite_parts
+                    )));
+                }
+
                 let mut key_parts = Vec::new();
                 for sub_strategy in strategies {
                     let part = match sub_strategy {
-                        KeyStrategy::IpAddress => client_ip.to_string(),
+                        KeyStrategy::IpAddress => self.ip_component(client_ip),
                         KeyStrategy::ApiKey => headers
                             .get("x-api-key")
                             .or_else(|| headers.get("authorization"))
@@ -88,17 +181,92 @@ impl KeyGenerator {
         }
     }
 
-    /// Extract client IP from various header sources
+    /// Sanitize a request path and bound its length for use in a key.
+    ///
+    /// Paths within `max_path_len` characters are only sanitized. Longer
+    /// paths keep a sanitized prefix followed by `-` and the first 16 hex
+    /// digits of the SHA-256 of the original path, so the result is exactly
+    /// `max_path_len` characters and the same path always maps the same way.
+    pub fn path_component(&self, path: &str) -> String {
+        let sanitized = Self::sanitize_key(path);
+        if sanitized.chars().count() <= self.max_path_len {
+            return sanitized;
+        }
+
+        let digest = format!("{:x}", Sha256::digest(path.as_bytes()));
+        let digest = &digest[..PATH_DIGEST_LEN];
+        if self.max_path_len <= PATH_DIGEST_LEN + 1 {
+            return digest[..self.max_path_len].to_string();
+        }
+
+        let keep = self.max_path_len - PATH_DIGEST_LEN - 1;
+        let prefix: String = sanitized.chars().take(keep).collect();
+        format!("{}-{}", prefix, digest)
+    }
+
+    /// Map a client IP to the network its bucket is keyed on.
+    ///
+    /// IPv4 addresses (including IPv4-mapped IPv6) are kept whole. IPv6
+    /// addresses are masked to the configured prefix and written as
+    /// `<network>/<prefix>`, e.g. `2001:db8:1:2::/64`. Anything that does
+    /// not parse as an address is used unchanged.
+    pub fn ip_component(&self, client_ip: &str) -> String {
+        let v6 = match client_ip.parse::<IpAddr>() {
+            Ok(IpAddr::V6(v6)) => v6,
+            _ => return client_ip.to_string(),
+        };
+        if let Some(v4) = v6.to_ipv4_mapped() {
+            return v4.to_string();
+        }
+
+        let prefix = self.ipv6_aggregate_prefix;
+        let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+        let network = Ipv6Addr::from(u128::from(v6) & mask);
+        format!("{}/{}", network, prefix)
+    }
+
+    /// Extract client IP from various header sources, trusting the whole of
+    /// `X-Forwarded-For` (its first entry)
     pub fn extract_client_ip(headers: &HashMap<String, String>) -> String {
+        Self::client_ip_behind(headers, 0)
+    }
+
+    /// Extract client IP from various header sources, reading
+    /// `X-Forwarded-For` as seen through the configured proxy hops
+    pub fn client_ip(&self, headers: &HashMap<String, String>) -> String {
+        Self::client_ip_behind(headers, self.trusted_proxy_hops)
+    }
+
+    fn client_ip_behind(headers: &HashMap<String, String>, trusted_proxy_hops: usize) -> String {
         headers
             .get("x-forwarded-for")
-            .and_then(|xff| xff.split(',').next().map(|ip| ip.trim()))
+            .and_then(|xff| forwarded_client_ip(xff, trusted_proxy_hops))
             .or_else(|| headers.get("x-real-ip").map(|s| s.as_str()))
             .or_else(|| headers.get("cf-connecting-ip").map(|s| s.as_str()))
             .unwrap_or("unknown")
             .to_string()
     }
 
+    /// Replace the identifying part of a key with its SHA-256 hex digest.
+    ///
+    /// A readable prefix is kept so keys can still be told apart in Redis,
+    /// but only a known type prefix: the `throttle:<strategy>:` prefix of
+    /// generated keys, or one of `ip:`, `api:`, `user:` and `composite:`.
+    /// Any other key is hashed entirely, since its first segment (a user id,
+    /// email or tenant) may be what identifies the client. The same input
+    /// always maps to the same output.
+    pub fn hash_key(key: &str) -> String {
+        let prefix = GENERATED_PREFIXES
+            .iter()
+            .chain(CLEAR_TYPE_PREFIXES)
+            .find(|prefix| key.starts_with(*prefix))
+            .map(|prefix| &key[..prefix.len()])
+            .unwrap_or("");
+
+        let digest = Sha256::digest(&key.as_bytes()[prefix.len()..]);
+        format!("{}{:x}", prefix, digest)
+    }
+
     /// Sanitize key components to ensure valid Redis keys
     pub fn sanitize_key(key: &str) -> String {
         key.chars()
@@ -113,6 +281,27 @@ impl KeyGenerator {
     }
 }
 
+/// The client address in an `X-Forwarded-For` value, given how many
+/// trusted proxies append to it.
+///
+/// Each proxy appends the address it received the request from, so behind
+/// `hops` proxies the client is the `hops`-th entry from the right and
+/// anything left of it was supplied by the client and may be forged. With
+/// 0 hops the header is trusted as sent and its first entry is used. A
+/// header with fewer entries than `hops` yields its first entry.
+pub fn forwarded_client_ip(xff: &str, trusted_proxy_hops: usize) -> Option<&str> {
+    let entries: Vec<&str> = xff.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .collect();
+    if trusted_proxy_hops == 0 {
+        return entries.first().copied();
+    }
+
+    let position = entries.len().saturating_sub(trusted_proxy_hops);
+    entries.get(position).copied()
+}
+
 impl Default for KeyGenerator {
     fn default() -> Self {
         Self::new(KeyStrategy::IpAddress)
@@ -136,7 +325,83 @@ mod tests {
         let generator = KeyGenerator::new(KeyStrategy::IpAddress);
         let headers = create_test_headers();
         let key = generator.generate_key(&headers, "192.168.1.1", "/api/test").unwrap();
-        assert_eq!(key, "throttle:ip:192.168.1.1:/api/test");
+        assert_eq!(key, "throttle:ip:192.168.1.1:_api_test");
+    }
+
+    #[test]
+    fn test_ipv6_same_prefix_shares_key() {
+        let generator = KeyGenerator::new(KeyStrategy::IpAddress);
+        let headers = HashMap::new();
+        let a = generator.generate_key(&headers, "2001:db8:1:2::1", "/api").unwrap();
+        let b = generator.generate_key(&headers, "2001:db8:1:2:ffff:abcd:0:9", "/api").unwrap();
+
+        assert_eq!(a, b);
+        assert_eq!(a, "throttle:ip:2001:db8:1:2::/64:_api");
+    }
+
+    #[test]
+    fn test_ipv6_different_prefix_gets_own_key() {
+        let generator = KeyGenerator::new(KeyStrategy::IpAddress);
+        let headers = HashMap::new();
+        let a = generator.generate_key(&headers, "2001:db8:1:2::1", "/api").unwrap();
+        let b = generator.generate_key(&headers, "2001:db8:1:3::1", "/api").unwrap();
+
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_ipv6_aggregate_prefix_is_configurable() {
+        let generator = KeyGenerator::new(KeyStrategy::IpAddress).with_ipv6_aggregate_prefix(48);
+        assert_eq!(generator.ip_component("2001:db8:1:2::1"), "2001:db8:1::/48");
+        assert_eq!(generator.ip_component("2001:db8:1:3::1"), "2001:db8:1::/48");
+
+        let generator = KeyGenerator::new(KeyStrategy::IpAddress).with_ipv6_aggregate_prefix(128);
+        assert_eq!(generator.ip_component("2001:db8::1"), "2001:db8::1/128");
+
+        let generator = KeyGenerator::new(KeyStrategy::IpAddress).with_ipv6_aggregate_prefix(0);
+        assert_eq!(generator.ip_component("2001:db8::1"), "::/0");
+    }
+
+    #[test]
+    fn test_ipv4_is_not_aggregated() {
+        let generator = KeyGenerator::new(KeyStrategy::IpAddress);
+        assert_eq!(generator.ip_component("10.0.0.1"), "10.0.0.1");
+        assert_ne!(generator.ip_component("10.0.0.1"), generator.ip_component("10.0.0.2"));
+        assert_eq!(generator.ip_component("::ffff:10.0.0.1"), "10.0.0.1");
+        assert_eq!(generator.ip_component("unknown"), "unknown");
+    }
+
+    #[test]
+    fn test_empty_composite_rejected() {
+        let generator = KeyGenerator::new(KeyStrategy::Composite(Vec::new()));
+        let err = generator.generate_key(&create_test_headers(), "10.0.0.1", "/api").unwrap_err();
+        assert!(err.to_string().contains("at least one"), "{}", err);
+    }
+
+    #[test]
+    fn test_composite_part_limit() {
+        let parts = vec![KeyStrategy::UserId, KeyStrategy::ApiKey, KeyStrategy::IpAddress];
+        let headers = create_test_headers();
+
+        let generator = KeyGenerator::new(KeyStrategy::Composite(parts.clone()));
+        assert!(generator.generate_key(&headers, "10.0.0.1", "/api").is_ok());
+
+        let generator = generator.with_max_composite_parts(2);
+        let err = generator.generate_key(&headers, "10.0.0.1", "/api").unwrap_err();
+        assert!(err.to_string().contains("at most 2"), "{}", err);
+
+        let oversized = KeyStrategy::Composite(vec![KeyStrategy::UserId; DEFAULT_MAX_COMPOSITE_PARTS + 1]);
+        assert!(KeyGenerator::new(oversized).generate_key(&headers, "10.0.0.1", "/api").is_err());
+    }
+
+    #[test]
+    fn test_composite_strategy_aggregates_ipv6() {
+        let strategy = KeyStrategy::Composite(vec![KeyStrategy::UserId, KeyStrategy::IpAddress]);
+        let generator = KeyGenerator::new(strategy);
+        let headers = create_test_headers();
+        let a = generator.generate_key(&headers, "2001:db8::1", "/api").unwrap();
+        let b = generator.generate_key(&headers, "2001:db8::2", "/api").unwrap();
+        assert_eq!(a, b);
     }
 
     #[test]
@@ -144,7 +409,7 @@ mod tests {
         let generator = KeyGenerator::new(KeyStrategy::ApiKey);
         let headers = create_test_headers();
         let key = generator.generate_key(&headers, "192.168.1.1", "/api/test").unwrap();
-        assert_eq!(key, "throttle:api:test-api-key:/api/test");
+        assert_eq!(key, "throttle:api:test-api-key:_api_test");
     }
 
     #[test]
@@ -152,7 +417,7 @@ mod tests {
         let generator = KeyGenerator::new(KeyStrategy::UserId);
         let headers = create_test_headers();
         let key = generator.generate_key(&headers, "192.168.1.1", "/api/test").unwrap();
-        assert_eq!(key, "throttle:user:user123:/api/test");
+        assert_eq!(key, "throttle:user:user123:_api_test");
     }
 
     #[test]
@@ -161,7 +426,38 @@ mod tests {
         let generator = KeyGenerator::new(strategy);
         let headers = create_test_headers();
         let key = generator.generate_key(&headers, "192.168.1.1", "/api/test").unwrap();
-        assert_eq!(key, "throttle:composite:user123:192.168.1.1:/api/test");
+        assert_eq!(key, "throttle:composite:user123:192.168.1.1:_api_test");
+    }
+
+    #[test]
+    fn test_long_path_is_truncated() {
+        let generator = KeyGenerator::new(KeyStrategy::IpAddress).with_max_path_len(32);
+        let path = format!("/api/{}", "a".repeat(1000));
+
+        let key = generator.generate_key(&HashMap::new(), "10.0.0.1", &path).unwrap();
+        let component = key.strip_prefix("throttle:ip:10.0.0.1:").unwrap();
+        assert_eq!(component.len(), 32);
+        assert!(component.starts_with("_api_aaaa"));
+    }
+
+    #[test]
+    fn test_path_is_sanitized() {
+        let generator = KeyGenerator::default();
+        let key = generator
+            .generate_key(&HashMap::new(), "10.0.0.1", "/search?q=a b&x=*")
+            .unwrap();
+        assert_eq!(key, "throttle:ip:10.0.0.1:_search_q_a_b_x__");
+    }
+
+    #[test]
+    fn test_path_truncation_is_consistent() {
+        let generator = KeyGenerator::default().with_max_path_len(40);
+        let long_a = format!("/reports/{}/a", "x".repeat(100));
+        let long_b = format!("/reports/{}/b", "x".repeat(100));
+
+        assert_eq!(generator.path_component(&long_a), generator.path_component(&long_a));
+        assert_ne!(generator.path_component(&long_a), generator.path_component(&long_b));
+        assert_eq!(generator.path_component("/short"), "_short");
     }
 
     #[test]
@@ -171,6 +467,56 @@ mod tests {
         assert_eq!(ip, "192.168.1.1");
     }
 
+    #[test]
+    fn test_client_ip_behind_one_proxy_ignores_spoofed_entries() {
+        let mut headers = HashMap::new();
+        headers.insert("x-forwarded-for".to_string(), "1.2.3.4, 203.0.113.7".to_string());
+
+        let generator = KeyGenerator::default().with_trusted_proxy_hops(1);
+        assert_eq!(generator.client_ip(&headers), "203.0.113.7");
+        assert_eq!(KeyGenerator::extract_client_ip(&headers), "1.2.3.4");
+    }
+
+    #[test]
+    fn test_client_ip_behind_two_proxies() {
+        let mut headers = HashMap::new();
+        headers.insert(
+            "x-forwarded-for".to_string(),
+            "1.2.3.4, 5.6.7.8, 203.0.113.7, 10.0.0.2".to_string(),
+        );
+
+        let generator = KeyGenerator::default().with_trusted_proxy_hops(2);
+        assert_eq!(generator.client_ip(&headers), "203.0.113.7");
+
+        // Fewer entries than hops: the furthest one available
+        assert_eq!(forwarded_client_ip("203.0.113.7", 2), Some("203.0.113.7"));
+        assert_eq!(forwarded_client_ip(" , ", 1), None);
+    }
+
+    #[test]
+    fn test_hash_key_is_stable_and_hides_identifiers() {
+        let key = "throttle:api:sk-live-12345:/api/test";
+        let hashed = KeyGenerator::hash_key(key);
+
+        assert_eq!(hashed, KeyGenerator::hash_key(key));
+        assert!(hashed.starts_with("throttle:api:"));
+        assert!(!hashed.contains("sk-live-12345"));
+        assert_eq!(hashed.len(), "throttle:api:".len() + 64);
+    }
+
+    #[test]
+    fn test_hash_key_prefixes() {
+        assert!(KeyGenerator::hash_key("ip:203.0.113.7").starts_with("ip:"));
+        assert_eq!(KeyGenerator::hash_key("tenant:user-42").len(), 64);
+        assert_eq!(KeyGenerator::hash_key("user-42").len(), 64);
+
+        // A leading segment that is not a known type is the identifier
+        let hashed = KeyGenerator::hash_key("alice@example.com:foo");
+        assert!(!hashed.contains("alice@example.com"), "{}", hashed);
+        assert!(!hashed.contains("foo"), "{}", hashed);
+        assert_ne!(KeyGenerator::hash_key("user-42"), KeyGenerator::hash_key("user-43"));
+    }
+
     #[test]
     fn test_sanitize_key() {
         let key = "test@key#with$special%chars";
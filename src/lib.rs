@@ -61,39 +61,60 @@
 //!
 //! ## Module Organization
 //!
+//! - [`adaptive`] - Per-key capacity that adapts to observed throttling
 //! - [`algorithms`] - Pluggable rate limiting algorithms (token bucket, sliding window)
+//! - [`bucket_store`] - Storage backends for shared bucket state (Redis, in-memory)
+//! - [`concurrency`] - Per-key caps on requests in flight
 //! - [`config`] - Configuration loading and validation
 //! - [`error`] - Custom error types with HTTP status mapping
+//! - [`expiry_events`] - Notifications when bucket keys expire in Redis
 //! - [`handlers`] - HTTP request handlers for all endpoints
+//! - [`hash_ring`] - Consistent hashing of keys across Redis nodes
+//! - [`nginx`] - nginx `limit_req` compatibility
+//! - [`quota`] - Per-key quotas over calendar periods
 //! - [`rate_limiter`] - Core rate limiting engine
 //! - [`redis`] - Redis client wrapper for distributed state
+//! - [`refund`] - Handing back tokens a check consumed for failed work
+//! - `redis_tls` - Minimum TLS version checks for `rediss://` nodes (`redis-tls` feature)
+//! - [`route_rules`] - Limits shared by every request to an endpoint
 //! - [`server`] - HTTP server setup and routing
 //! - [`throttler`] - Service orchestrator
 //! - [`token_bucket`] - Token bucket algorithm implementation
 //! - [`validation`] - Request input validation
 
+pub mod adaptive;
 pub mod algorithms;
+pub mod bucket_store;
+pub mod concurrency;
 pub mod config;
 pub mod config_validator;
 pub mod error;
+pub mod expiry_events;
 pub mod handlers;
+pub mod hash_ring;
 pub mod health;
 pub mod key_generator;
 pub mod metrics;
 pub mod middleware;
+pub mod nginx;
+pub mod quota;
 pub mod rate_limit_config;
 pub mod rate_limiter;
 pub mod redis;
+#[cfg(feature = "redis-tls")]
+pub mod redis_tls;
+pub mod refund;
 pub mod response;
+pub mod route_rules;
 pub mod server;
 pub mod throttler;
 pub mod token_bucket;
 pub mod validation;
 
 // Re-export commonly used types
-pub use algorithms::{AlgorithmConfig, AlgorithmState, RateLimitAlgorithm};
+pub use algorithms::{AlgorithmConfig, AlgorithmState, RateLimitAlgorithm, RateLimitAlgorithmRegistry};
 pub use config::Config;
-pub use rate_limit_config::{RateLimitConfig, RateLimitRule};
+pub use rate_limit_config::{RateLimitConfig, RateLimitRule, RateUnit};
 pub use error::ThrottlerError;
 pub use rate_limiter::RateLimiter;
 pub use throttler::Throttler;
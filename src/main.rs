@@ -22,6 +22,14 @@ async fn main() -> Result<()> {
         .init();
 
     tracing::info!("Starting throttler service");
+    if std::env::var_os("BIND_ADDRESS").is_none() {
+        tracing::info!(
+            "BIND_ADDRESS not set; defaulting to {} (environment={}, container={})",
+            config.bind_address,
+            config.environment,
+            Config::running_in_container()
+        );
+    }
     tracing::info!(
         "Configuration: bind_address={}, redis_url={}",
         config.bind_address,
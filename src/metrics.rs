@@ -1,9 +1,95 @@
+//! # Request Metrics
+//!
+//! [`MetricsCollector`] counts allowed and throttled checks per client in
+//! the process, so each instance only sees its own traffic.
+//!
+//! ## Fleet Aggregation
+//!
+//! With `Config::metrics_flush_interval_ms` set and Redis configured, each
+//! instance also adds the counts recorded since its last flush to shared
+//! Redis hashes, and `/metrics` serves those fleet-wide totals instead.
+//!
+//! Only deltas are flushed, never an instance's running totals: counts
+//! leave the unflushed table as they are sent and go back into it if the
+//! flush fails, so each request is added once however often an instance
+//! flushes or restarts. Fleet totals therefore only grow, as Prometheus
+//! expects of a counter; [`MetricsCollector::reset_client_metrics`] resets
+//! the local view only. An instance that dies loses at most its last
+//! interval of counts.
+//!
+//! ## Remaining Tokens
+//!
+//! With [`MetricsCollector::with_remaining_histogram`], every check also
+//! records how many tokens its bucket had left, as the Prometheus histogram
+//! `throttler_remaining_tokens`, showing how close clients run to their
+//! limits. It is per instance and never sampled.
+
+use std::collections::hash_map::RandomState;
 use std::collections::HashMap;
+use std::fmt::Write;
+use std::hash::BuildHasher;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use serde::{Deserialize, Serialize};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::error::ThrottlerError;
+use crate::redis::RedisClient;
+
+/// Redis key prefix of the fleet-wide request counter hashes
+pub const FLEET_METRICS_KEY: &str = "throttler-metrics";
+
+/// Per-client `(allowed, throttled)` counts
+type RequestCounts = HashMap<String, (u64, u64)>;
+
+/// Distribution of the tokens left after each check
+#[derive(Debug)]
+struct RemainingHistogram {
+    /// Bucket upper bounds, ascending; a `+Inf` bucket follows the last
+    bounds: Vec<f64>,
+    /// Observations per bucket, not cumulative, one more than `bounds`
+    counts: Vec<AtomicU64>,
+    /// Sum of all observations, as `f64` bits
+    sum: AtomicU64,
+}
+
+impl RemainingHistogram {
+    fn new(mut bounds: Vec<f64>) -> Self {
+        bounds.retain(|bound| bound.is_finite());
+        bounds.sort_by(f64::total_cmp);
+        bounds.dedup();
+        let counts = (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect();
+        Self { bounds, counts, sum: AtomicU64::new(0f64.to_bits()) }
+    }
+
+    fn observe(&self, remaining: f64) {
+        let bucket = self.bounds.partition_point(|&bound| bound < remaining);
+        self.counts[bucket].fetch_add(1, Ordering::Relaxed);
+        let _ = self.sum.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |sum| {
+            Some((f64::from_bits(sum) + remaining).to_bits())
+        });
+    }
+
+    /// The histogram in the Prometheus text exposition format
+    fn render(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# HELP throttler_remaining_tokens Tokens left in the checked bucket after each check\n");
+        out.push_str("# TYPE throttler_remaining_tokens histogram\n");
+
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            cumulative += count.load(Ordering::Relaxed);
+            let _ = writeln!(out, "throttler_remaining_tokens_bucket{{le=\"{}\"}} {}", bound, cumulative);
+        }
+        cumulative += self.counts[self.bounds.len()].load(Ordering::Relaxed);
+        let _ = writeln!(out, "throttler_remaining_tokens_bucket{{le=\"+Inf\"}} {}", cumulative);
+        let _ = writeln!(out, "throttler_remaining_tokens_sum {}", f64::from_bits(self.sum.load(Ordering::Relaxed)));
+        let _ = writeln!(out, "throttler_remaining_tokens_count {}", cumulative);
+        out
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ThrottleMetrics {
     pub total_requests: u64,
@@ -34,19 +120,102 @@ pub struct ClientMetrics {
     pub last_request: u64,
 }
 
+/// Per-client request counters.
+///
+/// With a sample rate below 1.0 only that fraction of requests is recorded,
+/// chosen at random, and reported counts are scaled back up, trading
+/// exactness for less contention on the counter lock. Requests that are not
+/// sampled only touch an atomic counter.
 #[derive(Debug, Clone)]
 pub struct MetricsCollector {
     client_metrics: Arc<RwLock<HashMap<String, ThrottleMetrics>>>,
+    /// Fraction of requests recorded (1.0 = every request)
+    sample_rate: f64,
+    /// Requests seen while sampling, recorded or not
+    seen: Arc<AtomicU64>,
+    /// Hashes the `seen` sequence into uniform sampling draws
+    sampler: RandomState,
+    /// Redis hashes the fleet's counts are flushed to, when aggregating
+    fleet_key: Option<String>,
+    /// Sampled counts recorded since the last flush to `fleet_key`
+    unflushed: Arc<RwLock<RequestCounts>>,
+    /// Tokens left after each check, when enabled
+    remaining_histogram: Option<Arc<RemainingHistogram>>,
 }
 
 impl MetricsCollector {
     pub fn new() -> Self {
+        Self::with_sample_rate(1.0)
+    }
+
+    /// Creates a collector recording `sample_rate` (clamped to 0.0-1.0) of requests
+    pub fn with_sample_rate(sample_rate: f64) -> Self {
         Self {
             client_metrics: Arc::new(RwLock::new(HashMap::new())),
+            sample_rate: sample_rate.clamp(0.0, 1.0),
+            seen: Arc::new(AtomicU64::new(0)),
+            sampler: RandomState::new(),
+            fleet_key: None,
+            unflushed: Arc::new(RwLock::new(HashMap::new())),
+            remaining_histogram: None,
+        }
+    }
+
+    /// Keeps counts recorded from now on for [`Self::flush_to`], which adds
+    /// them to the fleet counter hashes at `fleet_key`
+    pub fn with_fleet_key(mut self, fleet_key: impl Into<String>) -> Self {
+        self.fleet_key = Some(fleet_key.into());
+        self
+    }
+
+    /// Records the tokens left after each check in a histogram with the
+    /// given bucket upper bounds; a `+Inf` bucket is always added
+    pub fn with_remaining_histogram(mut self, bounds: Vec<f64>) -> Self {
+        self.remaining_histogram = Some(Arc::new(RemainingHistogram::new(bounds)));
+        self
+    }
+
+    /// Whether to record the current request
+    fn sampled(&self) -> bool {
+        if self.sample_rate >= 1.0 {
+            return true;
+        }
+        if self.sample_rate <= 0.0 {
+            return false;
         }
+
+        let n = self.seen.fetch_add(1, Ordering::Relaxed);
+        let draw = self.sampler.hash_one(n) as f64 / u64::MAX as f64;
+        draw < self.sample_rate
+    }
+
+    /// Estimates true counts from sampled ones
+    fn scaled(&self, metrics: &ThrottleMetrics) -> ThrottleMetrics {
+        if self.sample_rate >= 1.0 || self.sample_rate <= 0.0 {
+            return metrics.clone();
+        }
+
+        ThrottleMetrics {
+            total_requests: self.scale_count(metrics.total_requests),
+            allowed_requests: self.scale_count(metrics.allowed_requests),
+            throttled_requests: self.scale_count(metrics.throttled_requests),
+            last_reset: metrics.last_reset,
+        }
+    }
+
+    /// Estimates a true count from a sampled one
+    fn scale_count(&self, count: u64) -> u64 {
+        if self.sample_rate >= 1.0 || self.sample_rate <= 0.0 {
+            return count;
+        }
+        (count as f64 / self.sample_rate).round() as u64
     }
 
     pub async fn record_request(&self, client_id: &str, allowed: bool) {
+        if !self.sampled() {
+            return;
+        }
+
         let mut metrics = self.client_metrics.write().await;
         let client_metrics = metrics.entry(client_id.to_string()).or_default();
         
@@ -56,16 +225,120 @@ impl MetricsCollector {
         } else {
             client_metrics.throttled_requests += 1;
         }
+        drop(metrics);
+
+        if self.fleet_key.is_some() {
+            let mut unflushed = self.unflushed.write().await;
+            let counts = unflushed.entry(client_id.to_string()).or_default();
+            if allowed {
+                counts.0 += 1;
+            } else {
+                counts.1 += 1;
+            }
+        }
+    }
+
+    /// Records the tokens a check left in its bucket, if the remaining
+    /// tokens histogram is enabled
+    pub fn record_remaining(&self, remaining: f64) {
+        if let Some(histogram) = &self.remaining_histogram {
+            histogram.observe(remaining);
+        }
+    }
+
+    /// The remaining tokens histogram in the Prometheus text exposition
+    /// format, or nothing when it is not enabled
+    pub fn render_remaining_histogram(&self) -> String {
+        self.remaining_histogram.as_ref().map(|histogram| histogram.render()).unwrap_or_default()
+    }
+
+    /// Adds the counts recorded since the last flush to the fleet totals in
+    /// Redis, returning how many clients had new counts. Does nothing
+    /// without a fleet key.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if Redis could not be updated; the counts are kept
+    /// for the next flush.
+    pub async fn flush_to(&self, client: Arc<RedisClient>) -> Result<usize, ThrottlerError> {
+        let Some(fleet_key) = self.fleet_key.clone() else {
+            return Ok(0);
+        };
+        let pending = std::mem::take(&mut *self.unflushed.write().await);
+        if pending.is_empty() {
+            return Ok(0);
+        }
+
+        let scaled: RequestCounts = pending.iter()
+            .map(|(client_id, (allowed, throttled))| {
+                (client_id.clone(), (self.scale_count(*allowed), self.scale_count(*throttled)))
+            })
+            .collect();
+        let flushed = scaled.len();
+        let result = tokio::task::spawn_blocking(move || client.add_request_counts(&fleet_key, &scaled))
+            .await
+            .map_err(|e| ThrottlerError::InternalError(format!("Metrics flush task failed: {}", e)))
+            .and_then(|result| result);
+
+        if let Err(e) = result {
+            // Nothing was added; merge the counts back in for the next try
+            let mut unflushed = self.unflushed.write().await;
+            for (client_id, (allowed, throttled)) in pending {
+                let counts = unflushed.entry(client_id).or_default();
+                counts.0 += allowed;
+                counts.1 += throttled;
+            }
+            return Err(e);
+        }
+        Ok(flushed)
+    }
+
+    /// Per-client totals flushed by every instance, or `None` without a
+    /// fleet key
+    pub async fn fleet_metrics(
+        &self,
+        client: Arc<RedisClient>,
+    ) -> Result<Option<HashMap<String, ThrottleMetrics>>, ThrottlerError> {
+        let Some(fleet_key) = self.fleet_key.clone() else {
+            return Ok(None);
+        };
+        let counts = tokio::task::spawn_blocking(move || client.request_counts(&fleet_key))
+            .await
+            .map_err(|e| ThrottlerError::InternalError(format!("Metrics read task failed: {}", e)))??;
+
+        Ok(Some(counts.into_iter()
+            .map(|(client_id, (allowed, throttled))| (client_id, ThrottleMetrics {
+                total_requests: allowed + throttled,
+                allowed_requests: allowed,
+                throttled_requests: throttled,
+                ..ThrottleMetrics::default()
+            }))
+            .collect()))
     }
 
     pub async fn get_client_metrics(&self, client_id: &str) -> Option<ThrottleMetrics> {
         let metrics = self.client_metrics.read().await;
-        metrics.get(client_id).cloned()
+        metrics.get(client_id).map(|client_metrics| self.scaled(client_metrics))
     }
 
     pub async fn get_all_metrics(&self) -> HashMap<String, ThrottleMetrics> {
         let metrics = self.client_metrics.read().await;
-        metrics.clone()
+        metrics.iter()
+            .map(|(client_id, client_metrics)| (client_id.clone(), self.scaled(client_metrics)))
+            .collect()
+    }
+
+    /// The `n` clients with the most recorded requests, busiest first
+    pub async fn most_active(&self, n: usize) -> Vec<(String, ThrottleMetrics)> {
+        let metrics = self.client_metrics.read().await;
+        let mut clients: Vec<_> = metrics.iter()
+            .map(|(client_id, client_metrics)| (client_id.clone(), self.scaled(client_metrics)))
+            .collect();
+        clients.sort_by(|a, b| {
+            b.1.total_requests.cmp(&a.1.total_requests).then_with(|| a.0.cmp(&b.0))
+        });
+        clients.truncate(n);
+        clients
     }
 
     pub async fn reset_client_metrics(&self, client_id: &str) {
@@ -85,6 +358,206 @@ impl MetricsCollector {
             global.throttled_requests += client_metrics.throttled_requests;
         }
         
-        global
+        self.scaled(&global)
     }
-}
\ No newline at end of file
+}
+
+impl MetricsCollector {
+    /// Render per-client counters in the Prometheus text exposition format.
+    ///
+    /// Each series is labelled with the client key plus any metadata labels
+    /// found for that key in `labels`.
+    pub async fn render_prometheus(&self, labels: &HashMap<String, HashMap<String, String>>) -> String {
+        render_prometheus(&self.get_all_metrics().await, labels)
+    }
+}
+
+/// Render per-client counters, such as the fleet totals from
+/// [`MetricsCollector::fleet_metrics`], in the Prometheus text exposition
+/// format.
+pub fn render_prometheus(
+    metrics: &HashMap<String, ThrottleMetrics>,
+    labels: &HashMap<String, HashMap<String, String>>,
+) -> String {
+    let mut clients: Vec<_> = metrics.iter().collect();
+    clients.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut out = String::new();
+    out.push_str("# HELP throttler_requests_total Rate limit checks per key and result\n");
+    out.push_str("# TYPE throttler_requests_total counter\n");
+
+    for (client_id, client_metrics) in clients {
+        let mut label_set = vec![("key".to_string(), client_id.clone())];
+        if let Some(extra) = labels.get(client_id) {
+            let mut extra: Vec<_> = extra.iter()
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect();
+            extra.sort();
+            label_set.extend(extra);
+        }
+        let rendered: Vec<String> = label_set.iter()
+            .map(|(name, value)| format!("{}=\"{}\"", name, escape_label_value(value)))
+            .collect();
+        let rendered = rendered.join(",");
+
+        let _ = writeln!(out, "throttler_requests_total{{{},result=\"allowed\"}} {}",
+            rendered, client_metrics.allowed_requests);
+        let _ = writeln!(out, "throttler_requests_total{{{},result=\"throttled\"}} {}",
+            rendered, client_metrics.throttled_requests);
+    }
+
+    out
+}
+
+/// Escape a Prometheus label value (backslash, double quote, newline)
+fn escape_label_value(value: &str) -> String {
+    value.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl Default for MetricsCollector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_prometheus_output_includes_metadata_labels() {
+        let collector = MetricsCollector::new();
+        collector.record_request("acme-client", true).await;
+        collector.record_request("acme-client", false).await;
+
+        let labels = HashMap::from([(
+            "acme-client".to_string(),
+            HashMap::from([("tenant".to_string(), "acme".to_string())]),
+        )]);
+        let output = collector.render_prometheus(&labels).await;
+
+        assert!(output.contains(
+            r#"throttler_requests_total{key="acme-client",tenant="acme",result="allowed"} 1"#
+        ));
+        assert!(output.contains(
+            r#"throttler_requests_total{key="acme-client",tenant="acme",result="throttled"} 1"#
+        ));
+    }
+
+    #[test]
+    fn test_remaining_tokens_land_in_histogram_buckets() {
+        let collector = MetricsCollector::new().with_remaining_histogram(vec![10.0, 0.0, 1.0]);
+        for remaining in [0.0, 0.5, 1.0, 7.0, 10.0, 50.0] {
+            collector.record_remaining(remaining);
+        }
+
+        let output = collector.render_remaining_histogram();
+        assert!(output.contains("# TYPE throttler_remaining_tokens histogram"));
+        // Buckets are cumulative and include their upper bound
+        assert!(output.contains("throttler_remaining_tokens_bucket{le=\"0\"} 1\n"), "{}", output);
+        assert!(output.contains("throttler_remaining_tokens_bucket{le=\"1\"} 3\n"), "{}", output);
+        assert!(output.contains("throttler_remaining_tokens_bucket{le=\"10\"} 5\n"), "{}", output);
+        assert!(output.contains("throttler_remaining_tokens_bucket{le=\"+Inf\"} 6\n"), "{}", output);
+        assert!(output.contains("throttler_remaining_tokens_sum 68.5\n"), "{}", output);
+        assert!(output.contains("throttler_remaining_tokens_count 6\n"), "{}", output);
+
+        // Off unless enabled
+        let collector = MetricsCollector::new();
+        collector.record_remaining(3.0);
+        assert!(collector.render_remaining_histogram().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_full_sampling_records_everything() {
+        let collector = MetricsCollector::with_sample_rate(1.0);
+        for i in 0..100 {
+            collector.record_request("client", i % 4 != 0).await;
+        }
+
+        let metrics = collector.get_client_metrics("client").await.unwrap();
+        assert_eq!(metrics.total_requests, 100);
+        assert_eq!(metrics.allowed_requests, 75);
+        assert_eq!(metrics.throttled_requests, 25);
+    }
+
+    #[tokio::test]
+    async fn test_zero_sampling_records_nothing() {
+        let collector = MetricsCollector::with_sample_rate(0.0);
+        for _ in 0..100 {
+            collector.record_request("client", true).await;
+        }
+
+        assert!(collector.get_client_metrics("client").await.is_none());
+        assert_eq!(collector.get_global_metrics().await.total_requests, 0);
+    }
+
+    #[tokio::test]
+    async fn test_half_sampling_scales_counts() {
+        let collector = MetricsCollector::with_sample_rate(0.5);
+        for _ in 0..10_000 {
+            collector.record_request("client", true).await;
+        }
+
+        let recorded = collector.client_metrics.read().await["client"].total_requests;
+        assert!((4_500..=5_500).contains(&recorded), "recorded {}", recorded);
+
+        let estimate = collector.get_global_metrics().await.total_requests;
+        assert!((9_000..=11_000).contains(&estimate), "estimated {}", estimate);
+        assert_eq!(estimate, recorded * 2);
+    }
+
+    #[tokio::test]
+    async fn test_most_active_orders_by_requests() {
+        let collector = MetricsCollector::new();
+        for (client, count) in [("quiet", 1), ("busy", 5), ("medium", 3)] {
+            for _ in 0..count {
+                collector.record_request(client, true).await;
+            }
+        }
+
+        let top: Vec<_> = collector.most_active(2).await
+            .into_iter()
+            .map(|(client, metrics)| (client, metrics.total_requests))
+            .collect();
+        assert_eq!(top, vec![("busy".to_string(), 5), ("medium".to_string(), 3)]);
+    }
+
+    #[cfg(feature = "redis-tests")]
+    #[tokio::test]
+    async fn test_two_instances_flush_to_fleet_totals() {
+        let redis_url = std::env::var("REDIS_URL")
+            .unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+        let client = Arc::new(RedisClient::new(&redis_url).unwrap());
+        let fleet_key = format!("throttler:test:metrics:{}", uuid::Uuid::new_v4());
+        let first = MetricsCollector::new().with_fleet_key(fleet_key.clone());
+        let second = MetricsCollector::new().with_fleet_key(fleet_key);
+
+        for allowed in [true, true, false] {
+            first.record_request("shared", allowed).await;
+        }
+        second.record_request("shared", true).await;
+        second.record_request("only-second", false).await;
+        assert_eq!(first.flush_to(client.clone()).await.unwrap(), 1);
+        assert_eq!(second.flush_to(client.clone()).await.unwrap(), 2);
+
+        // Flushing again adds nothing new; later requests are added once
+        assert_eq!(first.flush_to(client.clone()).await.unwrap(), 0);
+        first.record_request("shared", true).await;
+        first.flush_to(client.clone()).await.unwrap();
+
+        let fleet = second.fleet_metrics(client).await.unwrap().unwrap();
+        assert_eq!(fleet["shared"].allowed_requests, 4);
+        assert_eq!(fleet["shared"].throttled_requests, 1);
+        assert_eq!(fleet["shared"].total_requests, 5);
+        assert_eq!(fleet["only-second"].throttled_requests, 1);
+
+        // Each instance still reports only its own traffic locally
+        assert_eq!(first.get_client_metrics("shared").await.unwrap().total_requests, 4);
+    }
+
+    #[test]
+    fn test_label_values_are_escaped() {
+        assert_eq!(escape_label_value("a\"b\\c\nd"), r#"a\"b\\c\nd"#);
+    }
+}
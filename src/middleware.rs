@@ -1,15 +1,33 @@
-use axum::{extract::Request, middleware::Next, response::Response};
+use axum::{
+    body::{Body, Bytes},
+    extract::{Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
 use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tracing::info;
 
-/// Logging middleware for request/response tracking
+use crate::config::{ResponseHeaderPolicy, RATE_LIMIT_HEADERS};
+use crate::error::{ErrorDetail, ThrottlerError};
+use crate::key_generator::forwarded_client_ip;
+use crate::rate_limiter::{RateLimiter, REQUEST_DEADLINE};
+
+/// Logging middleware for request/response tracking.
+///
+/// The state is `Config::trusted_proxy_hops`, used to find the client
+/// address in `X-Forwarded-For`.
 pub async fn logging_middleware(
+    State(trusted_proxy_hops): State<usize>,
     request: Request,
     next: Next,
 ) -> Response {
     let method = request.method().clone();
     let uri = request.uri().clone();
-    let client_ip = get_client_ip(&request);
+    let client_ip = get_client_ip(&request, trusted_proxy_hops);
 
     info!(
         target: "throttler::middleware",
@@ -33,12 +51,232 @@ pub async fn logging_middleware(
     response
 }
 
-fn get_client_ip(request: &Request) -> String {
+/// Exposes internal error details in 500 response bodies.
+///
+/// Only installed when `Config::verbose_errors` is on (development); otherwise
+/// clients see the generic message and the `error_id` only.
+pub async fn verbose_errors_middleware(
+    request: Request,
+    next: Next,
+) -> Response {
+    let response = next.run(request).await;
+
+    let Some(detail) = response.extensions().get::<ErrorDetail>().cloned() else {
+        return response;
+    };
+
+    let (mut parts, _) = response.into_parts();
+    let body = Json(serde_json::json!({
+        "error": "internal_error",
+        "message": detail.message,
+        "error_id": detail.error_id
+    }));
+    parts.headers.remove(axum::http::header::CONTENT_LENGTH);
+    (parts, body).into_response()
+}
+
+/// Removes rate limit headers that `Config::response_headers` does not permit.
+///
+/// Runs on every response, so handler-built headers and those added by
+/// `ThrottlerError::RateLimitExceeded` are filtered the same way.
+pub async fn response_headers_middleware(
+    State(policy): State<Arc<ResponseHeaderPolicy>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+
+    for name in RATE_LIMIT_HEADERS {
+        if !policy.permits(name) {
+            response.headers_mut().remove(*name);
+        }
+    }
+
+    response
+}
+
+/// Largest request body accepted, matching axum's default body limit
+pub const MAX_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// Largest body buffered to sniff for JSON
+pub const LENIENT_BODY_LIMIT: usize = MAX_BODY_BYTES;
+
+/// Refuses requests whose declared `Content-Length` exceeds
+/// [`MAX_BODY_BYTES`] before any of the body is read.
+///
+/// The JSON extractors enforce the same limit, but only while reading, and
+/// reading is what makes hyper answer `Expect: 100-continue` with
+/// `100 Continue`. Rejecting on the header alone means a client that asks
+/// first gets the `413` without ever sending the oversized body.
+pub async fn declared_length_limit_middleware(
+    request: Request,
+    next: Next,
+) -> Response {
+    let declared = request.headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    if declared.is_some_and(|length| length > MAX_BODY_BYTES as u64) {
+        return StatusCode::PAYLOAD_TOO_LARGE.into_response();
+    }
+
+    next.run(request).await
+}
+
+/// Treats bodies sent without a `Content-Type` as JSON.
+///
+/// Installed when `Config::lenient_content_type` is on, for minimal clients
+/// that POST without the header. A missing header is filled in when the
+/// body is valid JSON, and an empty body becomes `{}` so every field takes
+/// its default. Anything else passes through untouched and is rejected by
+/// the JSON extractors as before.
+pub async fn lenient_content_type_middleware(
+    request: Request,
+    next: Next,
+) -> Response {
+    if request.headers().contains_key(header::CONTENT_TYPE)
+        || !matches!(*request.method(), Method::POST | Method::PUT | Method::PATCH)
+    {
+        return next.run(request).await;
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let bytes = match axum::body::to_bytes(body, LENIENT_BODY_LIMIT).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+    };
+
+    let bytes = if bytes.iter().all(u8::is_ascii_whitespace) {
+        Bytes::from_static(b"{}")
+    } else {
+        bytes
+    };
+    if serde_json::from_slice::<serde::de::IgnoredAny>(&bytes).is_ok() {
+        parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        parts.headers.remove(header::CONTENT_LENGTH);
+    }
+
+    next.run(Request::from_parts(parts, Body::from(bytes))).await
+}
+
+/// Rejects `GET` and `DELETE` requests that carry a body with `400`.
+///
+/// Installed when `Config::strict_get_bodies` is on. These endpoints never
+/// read a body, so one is either a client bug or an attempt to smuggle data
+/// past infrastructure that does not expect it. An empty body passes.
+pub async fn strict_get_bodies_middleware(
+    request: Request,
+    next: Next,
+) -> Response {
+    if !matches!(*request.method(), Method::GET | Method::DELETE) {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let bytes = match axum::body::to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+    };
+    if !bytes.is_empty() {
+        return ThrottlerError::ValidationError(
+            format!("{} requests must not have a body", parts.method)
+        ).into_response();
+    }
+
+    next.run(Request::from_parts(parts, Body::from(bytes))).await
+}
+
+/// `Warning` sent while limits are enforced per instance
+pub const DEGRADED_WARNING: &str = "199 throttler \"operating in local-only mode\"";
+
+/// Adds a `Warning` header to responses while the limiter is degraded.
+///
+/// Installed when `Config::warn_on_degraded` is on. The state is checked
+/// after the handler runs, so the request that found Redis down already
+/// carries the warning and the first one served by Redis again does not.
+pub async fn degraded_warning_middleware(
+    State(rate_limiter): State<RateLimiter>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+    if rate_limiter.is_degraded() {
+        response.headers_mut().insert(header::WARNING, HeaderValue::from_static(DEGRADED_WARNING));
+    }
+    response
+}
+
+/// Header a gateway sets to the time it stops waiting, in ms since the epoch
+pub const DEADLINE_HEADER: &str = "X-Request-Deadline";
+
+/// Bounds each request by the deadline in `X-Request-Deadline`.
+///
+/// Installed when `Config::honor_request_deadline` is on. A deadline already
+/// passed is answered with `504` without doing any work; otherwise the
+/// request is abandoned with `504` once the deadline passes, and Redis
+/// operations within it are cut short at the deadline (see
+/// [`crate::rate_limiter::REQUEST_DEADLINE`]). Requests without the header
+/// are unaffected.
+pub async fn request_deadline_middleware(
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(value) = request.headers().get(DEADLINE_HEADER) else {
+        return next.run(request).await;
+    };
+    let deadline_ms = value.to_str().ok().and_then(|value| value.trim().parse::<u64>().ok());
+    let Some(deadline_ms) = deadline_ms else {
+        return ThrottlerError::ValidationError(
+            format!("{} must be milliseconds since the UNIX epoch", DEADLINE_HEADER)
+        ).into_response();
+    };
+
+    let now_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    if deadline_ms <= now_ms {
+        return ThrottlerError::DeadlineExceeded(
+            format!("deadline passed {}ms before the request was handled", now_ms - deadline_ms)
+        ).into_response();
+    }
+
+    let left = Duration::from_millis(deadline_ms - now_ms);
+    let deadline = Instant::now() + left;
+    match tokio::time::timeout(left, REQUEST_DEADLINE.scope(deadline, next.run(request))).await {
+        Ok(response) => response,
+        Err(_) => ThrottlerError::DeadlineExceeded(
+            "deadline passed while the request was being handled".to_string()
+        ).into_response(),
+    }
+}
+
+/// Shifts the rate limiter's clock forward for this request by the number of
+/// milliseconds in the `X-Test-Time` header.
+///
+/// Only compiled with the `testing` feature; release builds ignore the header.
+#[cfg(feature = "testing")]
+pub async fn test_time_middleware(
+    request: Request,
+    next: Next,
+) -> Response {
+    let offset_ms = request.headers()
+        .get("X-Test-Time")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or(0);
+
+    crate::rate_limiter::TEST_TIME_OFFSET_MS
+        .scope(offset_ms, next.run(request))
+        .await
+}
+
+fn get_client_ip(request: &Request, trusted_proxy_hops: usize) -> String {
     // Try to get real IP from headers first
     if let Some(forwarded) = request.headers().get("x-forwarded-for") {
         if let Ok(forwarded_str) = forwarded.to_str() {
-            if let Some(first_ip) = forwarded_str.split(',').next() {
-                return first_ip.trim().to_string();
+            if let Some(client_ip) = forwarded_client_ip(forwarded_str, trusted_proxy_hops) {
+                return client_ip.to_string();
             }
         }
     }
@@ -60,7 +298,89 @@ fn get_client_ip(request: &Request) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use axum::http::HeaderValue;
+    use crate::error::ThrottlerError;
+    use axum::http::{HeaderValue, StatusCode};
+    use axum::{routing::get, Router};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    async fn failing_handler() -> Result<(), ThrottlerError> {
+        Err(ThrottlerError::RedisError("connection refused by 10.0.0.5:6379".to_string()))
+    }
+
+    async fn rate_limited_handler() -> Result<(), ThrottlerError> {
+        Err(ThrottlerError::RateLimitExceeded { retry_after: 30, limit: 100, window_ms: 60000 })
+    }
+
+    fn with_policy(policy: &str) -> Router {
+        let policy = Arc::new(policy.parse::<ResponseHeaderPolicy>().unwrap());
+        Router::new()
+            .route("/", get(rate_limited_handler))
+            .layer(axum::middleware::from_fn_with_state(policy, response_headers_middleware))
+    }
+
+    async fn header_names(app: Router) -> Vec<String> {
+        let request = Request::builder().uri("/").body(axum::body::Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        response.headers().keys().map(|name| name.as_str().to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_denied_header_removed_from_rate_limit_error() {
+        let names = header_names(with_policy("deny:X-RateLimit-Window")).await;
+
+        assert!(!names.contains(&"x-ratelimit-window".to_string()));
+        assert!(names.contains(&"retry-after".to_string()));
+        assert!(names.contains(&"x-ratelimit-limit".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_allowlist_keeps_only_listed_rate_limit_headers() {
+        let names = header_names(with_policy("allow:retry-after")).await;
+
+        assert!(names.contains(&"retry-after".to_string()));
+        assert!(!names.contains(&"x-ratelimit-limit".to_string()));
+        assert!(!names.contains(&"x-ratelimit-window".to_string()));
+        // Non rate-limit headers are untouched
+        assert!(names.contains(&"content-type".to_string()));
+    }
+
+    #[test]
+    fn test_response_header_policy_parsing() {
+        assert_eq!("all".parse::<ResponseHeaderPolicy>().unwrap(), ResponseHeaderPolicy::All);
+        assert!("deny:X-RateLimit-Window".parse::<ResponseHeaderPolicy>().unwrap().permits("Retry-After"));
+        assert!("bogus".parse::<ResponseHeaderPolicy>().is_err());
+    }
+
+    async fn error_body(app: Router) -> serde_json::Value {
+        let request = Request::builder().uri("/").body(axum::body::Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_internal_error_details_hidden_by_default() {
+        let app = Router::new().route("/", get(failing_handler));
+        let body = error_body(app).await;
+
+        assert_eq!(body["message"], "An unexpected error occurred");
+        assert!(!body.to_string().contains("10.0.0.5"));
+        assert!(body["error_id"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_internal_error_details_shown_when_verbose() {
+        let app = Router::new()
+            .route("/", get(failing_handler))
+            .layer(axum::middleware::from_fn(verbose_errors_middleware));
+        let body = error_body(app).await;
+
+        assert!(body["message"].as_str().unwrap().contains("10.0.0.5"));
+        assert!(body["error_id"].is_string());
+    }
 
     #[test]
     fn test_get_client_ip_with_forwarded_header() {
@@ -70,8 +390,12 @@ mod tests {
             HeaderValue::from_static("192.168.1.1, 10.0.0.1")
         );
 
-        let ip = get_client_ip(&request);
+        let ip = get_client_ip(&request, 0);
         assert_eq!(ip, "192.168.1.1");
+
+        // Behind one proxy, the entry it appended is the client
+        let ip = get_client_ip(&request, 1);
+        assert_eq!(ip, "10.0.0.1");
     }
 
     #[test]
@@ -82,14 +406,14 @@ mod tests {
             HeaderValue::from_static("203.0.113.1")
         );
 
-        let ip = get_client_ip(&request);
+        let ip = get_client_ip(&request, 0);
         assert_eq!(ip, "203.0.113.1");
     }
 
     #[test]
     fn test_get_client_ip_fallback() {
         let request = Request::new(axum::body::Body::empty());
-        let ip = get_client_ip(&request);
+        let ip = get_client_ip(&request, 0);
         assert_eq!(ip, "unknown");
     }
 }
@@ -0,0 +1,166 @@
+//! # nginx `limit_req` Compatibility
+//!
+//! Translates nginx-style `limit_req` parameters into a token bucket so teams
+//! migrating from nginx can keep their existing zone definitions.
+//!
+//! ## Parameter Mapping
+//!
+//! ```text
+//! ┌──────────────────────────────┬─────────────────────────────────────────┐
+//! │  nginx                       │  Throttler                              │
+//! ├──────────────────────────────┼─────────────────────────────────────────┤
+//! │  zone=api                    │  bucket namespace (nginx:api:<key>)     │
+//! │  rate=10r/s, rate=30r/m      │  refill rate in tokens per second       │
+//! │  burst=5 (nodelay)           │  capacity = burst + 1                   │
+//! │  $binary_remote_addr, ...    │  key                                    │
+//! │  limit_req_status (503)      │  status returned when rejected          │
+//! └──────────────────────────────┴─────────────────────────────────────────┘
+//! ```
+//!
+//! nginx admits one request per rate interval plus up to `burst` excess
+//! requests; with `nodelay` those excess requests pass immediately. A bucket
+//! holding `burst + 1` tokens refilled at `rate` behaves the same way. Delayed
+//! (non-`nodelay`) admission is not emulated: excess requests within the
+//! burst are simply allowed.
+//!
+//! ## Rejection Status
+//!
+//! Rejected requests get `limit_req_status`, which defaults to 503 as in
+//! nginx. nginx's special 444 closes the connection without a response; an
+//! HTTP API cannot do that, so it maps to our usual `429 Too Many Requests`.
+
+use serde::Deserialize;
+
+use crate::error::ThrottlerError;
+
+/// nginx's default `limit_req_status`
+pub const DEFAULT_LIMIT_REQ_STATUS: u16 = 503;
+
+/// nginx's non-standard "close the connection" status
+const NGINX_CLOSE_CONNECTION: u16 = 444;
+
+/// Request body for `POST /nginx/limit`.
+///
+/// # Example JSON
+///
+/// ```json
+/// {"zone": "api", "rate": "10r/s", "burst": 5, "key": "203.0.113.7"}
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct NginxLimitRequest {
+    /// Shared memory zone name; namespaces the bucket
+    pub zone: String,
+    /// Rate in nginx syntax, `<n>r/s` or `<n>r/m`
+    pub rate: String,
+    /// Excess requests admitted beyond the rate (default 0)
+    #[serde(default)]
+    pub burst: u64,
+    /// Value of the zone's key variable, e.g. the client address
+    pub key: String,
+    /// Status for rejected requests, as nginx's `limit_req_status`
+    #[serde(default)]
+    pub limit_req_status: Option<u16>,
+}
+
+impl NginxLimitRequest {
+    /// Bucket key for this zone and key.
+    ///
+    /// Zones and keys are validated keys, which cannot contain `:`, so the
+    /// separator keeps zones apart from each other and from every key a
+    /// `/rate-limit/:key/check` can name.
+    pub fn bucket_key(&self) -> String {
+        format!("nginx:{}:{}", self.zone, self.key)
+    }
+
+    /// Bucket capacity: the request at the rate plus the burst
+    pub fn capacity(&self) -> u64 {
+        self.burst.saturating_add(1)
+    }
+
+    /// Refill rate in tokens per second
+    pub fn refill_rate(&self) -> Result<f64, ThrottlerError> {
+        parse_rate(&self.rate)
+    }
+
+    /// Status to answer a rejected request with.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ValidationError` for statuses nginx would not accept
+    /// (outside 400-599).
+    pub fn rejection_status(&self) -> Result<u16, ThrottlerError> {
+        match self.limit_req_status.unwrap_or(DEFAULT_LIMIT_REQ_STATUS) {
+            NGINX_CLOSE_CONNECTION => Ok(429),
+            status @ 400..=599 => Ok(status),
+            status => Err(ThrottlerError::ValidationError(format!(
+                "limit_req_status must be between 400 and 599, got {}",
+                status
+            ))),
+        }
+    }
+}
+
+/// Parses an nginx rate such as `10r/s` or `30r/m` into requests per second.
+///
+/// # Errors
+///
+/// Returns `ValidationError` if the rate is malformed or not positive.
+pub fn parse_rate(rate: &str) -> Result<f64, ThrottlerError> {
+    let invalid = || ThrottlerError::ValidationError(format!(
+        "Invalid nginx rate '{}'. Expected '<n>r/s' or '<n>r/m'",
+        rate
+    ));
+
+    let (count, per_secs) = if let Some(count) = rate.strip_suffix("r/s") {
+        (count, 1.0)
+    } else if let Some(count) = rate.strip_suffix("r/m") {
+        (count, 60.0)
+    } else {
+        return Err(invalid());
+    };
+
+    let count: u64 = count.parse().map_err(|_| invalid())?;
+    if count == 0 {
+        return Err(invalid());
+    }
+    Ok(count as f64 / per_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(rate: &str, burst: u64, status: Option<u16>) -> NginxLimitRequest {
+        NginxLimitRequest {
+            zone: "api".to_string(),
+            rate: rate.to_string(),
+            burst,
+            key: "203.0.113.7".to_string(),
+            limit_req_status: status,
+        }
+    }
+
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!(parse_rate("10r/s").unwrap(), 10.0);
+        assert_eq!(parse_rate("30r/m").unwrap(), 0.5);
+        assert!(parse_rate("0r/s").is_err());
+        assert!(parse_rate("10r/h").is_err());
+        assert!(parse_rate("fast").is_err());
+    }
+
+    #[test]
+    fn test_burst_maps_to_capacity() {
+        let req = request("1r/s", 5, None);
+        assert_eq!(req.capacity(), 6);
+        assert_eq!(req.bucket_key(), "nginx:api:203.0.113.7");
+    }
+
+    #[test]
+    fn test_rejection_status_mapping() {
+        assert_eq!(request("1r/s", 0, None).rejection_status().unwrap(), 503);
+        assert_eq!(request("1r/s", 0, Some(444)).rejection_status().unwrap(), 429);
+        assert_eq!(request("1r/s", 0, Some(429)).rejection_status().unwrap(), 429);
+        assert!(request("1r/s", 0, Some(200)).rejection_status().is_err());
+    }
+}
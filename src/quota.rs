@@ -0,0 +1,182 @@
+//! # Quotas
+//!
+//! API products often sell a monthly allowance on top of a per-second rate
+//! limit. A rule's `quota` caps how many tokens a key may spend per calendar
+//! period (`quota_period`, monthly by default), counted alongside its rate
+//! bucket. A request the rate limit admits is then charged against the
+//! quota, and denied with scope `quota` once the quota is spent, however
+//! many tokens the bucket still holds.
+//!
+//! ## Calendar Periods
+//!
+//! Periods follow the UTC calendar rather than a rolling window: a monthly
+//! quota resets at 00:00 UTC on the first of each month, a daily one at
+//! 00:00 UTC each day, whenever the key was first used. With Redis
+//! configured, each period's usage is its own key expiring at the period's
+//! end, so every instance resets together.
+//!
+//! ## Ordering
+//!
+//! The quota is only charged for requests the rate limit admits, so a burst
+//! of rate-limited retries does not eat into the allowance. A request denied
+//! by the quota has still spent its rate tokens.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::ThrottlerError;
+
+const MS_PER_DAY: u64 = 86_400_000;
+
+/// Calendar period a quota is counted over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaPeriod {
+    /// Resets at 00:00 UTC each day
+    Daily,
+    /// Resets at 00:00 UTC on the first of each month (default)
+    #[default]
+    Monthly,
+}
+
+impl QuotaPeriod {
+    /// When the period containing `now_ms` ends and the quota resets (ms
+    /// since UNIX epoch)
+    pub fn reset_at(&self, now_ms: u64) -> u64 {
+        let today = now_ms / MS_PER_DAY;
+        match self {
+            QuotaPeriod::Daily => (today + 1) * MS_PER_DAY,
+            QuotaPeriod::Monthly => {
+                let (year, month, _) = civil_from_days(today);
+                let (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+                days_from_civil(year, month, 1) * MS_PER_DAY
+            }
+        }
+    }
+}
+
+/// A key's quota as of its latest request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuotaState {
+    /// Tokens left in the current period
+    pub remaining: u64,
+    /// When the quota resets (ms since UNIX epoch)
+    pub reset_at_ms: u64,
+}
+
+/// `(year, month, day)` of a day counted from 1970-01-01
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    // Shift the epoch to 0000-03-01 so leap days fall at the end of a year
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Days from 1970-01-01 to `(year, month, day)`, for dates from 1970 on
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year % 400;
+    let shifted_month = if month > 2 { month - 3 } else { month + 9 };
+    let day_of_year = (153 * shifted_month + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Tokens used per key in its current quota period.
+#[derive(Debug, Default)]
+pub struct QuotaTable {
+    /// `(used, reset_at_ms)` per key
+    usage: Mutex<HashMap<String, (u64, u64)>>,
+}
+
+impl QuotaTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Charges `cost` against `key`'s quota of `limit` for the period ending
+    /// at `reset_at_ms`; usage from an earlier period is discarded. Returns
+    /// whether the charge fit and the tokens used afterwards. A charge that
+    /// does not fit changes nothing.
+    pub fn consume(&self, key: &str, cost: u64, limit: u64, reset_at_ms: u64) -> Result<(bool, u64), ThrottlerError> {
+        let mut usage = self.usage.lock()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire lock on quotas".to_string()))?;
+        let (used, period) = usage.entry(key.to_string()).or_insert((0, reset_at_ms));
+        if *period != reset_at_ms {
+            *used = 0;
+            *period = reset_at_ms;
+        }
+
+        if used.saturating_add(cost) > limit {
+            return Ok((false, *used));
+        }
+        *used += cost;
+        Ok((true, *used))
+    }
+
+    /// Tokens `key` has used in the period ending at `reset_at_ms`, without
+    /// charging anything
+    pub fn used(&self, key: &str, reset_at_ms: u64) -> Result<u64, ThrottlerError> {
+        let usage = self.usage.lock()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire lock on quotas".to_string()))?;
+        Ok(usage.get(key)
+            .filter(|(_, period)| *period == reset_at_ms)
+            .map_or(0, |(used, _)| *used))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-01-31T12:00:00Z
+    const JAN_31: u64 = 1_706_702_400_000;
+    // 2024-02-01T00:00:00Z
+    const FEB_1: u64 = 1_706_745_600_000;
+    // 2024-03-01T00:00:00Z
+    const MAR_1: u64 = 1_709_251_200_000;
+
+    #[test]
+    fn test_periods_reset_on_calendar_boundaries() {
+        assert_eq!(QuotaPeriod::Monthly.reset_at(JAN_31), FEB_1);
+        assert_eq!(QuotaPeriod::Monthly.reset_at(FEB_1), MAR_1);
+        assert_eq!(QuotaPeriod::Monthly.reset_at(MAR_1 - 1), MAR_1);
+        // December rolls into the next year
+        assert_eq!(QuotaPeriod::Monthly.reset_at(1_734_393_600_000), 1_735_689_600_000);
+        assert_eq!(QuotaPeriod::Daily.reset_at(JAN_31), FEB_1);
+        assert_eq!(QuotaPeriod::Daily.reset_at(FEB_1), FEB_1 + MS_PER_DAY);
+    }
+
+    #[test]
+    fn test_quota_is_capped_and_resets_with_the_period() {
+        let table = QuotaTable::new();
+
+        assert_eq!(table.consume("k", 2, 3, FEB_1).unwrap(), (true, 2));
+        assert_eq!(table.consume("k", 2, 3, FEB_1).unwrap(), (false, 2));
+        assert_eq!(table.consume("k", 1, 3, FEB_1).unwrap(), (true, 3));
+
+        // A new period starts from zero
+        assert_eq!(table.consume("k", 2, 3, MAR_1).unwrap(), (true, 2));
+    }
+
+    #[test]
+    fn test_used_reads_the_current_period_only() {
+        let table = QuotaTable::new();
+        assert_eq!(table.used("k", FEB_1).unwrap(), 0);
+
+        table.consume("k", 2, 3, FEB_1).unwrap();
+        assert_eq!(table.used("k", FEB_1).unwrap(), 2);
+        assert_eq!(table.used("k", MAR_1).unwrap(), 0);
+        assert_eq!(table.consume("k", 1, 3, FEB_1).unwrap(), (true, 3));
+    }
+}
@@ -1,40 +1,100 @@
+use crate::quota::QuotaPeriod;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::time::Duration;
 
 /// Configuration for rate limiting rules
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct RateLimitConfig {
     pub rules: HashMap<String, RateLimitRule>,
     pub default_rule: RateLimitRule,
 }
 
+/// Maximum number of metadata labels on a rule
+pub const MAX_METADATA_LABELS: usize = 16;
+/// Maximum length of a metadata label name
+pub const MAX_METADATA_KEY_LEN: usize = 64;
+/// Maximum length of a metadata label value
+pub const MAX_METADATA_VALUE_LEN: usize = 256;
+
+/// Time unit the request count of a [`RateLimitRule`] is expressed in.
+///
+/// Lets a rule say "1000 per hour" directly; [`RateLimitRule::refill_per_second`]
+/// converts it to the canonical per-second refill rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateUnit {
+    /// Requests per second (default)
+    #[default]
+    PerSecond,
+    /// Requests per the rule's `window_size`
+    PerWindow,
+    /// Requests per minute
+    PerMinute,
+    /// Requests per hour
+    PerHour,
+}
+
+impl RateUnit {
+    /// Length of one unit in seconds, given the rule's window
+    pub fn seconds(&self, window_size: Duration) -> f64 {
+        match self {
+            RateUnit::PerSecond => 1.0,
+            RateUnit::PerWindow => window_size.as_secs_f64(),
+            RateUnit::PerMinute => 60.0,
+            RateUnit::PerHour => 3600.0,
+        }
+    }
+}
+
 /// Individual rate limiting rule
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RateLimitRule {
+    /// Sustained request rate, counted per `rate_unit` (per second by default)
     pub requests_per_second: u32,
     pub burst_capacity: u32,
+    /// How long a bucket may sit idle before it expires (its TTL); must be
+    /// at least the time to refill `burst_capacity`
     pub window_size: Duration,
     pub enabled: bool,
+    /// Unit `requests_per_second` is expressed in
+    #[serde(default)]
+    pub rate_unit: RateUnit,
+    /// Operator-defined labels (tenant, plan tier, ...) used in status
+    /// output and as metric labels
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// When a temporary rule lapses (ms since UNIX epoch); the key then
+    /// reverts to the default. `None` never expires.
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    /// Most requests the key may have in flight at once, tracked through
+    /// concurrency slots; `None` leaves them unbounded
+    #[serde(default)]
+    pub concurrency_limit: Option<u32>,
+    /// Most tokens the key may spend per `quota_period`; `None` sets no quota
+    #[serde(default)]
+    pub quota: Option<u64>,
+    /// Calendar period the quota resets on
+    #[serde(default)]
+    pub quota_period: QuotaPeriod,
+    /// Revision of the stored rule, bumped by every change to it; sent as
+    /// the rule's `ETag` for conditional updates
+    #[serde(default)]
+    pub version: u64,
 }
 
 /// Rate limit strategy enumeration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RateLimitStrategy {
+    /// Tokens refill continuously up to a burst capacity (default)
+    #[default]
     TokenBucket,
     FixedWindow,
     SlidingWindow,
 }
 
-impl Default for RateLimitConfig {
-    fn default() -> Self {
-        Self {
-            rules: HashMap::new(),
-            default_rule: RateLimitRule::default(),
-        }
-    }
-}
-
 impl Default for RateLimitRule {
     fn default() -> Self {
         Self {
@@ -42,12 +102,23 @@ impl Default for RateLimitRule {
             burst_capacity: 20,
             window_size: Duration::from_secs(60),
             enabled: true,
+            rate_unit: RateUnit::PerSecond,
+            metadata: HashMap::new(),
+            expires_at: None,
+            concurrency_limit: None,
+            quota: None,
+            quota_period: QuotaPeriod::Monthly,
+            version: 0,
         }
     }
 }
 
 impl RateLimitConfig {
-    /// Get rate limit rule for a specific key, falling back to default
+    /// Get rate limit rule for a specific key: its exact rule, else the
+    /// default rule.
+    ///
+    /// Prefix pattern rules are held by [`crate::throttler::Throttler`]
+    /// alone (see [`PatternRules`]).
     pub fn get_rule(&self, key: &str) -> &RateLimitRule {
         self.rules.get(key).unwrap_or(&self.default_rule)
     }
@@ -85,12 +156,70 @@ impl RateLimitRule {
             burst_capacity,
             window_size,
             enabled: true,
+            rate_unit: RateUnit::PerSecond,
+            metadata: HashMap::new(),
+            expires_at: None,
+            concurrency_limit: None,
+            quota: None,
+            quota_period: QuotaPeriod::Monthly,
+            version: 0,
         }
     }
 
+    /// Attach metadata labels to the rule
+    pub fn with_metadata(mut self, metadata: HashMap<String, String>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Express the request count in another unit, e.g. 1000 per hour
+    pub fn with_rate_unit(mut self, rate_unit: RateUnit) -> Self {
+        self.rate_unit = rate_unit;
+        self
+    }
+
+    /// Make the rule temporary, lapsing at `expires_at` (ms since UNIX epoch)
+    pub fn with_expires_at(mut self, expires_at: u64) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    /// Cap the requests the key may have in flight at once
+    pub fn with_concurrency_limit(mut self, concurrency_limit: u32) -> Self {
+        self.concurrency_limit = Some(concurrency_limit);
+        self
+    }
+
+    /// Cap the tokens the key may spend per calendar period
+    pub fn with_quota(mut self, quota: u64, quota_period: QuotaPeriod) -> Self {
+        self.quota = Some(quota);
+        self.quota_period = quota_period;
+        self
+    }
+
+    /// Whether the rule has lapsed as of `now_ms`
+    pub fn is_expired(&self, now_ms: u64) -> bool {
+        self.expires_at.is_some_and(|expires_at| now_ms >= expires_at)
+    }
+
+    /// Canonical refill rate in tokens per second, converted from `rate_unit`
+    pub fn refill_per_second(&self) -> f64 {
+        let unit_secs = self.rate_unit.seconds(self.window_size);
+        if unit_secs <= 0.0 {
+            return 0.0;
+        }
+        self.requests_per_second as f64 / unit_secs
+    }
+
+    /// The rule's `window_size` in ms, which bounds how long its buckets
+    /// are kept idle
+    pub fn window_ms(&self) -> u64 {
+        self.window_size.as_millis() as u64
+    }
+
     /// Calculate refill rate in tokens per millisecond
     pub fn refill_rate_ms(&self) -> f64 {
-        self.requests_per_second as f64 / 1000.0
+        self.refill_per_second() / 1000.0
     }
 
     /// Validate rule parameters
@@ -104,16 +233,290 @@ impl RateLimitRule {
         if self.window_size.as_secs() == 0 {
             return Err("Window size must be greater than 0".to_string());
         }
-        Ok(())
+        if self.concurrency_limit == Some(0) {
+            return Err("Concurrency limit must be greater than 0".to_string());
+        }
+        if self.quota == Some(0) {
+            return Err("Quota must be greater than 0".to_string());
+        }
+        // The window is the bucket's idle TTL; expiring before an empty
+        // bucket has refilled would hand back a full bucket early
+        let refill_secs = self.burst_capacity as f64 / self.refill_per_second();
+        if refill_secs - self.window_size.as_secs_f64() > 1e-6 {
+            return Err(format!(
+                "Window size must be at least the {}s it takes to refill the burst capacity",
+                refill_secs.ceil()
+            ));
+        }
+        validate_metadata(&self.metadata)
     }
 
-    /// Create a disabled rule
+    /// Create a disabled rule.
+    ///
+    /// A disabled rule never limits: checks under it are allowed without
+    /// consuming. Its zero capacity and window would otherwise make a bucket
+    /// that starts empty and never refills, so every consumption path checks
+    /// `enabled` before building one.
     pub fn disabled() -> Self {
         Self {
             requests_per_second: 0,
             burst_capacity: 0,
             window_size: Duration::from_secs(0),
             enabled: false,
+            rate_unit: RateUnit::PerSecond,
+            metadata: HashMap::new(),
+            expires_at: None,
+            concurrency_limit: None,
+            quota: None,
+            quota_period: QuotaPeriod::Monthly,
+            version: 0,
+        }
+    }
+}
+
+/// Check metadata labels against the count and size bounds.
+///
+/// Label names follow Prometheus label naming (`[a-zA-Z_][a-zA-Z0-9_]*`)
+/// since they are exported as metric labels; `key` and `result` are
+/// reserved for the labels every series already carries.
+pub fn validate_metadata(metadata: &HashMap<String, String>) -> Result<(), String> {
+    if metadata.len() > MAX_METADATA_LABELS {
+        return Err(format!("At most {} metadata labels are allowed", MAX_METADATA_LABELS));
+    }
+
+    for (name, value) in metadata {
+        if name.is_empty() || name.len() > MAX_METADATA_KEY_LEN {
+            return Err(format!(
+                "Metadata label names must be 1-{} characters", MAX_METADATA_KEY_LEN
+            ));
+        }
+        let valid_name = name.chars().enumerate().all(|(i, c)| {
+            c == '_' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit())
+        });
+        if !valid_name || name == "key" || name == "result" || name.starts_with("__") {
+            return Err(format!("Invalid metadata label name: {}", name));
+        }
+        if value.len() > MAX_METADATA_VALUE_LEN {
+            return Err(format!(
+                "Metadata label '{}' exceeds {} characters", name, MAX_METADATA_VALUE_LEN
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Prefix pattern rules in the order they were registered.
+///
+/// A key matches every pattern whose literal prefix it starts with. The
+/// longest prefix wins; patterns with equally long prefixes (`tenant-` and
+/// `tenant-*`) fall back to registration order, earliest first.
+#[derive(Debug, Clone, Default)]
+pub struct PatternRules {
+    entries: Vec<(String, RateLimitRule)>,
+}
+
+impl PatternRules {
+    /// Add or update the rule for a pattern. An updated pattern keeps its
+    /// original place in the registration order.
+    ///
+    /// Patterns are a literal prefix optionally followed by a single trailing
+    /// `*` (e.g. `tenant-acme:*`); wildcards anywhere else are rejected.
+    pub fn set(&mut self, pattern: String, rule: RateLimitRule) -> Result<(), String> {
+        validate_pattern(&pattern)?;
+        match self.entries.iter_mut().find(|(existing, _)| *existing == pattern) {
+            Some((_, existing)) => *existing = rule,
+            None => self.entries.push((pattern, rule)),
+        }
+        Ok(())
+    }
+
+    /// Remove a pattern's rule, returning it if it existed
+    pub fn remove(&mut self, pattern: &str) -> Option<RateLimitRule> {
+        let index = self.entries.iter().position(|(existing, _)| existing == pattern)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    /// The rule registered under exactly `pattern`
+    pub fn get(&self, pattern: &str) -> Option<&RateLimitRule> {
+        self.entries.iter().find(|(existing, _)| existing == pattern).map(|(_, rule)| rule)
+    }
+
+    /// Whether a rule is registered under exactly `pattern`
+    pub fn contains(&self, pattern: &str) -> bool {
+        self.get(pattern).is_some()
+    }
+
+    /// Find the pattern governing a key: the longest matching prefix, then
+    /// the earliest registered
+    pub fn matching(&self, key: &str) -> Option<(&String, &RateLimitRule)> {
+        self.entries
+            .iter()
+            .filter(|(pattern, _)| key.starts_with(pattern_prefix(pattern)))
+            // `max_by_key` keeps the last maximum, so search newest first
+            .rev()
+            .max_by_key(|(pattern, _)| pattern_prefix(pattern).len())
+            .map(|(pattern, rule)| (pattern, rule))
+    }
+
+    /// Keep only the patterns whose rule satisfies `keep`
+    pub fn retain(&mut self, mut keep: impl FnMut(&RateLimitRule) -> bool) {
+        self.entries.retain(|(_, rule)| keep(rule));
+    }
+
+    /// Number of registered patterns
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether no patterns are registered
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// Check that a pattern is a non-empty literal prefix with at most a single
+/// trailing `*`.
+pub fn validate_pattern(pattern: &str) -> Result<(), String> {
+    let prefix = pattern_prefix(pattern);
+    if prefix.is_empty() {
+        return Err("Pattern must have a non-empty prefix".to_string());
+    }
+    if prefix.contains('*') {
+        return Err("Only a single trailing '*' wildcard is supported".to_string());
+    }
+    Ok(())
+}
+
+/// The literal prefix of a pattern, without its trailing `*`
+fn pattern_prefix(pattern: &str) -> &str {
+    pattern.strip_suffix('*').unwrap_or(pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(requests_per_second: u32) -> RateLimitRule {
+        RateLimitRule::new(requests_per_second, requests_per_second * 2, Duration::from_secs(60))
+    }
+
+    #[test]
+    fn test_longest_prefix_wins() {
+        let mut patterns = PatternRules::default();
+        patterns.set("tenant-*".to_string(), rule(1)).unwrap();
+        patterns.set("tenant-acme:*".to_string(), rule(2)).unwrap();
+        patterns.set("tenant-acme:eu:*".to_string(), rule(3)).unwrap();
+
+        let rps = |key| patterns.matching(key).unwrap().1.requests_per_second;
+        assert_eq!(rps("tenant-acme:eu:client"), 3);
+        assert_eq!(rps("tenant-acme:us:client"), 2);
+        assert_eq!(rps("tenant-globex:client"), 1);
+        assert!(patterns.matching("other-key").is_none());
+    }
+
+    #[test]
+    fn test_equal_prefixes_resolve_in_registration_order() {
+        // `tenant-` and `tenant-*` match exactly the same keys
+        let mut patterns = PatternRules::default();
+        patterns.set("tenant-".to_string(), rule(1)).unwrap();
+        patterns.set("tenant-*".to_string(), rule(2)).unwrap();
+        for _ in 0..10 {
+            assert_eq!(patterns.matching("tenant-acme").unwrap().0, "tenant-");
+        }
+
+        // Updating a pattern keeps its place; removing it hands over
+        patterns.set("tenant-".to_string(), rule(3)).unwrap();
+        assert_eq!(patterns.matching("tenant-acme").unwrap().1.requests_per_second, 3);
+        patterns.remove("tenant-");
+        assert_eq!(patterns.matching("tenant-acme").unwrap().0, "tenant-*");
+    }
+
+    #[test]
+    fn test_invalid_patterns_rejected() {
+        let mut patterns = PatternRules::default();
+        assert!(patterns.set("*".to_string(), rule(5)).is_err());
+        assert!(patterns.set("tenant-*:x*".to_string(), rule(5)).is_err());
+        assert!(patterns.is_empty());
+    }
+
+    #[test]
+    fn test_window_must_cover_refill_time() {
+        // 20 tokens at 10/s refill in 2s
+        assert!(RateLimitRule::new(10, 20, Duration::from_secs(2)).validate().is_ok());
+        assert!(RateLimitRule::new(10, 20, Duration::from_secs(1)).validate().is_err());
+
+        // A per-window rule refills exactly over its window
+        let rule = RateLimitRule::new(100, 100, Duration::from_secs(60))
+            .with_rate_unit(RateUnit::PerWindow);
+        assert!(rule.validate().is_ok());
+        let rule = RateLimitRule::new(100, 200, Duration::from_secs(60))
+            .with_rate_unit(RateUnit::PerWindow);
+        assert!(rule.validate().is_err());
+    }
+
+    #[test]
+    fn test_rate_units_convert_to_per_second() {
+        let window = Duration::from_secs(60);
+        let per_second = RateLimitRule::new(1, 10, window);
+        let per_minute = RateLimitRule::new(60, 10, window).with_rate_unit(RateUnit::PerMinute);
+        let per_hour = RateLimitRule::new(3600, 10, window).with_rate_unit(RateUnit::PerHour);
+        let per_window = RateLimitRule::new(60, 10, window).with_rate_unit(RateUnit::PerWindow);
+
+        for rule in [&per_second, &per_minute, &per_hour, &per_window] {
+            assert_eq!(rule.refill_per_second(), 1.0);
+            assert_eq!(rule.refill_rate_ms(), 0.001);
+        }
+
+        let slow = RateLimitRule::new(1000, 10, window).with_rate_unit(RateUnit::PerHour);
+        assert!((slow.refill_per_second() - 1000.0 / 3600.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_rate_unit_defaults_when_absent() {
+        let json = r#"{"requests_per_second": 5, "burst_capacity": 5,
+            "window_size": {"secs": 60, "nanos": 0}, "enabled": true}"#;
+        let rule: RateLimitRule = serde_json::from_str(json).unwrap();
+        assert_eq!(rule.rate_unit, RateUnit::PerSecond);
+
+        let json = r#"{"requests_per_second": 5, "burst_capacity": 5,
+            "window_size": {"secs": 60, "nanos": 0}, "enabled": true, "rate_unit": "per_hour"}"#;
+        let rule: RateLimitRule = serde_json::from_str(json).unwrap();
+        assert_eq!(rule.rate_unit, RateUnit::PerHour);
+    }
+
+    #[test]
+    fn test_metadata_within_bounds_is_valid() {
+        let metadata = HashMap::from([
+            ("tenant".to_string(), "acme".to_string()),
+            ("plan_tier".to_string(), "gold".to_string()),
+        ]);
+        assert!(rule(5).with_metadata(metadata).validate().is_ok());
+    }
+
+    #[test]
+    fn test_metadata_bounds_enforced() {
+        let too_many: HashMap<String, String> = (0..=MAX_METADATA_LABELS)
+            .map(|i| (format!("label_{}", i), "x".to_string()))
+            .collect();
+        assert!(validate_metadata(&too_many).is_err());
+
+        let long_value = HashMap::from([
+            ("tenant".to_string(), "x".repeat(MAX_METADATA_VALUE_LEN + 1)),
+        ]);
+        assert!(validate_metadata(&long_value).is_err());
+
+        let long_name = HashMap::from([
+            ("x".repeat(MAX_METADATA_KEY_LEN + 1), "acme".to_string()),
+        ]);
+        assert!(validate_metadata(&long_name).is_err());
+    }
+
+    #[test]
+    fn test_metadata_label_names_must_be_metric_safe() {
+        for name in ["plan-tier", "1tier", "key", "result", "__name", ""] {
+            let metadata = HashMap::from([(name.to_string(), "x".to_string())]);
+            assert!(validate_metadata(&metadata).is_err(), "{} should be rejected", name);
         }
     }
-}
\ No newline at end of file
+}
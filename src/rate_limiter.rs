@@ -41,8 +41,126 @@
 //! The `RateLimiter` uses `Arc<RwLock<HashMap>>` for the local bucket store,
 //! allowing concurrent read access with exclusive write access for modifications.
 //!
+//! ## Burst vs Steady State
+//!
+//! Buckets use two parameters, matching [`RateLimitRule`]: the burst capacity
+//! is the ceiling a bucket fills to (and starts at, including after a reset),
+//! while the refill rate alone governs sustained throughput. A client can
+//! spend a full burst at once and then settles to the refill rate.
+//! [`RateLimiter::check_rate_limit_with_rule`] applies a rule's
+//! `burst_capacity` and its per-second refill rate (converted from the rule's
+//! `rate_unit`) this way.
+//!
+//! ## Redis Timeouts and Fallback
+//!
+//! [`RateLimiter::check_rate_limit_shared`] consumes from the Redis bucket when
+//! a client is configured. The blocking Redis round trip runs on a
+//! `spawn_blocking` worker bounded by `Config::redis_op_timeout_ms`; if Redis
+//! errors or stalls past the timeout, the check falls back to the local bucket
+//! so a slow Redis cannot pile up requests.
+//!
+//! Until Redis answers again the limiter is degraded
+//! ([`RateLimiter::is_degraded`]): each instance enforces limits on its own,
+//! so a client spread across instances may get more than its limit. With
+//! `Config::warn_on_degraded` every response in that state carries
+//! `Warning: 199 throttler "operating in local-only mode"`.
+//!
+//! ## Check-then-Commit
+//!
+//! Gateways that pre-check before proxying can use
+//! [`RateLimiter::peek_rate_limit_shared`] to learn whether a request would be
+//! allowed without consuming, and consume only once the downstream call has
+//! succeeded. Unlike a reservation nothing is held between the two steps, so
+//! the later consume may find the bucket already drained by other clients.
+//!
+//! ## Write Races
+//!
+//! A bucket write is rejected when another instance stored a newer bucket
+//! between our read and our write. Rather than failing the request, the
+//! consume re-reads the bucket and tries again, up to
+//! `Config::redis_race_retries` extra times; only a race that persists past
+//! that surfaces as an error (and so falls back to the local bucket).
+//!
+//! Every lost race is counted (`write_races` in `/admin/stats`), and with
+//! `Config::race_log_size` set the most recent ones are kept with their key
+//! and read/reject times. A count that climbs with traffic, or one key
+//! dominating the log, points to a hot key rather than occasional bad luck.
+//!
+//! ## Write Batching
+//!
+//! By default every Redis consume writes the bucket back. With
+//! `Config::min_redis_write_interval_ms` set, a bucket is written at most
+//! once per interval per instance. Tokens consumed in between are kept as a
+//! pending count that is subtracted from every fresh Redis read, so this
+//! instance never re-admits them, and is applied on the next write. Other
+//! instances see them at most one interval late. Pending counts are also
+//! flushed periodically by the server and on shutdown.
+//!
+//! ## State Migration
+//!
+//! [`RateLimiter::export_state`] snapshots every local bucket, refilled up to
+//! the export time, and [`RateLimiter::import_state`] loads such a snapshot
+//! into another instance (e.g. during a blue/green deploy). Imported buckets
+//! restart their refill clock at import time, so the time spent moving the
+//! state never grants extra tokens.
+//!
+//! ## Global Limit
+//!
+//! `Config::global_rate_limit` caps requests per second across all keys to
+//! protect the service itself. [`RateLimiter::check_global_limit`] draws from
+//! a single in-process bucket; handlers check it before the per-key bucket so
+//! a global denial does not spend the client's own tokens.
+//!
+//! ## Fair Queueing
+//!
+//! Concurrent consumes on one key are otherwise granted in whatever order
+//! they win the bucket lock. With `Config::fair_queueing` enabled,
+//! [`RateLimiter::check_rate_limit_shared`] queues requests per key and
+//! serves them in arrival order: the head of the queue waits for the next
+//! token while later arrivals wait behind it. At most
+//! `Config::fair_queue_depth` requests may wait on a key; arrivals beyond
+//! that are denied immediately, and a request is denied as soon as its wait
+//! would run past `Config::fair_queue_max_wait_ms`. Waiting ties up a task
+//! per queued request, so this is off by default.
+//!
+//! ## Redis Concurrency
+//!
+//! Each Redis operation holds a blocking thread and a connection while it
+//! runs. With `Config::max_redis_concurrency` set, at most that many run at
+//! once; further operations wait up to `Config::redis_queue_timeout_ms` for
+//! a slot (0 = fail at once) and then fail like an unreachable Redis, so a
+//! burst falls back to local state instead of piling onto Redis.
+//!
+//! ## Hybrid Leases
+//!
+//! With `Config::hybrid_local_burst` set, each instance leases up to that
+//! many tokens from a key's Redis bucket at a time and admits requests from
+//! the lease without touching Redis. Once the lease runs short, or
+//! `Config::hybrid_sync_interval_ms` after it was last synced, the next
+//! consume tops it up from Redis again. A hot key then costs one Redis round
+//! trip per lease rather than per request.
+//!
+//! Every admitted token has been drawn from Redis, but a lease is invisible
+//! to other instances: while they hold leased tokens the Redis bucket keeps
+//! refilling up to its full capacity. Within one refill period a key can
+//! therefore admit up to `capacity + instances × hybrid_local_burst`
+//! requests, a slight over-admission that shrinks with the lease size.
+//! Conversely, tokens leased by an instance that stops seeing the key sit
+//! unused until the periodic cleanup finds the lease past its sync time and
+//! returns them to the Redis bucket, so it may briefly under-admit
+//! elsewhere.
+//!
+//! ## Rule Changes
+//!
+//! Buckets are created from the capacity and refill rate of the first
+//! consume, but every later consume passes the currently effective values
+//! and the bucket adopts them: time already elapsed is refilled at the old
+//! rate, the token count is kept, and it is clamped to the new capacity. A
+//! rule update therefore applies to the next request without a reset.
+//!
 //! ## Usage
 //!
+
 //! ```rust,no_run
 //! use throttler::config::Config;
 //! use throttler::rate_limiter::RateLimiter;
@@ -60,12 +178,48 @@
 //! # }
 //! ```
 
-use std::collections::HashMap;
-use std::sync::{Arc, RwLock};
-use std::time::{SystemTime, UNIX_EPOCH};
-use crate::config::Config;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use crate::concurrency::{ConcurrencySlot, SlotTable};
+use crate::config::{Config, ConsistencyMode, RemainingSemantics};
 use crate::error::ThrottlerError;
+use crate::key_generator::KeyGenerator;
+use crate::quota::{QuotaPeriod, QuotaState, QuotaTable};
+use crate::rate_limit_config::RateLimitRule;
+use crate::algorithms::registry::TOKEN_BUCKET_ALGORITHM;
+use crate::algorithms::{RateLimitAlgorithm, RateLimitAlgorithmRegistry};
+use crate::bucket_store::BucketStore;
 use crate::redis::RedisClient;
+use crate::token_bucket::TokenBucket;
+
+tokio::task_local! {
+    /// When the caller stops waiting for the current request, from
+    /// `X-Request-Deadline`; Redis operations give up at this point
+    pub static REQUEST_DEADLINE: Instant;
+}
+
+#[cfg(feature = "testing")]
+tokio::task_local! {
+    /// Milliseconds added to the limiter's clock for the current task
+    pub static TEST_TIME_OFFSET_MS: u64;
+}
+
+/// Current time in milliseconds since the UNIX epoch, as seen by the limiter
+pub(crate) fn now_ms() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+
+    #[cfg(feature = "testing")]
+    let now = now + TEST_TIME_OFFSET_MS.try_with(|offset| *offset).unwrap_or(0);
+
+    now
+}
 
 /// Core rate limiting engine using the token bucket algorithm.
 ///
@@ -103,8 +257,419 @@ pub struct RateLimiter {
     config: Arc<Config>,
     /// In-memory token buckets for local mode
     local_buckets: Arc<RwLock<HashMap<String, LocalBucket>>>,
-    /// Optional Redis client for distributed mode
-    redis_client: Option<Arc<RedisClient>>,
+    /// Shared bucket store for distributed mode (Redis unless injected)
+    store: Option<Arc<dyn BucketStore>>,
+    /// Read replica for reads that tolerate lag (status, dry runs)
+    replica: Option<Arc<dyn BucketStore>>,
+    /// Service-wide bucket shared by all keys, when a global limit is set
+    global_bucket: Option<Arc<Mutex<TokenBucket>>>,
+    /// Consumption not yet written back to Redis
+    write_batcher: Arc<WriteBatcher>,
+    /// Consecutive denials per key since its last allowed request
+    denial_streaks: Arc<RwLock<HashMap<String, DenialStreak>>>,
+    /// Per-key arrival-order queues, used when fair queueing is enabled
+    fair_queues: Arc<FairQueues>,
+    /// Permits for Redis operations in flight, when their number is bounded
+    redis_permits: Option<Arc<Semaphore>>,
+    /// Tokens leased from Redis buckets, used in hybrid mode
+    leases: Arc<LocalLeases>,
+    /// Concurrency slots for local mode
+    local_slots: Arc<SlotTable>,
+    /// Quota usage for local mode
+    local_quotas: Arc<QuotaTable>,
+    /// Set when a failed Redis operation fell back to local state, cleared
+    /// by the next one that succeeds
+    degraded: Arc<AtomicBool>,
+    /// Registered algorithm used in place of the token bucket, when
+    /// `Config::algorithm` names one
+    algorithm: Option<Arc<dyn RateLimitAlgorithm>>,
+}
+
+/// Look-ahead used when computing the retry budget for denied clients
+pub const RETRY_BUDGET_HORIZON_SECS: f64 = 1.0;
+
+/// Estimated fixed cost of one local bucket entry, excluding its key bytes
+pub const BUCKET_ENTRY_BYTES: usize =
+    std::mem::size_of::<String>() + std::mem::size_of::<LocalBucket>();
+
+/// Most buckets one page of [`RateLimiter::list_buckets`] holds
+pub const MAX_LIST_BUCKETS: usize = 1000;
+
+/// Buckets the Redis sweeper reads per `SCAN` page
+const STORE_SWEEP_PAGE: usize = 500;
+
+/// Prefix of every bucket key in the shared store
+const STORE_KEY_PREFIX: &str = "throttler:";
+
+/// Prefix of every concurrency slot set in the shared store, outside
+/// [`STORE_KEY_PREFIX`] so bucket listings never meet one
+const CONCURRENCY_KEY_PREFIX: &str = "throttler-concurrency:";
+
+/// Prefix of every quota counter in the shared store, outside
+/// [`STORE_KEY_PREFIX`] for the same reason
+const QUOTA_KEY_PREFIX: &str = "throttler-quota:";
+
+/// A bucket's current state, as listed by [`RateLimiter::list_buckets`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BucketSummary {
+    /// Rate limit key (hashed when `Config::hash_keys` is on)
+    pub key: String,
+    /// Whole tokens available now, after refill
+    pub remaining: u64,
+    pub capacity: u64,
+    /// Last refill or consume (ms since UNIX epoch)
+    pub last_activity_ms: u64,
+}
+
+impl BucketSummary {
+    fn new(key: String, tokens: f64, capacity: u64, refill_rate: f64, last_refill: u64, now: u64) -> Self {
+        let elapsed_secs = now.saturating_sub(last_refill) as f64 / 1000.0;
+        let tokens = (tokens + refill_rate * elapsed_secs).min(capacity as f64);
+        Self {
+            key,
+            remaining: tokens.max(0.0).floor() as u64,
+            capacity,
+            last_activity_ms: last_refill,
+        }
+    }
+}
+
+/// A bucket as read at one moment, by [`RateLimiter::bucket_snapshot`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BucketSnapshot {
+    /// Tokens available, after refill
+    pub tokens: f64,
+    /// Tokens the refill added
+    pub refilled: f64,
+    /// Time since the bucket was last refilled, in ms (0 for a new bucket)
+    pub elapsed_ms: u64,
+}
+
+/// One page of [`RateLimiter::list_buckets`]
+#[derive(Debug, Clone, Serialize)]
+pub struct BucketListing {
+    pub keys: Vec<BucketSummary>,
+    /// Pass back as `cursor` for the next page; `None` on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// Portable snapshot of all local buckets, produced by
+/// [`RateLimiter::export_state`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SerializableState {
+    /// When the snapshot was taken (ms since UNIX epoch)
+    pub exported_at: u64,
+    /// Bucket state per key
+    pub buckets: HashMap<String, BucketState>,
+}
+
+/// State of one local bucket in a [`SerializableState`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BucketState {
+    pub tokens: f64,
+    pub capacity: u64,
+    pub refill_rate: f64,
+    pub last_refill: u64,
+}
+
+/// A key's denials since it was last allowed, behind its retry budget
+#[derive(Debug, Clone, Copy, Default)]
+struct DenialStreak {
+    denials: u64,
+    /// When the latest denial happened (ms since UNIX epoch)
+    last_denied_ms: u64,
+}
+
+/// Tokens consumed against a Redis bucket but not yet written back.
+#[derive(Default)]
+struct PendingWrite {
+    /// Tokens consumed since the last write
+    consumed: f64,
+    /// When this instance last wrote the bucket (ms since UNIX epoch)
+    last_write_ms: u64,
+    /// Redis TTL the bucket was last consumed under
+    ttl_secs: Option<usize>,
+}
+
+/// A bucket write rejected because another instance stored a newer bucket
+/// first, as listed by [`RateLimiter::recent_races`]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WriteRace {
+    /// Store key of the contended bucket
+    pub key: String,
+    /// When the bucket was read (ms since UNIX epoch)
+    pub read_at_ms: u64,
+    /// When its write was rejected (ms since UNIX epoch)
+    pub rejected_at_ms: u64,
+}
+
+/// Counts lost write races and keeps the most recent `capacity` of them.
+#[derive(Default)]
+struct RaceLog {
+    capacity: usize,
+    count: AtomicU64,
+    recent: Mutex<VecDeque<WriteRace>>,
+}
+
+impl RaceLog {
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, VecDeque<WriteRace>>, ThrottlerError> {
+        self.recent.lock()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire lock on race log".to_string()))
+    }
+
+    /// Records a write to `key`, read at `read_at_ms`, that lost a race
+    fn record(&self, key: &str, read_at_ms: u64) -> Result<(), ThrottlerError> {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        let rejected_at_ms = now_ms();
+        tracing::debug!(key = %key, read_at_ms, rejected_at_ms, "Bucket write lost a race");
+
+        if self.capacity == 0 {
+            return Ok(());
+        }
+        let mut recent = self.lock()?;
+        if recent.len() >= self.capacity {
+            recent.pop_front();
+        }
+        recent.push_back(WriteRace { key: key.to_string(), read_at_ms, rejected_at_ms });
+        Ok(())
+    }
+}
+
+/// Tracks pending consumption per Redis key to space out bucket writes.
+#[derive(Default)]
+struct WriteBatcher {
+    min_interval_ms: u64,
+    pending: Mutex<HashMap<String, PendingWrite>>,
+    writes: AtomicU64,
+    /// Writes rejected by a race, retried or not
+    races: RaceLog,
+}
+
+impl WriteBatcher {
+    fn new(min_interval_ms: u64, race_log_size: usize) -> Self {
+        Self {
+            min_interval_ms,
+            races: RaceLog { capacity: race_log_size, ..RaceLog::default() },
+            ..Self::default()
+        }
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, HashMap<String, PendingWrite>>, ThrottlerError> {
+        self.pending.lock()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire lock on pending writes".to_string()))
+    }
+
+    /// Tokens consumed for `key` that Redis does not know about yet
+    fn pending(&self, key: &str) -> Result<f64, ThrottlerError> {
+        Ok(self.lock()?.get(key).map_or(0.0, |p| p.consumed))
+    }
+
+    /// Records `cost` consumed tokens and returns whether the bucket is due
+    /// to be written (claiming the write slot if so). `ttl_secs` is the TTL
+    /// a deferred write of the bucket is stored with.
+    fn record_consume(&self, key: &str, now_ms: u64, cost: f64, ttl_secs: usize) -> Result<bool, ThrottlerError> {
+        let mut pending = self.lock()?;
+        let entry = pending.entry(key.to_string()).or_default();
+        entry.consumed += cost;
+        entry.ttl_secs = Some(ttl_secs);
+
+        let due = now_ms.saturating_sub(entry.last_write_ms) >= self.min_interval_ms;
+        if due {
+            entry.last_write_ms = now_ms;
+        }
+        Ok(due)
+    }
+
+    /// Marks `included` pending tokens as persisted
+    fn written(&self, key: &str, included: f64) -> Result<(), ThrottlerError> {
+        self.writes.fetch_add(1, Ordering::Relaxed);
+        let mut pending = self.lock()?;
+        if let Some(entry) = pending.get_mut(key) {
+            entry.consumed = (entry.consumed - included).max(0.0);
+        }
+        Ok(())
+    }
+
+    /// Undoes a consume whose write lost a race: the `cost` tokens are given
+    /// back and the write slot released so the retry writes straight away.
+    fn rejected(&self, key: &str, cost: f64) -> Result<(), ThrottlerError> {
+        let mut pending = self.lock()?;
+        if let Some(entry) = pending.get_mut(key) {
+            entry.consumed = (entry.consumed - cost).max(0.0);
+            entry.last_write_ms = 0;
+        }
+        Ok(())
+    }
+
+    /// Drops pending consumption for a key whose bucket was reset
+    fn forget(&self, key: &str) -> Result<(), ThrottlerError> {
+        self.lock()?.remove(key);
+        Ok(())
+    }
+
+    /// Keys with consumption that has not been written yet, with the TTL
+    /// their buckets were last consumed under
+    fn unwritten_keys(&self) -> Result<Vec<(String, Option<usize>)>, ThrottlerError> {
+        Ok(self.lock()?
+            .iter()
+            .filter(|(_, p)| p.consumed > 0.0)
+            .map(|(key, p)| (key.clone(), p.ttl_secs))
+            .collect())
+    }
+}
+
+/// Arrival-order queues for keys with requests waiting on a token.
+///
+/// Each key has a FIFO turn lock (tokio's `Mutex` wakes waiters in the order
+/// they started waiting) and a count of requests queued on it. Entries are
+/// removed once their last request leaves.
+#[derive(Default)]
+struct FairQueues {
+    max_depth: usize,
+    queues: Mutex<HashMap<String, KeyQueue>>,
+}
+
+#[derive(Default)]
+struct KeyQueue {
+    /// Held by the request currently at the head of the queue
+    turn: Arc<tokio::sync::Mutex<()>>,
+    /// Requests queued on the key, including the head
+    depth: usize,
+}
+
+/// A request's place in a key's queue; leaves the queue when dropped.
+struct QueueSlot<'a> {
+    queues: &'a FairQueues,
+    key: String,
+    turn: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl FairQueues {
+    fn new(max_depth: usize) -> Self {
+        Self { max_depth, ..Self::default() }
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, HashMap<String, KeyQueue>>, ThrottlerError> {
+        self.queues.lock()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire lock on fair queues".to_string()))
+    }
+
+    /// Joins the queue for `key`, or returns `None` if it is full
+    fn enter(&self, key: &str) -> Result<Option<QueueSlot<'_>>, ThrottlerError> {
+        let mut queues = self.lock()?;
+        let queue = queues.entry(key.to_string()).or_default();
+        if queue.depth >= self.max_depth {
+            return Ok(None);
+        }
+        queue.depth += 1;
+
+        Ok(Some(QueueSlot { queues: self, key: key.to_string(), turn: Arc::clone(&queue.turn) }))
+    }
+
+    /// Requests currently queued across all keys
+    fn queued(&self) -> Result<u64, ThrottlerError> {
+        Ok(self.lock()?.values().map(|queue| queue.depth as u64).sum())
+    }
+}
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        let Ok(mut queues) = self.queues.queues.lock() else {
+            return;
+        };
+        if let Some(queue) = queues.get_mut(&self.key) {
+            queue.depth -= 1;
+            if queue.depth == 0 {
+                queues.remove(&self.key);
+            }
+        }
+    }
+}
+
+/// Tokens this instance has leased from Redis buckets, per Redis key.
+#[derive(Default)]
+struct LocalLeases {
+    leases: Mutex<HashMap<String, Lease>>,
+}
+
+#[derive(Default)]
+struct Lease {
+    /// Leased tokens not yet spent
+    tokens: f64,
+    /// Tokens left in the Redis bucket at the last sync, for reporting
+    redis_remaining: f64,
+    /// When the lease must be synced with Redis again (ms since epoch)
+    sync_due_ms: u64,
+    /// How the Redis bucket it was drawn from is sized, to return it
+    sizing: Option<StoredSizing>,
+}
+
+impl LocalLeases {
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, HashMap<String, Lease>>, ThrottlerError> {
+        self.leases.lock()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire lock on leases".to_string()))
+    }
+
+    /// Spends `cost` tokens from a lease that is not due for a sync,
+    /// returning the tokens left to report, or `None` if Redis is needed
+    fn consume(&self, key: &str, now_ms: u64, cost: f64) -> Result<Option<f64>, ThrottlerError> {
+        let mut leases = self.lock()?;
+        Ok(leases.get_mut(key)
+            .filter(|lease| now_ms < lease.sync_due_ms && lease.tokens >= cost)
+            .map(|lease| {
+                lease.tokens -= cost;
+                lease.tokens + lease.redis_remaining
+            }))
+    }
+
+    /// Leased tokens still held for `key`, whether or not a sync is due
+    fn held(&self, key: &str) -> Result<f64, ThrottlerError> {
+        Ok(self.lock()?.get(key).map_or(0.0, |lease| lease.tokens))
+    }
+
+    /// Adds `drawn` tokens from a sync to the lease and spends `cost` from
+    /// it if it now holds enough. Returns whether it did and the tokens left.
+    fn synced(
+        &self,
+        key: &str,
+        drawn: f64,
+        redis_remaining: f64,
+        sync_due_ms: u64,
+        sizing: StoredSizing,
+        cost: f64,
+    ) -> Result<(bool, f64), ThrottlerError> {
+        let mut leases = self.lock()?;
+        let lease = leases.entry(key.to_string()).or_default();
+        lease.tokens += drawn;
+        lease.redis_remaining = redis_remaining;
+        lease.sync_due_ms = sync_due_ms;
+        lease.sizing = Some(sizing);
+
+        if lease.tokens < cost {
+            return Ok((false, lease.tokens + redis_remaining));
+        }
+        lease.tokens -= cost;
+        Ok((true, lease.tokens + redis_remaining))
+    }
+
+    /// Drops the lease for a key whose bucket was reset
+    fn forget(&self, key: &str) -> Result<(), ThrottlerError> {
+        self.lock()?.remove(key);
+        Ok(())
+    }
+
+    /// Removes the leases due for a sync as of `now_ms`, returning each
+    /// one's key, unspent tokens and bucket sizing
+    fn take_due(&self, now_ms: u64) -> Result<Vec<(String, f64, Option<StoredSizing>)>, ThrottlerError> {
+        let mut leases = self.lock()?;
+        let due: Vec<String> = leases.iter()
+            .filter(|(_, lease)| lease.sync_due_ms <= now_ms)
+            .map(|(key, _)| key.clone())
+            .collect();
+        Ok(due.into_iter()
+            .filter_map(|key| leases.remove(&key).map(|lease| (key, lease.tokens, lease.sizing)))
+            .collect())
+    }
 }
 
 /// Local (in-memory) token bucket state.
@@ -128,42 +693,197 @@ struct LocalBucket {
     refill_rate: f64,
     /// Timestamp of last refill (milliseconds since UNIX epoch)
     last_refill: u64,
+    /// Whether local state has changed since it was last written to Redis
+    dirty: bool,
+    /// How long the bucket may sit idle before it expires, from the rule's
+    /// `window_size` (ms); `None` uses the sweeper's default age
+    window_ms: Option<u64>,
+}
+
+impl LocalBucket {
+    /// Switches to a new capacity and refill rate, keeping the token count
+    /// but never above the new capacity
+    fn apply_params(&mut self, capacity: u64, refill_rate: f64) {
+        self.capacity = capacity;
+        self.refill_rate = refill_rate;
+        self.tokens = self.tokens.min(capacity as f64);
+    }
+
+    /// Redis TTL for this bucket: the rule window when there is one,
+    /// otherwise the time to refill from empty
+    fn ttl_secs(&self) -> usize {
+        bucket_ttl_secs(self.capacity, self.refill_rate, self.window_ms)
+    }
+
+    fn to_token_bucket(&self) -> TokenBucket {
+        TokenBucket {
+            capacity: self.capacity,
+            tokens: self.tokens,
+            refill_rate: self.refill_rate,
+            last_refill: self.last_refill,
+        }
+    }
 }
 
 impl RateLimiter {
     pub fn new(config: Config) -> Result<Self, ThrottlerError> {
-        let redis_client = if !config.redis_url.is_empty() {
-            Some(Arc::new(RedisClient::new(&config.redis_url)?))
+        let store = if !config.redis_nodes().is_empty() {
+            Some(Arc::new(RedisClient::from_config(&config)?) as Arc<dyn BucketStore>)
         } else {
             None
         };
+        let replica = RedisClient::replica_from_config(&config)?
+            .map(|client| Arc::new(client) as Arc<dyn BucketStore>);
+
+        let mut limiter = Self::build(config, store)?;
+        if limiter.store.is_some() {
+            limiter.replica = replica;
+        }
+        Ok(limiter)
+    }
+
+    /// Sends reads that tolerate replication lag (status and dry-run
+    /// checks) to `replica`; consumes and writes stay on the primary store.
+    pub fn with_replica(mut self, replica: Arc<dyn BucketStore>) -> Self {
+        self.replica = Some(replica);
+        self
+    }
+
+    /// Creates a limiter whose distributed state lives in `store` instead
+    /// of the Redis at `config.redis_url`, e.g. a
+    /// [`MemoryStore`](crate::bucket_store::MemoryStore) in tests.
+    pub fn with_store(config: Config, store: Arc<dyn BucketStore>) -> Result<Self, ThrottlerError> {
+        Self::build(config, Some(store))
+    }
+
+    fn build(config: Config, store: Option<Arc<dyn BucketStore>>) -> Result<Self, ThrottlerError> {
+        let algorithm = RateLimitAlgorithmRegistry::create(&config.algorithm, &config)?.map(Arc::from);
+
+        let global_bucket = (config.global_rate_limit > 0).then(|| {
+            let limit = config.global_rate_limit;
+            Arc::new(Mutex::new(TokenBucket::new(limit, limit as f64)))
+        });
+
+        let write_batcher = Arc::new(WriteBatcher::new(config.min_redis_write_interval_ms, config.race_log_size));
+        let fair_queues = Arc::new(FairQueues::new(config.fair_queue_depth));
+        let redis_permits = (config.max_redis_concurrency > 0)
+            .then(|| Arc::new(Semaphore::new(config.max_redis_concurrency)));
 
         Ok(RateLimiter {
             config: Arc::new(config),
             local_buckets: Arc::new(RwLock::new(HashMap::new())),
-            redis_client,
+            store,
+            replica: None,
+            global_bucket,
+            write_batcher,
+            denial_streaks: Arc::new(RwLock::new(HashMap::new())),
+            fair_queues,
+            redis_permits,
+            leases: Arc::new(LocalLeases::default()),
+            local_slots: Arc::new(SlotTable::new()),
+            local_quotas: Arc::new(QuotaTable::new()),
+            degraded: Arc::new(AtomicBool::new(false)),
+            algorithm,
         })
     }
 
+    /// The configuration this limiter was created with
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Name of the algorithm limiting every key: the registered algorithm
+    /// `Config::algorithm` selected, or the built-in token bucket
+    pub fn algorithm_name(&self) -> &str {
+        match self.algorithm {
+            Some(_) => &self.config.algorithm,
+            None => TOKEN_BUCKET_ALGORITHM,
+        }
+    }
+
+    /// Redis key holding the bucket for a rate limit key
+    pub fn redis_key(&self, key: &str) -> String {
+        if self.config.hash_keys {
+            format!("{}{}", STORE_KEY_PREFIX, KeyGenerator::hash_key(key))
+        } else {
+            format!("{}{}", STORE_KEY_PREFIX, key)
+        }
+    }
+
+    /// Store key holding the concurrency slots for a rate limit key
+    fn concurrency_key(&self, key: &str) -> String {
+        if self.config.hash_keys {
+            format!("{}{}", CONCURRENCY_KEY_PREFIX, KeyGenerator::hash_key(key))
+        } else {
+            format!("{}{}", CONCURRENCY_KEY_PREFIX, key)
+        }
+    }
+
+    /// Store key of `key`'s quota counter for the period ending at `reset_at_ms`
+    fn quota_key(&self, key: &str, reset_at_ms: u64) -> String {
+        if self.config.hash_keys {
+            format!("{}{}:{}", QUOTA_KEY_PREFIX, KeyGenerator::hash_key(key), reset_at_ms)
+        } else {
+            format!("{}{}:{}", QUOTA_KEY_PREFIX, key, reset_at_ms)
+        }
+    }
+
     /// Check rate limit using default configuration
     pub fn check_rate_limit(&self, key: &str) -> Result<(bool, u64), ThrottlerError> {
         let capacity = self.config.default_capacity;
-        let refill_rate = self.config.default_refill_rate as f64;
+        let refill_rate = self.config.default_refill_rate;
 
         self.check_rate_limit_with_params(key, capacity, refill_rate)
     }
 
+    /// Check rate limit using a rule's burst capacity and steady refill rate.
+    ///
+    /// The rule's `window_size` bounds how long the bucket may sit idle: it
+    /// becomes the bucket's Redis TTL and its age limit in
+    /// [`cleanup_expired_buckets`](Self::cleanup_expired_buckets).
+    pub fn check_rate_limit_with_rule(
+        &self,
+        key: &str,
+        rule: &RateLimitRule,
+    ) -> Result<(bool, u64), ThrottlerError> {
+        let (allowed, remaining) = self.consume_local(
+            key,
+            rule.burst_capacity as u64,
+            rule.refill_per_second(),
+            1,
+            Some(rule.window_ms()),
+        )?;
+        Ok((allowed, remaining.floor() as u64))
+    }
+
     /// Check rate limit with specific parameters
+    ///
+    /// `capacity` is the burst ceiling; `refill_rate` is the sustained rate in
+    /// tokens per second.
     pub fn check_rate_limit_with_params(
         &self,
         key: &str,
         capacity: u64,
         refill_rate: f64,
     ) -> Result<(bool, u64), ThrottlerError> {
-        let current_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
+        let (allowed, remaining) = self.consume_local(key, capacity, refill_rate, 1, None)?;
+        Ok((allowed, remaining.floor() as u64))
+    }
+
+    /// Consumes `cost` tokens from the local bucket, reporting the unfloored
+    /// remaining count. `window_ms` is the idle limit of the rule in use, if any.
+    fn consume_local(
+        &self,
+        key: &str,
+        capacity: u64,
+        refill_rate: f64,
+        cost: u64,
+        window_ms: Option<u64>,
+    ) -> Result<(bool, f64), ThrottlerError> {
+        if let Some(algorithm) = &self.algorithm {
+            return self.consume_with_algorithm(algorithm.as_ref(), key, cost);
+        }
+        let current_time = now_ms();
 
         let mut buckets = self.local_buckets.write()
             .map_err(|_| ThrottlerError::InternalError("Failed to acquire write lock on buckets".to_string()))?;
@@ -174,6 +894,8 @@ impl RateLimiter {
                 capacity,
                 refill_rate,
                 last_refill: current_time,
+                dirty: true,
+                window_ms,
             }
         });
 
@@ -183,80 +905,2644 @@ impl RateLimiter {
         let tokens_to_add = bucket.refill_rate * elapsed_secs;
         bucket.tokens = (bucket.tokens + tokens_to_add).min(bucket.capacity as f64);
         bucket.last_refill = current_time;
+        bucket.dirty = true;
 
-        // Try to consume a token
-        if bucket.tokens >= 1.0 {
-            bucket.tokens -= 1.0;
-            Ok((true, bucket.tokens.floor() as u64))
+        // Follow the current rule; time already elapsed was refilled at the old rate
+        bucket.apply_params(capacity, refill_rate);
+        bucket.window_ms = window_ms;
+
+        // Try to consume the tokens
+        let cost = cost as f64;
+        if bucket.tokens >= cost {
+            bucket.tokens -= cost;
+            Ok((true, self.reported_remaining(bucket.tokens, cost)))
         } else {
-            Ok((false, 0))
+            Ok((false, 0.0))
         }
     }
 
-    /// Get remaining tokens for a key
-    pub fn get_remaining_tokens(&self, key: &str) -> Result<u64, ThrottlerError> {
-        let buckets = self.local_buckets.read()
-            .map_err(|_| ThrottlerError::InternalError("Failed to acquire read lock on buckets".to_string()))?;
-
-        match buckets.get(key) {
-            Some(bucket) => Ok(bucket.tokens.floor() as u64),
-            None => Ok(self.config.default_capacity),
+    /// Consumes `cost` through a registered algorithm, which keeps its own
+    /// state, reporting what it says is left
+    fn consume_with_algorithm(
+        &self,
+        algorithm: &dyn RateLimitAlgorithm,
+        key: &str,
+        cost: u64,
+    ) -> Result<(bool, f64), ThrottlerError> {
+        if !algorithm.is_allowed(key, cost)? {
+            return Ok((false, 0.0));
         }
+        let remaining = algorithm.get_state(key)?.available_tokens as f64;
+        Ok((true, self.reported_remaining(remaining, cost as f64)))
     }
 
-    /// Reset rate limit for a specific key
-    pub fn reset(&self, key: &str) -> Result<(), ThrottlerError> {
-        if let Some(redis_client) = &self.redis_client {
-            let redis_key = format!("throttler:{}", key);
-            redis_client.delete_token_bucket(&redis_key)?;
-        }
-
-        let mut buckets = self.local_buckets.write()
-            .map_err(|_| ThrottlerError::InternalError("Failed to acquire write lock on buckets".to_string()))?;
-        buckets.remove(key);
+    /// Consume one token from the service-wide bucket.
+    ///
+    /// Always allows when no global limit is configured.
+    pub fn check_global_limit(&self) -> Result<bool, ThrottlerError> {
+        let Some(global_bucket) = &self.global_bucket else {
+            return Ok(true);
+        };
 
-        Ok(())
+        let mut bucket = global_bucket.lock()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire lock on global bucket".to_string()))?;
+        bucket.try_consume(1)
     }
 
-    /// Cleanup expired buckets
-    pub fn cleanup_expired_buckets(&self, max_age_ms: u64) -> Result<usize, ThrottlerError> {
-        let current_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
+    /// Whether the service-wide bucket has a token, without consuming it.
+    ///
+    /// Always true when no global limit is configured.
+    pub fn peek_global_limit(&self) -> Result<bool, ThrottlerError> {
+        let Some(global_bucket) = &self.global_bucket else {
+            return Ok(true);
+        };
 
-        let mut buckets = self.local_buckets.write()
-            .map_err(|_| ThrottlerError::InternalError("Failed to acquire write lock on buckets".to_string()))?;
+        let mut bucket = global_bucket.lock()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire lock on global bucket".to_string()))?;
+        Ok(bucket.available_tokens()? >= 1)
+    }
 
-        let initial_count = buckets.len();
+    /// Remaining count to report after a successful consume of `cost` tokens,
+    /// honoring `Config::remaining_semantics`.
+    fn reported_remaining(&self, tokens_after: f64, cost: f64) -> f64 {
+        match self.config.remaining_semantics {
+            RemainingSemantics::After => tokens_after,
+            RemainingSemantics::Before => tokens_after + cost,
+        }
+    }
 
-        buckets.retain(|_, bucket| {
-            current_time - bucket.last_refill < max_age_ms
-        });
+    /// Check rate limit against shared Redis state using default configuration
+    pub async fn check_rate_limit_shared(&self, key: &str) -> Result<(bool, u64), ThrottlerError> {
+        let capacity = self.config.default_capacity;
+        let refill_rate = self.config.default_refill_rate;
 
-        let cleaned_count = initial_count - buckets.len();
-        Ok(cleaned_count)
+        self.check_rate_limit_shared_with_params(key, capacity, refill_rate).await
     }
 
-    /// Get statistics about the rate limiter
-    pub fn get_stats(&self) -> Result<HashMap<String, u64>, ThrottlerError> {
-        let mut stats = HashMap::new();
+    /// Check rate limit against shared Redis state with specific parameters.
+    ///
+    /// Falls back to the local bucket when Redis is not configured, returns
+    /// an error, or does not answer within `redis_op_timeout_ms`.
+    pub async fn check_rate_limit_shared_with_params(
+        &self,
+        key: &str,
+        capacity: u64,
+        refill_rate: f64,
+    ) -> Result<(bool, u64), ThrottlerError> {
+        let (allowed, remaining) = self
+            .check_rate_limit_shared_exact_with_params(key, capacity, refill_rate)
+            .await?;
+        Ok((allowed, remaining.floor() as u64))
+    }
 
-        let buckets = self.local_buckets.read()
-            .map_err(|_| ThrottlerError::InternalError("Failed to acquire read lock on buckets".to_string()))?;
+    /// Like [`Self::check_rate_limit_shared`], but reports the remaining
+    /// count unfloored (e.g. `4.5`) for clients that want fractional precision.
+    pub async fn check_rate_limit_shared_exact(&self, key: &str) -> Result<(bool, f64), ThrottlerError> {
+        let capacity = self.config.default_capacity;
+        let refill_rate = self.config.default_refill_rate;
 
-        stats.insert("local_buckets".to_string(), buckets.len() as u64);
-        stats.insert("redis_enabled".to_string(), if self.redis_client.is_some() { 1 } else { 0 });
+        self.check_rate_limit_shared_exact_with_params(key, capacity, refill_rate).await
+    }
 
-        Ok(stats)
+    /// Like [`Self::check_rate_limit_shared_with_params`], but reports the
+    /// remaining count unfloored.
+    pub async fn check_rate_limit_shared_exact_with_params(
+        &self,
+        key: &str,
+        capacity: u64,
+        refill_rate: f64,
+    ) -> Result<(bool, f64), ThrottlerError> {
+        self.consume_tokens_shared(key, capacity, refill_rate, 1, None).await
     }
 
-    /// Check if Redis is available
-    pub fn is_redis_available(&self) -> bool {
-        if let Some(redis_client) = &self.redis_client {
-            redis_client.ping().is_ok()
+    /// Consumes `cost` tokens at once against shared state, all or nothing,
+    /// reporting the remaining count unfloored. A cost of 0 consumes nothing
+    /// and is always allowed.
+    ///
+    /// `window_ms` is the window of the rule in use, if any; as in
+    /// [`Self::check_rate_limit_with_rule`] it becomes the bucket's Redis
+    /// TTL and its idle limit in the sweeper.
+    pub async fn consume_tokens_shared(
+        &self,
+        key: &str,
+        capacity: u64,
+        refill_rate: f64,
+        cost: u64,
+        window_ms: Option<u64>,
+    ) -> Result<(bool, f64), ThrottlerError> {
+        let result = if let Some(algorithm) = &self.algorithm {
+            self.consume_with_algorithm(algorithm.as_ref(), key, cost)?
+        } else if self.config.fair_queueing {
+            self.consume_in_order(key, capacity, refill_rate, cost, window_ms).await?
         } else {
-            false
+            self.consume_shared(key, capacity, refill_rate, cost, window_ms).await?
+        };
+
+        if self.config.retry_budget {
+            self.record_outcome(key, result.0)?;
+        }
+
+        Ok(result)
+    }
+
+    /// Moves `tokens` from one key's bucket to another's in one atomic
+    /// step, for billing models where a client borrows capacity from a
+    /// pooled allowance.
+    ///
+    /// Both buckets are refilled first; missing ones start full from the
+    /// default configuration. Returns `(false, 0.0)`, changing nothing, when
+    /// the source lacks the tokens. Otherwise returns `true` and the tokens
+    /// moved, which stop short of overflowing the destination's capacity;
+    /// the source is only charged for what was moved.
+    ///
+    /// Runs against shared state when configured (both keys must then be on
+    /// the same Redis node), falling back to local buckets like a consume.
+    pub async fn transfer(&self, from_key: &str, to_key: &str, tokens: u64) -> Result<(bool, f64), ThrottlerError> {
+        if from_key == to_key {
+            return Err(ThrottlerError::ValidationError(
+                "Cannot transfer tokens from a key to itself".to_string()
+            ));
         }
+        let capacity = self.config.default_capacity;
+        let refill_rate = self.config.default_refill_rate;
+
+        if let Some(store) = &self.store {
+            let store = Arc::clone(store);
+            let write_batcher = Arc::clone(&self.write_batcher);
+            let from = self.redis_key(from_key);
+            let to = self.redis_key(to_key);
+
+            // Consumes not yet written back are still owed by the source
+            let result = self.run_redis_op(move || {
+                let reserved = write_batcher.pending(&from)?;
+                store.transfer_tokens(&from, &to, tokens as f64, reserved, capacity, refill_rate)
+            }).await;
+
+            match result {
+                Ok(moved) => return Ok((moved.is_some(), moved.unwrap_or(0.0))),
+                Err(e) => self.fall_back_to_local(from_key, e)?,
+            }
+        }
+
+        self.transfer_local(from_key, to_key, tokens as f64, capacity, refill_rate)
+    }
+
+    /// Returns `tokens` to a key's bucket, never beyond its capacity,
+    /// returning the tokens it then holds. Missing buckets start full, so
+    /// a refund to one changes nothing.
+    ///
+    /// Runs against shared state when configured, falling back to local
+    /// buckets like a consume. `window_ms` is as for
+    /// [`Self::consume_tokens_shared`].
+    pub async fn refund_tokens(
+        &self,
+        key: &str,
+        capacity: u64,
+        refill_rate: f64,
+        tokens: u64,
+        window_ms: Option<u64>,
+    ) -> Result<f64, ThrottlerError> {
+        if self.algorithm.is_some() {
+            return Err(ThrottlerError::ValidationError(
+                "Refunds are not supported by the configured rate limit algorithm".to_string()
+            ));
+        }
+
+        if let Some(store) = &self.store {
+            let store = Arc::clone(store);
+            let write_batcher = Arc::clone(&self.write_batcher);
+            let redis_key = self.redis_key(key);
+            let race_retries = self.config.redis_race_retries;
+            let max_idle_secs = self.config.max_refill_elapsed_secs;
+            let sizing = StoredSizing::new(capacity, refill_rate, window_ms);
+
+            let result = self.run_redis_op(move || {
+                retry_on_race(race_retries, || {
+                    refund_to_redis(store.as_ref(), &write_batcher, &redis_key, sizing, max_idle_secs, tokens as f64)
+                })
+            }).await;
+
+            match result {
+                Ok(held) => return Ok(held),
+                Err(e) => self.fall_back_to_local(key, e)?,
+            }
+        }
+
+        let current_time = now_ms();
+        let mut buckets = self.local_buckets.write()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire write lock on buckets".to_string()))?;
+
+        let Some(bucket) = buckets.get_mut(key) else {
+            return Ok(capacity as f64);
+        };
+        let elapsed_secs = current_time.saturating_sub(bucket.last_refill) as f64 / 1000.0;
+        bucket.tokens = (bucket.tokens + bucket.refill_rate * elapsed_secs).min(bucket.capacity as f64);
+        bucket.last_refill = current_time;
+        bucket.apply_params(capacity, refill_rate);
+        bucket.window_ms = window_ms;
+        bucket.tokens = (bucket.tokens + tokens as f64).min(bucket.capacity as f64);
+        bucket.dirty = true;
+        Ok(bucket.tokens)
+    }
+
+    fn transfer_local(
+        &self,
+        from_key: &str,
+        to_key: &str,
+        tokens: f64,
+        capacity: u64,
+        refill_rate: f64,
+    ) -> Result<(bool, f64), ThrottlerError> {
+        let current_time = now_ms();
+
+        let mut buckets = self.local_buckets.write()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire write lock on buckets".to_string()))?;
+
+        // Refilled copies, so a short source leaves both untouched
+        let refilled = |key: &str| {
+            let mut bucket = buckets.get(key).cloned().unwrap_or(LocalBucket {
+                tokens: capacity as f64,
+                capacity,
+                refill_rate,
+                last_refill: current_time,
+                dirty: true,
+                window_ms: None,
+            });
+            let elapsed_secs = current_time.saturating_sub(bucket.last_refill) as f64 / 1000.0;
+            bucket.tokens = (bucket.tokens + bucket.refill_rate * elapsed_secs).min(bucket.capacity as f64);
+            bucket.last_refill = current_time;
+            bucket
+        };
+
+        let mut source = refilled(from_key);
+        if source.tokens < tokens {
+            return Ok((false, 0.0));
+        }
+        let mut destination = refilled(to_key);
+
+        let moved = tokens.min(destination.capacity as f64 - destination.tokens).max(0.0);
+        source.tokens -= moved;
+        destination.tokens += moved;
+        source.dirty = true;
+        destination.dirty = true;
+
+        buckets.insert(from_key.to_string(), source);
+        buckets.insert(to_key.to_string(), destination);
+        Ok((true, moved))
+    }
+
+    async fn consume_shared(
+        &self,
+        key: &str,
+        capacity: u64,
+        refill_rate: f64,
+        cost: u64,
+        window_ms: Option<u64>,
+    ) -> Result<(bool, f64), ThrottlerError> {
+        if let Some(store) = &self.store {
+            let store = Arc::clone(store);
+            let write_batcher = Arc::clone(&self.write_batcher);
+            let redis_key = self.redis_key(key);
+            let race_retries = self.config.redis_race_retries;
+            let max_idle_secs = self.config.max_refill_elapsed_secs;
+            let sizing = StoredSizing::new(capacity, refill_rate, window_ms);
+
+            let result = if self.config.hybrid_local_burst > 0 {
+                self.consume_leased(store, redis_key, sizing, cost).await
+            } else {
+                self.run_redis_op(move || {
+                    retry_on_race(race_retries, || {
+                        consume_from_redis(store.as_ref(), &write_batcher, &redis_key, sizing, max_idle_secs, cost)
+                    })
+                }).await
+            };
+
+            match result {
+                Ok((true, tokens)) => return Ok((true, self.reported_remaining(tokens, cost as f64))),
+                Ok((false, _)) => return Ok((false, 0.0)),
+                Err(e) => self.fall_back_to_local(key, e)?,
+            }
+        }
+
+        self.consume_local(key, capacity, refill_rate, cost, window_ms)
+    }
+
+    /// Consumes `cost` tokens from this instance's lease on the key's Redis
+    /// bucket, syncing with Redis only when the lease is short or due.
+    ///
+    /// A sync tops the lease up to `Config::hybrid_local_burst` tokens (or
+    /// `cost`, if larger) with as many as the Redis bucket can spare.
+    async fn consume_leased(
+        &self,
+        store: Arc<dyn BucketStore>,
+        redis_key: String,
+        sizing: StoredSizing,
+        cost: u64,
+    ) -> Result<(bool, f64), ThrottlerError> {
+        let cost = cost as f64;
+        let now = now_ms();
+        if let Some(tokens) = self.leases.consume(&redis_key, now, cost)? {
+            return Ok((true, tokens));
+        }
+
+        let leases = Arc::clone(&self.leases);
+        let write_batcher = Arc::clone(&self.write_batcher);
+        let race_retries = self.config.redis_race_retries;
+        let max_idle_secs = self.config.max_refill_elapsed_secs;
+        let lease_size = (self.config.hybrid_local_burst as f64).max(cost);
+        let sync_due_ms = now + self.config.hybrid_sync_interval_ms;
+
+        self.run_redis_op(move || {
+            let wanted = (lease_size - leases.held(&redis_key)?).max(0.0);
+            let (drawn, redis_remaining) = retry_on_race(race_retries, || {
+                lease_from_redis(store.as_ref(), &write_batcher, &redis_key, sizing, max_idle_secs, wanted)
+            })?;
+            leases.synced(&redis_key, drawn, redis_remaining, sync_due_ms, sizing, cost)
+        }).await
+    }
+
+    /// Takes a concurrency slot for `key` unless `limit` are already held
+    /// (`None` = unbounded), returning `None` when the key is at its limit.
+    ///
+    /// The slot lapses after `Config::concurrency_slot_ttl_ms` unless
+    /// released first. Uses shared state when configured, falling back to
+    /// local slots like a consume.
+    pub async fn acquire_concurrency_slot(
+        &self,
+        key: &str,
+        limit: Option<u32>,
+    ) -> Result<Option<ConcurrencySlot>, ThrottlerError> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let limit = limit.map_or(u64::MAX, u64::from);
+        let ttl_ms = self.config.concurrency_slot_ttl_ms;
+
+        if let Some(store) = &self.store {
+            let store = Arc::clone(store);
+            let store_key = self.concurrency_key(key);
+            let slot_id = id.clone();
+
+            let result = self.run_redis_op(move || store.acquire_slot(&store_key, &slot_id, limit, ttl_ms)).await;
+            match result {
+                Ok(held) => return Ok(held.map(|held| ConcurrencySlot { id, held })),
+                Err(e) => self.fall_back_to_local(key, e)?,
+            }
+        }
+
+        Ok(self.local_slots.acquire(key, &id, limit, ttl_ms, now_ms())?
+            .map(|held| ConcurrencySlot { id, held }))
+    }
+
+    /// Releases a concurrency slot, returning whether it was still held
+    /// (`false` once it has lapsed or was already released).
+    ///
+    /// Slots taken locally while Redis was unavailable are found there too.
+    pub async fn release_concurrency_slot(&self, key: &str, slot_id: &str) -> Result<bool, ThrottlerError> {
+        if let Some(store) = &self.store {
+            let store = Arc::clone(store);
+            let store_key = self.concurrency_key(key);
+            let id = slot_id.to_string();
+
+            match self.run_redis_op(move || store.release_slot(&store_key, &id)).await {
+                Ok(true) => return Ok(true),
+                Ok(false) => {}
+                Err(e) => self.fall_back_to_local(key, e)?,
+            }
+        }
+
+        self.local_slots.release(key, slot_id, now_ms())
+    }
+
+    /// Charges `cost` tokens against `key`'s quota of `limit` for the
+    /// current `period`, returning whether they fit and the quota left.
+    /// Nothing is charged when they do not.
+    ///
+    /// Uses shared state when configured, falling back to local usage like
+    /// a consume.
+    pub async fn consume_quota(
+        &self,
+        key: &str,
+        cost: u64,
+        limit: u64,
+        period: QuotaPeriod,
+    ) -> Result<(bool, QuotaState), ThrottlerError> {
+        let reset_at_ms = period.reset_at(now_ms());
+        let state = |used: u64| QuotaState { remaining: limit.saturating_sub(used), reset_at_ms };
+
+        if let Some(store) = &self.store {
+            let store = Arc::clone(store);
+            let store_key = self.quota_key(key, reset_at_ms);
+
+            let result = self.run_redis_op(move || store.consume_quota(&store_key, cost, limit, reset_at_ms)).await;
+            match result {
+                Ok((consumed, used)) => return Ok((consumed, state(used))),
+                Err(e) => self.fall_back_to_local(key, e)?,
+            }
+        }
+
+        let (consumed, used) = self.local_quotas.consume(key, cost, limit, reset_at_ms)?;
+        Ok((consumed, state(used)))
+    }
+
+    /// What is left of `key`'s quota of `limit` for the current `period`,
+    /// read without charging it, for dry runs.
+    pub async fn peek_quota(&self, key: &str, limit: u64, period: QuotaPeriod) -> Result<QuotaState, ThrottlerError> {
+        let reset_at_ms = period.reset_at(now_ms());
+        let state = |used: u64| QuotaState { remaining: limit.saturating_sub(used), reset_at_ms };
+
+        if let Some(store) = &self.store {
+            let store = Arc::clone(store);
+            let store_key = self.quota_key(key, reset_at_ms);
+
+            match self.run_redis_op(move || store.quota_used(&store_key, reset_at_ms)).await {
+                Ok(used) => return Ok(state(used)),
+                Err(e) => self.fall_back_to_local(key, e)?,
+            }
+        }
+
+        Ok(state(self.local_quotas.used(key, reset_at_ms)?))
+    }
+
+    /// Handles a failed shared-state operation: in lenient mode it is logged
+    /// and the caller continues with the local bucket; in strict mode it
+    /// becomes [`ThrottlerError::StoreUnavailable`]. A passed request
+    /// deadline is returned as is in either mode.
+    fn fall_back_to_local(&self, key: &str, error: ThrottlerError) -> Result<(), ThrottlerError> {
+        if let ThrottlerError::DeadlineExceeded(_) = error {
+            return Err(error);
+        }
+        if self.config.consistency_mode == ConsistencyMode::Strict {
+            return Err(ThrottlerError::StoreUnavailable(error.to_string()));
+        }
+
+        tracing::warn!(
+            key = %key,
+            error = %error,
+            "Redis unavailable, falling back to local rate limiting"
+        );
+        self.degraded.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Whether Redis is configured but the last operation on it failed, so
+    /// limits are currently enforced by this instance alone
+    pub fn is_degraded(&self) -> bool {
+        self.degraded.load(Ordering::Relaxed)
+    }
+
+    /// Waits in the key's queue, then for `cost` tokens, so concurrent
+    /// requests are granted in arrival order. Denies immediately when the
+    /// queue is full, the bucket never refills, or it can never hold `cost`,
+    /// and once the wait would run past `Config::fair_queue_max_wait_ms`.
+    async fn consume_in_order(
+        &self,
+        key: &str,
+        capacity: u64,
+        refill_rate: f64,
+        cost: u64,
+        window_ms: Option<u64>,
+    ) -> Result<(bool, f64), ThrottlerError> {
+        if cost > capacity {
+            return Ok((false, 0.0));
+        }
+
+        let started = Instant::now();
+        let max_wait = Duration::from_millis(self.config.fair_queue_max_wait_ms);
+        let Some(slot) = self.fair_queues.enter(key)? else {
+            tracing::debug!(key = %key, "Fair queue full, shedding request");
+            return Ok((false, 0.0));
+        };
+        let Ok(_turn) = tokio::time::timeout(max_wait, slot.turn.lock()).await else {
+            tracing::debug!(key = %key, "Fair queue wait too long, shedding request");
+            return Ok((false, 0.0));
+        };
+
+        loop {
+            let result = self.consume_shared(key, capacity, refill_rate, cost, window_ms).await?;
+            if result.0 || refill_rate <= 0.0 {
+                return Ok(result);
+            }
+            let next_token = Duration::from_secs_f64(1.0 / refill_rate);
+            if started.elapsed() + next_token > max_wait {
+                tracing::debug!(key = %key, "Fair queue wait too long, shedding request");
+                return Ok(result);
+            }
+            tokio::time::sleep(next_token).await;
+        }
+    }
+
+    /// Whether a request would be allowed against shared state, without
+    /// consuming a token, using default configuration.
+    pub async fn peek_rate_limit_shared(&self, key: &str) -> Result<(bool, u64), ThrottlerError> {
+        let capacity = self.config.default_capacity;
+        let refill_rate = self.config.default_refill_rate;
+
+        self.peek_rate_limit_shared_with_params(key, capacity, refill_rate).await
+    }
+
+    /// Whether a request would be allowed against shared state, without
+    /// consuming a token. Returns the would-be result and the tokens that
+    /// would remain, following `Config::remaining_semantics`.
+    ///
+    /// Falls back to the local bucket like [`Self::check_rate_limit_shared_with_params`].
+    pub async fn peek_rate_limit_shared_with_params(
+        &self,
+        key: &str,
+        capacity: u64,
+        refill_rate: f64,
+    ) -> Result<(bool, u64), ThrottlerError> {
+        let tokens = self.shared_tokens(key, capacity, refill_rate).await?;
+        Ok(self.peek_result(tokens))
+    }
+
+    /// Whether `cost` tokens could be consumed against shared state, without
+    /// consuming them. Reports like [`Self::consume_tokens_shared`]: the
+    /// would-be result and the tokens left afterwards, or those held when
+    /// it would be denied.
+    pub async fn peek_tokens_shared(
+        &self,
+        key: &str,
+        capacity: u64,
+        refill_rate: f64,
+        cost: u64,
+    ) -> Result<(bool, f64), ThrottlerError> {
+        let tokens = self.shared_tokens(key, capacity, refill_rate).await?.min(capacity as f64);
+        let cost = cost as f64;
+        if tokens >= cost {
+            Ok((true, self.reported_remaining(tokens - cost, cost)))
+        } else {
+            Ok((false, tokens))
+        }
+    }
+
+    /// Tokens a key's bucket holds now, refilled but unmodified, from shared
+    /// state or the local bucket. A key without a bucket reports a full
+    /// `capacity`, just as a consume would create it; none is created.
+    ///
+    /// Shared state is read from the replica when one is configured, falling
+    /// back to the primary if the replica fails.
+    async fn shared_tokens(
+        &self,
+        key: &str,
+        capacity: u64,
+        refill_rate: f64,
+    ) -> Result<f64, ThrottlerError> {
+        Ok(self.shared_snapshot(key, capacity, refill_rate).await?.tokens)
+    }
+
+    /// A key's bucket as [`Self::shared_tokens`] reads it when sized with
+    /// `capacity` and `refill_rate`, along with the refill that read
+    /// applied, for `?debug=true` checks.
+    ///
+    /// Buckets of other requests may change between a snapshot and the
+    /// check it describes, so the figures explain a decision rather than
+    /// reproduce it exactly.
+    pub async fn bucket_snapshot(
+        &self,
+        key: &str,
+        capacity: u64,
+        refill_rate: f64,
+    ) -> Result<BucketSnapshot, ThrottlerError> {
+        let mut snapshot = self.shared_snapshot(key, capacity, refill_rate).await?;
+        snapshot.tokens = snapshot.tokens.min(capacity as f64);
+        Ok(snapshot)
+    }
+
+    async fn shared_snapshot(
+        &self,
+        key: &str,
+        capacity: u64,
+        refill_rate: f64,
+    ) -> Result<BucketSnapshot, ThrottlerError> {
+        if let Some(store) = &self.store {
+            let store = Arc::clone(store);
+            let replica = self.replica.clone();
+            let write_batcher = Arc::clone(&self.write_batcher);
+            let redis_key = self.redis_key(key);
+            let max_idle_secs = self.config.max_refill_elapsed_secs;
+
+            let result = self.run_redis_op(move || {
+                let stored = match replica {
+                    Some(replica) => replica.get_token_bucket(&redis_key).or_else(|e| {
+                        tracing::warn!(key = %redis_key, error = %e, "Redis replica unavailable, reading from primary");
+                        store.get_token_bucket(&redis_key)
+                    }),
+                    None => store.get_token_bucket(&redis_key),
+                };
+                let mut bucket = stored?
+                    .unwrap_or_else(|| TokenBucket::new(capacity, refill_rate));
+                let stored_tokens = bucket.tokens;
+                let elapsed_ms = now_ms().saturating_sub(bucket.last_refill);
+                bucket.refill_within(max_idle_secs)?;
+                Ok(BucketSnapshot {
+                    tokens: (bucket.tokens - write_batcher.pending(&redis_key)?).max(0.0),
+                    refilled: bucket.tokens - stored_tokens,
+                    elapsed_ms,
+                })
+            }).await;
+
+            match result {
+                Ok(snapshot) => return Ok(snapshot),
+                Err(e) => self.fall_back_to_local(key, e)?,
+            }
+        }
+
+        self.local_snapshot(key, capacity)
+    }
+
+    /// Tokens a local bucket would hold now, refilled but unmodified
+    fn local_tokens(&self, key: &str, capacity: u64) -> Result<f64, ThrottlerError> {
+        Ok(self.local_snapshot(key, capacity)?.tokens)
+    }
+
+    fn local_snapshot(&self, key: &str, capacity: u64) -> Result<BucketSnapshot, ThrottlerError> {
+        let buckets = self.local_buckets.read()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire read lock on buckets".to_string()))?;
+
+        Ok(match buckets.get(key) {
+            Some(bucket) => {
+                let elapsed_ms = now_ms().saturating_sub(bucket.last_refill);
+                let tokens = (bucket.tokens + bucket.refill_rate * elapsed_ms as f64 / 1000.0)
+                    .min(bucket.capacity as f64);
+                BucketSnapshot { tokens, refilled: tokens - bucket.tokens, elapsed_ms }
+            }
+            None => BucketSnapshot { tokens: capacity as f64, refilled: 0.0, elapsed_ms: 0 },
+        })
+    }
+
+    /// Would-be outcome of consuming one token from a bucket holding `tokens`
+    fn peek_result(&self, tokens: f64) -> (bool, u64) {
+        if tokens >= 1.0 {
+            (true, self.reported_remaining(tokens - 1.0, 1.0).floor() as u64)
+        } else {
+            (false, 0)
+        }
+    }
+
+    /// Seconds a denied client should wait until a bucket holding
+    /// `tokens_held` at `refill_rate` has the `tokens_needed` it asked for,
+    /// for `Retry-After`.
+    ///
+    /// [`Self::retry_after_ms`] rounded up to whole seconds: at least 1, and
+    /// clamped to `Config::max_retry_after_secs` so buckets that refill
+    /// slowly or never do not produce absurd values.
+    pub fn retry_after_secs(&self, refill_rate: f64, tokens_held: f64, tokens_needed: u64) -> Result<u64, ThrottlerError> {
+        Ok(self.retry_after_ms(refill_rate, tokens_held, tokens_needed)?.div_ceil(1000))
+    }
+
+    /// Milliseconds until a bucket holding `tokens_held` at `refill_rate`
+    /// has `tokens_needed`, for `X-RateLimit-Retry-After-Ms`.
+    ///
+    /// At least 1, and clamped to `Config::max_retry_after_secs`.
+    pub fn retry_after_ms(&self, refill_rate: f64, tokens_held: f64, tokens_needed: u64) -> Result<u64, ThrottlerError> {
+        let max_wait = Duration::from_secs(self.config.max_retry_after_secs.max(1));
+        let tokens_needed = tokens_needed.max(1);
+        let mut bucket = TokenBucket::new(tokens_needed, refill_rate);
+        bucket.tokens = tokens_held.clamp(0.0, tokens_needed as f64);
+
+        let wait = bucket.time_until_tokens_capped(tokens_needed, max_wait)?;
+        Ok(((wait.as_secs_f64() * 1000.0).ceil() as u64).clamp(1, max_wait.as_millis() as u64))
+    }
+
+    /// Track consecutive denials per key, the "debt" behind the retry budget
+    fn record_outcome(&self, key: &str, allowed: bool) -> Result<(), ThrottlerError> {
+        let mut streaks = self.denial_streaks.write()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire write lock on denial streaks".to_string()))?;
+
+        if allowed {
+            streaks.remove(key);
+        } else {
+            let streak = streaks.entry(key.to_string()).or_default();
+            streak.denials += 1;
+            streak.last_denied_ms = now_ms();
+        }
+        Ok(())
+    }
+
+    /// How many more retries a denied client should attempt.
+    ///
+    /// The tokens refilled over [`RETRY_BUDGET_HORIZON_SECS`], or over the
+    /// time to the next token when that is longer, minus the denials the key
+    /// has already accumulated since it was last allowed. A bucket refilling
+    /// slower than one token per horizon so still allows one retry, timed by
+    /// `Retry-After`. Zero means the client should stop retrying and back
+    /// off entirely.
+    pub fn retry_budget(&self, key: &str, refill_rate: f64) -> Result<u64, ThrottlerError> {
+        let streaks = self.denial_streaks.read()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire read lock on denial streaks".to_string()))?;
+
+        let debt = streaks.get(key).map_or(0, |streak| streak.denials);
+        let refillable = if refill_rate > 0.0 {
+            ((refill_rate * RETRY_BUDGET_HORIZON_SECS).floor() as u64).max(1)
+        } else {
+            0
+        };
+        Ok(refillable.saturating_sub(debt))
+    }
+
+    /// Forgets the denial streaks of `keys`, e.g. once they are reset
+    fn forget_streaks<'a>(&self, keys: impl IntoIterator<Item = &'a String>) -> Result<(), ThrottlerError> {
+        let mut streaks = self.denial_streaks.write()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire write lock on denial streaks".to_string()))?;
+        for key in keys {
+            streaks.remove(key);
+        }
+        Ok(())
+    }
+
+    /// Runs a blocking Redis operation off the async runtime, bounded by the
+    /// configured operation timeout and by the request's deadline, if any.
+    ///
+    /// Running out of the deadline is a `DeadlineExceeded`, which callers
+    /// pass on rather than falling back to local state: nobody is waiting
+    /// for the answer any more.
+    async fn run_redis_op<T, F>(&self, op: F) -> Result<T, ThrottlerError>
+    where
+        F: FnOnce() -> Result<T, ThrottlerError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let timeout_ms = self.config.redis_op_timeout_ms;
+        let op_timeout = (timeout_ms > 0).then(|| Duration::from_millis(timeout_ms));
+        let deadline_left = REQUEST_DEADLINE
+            .try_with(|deadline| deadline.saturating_duration_since(Instant::now()))
+            .ok();
+        if deadline_left == Some(Duration::ZERO) {
+            return Err(ThrottlerError::DeadlineExceeded(
+                "Request deadline passed before the Redis operation".to_string()
+            ));
+        }
+
+        // The permit moves into the task, so an operation that outlives its
+        // timeout still counts against the limit until Redis answers
+        let permit = self.acquire_redis_permit().await?;
+        let task = tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            op()
+        });
+        let joined = match (op_timeout, deadline_left) {
+            (_, Some(left)) if op_timeout.is_none_or(|op_timeout| left < op_timeout) => {
+                tokio::time::timeout(left, task)
+                    .await
+                    .map_err(|_| ThrottlerError::DeadlineExceeded(
+                        "Request deadline passed during a Redis operation".to_string()
+                    ))?
+            }
+            (Some(op_timeout), _) => {
+                tokio::time::timeout(op_timeout, task)
+                    .await
+                    .map_err(|_| ThrottlerError::RedisError(
+                        format!("Redis operation timed out after {}ms", timeout_ms)
+                    ))?
+            }
+            (None, _) => task.await,
+        };
+
+        let result = joined.map_err(|e| ThrottlerError::InternalError(format!("Redis task failed: {}", e)))?;
+        if result.is_ok() && self.degraded.swap(false, Ordering::Relaxed) {
+            tracing::info!("Redis reachable again, leaving local-only mode");
+        }
+        result
+    }
+
+    /// Waits up to `Config::redis_queue_timeout_ms` for one of the
+    /// `Config::max_redis_concurrency` Redis operation slots (no wait when
+    /// unbounded). A full house is a `RedisError`, so the request falls back
+    /// to local state or fails like any other Redis failure.
+    async fn acquire_redis_permit(&self) -> Result<Option<OwnedSemaphorePermit>, ThrottlerError> {
+        let Some(permits) = &self.redis_permits else {
+            return Ok(None);
+        };
+
+        let saturated = || ThrottlerError::RedisError(format!(
+            "Redis concurrency limit of {} operations reached",
+            self.config.max_redis_concurrency
+        ));
+        let wait = Duration::from_millis(self.config.redis_queue_timeout_ms);
+        let permit = if wait.is_zero() {
+            Arc::clone(permits).try_acquire_owned().map_err(|_| saturated())?
+        } else {
+            tokio::time::timeout(wait, Arc::clone(permits).acquire_owned())
+                .await
+                .map_err(|_| saturated())?
+                .map_err(|_| saturated())?
+        };
+        Ok(Some(permit))
+    }
+
+    /// Get remaining tokens for a key from its local bucket, refilled up to now.
+    ///
+    /// Reads never create buckets: a key that has not been seen reports the
+    /// full default capacity, which is what its first consume starts from.
+    pub fn get_remaining_tokens(&self, key: &str) -> Result<u64, ThrottlerError> {
+        Ok(self.local_tokens(key, self.config.default_capacity)?.floor() as u64)
+    }
+
+    /// Get remaining tokens for a key from shared state, like
+    /// [`Self::check_rate_limit_shared`] sees it, without consuming or
+    /// creating a bucket.
+    pub async fn get_remaining_tokens_shared(&self, key: &str) -> Result<u64, ThrottlerError> {
+        let capacity = self.config.default_capacity;
+        let refill_rate = self.config.default_refill_rate;
+
+        self.get_remaining_tokens_shared_with_params(key, capacity, refill_rate).await
+    }
+
+    /// Like [`Self::get_remaining_tokens_shared`], for a bucket of `capacity`
+    /// refilling at `refill_rate`, e.g. as a key's rule sizes it.
+    pub async fn get_remaining_tokens_shared_with_params(
+        &self,
+        key: &str,
+        capacity: u64,
+        refill_rate: f64,
+    ) -> Result<u64, ThrottlerError> {
+        let tokens = self.shared_tokens(key, capacity, refill_rate).await?;
+        Ok(tokens.min(capacity as f64).floor() as u64)
+    }
+
+    /// Reset rate limit for a specific key
+    pub fn reset(&self, key: &str) -> Result<(), ThrottlerError> {
+        if let Some(algorithm) = &self.algorithm {
+            algorithm.reset(key)?;
+        }
+        if let Some(store) = &self.store {
+            let redis_key = self.redis_key(key);
+            store.delete_token_bucket(&redis_key)?;
+            self.write_batcher.forget(&redis_key)?;
+            self.leases.forget(&redis_key)?;
+        }
+
+        self.forget_streaks([&key.to_string()])?;
+
+        let mut buckets = self.local_buckets.write()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire write lock on buckets".to_string()))?;
+        buckets.remove(key);
+
+        Ok(())
+    }
+
+    /// Reset rate limits for several keys, returning whether each was reset.
+    ///
+    /// In Redis mode all buckets are removed with a single `DEL`; if that
+    /// fails nothing is reset locally either and the error is returned.
+    pub fn reset_many(&self, keys: &[String]) -> Result<HashMap<String, bool>, ThrottlerError> {
+        if let Some(store) = &self.store {
+            let redis_keys: Vec<String> = keys.iter()
+                .map(|key| self.redis_key(key))
+                .collect();
+            store.delete_token_buckets(&redis_keys)?;
+            for redis_key in &redis_keys {
+                self.write_batcher.forget(redis_key)?;
+                self.leases.forget(redis_key)?;
+            }
+        }
+        self.forget_streaks(keys)?;
+
+        let mut buckets = self.local_buckets.write()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire write lock on buckets".to_string()))?;
+
+        Ok(keys.iter()
+            .map(|key| {
+                buckets.remove(key);
+                (key.clone(), true)
+            })
+            .collect())
+    }
+
+    /// Snapshot every local bucket, with tokens refilled up to now.
+    pub fn export_state(&self) -> Result<SerializableState, ThrottlerError> {
+        let exported_at = now_ms();
+
+        let buckets = self.local_buckets.read()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire read lock on buckets".to_string()))?;
+
+        let buckets = buckets.iter()
+            .map(|(key, bucket)| {
+                let elapsed_secs = exported_at.saturating_sub(bucket.last_refill) as f64 / 1000.0;
+                let tokens = (bucket.tokens + bucket.refill_rate * elapsed_secs)
+                    .min(bucket.capacity as f64);
+                (key.clone(), BucketState {
+                    tokens,
+                    capacity: bucket.capacity,
+                    refill_rate: bucket.refill_rate,
+                    last_refill: exported_at.max(bucket.last_refill),
+                })
+            })
+            .collect();
+
+        Ok(SerializableState { exported_at, buckets })
+    }
+
+    /// Load buckets from a snapshot, replacing local state for those keys.
+    ///
+    /// Refill restarts from the import time rather than the export time, so
+    /// tokens are not granted for the transfer gap. Returns how many buckets
+    /// were imported.
+    pub fn import_state(&self, state: SerializableState) -> Result<usize, ThrottlerError> {
+        let imported_at = now_ms();
+
+        let mut buckets = self.local_buckets.write()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire write lock on buckets".to_string()))?;
+
+        let count = state.buckets.len();
+        for (key, bucket) in state.buckets {
+            if !bucket.tokens.is_finite() || !bucket.refill_rate.is_finite() {
+                return Err(ThrottlerError::ValidationError(
+                    format!("Invalid bucket state for key: {}", key)
+                ));
+            }
+            buckets.insert(key, LocalBucket {
+                tokens: bucket.tokens.clamp(0.0, bucket.capacity as f64),
+                capacity: bucket.capacity,
+                refill_rate: bucket.refill_rate,
+                last_refill: imported_at,
+                dirty: true,
+                window_ms: None,
+            });
+        }
+
+        Ok(count)
+    }
+
+    /// Removes buckets idle for longer than their rule's window, or than
+    /// `max_age_ms` for buckets not created under a rule (but never before
+    /// they would have refilled), returning how many were removed.
+    ///
+    /// Expired local concurrency slots are dropped too, as are denial
+    /// streaks without a denial in `max_age_ms`, and hybrid leases past
+    /// their sync time are returned to Redis.
+    pub fn cleanup_expired_buckets(&self, max_age_ms: u64) -> Result<usize, ThrottlerError> {
+        let current_time = now_ms();
+
+        let mut buckets = self.local_buckets.write()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire write lock on buckets".to_string()))?;
+
+        let initial_count = buckets.len();
+
+        buckets.retain(|_, bucket| {
+            let max_age_ms = bucket.window_ms.unwrap_or_else(|| {
+                max_age_ms.max(bucket_ttl_secs(bucket.capacity, bucket.refill_rate, None) as u64 * 1000)
+            });
+            current_time.saturating_sub(bucket.last_refill) < max_age_ms
+        });
+
+        let cleaned_count = initial_count - buckets.len();
+        drop(buckets);
+
+        self.local_slots.sweep(current_time)?;
+        self.denial_streaks.write()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire write lock on denial streaks".to_string()))?
+            .retain(|_, streak| current_time.saturating_sub(streak.last_denied_ms) < max_age_ms);
+        self.expire_leases(current_time)?;
+        Ok(cleaned_count)
+    }
+
+    /// Drops hybrid leases past their sync time, returning their unspent
+    /// tokens to the Redis buckets they were drawn from, and returns how
+    /// many were dropped.
+    ///
+    /// A lease still in use would have been synced by now, so a due one
+    /// belongs to a key this instance stopped seeing. A return that fails
+    /// is logged and its tokens are lost, as if the instance had stopped.
+    fn expire_leases(&self, now_ms: u64) -> Result<usize, ThrottlerError> {
+        let due = self.leases.take_due(now_ms)?;
+        let Some(store) = &self.store else {
+            return Ok(due.len());
+        };
+
+        for (redis_key, tokens, sizing) in &due {
+            let Some(sizing) = sizing.filter(|_| *tokens > 0.0) else {
+                continue;
+            };
+            let result = retry_on_race(self.config.redis_race_retries, || {
+                refund_to_redis(
+                    store.as_ref(),
+                    &self.write_batcher,
+                    redis_key,
+                    sizing,
+                    self.config.max_refill_elapsed_secs,
+                    *tokens,
+                )
+            });
+            if let Err(e) = result {
+                tracing::warn!(key = %redis_key, error = %e, "Failed to return an expired lease to Redis");
+            }
+        }
+
+        Ok(due.len())
+    }
+
+    /// Deletes shared store buckets last refilled more than `max_age_ms`
+    /// ago, returning how many were removed. A no-op without Redis.
+    ///
+    /// Redis normally expires idle buckets through their TTL; this catches
+    /// buckets whose TTL is too long or missing. It scans every bucket, so
+    /// it is meant to run rarely. A bucket used again between the scan and
+    /// the delete starts over full, as it would after expiring.
+    pub fn sweep_stale_store_buckets(&self, max_age_ms: u64) -> Result<usize, ThrottlerError> {
+        let Some(store) = &self.store else {
+            return Ok(0);
+        };
+        let current_time = now_ms();
+
+        let mut swept = 0;
+        let mut cursor = None;
+        loop {
+            let page = store.scan_buckets(STORE_KEY_PREFIX, cursor.as_deref(), STORE_SWEEP_PAGE)?;
+            let stale: Vec<String> = page.buckets.into_iter()
+                .filter(|(_, bucket)| current_time.saturating_sub(bucket.last_refill) >= max_age_ms)
+                .map(|(key, _)| key)
+                .collect();
+            if !stale.is_empty() {
+                store.delete_token_buckets(&stale)?;
+                swept += stale.len();
+            }
+
+            cursor = page.cursor;
+            if cursor.is_none() {
+                return Ok(swept);
+            }
+        }
+    }
+
+    /// Writes Redis buckets with batched, not-yet-written consumption,
+    /// returning how many were persisted.
+    pub fn flush_pending_writes(&self) -> Result<usize, ThrottlerError> {
+        let Some(store) = &self.store else {
+            return Ok(0);
+        };
+
+        let mut flushed = 0;
+        for (redis_key, ttl_secs) in self.write_batcher.unwritten_keys()? {
+            let Some(mut bucket) = store.get_token_bucket(&redis_key)? else {
+                // Expired in Redis: nothing left to apply the consumption to
+                self.write_batcher.written(&redis_key, f64::MAX)?;
+                continue;
+            };
+
+            bucket.refill_within(self.config.max_refill_elapsed_secs)?;
+            let pending = self.write_batcher.pending(&redis_key)?;
+            bucket.tokens = (bucket.tokens - pending).max(0.0);
+
+            let ttl = ttl_secs.unwrap_or_else(|| bucket_ttl_secs(bucket.capacity, bucket.refill_rate, None));
+            if let Err(e) = store.set_token_bucket(&redis_key, &bucket, ttl) {
+                tracing::warn!(key = %redis_key, error = %e, "Failed to write pending consumption to Redis");
+                continue;
+            }
+            self.write_batcher.written(&redis_key, pending)?;
+            flushed += 1;
+        }
+
+        Ok(flushed)
+    }
+
+    /// Writes locally-modified buckets to Redis, returning how many were persisted.
+    ///
+    /// Local buckets accumulate state in local-only mode or while Redis is
+    /// unreachable; flushing on shutdown keeps that state from being lost.
+    /// Buckets that fail to write are logged and left dirty. Batched Redis
+    /// consumption is written as well.
+    pub fn flush_to_redis(&self) -> Result<usize, ThrottlerError> {
+        let Some(store) = &self.store else {
+            return Ok(0);
+        };
+
+        let mut flushed = self.flush_pending_writes()?;
+
+        let dirty: Vec<(String, TokenBucket, usize)> = {
+            let buckets = self.local_buckets.read()
+                .map_err(|_| ThrottlerError::InternalError("Failed to acquire read lock on buckets".to_string()))?;
+            buckets.iter()
+                .filter(|(_, bucket)| bucket.dirty)
+                .map(|(key, bucket)| (key.clone(), bucket.to_token_bucket(), bucket.ttl_secs()))
+                .collect()
+        };
+
+        for (key, bucket, ttl) in dirty {
+            let redis_key = self.redis_key(&key);
+            if let Err(e) = store.set_token_bucket(&redis_key, &bucket, ttl) {
+                tracing::warn!(key = %key, error = %e, "Failed to flush local bucket to Redis");
+                continue;
+            }
+
+            let mut buckets = self.local_buckets.write()
+                .map_err(|_| ThrottlerError::InternalError("Failed to acquire write lock on buckets".to_string()))?;
+            if let Some(local) = buckets.get_mut(&key) {
+                // Leave it dirty if it changed while we were writing
+                if local.last_refill == bucket.last_refill {
+                    local.dirty = false;
+                }
+            }
+            flushed += 1;
+        }
+
+        Ok(flushed)
+    }
+
+    /// Flushes locally-modified buckets to Redis, giving up after `timeout`.
+    pub async fn flush_to_redis_within(&self, timeout: Duration) -> Result<usize, ThrottlerError> {
+        let limiter = self.clone();
+        let task = tokio::task::spawn_blocking(move || limiter.flush_to_redis());
+
+        tokio::time::timeout(timeout, task)
+            .await
+            .map_err(|_| ThrottlerError::RedisError(
+                format!("Flushing buckets to Redis timed out after {}ms", timeout.as_millis())
+            ))?
+            .map_err(|e| ThrottlerError::InternalError(format!("Flush task failed: {}", e)))?
+    }
+
+    /// Get statistics about the rate limiter
+    pub fn get_stats(&self) -> Result<HashMap<String, u64>, ThrottlerError> {
+        let mut stats = HashMap::new();
+
+        let buckets = self.local_buckets.read()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire read lock on buckets".to_string()))?;
+
+        let key_bytes: usize = buckets.keys().map(String::len).sum();
+        let memory = buckets.len() * BUCKET_ENTRY_BYTES + key_bytes;
+
+        stats.insert("local_buckets".to_string(), buckets.len() as u64);
+        stats.insert("estimated_memory_bytes".to_string(), memory as u64);
+        stats.insert("redis_enabled".to_string(), if self.store.is_some() { 1 } else { 0 });
+        stats.insert("redis_writes".to_string(), self.write_batcher.writes.load(Ordering::Relaxed));
+        stats.insert("fair_queued".to_string(), self.fair_queues.queued()?);
+        stats.insert("corrupt_buckets".to_string(), self.corrupt_buckets());
+        stats.insert("write_races".to_string(), self.write_batcher.races.count.load(Ordering::Relaxed));
+
+        Ok(stats)
+    }
+
+    /// The most recent Redis writes that lost a race, oldest first; at most
+    /// `Config::race_log_size` of them
+    pub fn recent_races(&self) -> Result<Vec<WriteRace>, ThrottlerError> {
+        Ok(self.write_batcher.races.lock()?.iter().cloned().collect())
+    }
+
+    /// Buckets found corrupt in the shared store (or replica) and started
+    /// over instead of failing the request
+    pub fn corrupt_buckets(&self) -> u64 {
+        [&self.store, &self.replica].into_iter()
+            .flatten()
+            .map(|store| store.corrupt_buckets())
+            .sum()
+    }
+
+    /// Lists buckets with their current remaining tokens, a page of up to
+    /// `limit` (clamped to 1..=[`MAX_LIST_BUCKETS`]) at a time.
+    ///
+    /// Reads the shared store when there is one (`SCAN` in Redis), otherwise
+    /// local buckets in key order. Pass the previous page's `next_cursor` as
+    /// `cursor` to continue; cursors from one mode are not valid in the other.
+    pub async fn list_buckets(&self, limit: usize, cursor: Option<String>) -> Result<BucketListing, ThrottlerError> {
+        let limit = limit.clamp(1, MAX_LIST_BUCKETS);
+        let now = now_ms();
+
+        if let Some(store) = &self.store {
+            let store = store.clone();
+            let page = self.run_redis_op(move || {
+                store.scan_buckets(STORE_KEY_PREFIX, cursor.as_deref(), limit)
+            }).await?;
+
+            let keys = page.buckets.into_iter()
+                .map(|(key, bucket)| {
+                    let key = key.strip_prefix(STORE_KEY_PREFIX).map(str::to_string).unwrap_or(key);
+                    BucketSummary::new(key, bucket.tokens, bucket.capacity, bucket.refill_rate, bucket.last_refill, now)
+                })
+                .collect();
+            return Ok(BucketListing { keys, next_cursor: page.cursor });
+        }
+
+        let buckets = self.local_buckets.read()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire read lock on buckets".to_string()))?;
+
+        let mut keys: Vec<&String> = buckets.keys()
+            .filter(|key| cursor.as_deref().is_none_or(|after| key.as_str() > after))
+            .collect();
+        keys.sort();
+
+        let more = keys.len() > limit;
+        let keys: Vec<BucketSummary> = keys.into_iter()
+            .take(limit)
+            .map(|key| {
+                let bucket = &buckets[key];
+                BucketSummary::new(key.clone(), bucket.tokens, bucket.capacity, bucket.refill_rate, bucket.last_refill, now)
+            })
+            .collect();
+        let next_cursor = if more { keys.last().map(|summary| summary.key.clone()) } else { None };
+
+        Ok(BucketListing { keys, next_cursor })
+    }
+
+    /// The `n` local buckets with the largest estimated footprint, largest
+    /// first, as `(key, bytes)`.
+    ///
+    /// Every bucket has the same fixed cost, so size differences come from
+    /// key length; unusually long keys often point at unbounded input (e.g.
+    /// a raw URL) leaking into the key.
+    pub fn largest_keys(&self, n: usize) -> Result<Vec<(String, u64)>, ThrottlerError> {
+        let buckets = self.local_buckets.read()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire read lock on buckets".to_string()))?;
+
+        let mut sizes: Vec<(String, u64)> = buckets.keys()
+            .map(|key| (key.clone(), (BUCKET_ENTRY_BYTES + key.len()) as u64))
+            .collect();
+        sizes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        sizes.truncate(n);
+
+        Ok(sizes)
+    }
+
+    /// Whether buckets are kept in a shared store (Redis) rather than locally
+    pub fn has_shared_store(&self) -> bool {
+        self.store.is_some()
+    }
+
+    /// Check if Redis is available
+    pub fn is_redis_available(&self) -> bool {
+        if let Some(store) = &self.store {
+            store.ping().is_ok()
+        } else {
+            false
+        }
+    }
+}
+
+/// Runs `attempt` until it completes without losing a write race, retrying
+/// at most `retries` extra times. `Ok(None)` from `attempt` signals a race.
+fn retry_on_race<T>(
+    retries: u32,
+    mut attempt: impl FnMut() -> Result<Option<T>, ThrottlerError>,
+) -> Result<T, ThrottlerError> {
+    for _ in 0..retries {
+        if let Some(result) = attempt()? {
+            return Ok(result);
+        }
+    }
+    attempt()?.ok_or_else(|| ThrottlerError::RedisError(format!(
+        "Token bucket update was rejected due to race condition after {} retries",
+        retries
+    )))
+}
+
+/// Reads and consumes `cost` tokens from a bucket stored in Redis, returning
+/// whether the consume succeeded and the tokens left afterwards, or `None` if
+/// the write lost a race with another instance and nothing was consumed.
+///
+/// Consumption this instance has not written yet is subtracted before
+/// deciding. The bucket is written back only when the batcher says it is
+/// due; denials change nothing worth persisting.
+fn consume_from_redis(
+    client: &dyn BucketStore,
+    write_batcher: &WriteBatcher,
+    redis_key: &str,
+    sizing: StoredSizing,
+    max_idle_secs: u64,
+    cost: u64,
+) -> Result<Option<(bool, f64)>, ThrottlerError> {
+    let read_at_ms = now_ms();
+    let (mut bucket, pending) = read_from_redis(client, write_batcher, redis_key, sizing, max_idle_secs)?;
+
+    if !bucket.try_consume(cost)? {
+        return Ok(Some((false, bucket.tokens)));
+    }
+
+    let cost = cost as f64;
+    if write_batcher.record_consume(redis_key, bucket.last_refill, cost, sizing.ttl_secs)? {
+        if !client.try_set_token_bucket(redis_key, &bucket, sizing.ttl_secs)? {
+            write_batcher.rejected(redis_key, cost)?;
+            write_batcher.races.record(redis_key, read_at_ms)?;
+            return Ok(None);
+        }
+        write_batcher.written(redis_key, pending + cost)?;
+    }
+
+    Ok(Some((true, bucket.tokens)))
+}
+
+/// Draws up to `wanted` tokens from a bucket stored in Redis for a local
+/// lease, returning the tokens drawn and those left in the bucket, or `None`
+/// if the write lost a race and nothing was drawn.
+fn lease_from_redis(
+    client: &dyn BucketStore,
+    write_batcher: &WriteBatcher,
+    redis_key: &str,
+    sizing: StoredSizing,
+    max_idle_secs: u64,
+    wanted: f64,
+) -> Result<Option<(f64, f64)>, ThrottlerError> {
+    let read_at_ms = now_ms();
+    let (mut bucket, pending) = read_from_redis(client, write_batcher, redis_key, sizing, max_idle_secs)?;
+
+    let drawn = wanted.min(bucket.tokens);
+    if drawn <= 0.0 {
+        return Ok(Some((0.0, bucket.tokens)));
+    }
+
+    bucket.tokens -= drawn;
+    if !client.try_set_token_bucket(redis_key, &bucket, sizing.ttl_secs)? {
+        write_batcher.races.record(redis_key, read_at_ms)?;
+        return Ok(None);
+    }
+    write_batcher.written(redis_key, pending)?;
+
+    Ok(Some((drawn, bucket.tokens)))
+}
+
+/// Returns `tokens` to a bucket stored in Redis, up to its capacity,
+/// returning the tokens it then holds, or `None` if the write lost a race
+/// and nothing was returned.
+fn refund_to_redis(
+    client: &dyn BucketStore,
+    write_batcher: &WriteBatcher,
+    redis_key: &str,
+    sizing: StoredSizing,
+    max_idle_secs: u64,
+    tokens: f64,
+) -> Result<Option<f64>, ThrottlerError> {
+    let read_at_ms = now_ms();
+    let (mut bucket, pending) = read_from_redis(client, write_batcher, redis_key, sizing, max_idle_secs)?;
+
+    bucket.tokens = (bucket.tokens + tokens).min(bucket.capacity as f64);
+    if !client.try_set_token_bucket(redis_key, &bucket, sizing.ttl_secs)? {
+        write_batcher.races.record(redis_key, read_at_ms)?;
+        return Ok(None);
+    }
+    write_batcher.written(redis_key, pending)?;
+
+    Ok(Some(bucket.tokens))
+}
+
+/// Reads a bucket from Redis (a full one if missing), refilled and adapted
+/// to the effective `sizing`, less the consumption this instance has not
+/// written yet; see [`TokenBucket::refill_within`] for `max_idle_secs`.
+/// Returns it with that pending count.
+fn read_from_redis(
+    client: &dyn BucketStore,
+    write_batcher: &WriteBatcher,
+    redis_key: &str,
+    sizing: StoredSizing,
+    max_idle_secs: u64,
+) -> Result<(TokenBucket, f64), ThrottlerError> {
+    let StoredSizing { capacity, refill_rate, .. } = sizing;
+    let mut bucket = client.get_token_bucket(redis_key)?
+        .unwrap_or_else(|| TokenBucket::new(capacity, refill_rate));
+
+    bucket.refill_within(max_idle_secs)?;
+    if bucket.capacity != capacity || bucket.refill_rate != refill_rate {
+        bucket.capacity = capacity;
+        bucket.refill_rate = refill_rate;
+        bucket.tokens = bucket.tokens.min(capacity as f64);
+    }
+    let pending = write_batcher.pending(redis_key)?;
+    bucket.tokens = (bucket.tokens - pending).max(0.0);
+
+    Ok((bucket, pending))
+}
+
+/// How a bucket stored in Redis is sized and how long it is kept idle
+#[derive(Debug, Clone, Copy)]
+struct StoredSizing {
+    capacity: u64,
+    refill_rate: f64,
+    /// Redis TTL, see [`bucket_ttl_secs`]
+    ttl_secs: usize,
+}
+
+impl StoredSizing {
+    fn new(capacity: u64, refill_rate: f64, window_ms: Option<u64>) -> Self {
+        Self { capacity, refill_rate, ttl_secs: bucket_ttl_secs(capacity, refill_rate, window_ms) }
+    }
+}
+
+/// Redis TTL for a bucket: the window of the rule it is used under, if
+/// any; otherwise the seconds until an empty bucket is full again, after
+/// which a stored bucket is indistinguishable from a fresh one and can
+/// expire.
+fn bucket_ttl_secs(capacity: u64, refill_rate: f64, window_ms: Option<u64>) -> usize {
+    if let Some(window_ms) = window_ms {
+        return (window_ms.div_ceil(1000) as usize).max(1);
+    }
+    if refill_rate <= 0.0 {
+        return 3600;
+    }
+    ((capacity as f64 / refill_rate).ceil() as usize).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bucket_store::{BucketPage, MemoryStore};
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    /// Starts a TCP server that accepts connections but never answers,
+    /// simulating a stalled Redis. Connections are dropped after a while so
+    /// blocked workers can finish.
+    fn spawn_stalled_redis() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                std::thread::spawn(move || {
+                    std::thread::sleep(Duration::from_millis(500));
+                    drop(stream);
+                });
+            }
+        });
+        format!("redis://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_stalled_redis_times_out_and_falls_back_to_local() {
+        let config = Config {
+            redis_url: spawn_stalled_redis(),
+            redis_op_timeout_ms: 50,
+            ..Config::default()
+        };
+        let limiter = RateLimiter::new(config).unwrap();
+
+        let start = Instant::now();
+        let (allowed, remaining) = limiter.check_rate_limit_shared("stalled").await.unwrap();
+
+        assert!(start.elapsed() < Duration::from_millis(400));
+        assert!(allowed);
+        assert_eq!(remaining, 99);
+        assert_eq!(limiter.get_stats().unwrap()["local_buckets"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_redis_concurrency_limit_queues_or_rejects() {
+        let in_flight = Arc::new(AtomicU64::new(0));
+        let peak = Arc::new(AtomicU64::new(0));
+        let slow_op = || {
+            let in_flight = Arc::clone(&in_flight);
+            let peak = Arc::clone(&peak);
+            move || {
+                let now = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                std::thread::sleep(Duration::from_millis(100));
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                Ok(())
+            }
+        };
+
+        // Excess operations wait their turn
+        let limiter = RateLimiter::new(Config {
+            max_redis_concurrency: 1,
+            redis_queue_timeout_ms: 1000,
+            ..Config::default()
+        }).unwrap();
+        let (a, b, c) = tokio::join!(
+            limiter.run_redis_op(slow_op()),
+            limiter.run_redis_op(slow_op()),
+            limiter.run_redis_op(slow_op()),
+        );
+        assert!(a.is_ok() && b.is_ok() && c.is_ok());
+        assert_eq!(peak.load(Ordering::SeqCst), 1);
+
+        // Without a queue they are turned away while the slot is taken
+        let limiter = RateLimiter::new(Config {
+            max_redis_concurrency: 1,
+            redis_queue_timeout_ms: 0,
+            ..Config::default()
+        }).unwrap();
+        let (a, b) = tokio::join!(limiter.run_redis_op(slow_op()), async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            limiter.run_redis_op(slow_op()).await
+        });
+        assert!(a.is_ok());
+        assert!(matches!(b, Err(ThrottlerError::RedisError(ref msg)) if msg.contains("concurrency limit")));
+
+        // Once it is released the next operation runs
+        assert!(limiter.run_redis_op(slow_op()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_transfer_moves_tokens_between_local_buckets() {
+        let limiter = RateLimiter::new(Config { default_capacity: 10, default_refill_rate: 0.0, ..Config::default() }).unwrap();
+        for _ in 0..6 {
+            limiter.check_rate_limit_shared("member").await.unwrap();
+        }
+
+        // Borrow from the pool
+        assert_eq!(limiter.transfer("pool", "member", 4).await.unwrap(), (true, 4.0));
+        assert_eq!(limiter.get_remaining_tokens("pool").unwrap(), 6);
+        assert_eq!(limiter.get_remaining_tokens("member").unwrap(), 8);
+
+        // The pool cannot lend what it does not have
+        assert_eq!(limiter.transfer("pool", "member", 7).await.unwrap(), (false, 0.0));
+        assert_eq!(limiter.get_remaining_tokens("pool").unwrap(), 6);
+        assert_eq!(limiter.get_remaining_tokens("member").unwrap(), 8);
+
+        // Only what fits below the member's capacity moves
+        assert_eq!(limiter.transfer("pool", "member", 5).await.unwrap(), (true, 2.0));
+        assert_eq!(limiter.get_remaining_tokens("pool").unwrap(), 4);
+        assert_eq!(limiter.get_remaining_tokens("member").unwrap(), 10);
+
+        assert!(limiter.transfer("pool", "pool", 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_transfer_moves_tokens_between_shared_buckets() {
+        let store = Arc::new(MemoryStore::new());
+        store.set_time(1_000_000).unwrap();
+        let config = Config { default_capacity: 10, default_refill_rate: 0.0, ..Config::default() };
+        let limiter = RateLimiter::with_store(config, store.clone()).unwrap();
+        for _ in 0..6 {
+            limiter.check_rate_limit_shared("member").await.unwrap();
+        }
+        let tokens = |key: &str| store.get_token_bucket(&limiter.redis_key(key)).unwrap().unwrap().tokens;
+
+        assert_eq!(limiter.transfer("pool", "member", 4).await.unwrap(), (true, 4.0));
+        assert_eq!((tokens("pool"), tokens("member")), (6.0, 8.0));
+
+        assert_eq!(limiter.transfer("pool", "member", 7).await.unwrap(), (false, 0.0));
+        assert_eq!((tokens("pool"), tokens("member")), (6.0, 8.0));
+
+        assert_eq!(limiter.transfer("pool", "member", 5).await.unwrap(), (true, 2.0));
+        assert_eq!((tokens("pool"), tokens("member")), (4.0, 10.0));
+    }
+
+    #[test]
+    fn test_stats_estimate_memory_per_bucket() {
+        let limiter = RateLimiter::new(Config::default()).unwrap();
+        for i in 0..10 {
+            limiter.check_rate_limit(&format!("key-{}", i)).unwrap();
+        }
+        limiter.check_rate_limit(&"long".repeat(50)).unwrap();
+
+        let stats = limiter.get_stats().unwrap();
+        assert_eq!(stats["local_buckets"], 11);
+        let key_bytes = 10 * "key-0".len() + 200;
+        assert_eq!(stats["estimated_memory_bytes"], (11 * BUCKET_ENTRY_BYTES + key_bytes) as u64);
+        let per_bucket = stats["estimated_memory_bytes"] / stats["local_buckets"];
+        assert!((48..=512).contains(&per_bucket), "{} bytes per bucket", per_bucket);
+
+        let largest = limiter.largest_keys(2).unwrap();
+        assert_eq!(largest.len(), 2);
+        assert_eq!(largest[0], ("long".repeat(50), (BUCKET_ENTRY_BYTES + 200) as u64));
+        assert_eq!(largest[1].0, "key-0");
+    }
+
+    #[tokio::test]
+    async fn test_list_buckets_pages_local_buckets() {
+        let limiter = RateLimiter::new(Config { default_refill_rate: 0.0, ..Config::default() }).unwrap();
+        for i in 0..5 {
+            for _ in 0..i {
+                limiter.check_rate_limit(&format!("key-{}", i)).unwrap();
+            }
+        }
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = limiter.list_buckets(3, cursor).await.unwrap();
+            assert!(page.keys.len() <= 3);
+            seen.extend(page.keys.into_iter().map(|summary| (summary.key, summary.remaining)));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        let expected: Vec<_> = (1..5u64).map(|i| (format!("key-{}", i), 100 - i)).collect();
+        assert_eq!(seen, expected);
+
+        // The limit is bounded
+        assert_eq!(limiter.list_buckets(0, None).await.unwrap().keys.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_redis_op_reports_timeout() {
+        let config = Config {
+            redis_url: spawn_stalled_redis(),
+            redis_op_timeout_ms: 50,
+            ..Config::default()
+        };
+        let limiter = RateLimiter::new(config).unwrap();
+        let client = Arc::clone(limiter.store.as_ref().unwrap());
+
+        let err = limiter.run_redis_op(move || client.ping()).await.unwrap_err();
+        assert!(err.to_string().contains("timed out after 50ms"));
+    }
+
+    #[tokio::test]
+    async fn test_redis_op_bounded_by_request_deadline() {
+        let limiter = RateLimiter::new(Config {
+            redis_url: spawn_stalled_redis(),
+            redis_op_timeout_ms: 5_000,
+            ..Config::default()
+        }).unwrap();
+
+        // The deadline is tighter than the op timeout, so it wins
+        let client = Arc::clone(limiter.store.as_ref().unwrap());
+        let started = Instant::now();
+        let err = REQUEST_DEADLINE
+            .scope(Instant::now() + Duration::from_millis(50), limiter.run_redis_op(move || client.ping()))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ThrottlerError::DeadlineExceeded(_)), "{:?}", err);
+        assert!(started.elapsed() < Duration::from_secs(2));
+
+        // Already passed: the operation is not attempted, and the check
+        // does not fall back to local state
+        let err = REQUEST_DEADLINE
+            .scope(Instant::now(), limiter.check_rate_limit_shared("late"))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ThrottlerError::DeadlineExceeded(_)), "{:?}", err);
+        assert_eq!(limiter.get_remaining_tokens("late").unwrap(), 100);
+    }
+
+    fn unreachable_redis(consistency_mode: ConsistencyMode) -> RateLimiter {
+        RateLimiter::new(Config {
+            redis_url: "redis://127.0.0.1:1".to_string(),
+            consistency_mode,
+            ..Config::default()
+        }).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_strict_mode_fails_when_redis_unreachable() {
+        let limiter = unreachable_redis(ConsistencyMode::Strict);
+
+        let err = limiter.check_rate_limit_shared("strict").await.unwrap_err();
+        assert!(matches!(err, ThrottlerError::StoreUnavailable(_)));
+        let err = limiter.peek_rate_limit_shared("strict").await.unwrap_err();
+        assert!(matches!(err, ThrottlerError::StoreUnavailable(_)));
+
+        // Nothing was decided locally
+        assert_eq!(limiter.get_stats().unwrap()["local_buckets"], 0);
+    }
+
+    #[tokio::test]
+    async fn test_lenient_mode_falls_back_when_redis_unreachable() {
+        let limiter = unreachable_redis(ConsistencyMode::Lenient);
+
+        let (allowed, remaining) = limiter.check_rate_limit_shared("lenient").await.unwrap();
+        assert!(allowed);
+        assert_eq!(remaining, 99);
+        assert_eq!(limiter.get_stats().unwrap()["local_buckets"], 1);
+    }
+
+    #[test]
+    fn test_remaining_semantics_differ_by_consumed_cost() {
+        let after = RateLimiter::new(Config::default()).unwrap();
+        let before = RateLimiter::new(Config {
+            remaining_semantics: RemainingSemantics::Before,
+            ..Config::default()
+        }).unwrap();
+
+        for _ in 0..3 {
+            let (_, remaining_after) = after.check_rate_limit("key").unwrap();
+            let (_, remaining_before) = before.check_rate_limit("key").unwrap();
+            assert_eq!(remaining_before - remaining_after, 1);
+        }
+
+        let (_, remaining_before) = before.check_rate_limit("fresh").unwrap();
+        assert_eq!(remaining_before, 100);
+    }
+
+    #[test]
+    fn test_flush_without_redis_is_noop() {
+        let limiter = RateLimiter::new(Config::default()).unwrap();
+        limiter.check_rate_limit("key").unwrap();
+        assert_eq!(limiter.flush_to_redis().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_flush_is_bounded_by_timeout() {
+        let config = Config {
+            redis_url: spawn_stalled_redis(),
+            redis_op_timeout_ms: 0,
+            ..Config::default()
+        };
+        let limiter = RateLimiter::new(config).unwrap();
+        limiter.check_rate_limit("key").unwrap();
+
+        let start = Instant::now();
+        let result = limiter.flush_to_redis_within(Duration::from_millis(50)).await;
+
+        assert!(result.is_err());
+        assert!(start.elapsed() < Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_reset_many_restores_each_key() {
+        let limiter = RateLimiter::new(Config::default()).unwrap();
+        for key in ["a", "b"] {
+            limiter.check_rate_limit_with_params(key, 1, 0.0).unwrap();
+            assert!(!limiter.check_rate_limit_with_params(key, 1, 0.0).unwrap().0);
+        }
+
+        let results = limiter.reset_many(&["a".to_string(), "b".to_string()]).unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert!(results.values().all(|reset| *reset));
+        assert!(limiter.check_rate_limit_with_params("a", 1, 0.0).unwrap().0);
+        assert!(limiter.check_rate_limit_with_params("b", 1, 0.0).unwrap().0);
+    }
+
+    #[test]
+    fn test_export_import_round_trip_preserves_remaining() {
+        let source = RateLimiter::new(Config::default()).unwrap();
+        for _ in 0..30 {
+            source.check_rate_limit_with_params("migrating", 100, 1.0).unwrap();
+        }
+
+        let json = serde_json::to_string(&source.export_state().unwrap()).unwrap();
+        let state: SerializableState = serde_json::from_str(&json).unwrap();
+
+        let target = RateLimiter::new(Config::default()).unwrap();
+        assert_eq!(target.import_state(state).unwrap(), 1);
+
+        let remaining = target.get_remaining_tokens("migrating").unwrap();
+        assert!((70..=71).contains(&remaining), "remaining was {}", remaining);
+    }
+
+    #[test]
+    fn test_import_does_not_credit_transfer_time() {
+        let target = RateLimiter::new(Config::default()).unwrap();
+        let exported_at = now_ms() - 60_000;
+        let state = SerializableState {
+            exported_at,
+            buckets: HashMap::from([("k".to_string(), BucketState {
+                tokens: 0.0,
+                capacity: 10,
+                refill_rate: 1.0,
+                last_refill: exported_at,
+            })]),
+        };
+
+        target.import_state(state).unwrap();
+
+        // A minute in transit would have refilled the bucket; it must not
+        assert!(!target.check_rate_limit_with_params("k", 10, 1.0).unwrap().0);
+    }
+
+    #[test]
+    fn test_hashed_redis_key_hides_raw_id() {
+        let limiter = RateLimiter::new(Config { hash_keys: true, ..Config::default() }).unwrap();
+        let key = "tenant:alice@example.com";
+
+        let redis_key = limiter.redis_key(key);
+        assert_eq!(redis_key, limiter.redis_key(key));
+        assert_eq!(redis_key.len(), "throttler:".len() + 64);
+        assert!(!redis_key.contains("tenant") && !redis_key.contains("alice"));
+
+        let plain = RateLimiter::new(Config::default()).unwrap();
+        assert_eq!(plain.redis_key(key), "throttler:tenant:alice@example.com");
+    }
+
+    #[tokio::test]
+    async fn test_retry_budget_shrinks_with_denials() {
+        let limiter = RateLimiter::new(Config { retry_budget: true, ..Config::default() }).unwrap();
+
+        assert!(limiter.check_rate_limit_shared_with_params("k", 1, 3.0).await.unwrap().0);
+        assert!(!limiter.check_rate_limit_shared_with_params("k", 1, 3.0).await.unwrap().0);
+        assert_eq!(limiter.retry_budget("k", 3.0).unwrap(), 2);
+
+        for _ in 0..5 {
+            limiter.check_rate_limit_shared_with_params("k", 1, 3.0).await.unwrap();
+        }
+        assert_eq!(limiter.retry_budget("k", 3.0).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_retry_budget_zero_without_refill() {
+        let limiter = RateLimiter::new(Config { retry_budget: true, ..Config::default() }).unwrap();
+        assert_eq!(limiter.retry_budget("k", 0.0).unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_retry_budget_for_slow_refill() {
+        let limiter = RateLimiter::new(Config { retry_budget: true, ..Config::default() }).unwrap();
+        let per_minute = 1.0 / 60.0;
+
+        // Under a token per second still leaves the retry for the next token
+        assert_eq!(limiter.retry_budget("slow", per_minute).unwrap(), 1);
+        assert!(limiter.check_rate_limit_shared_with_params("slow", 1, per_minute).await.unwrap().0);
+        assert!(!limiter.check_rate_limit_shared_with_params("slow", 1, per_minute).await.unwrap().0);
+        assert_eq!(limiter.retry_budget("slow", per_minute).unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_denial_streaks_are_pruned_and_reset() {
+        let limiter = RateLimiter::new(Config { retry_budget: true, ..Config::default() }).unwrap();
+        for key in ["stale", "reset"] {
+            limiter.check_rate_limit_shared_with_params(key, 1, 3.0).await.unwrap();
+            limiter.check_rate_limit_shared_with_params(key, 1, 3.0).await.unwrap();
+        }
+        assert_eq!(limiter.retry_budget("reset", 3.0).unwrap(), 2);
+
+        limiter.reset("reset").unwrap();
+        assert_eq!(limiter.retry_budget("reset", 3.0).unwrap(), 3);
+
+        limiter.denial_streaks.write().unwrap().get_mut("stale").unwrap().last_denied_ms -= 60_000;
+        limiter.cleanup_expired_buckets(60_000).unwrap();
+        assert!(limiter.denial_streaks.read().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_fractional_refill_rate_refills_slowly() {
+        let limiter = RateLimiter::new(Config {
+            default_capacity: 1,
+            default_refill_rate: 0.5,
+            ..Config::default()
+        }).unwrap();
+
+        assert!(limiter.check_rate_limit("slow").unwrap().0);
+        // 1s at 0.5 tokens/sec is only half a token
+        advance(&limiter, "slow", 1000);
+        assert!(!limiter.check_rate_limit("slow").unwrap().0);
+
+        // Another 1.5s brings it past a whole token
+        advance(&limiter, "slow", 1500);
+        assert!(limiter.check_rate_limit("slow").unwrap().0);
+    }
+
+    /// Moves a local bucket's refill clock back to simulate elapsed time
+    fn fair_limiter(depth: usize) -> RateLimiter {
+        RateLimiter::new(Config {
+            default_capacity: 1,
+            default_refill_rate: 20.0,
+            fair_queueing: true,
+            fair_queue_depth: depth,
+            ..Config::default()
+        }).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_fair_queue_serves_in_arrival_order() {
+        let limiter = fair_limiter(16);
+        assert!(limiter.check_rate_limit_shared("hot").await.unwrap().0);
+
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let mut handles = Vec::new();
+        for i in 0..5 {
+            let limiter = limiter.clone();
+            let order = Arc::clone(&order);
+            handles.push(tokio::spawn(async move {
+                let (allowed, _) = limiter.check_rate_limit_shared("hot").await.unwrap();
+                order.lock().unwrap().push((i, allowed));
+            }));
+            // Space out arrivals so the intended order is unambiguous
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+
+        let order = order.lock().unwrap();
+        assert_eq!(*order, (0..5).map(|i| (i, true)).collect::<Vec<_>>());
+        assert_eq!(limiter.fair_queues.queued().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_fair_queue_denies_waits_past_the_cap() {
+        let limiter = RateLimiter::new(Config {
+            default_capacity: 1,
+            default_refill_rate: 0.1,
+            fair_queueing: true,
+            fair_queue_max_wait_ms: 50,
+            ..Config::default()
+        }).unwrap();
+        assert!(limiter.check_rate_limit_shared("slow").await.unwrap().0);
+
+        // The next token is 10s away: denied at once rather than waited for
+        let started = Instant::now();
+        assert!(!limiter.check_rate_limit_shared("slow").await.unwrap().0);
+        assert!(started.elapsed() < Duration::from_millis(50));
+        assert_eq!(limiter.fair_queues.queued().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_fair_queue_sheds_when_full() {
+        let limiter = fair_limiter(2);
+        assert!(limiter.check_rate_limit_shared("hot").await.unwrap().0);
+
+        let waiters: Vec<_> = (0..2)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move { limiter.check_rate_limit_shared("hot").await.unwrap() })
+            })
+            .collect();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(limiter.fair_queues.queued().unwrap(), 2);
+
+        let started = Instant::now();
+        let (allowed, _) = limiter.check_rate_limit_shared("hot").await.unwrap();
+        assert!(!allowed);
+        assert!(started.elapsed() < Duration::from_millis(20));
+
+        for waiter in waiters {
+            assert!(waiter.await.unwrap().0);
+        }
+    }
+
+    fn advance(limiter: &RateLimiter, key: &str, ms: u64) {
+        limiter.local_buckets.write().unwrap().get_mut(key).unwrap().last_refill -= ms;
+    }
+
+    fn allowed_now(limiter: &RateLimiter, key: &str, rule: &RateLimitRule) -> usize {
+        (0..100)
+            .take_while(|_| limiter.check_rate_limit_with_rule(key, rule).unwrap().0)
+            .count()
+    }
+
+    #[test]
+    fn test_rule_window_drives_local_expiry() {
+        let limiter = RateLimiter::new(Config::default()).unwrap();
+        let rule = RateLimitRule::new(2, 10, Duration::from_secs(5));
+        limiter.check_rate_limit_with_rule("windowed", &rule).unwrap();
+        limiter.check_rate_limit("unruled").unwrap();
+
+        advance(&limiter, "windowed", 4_000);
+        advance(&limiter, "unruled", 4_000);
+        assert_eq!(limiter.cleanup_expired_buckets(60_000).unwrap(), 0);
+
+        // Past its 5s window the rule's bucket expires; the default age
+        // still keeps the other one
+        advance(&limiter, "windowed", 1_500);
+        advance(&limiter, "unruled", 1_500);
+        assert_eq!(limiter.cleanup_expired_buckets(60_000).unwrap(), 1);
+        assert_eq!(limiter.get_stats().unwrap()["local_buckets"], 1);
+        assert!(limiter.local_buckets.read().unwrap().contains_key("unruled"));
+    }
+
+    #[test]
+    fn test_rule_window_is_redis_ttl() {
+        let store = Arc::new(MemoryStore::new());
+        store.set_time(now_ms()).unwrap();
+        let limiter = RateLimiter::with_store(Config::default(), store.clone()).unwrap();
+        let rule = RateLimitRule::new(10, 10, Duration::from_secs(30));
+        limiter.check_rate_limit_with_rule("ttl-window", &rule).unwrap();
+
+        assert_eq!(limiter.flush_to_redis().unwrap(), 1);
+        let redis_key = limiter.redis_key("ttl-window");
+
+        // Refilling takes 1s, but the bucket lives for the whole window
+        store.advance(29_000).unwrap();
+        assert!(store.get_token_bucket(&redis_key).unwrap().is_some());
+        store.advance(2_000).unwrap();
+        assert!(store.get_token_bucket(&redis_key).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_drops_expired_local_slots() {
+        let limiter = RateLimiter::new(Config { concurrency_slot_ttl_ms: 1, ..Config::default() }).unwrap();
+        limiter.acquire_concurrency_slot("leaky", Some(1)).await.unwrap().unwrap();
+        assert_eq!(limiter.local_slots.key_count().unwrap(), 1);
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        limiter.cleanup_expired_buckets(60_000).unwrap();
+        assert_eq!(limiter.local_slots.key_count().unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_shared_consume_keeps_bucket_for_rule_window() {
+        let store = Arc::new(MemoryStore::new());
+        store.set_time(now_ms()).unwrap();
+        let limiter = RateLimiter::with_store(Config::default(), store.clone()).unwrap();
+        limiter.consume_tokens_shared("shared-window", 10, 10.0, 1, Some(30_000)).await.unwrap();
+        let redis_key = limiter.redis_key("shared-window");
+
+        // Refilling takes 1s, but the bucket lives for the whole window
+        store.advance(29_000).unwrap();
+        assert!(store.get_token_bucket(&redis_key).unwrap().is_some());
+        store.advance(2_000).unwrap();
+        assert!(store.get_token_bucket(&redis_key).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_local_fallback_keeps_rule_window() {
+        let limiter = unreachable_redis(ConsistencyMode::Lenient);
+        limiter.consume_tokens_shared("fallback-window", 10, 10.0, 1, Some(30_000)).await.unwrap();
+
+        advance(&limiter, "fallback-window", 20_000);
+        assert_eq!(limiter.cleanup_expired_buckets(10_000).unwrap(), 0);
+        advance(&limiter, "fallback-window", 11_000);
+        assert_eq!(limiter.cleanup_expired_buckets(10_000).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_burst_then_settle_to_refill_rate() {
+        let limiter = RateLimiter::new(Config::default()).unwrap();
+        let rule = RateLimitRule::new(2, 10, Duration::from_secs(60));
+
+        // A fresh bucket allows the full burst at once
+        assert_eq!(allowed_now(&limiter, "bursty", &rule), 10);
+
+        // Then throughput settles to the refill rate
+        advance(&limiter, "bursty", 1000);
+        assert_eq!(allowed_now(&limiter, "bursty", &rule), 2);
+        advance(&limiter, "bursty", 3000);
+        assert_eq!(allowed_now(&limiter, "bursty", &rule), 6);
+
+        // A long idle period refills only up to the burst ceiling
+        advance(&limiter, "bursty", 60_000);
+        assert_eq!(allowed_now(&limiter, "bursty", &rule), 10);
+    }
+
+    #[test]
+    fn test_equivalent_rate_units_limit_alike() {
+        use crate::rate_limit_config::RateUnit;
+
+        let limiter = RateLimiter::new(Config::default()).unwrap();
+        let window = Duration::from_secs(60);
+        let rules = [
+            ("per-second", RateLimitRule::new(2, 5, window)),
+            ("per-minute", RateLimitRule::new(120, 5, window).with_rate_unit(RateUnit::PerMinute)),
+            ("per-hour", RateLimitRule::new(7200, 5, window).with_rate_unit(RateUnit::PerHour)),
+            ("per-window", RateLimitRule::new(120, 5, window).with_rate_unit(RateUnit::PerWindow)),
+        ];
+
+        for (key, rule) in &rules {
+            assert_eq!(allowed_now(&limiter, key, rule), 5, "{}", key);
+            advance(&limiter, key, 1000);
+            assert_eq!(allowed_now(&limiter, key, rule), 2, "{}", key);
+        }
+    }
+
+    #[test]
+    fn test_reset_bucket_gets_full_burst() {
+        let limiter = RateLimiter::new(Config::default()).unwrap();
+        let rule = RateLimitRule::new(1, 5, Duration::from_secs(60));
+
+        assert_eq!(allowed_now(&limiter, "reset-me", &rule), 5);
+        limiter.reset("reset-me").unwrap();
+        assert_eq!(allowed_now(&limiter, "reset-me", &rule), 5);
+    }
+
+    #[test]
+    fn test_global_limit_is_shared_across_keys() {
+        let limiter = RateLimiter::new(Config {
+            global_rate_limit: 2,
+            ..Config::default()
+        }).unwrap();
+
+        assert!(limiter.check_global_limit().unwrap());
+        assert!(limiter.check_global_limit().unwrap());
+        assert!(!limiter.check_global_limit().unwrap());
+    }
+
+    #[test]
+    fn test_global_limit_disabled_by_default() {
+        let limiter = RateLimiter::new(Config::default()).unwrap();
+
+        for _ in 0..1000 {
+            assert!(limiter.check_global_limit().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_race_is_retried_until_write_succeeds() {
+        let mut attempts = 0;
+        let result = retry_on_race(3, || {
+            attempts += 1;
+            // The first two writes lose to a concurrent writer
+            Ok((attempts > 2).then_some(attempts))
+        });
+
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[test]
+    fn test_persistent_race_errors_after_retries() {
+        let mut attempts = 0;
+        let result: Result<(), _> = retry_on_race(2, || {
+            attempts += 1;
+            Ok(None)
+        });
+
+        assert!(matches!(result, Err(ThrottlerError::RedisError(_))));
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn test_rejected_write_returns_token() {
+        let batcher = WriteBatcher::new(1000, 0);
+        assert!(batcher.record_consume("k", 5000, 1.0, 60).unwrap());
+        batcher.rejected("k", 1.0).unwrap();
+
+        assert_eq!(batcher.pending("k").unwrap(), 0.0);
+        // The retry is due immediately despite the interval
+        assert!(batcher.record_consume("k", 5001, 1.0, 60).unwrap());
+    }
+
+    #[test]
+    fn test_write_batcher_spaces_writes() {
+        let batcher = WriteBatcher::new(1000, 0);
+
+        assert!(batcher.record_consume("k", 10_000, 1.0, 60).unwrap());
+        batcher.written("k", 1.0).unwrap();
+
+        // Within the interval: consumption accumulates instead of writing
+        assert!(!batcher.record_consume("k", 10_100, 1.0, 60).unwrap());
+        assert!(!batcher.record_consume("k", 10_200, 1.0, 60).unwrap());
+        assert_eq!(batcher.pending("k").unwrap(), 2.0);
+        assert_eq!(batcher.unwritten_keys().unwrap(), vec![("k".to_string(), Some(60))]);
+
+        // Interval elapsed: due again, and the write covers all pending tokens
+        assert!(batcher.record_consume("k", 11_000, 1.0, 60).unwrap());
+        batcher.written("k", 3.0).unwrap();
+        assert_eq!(batcher.pending("k").unwrap(), 0.0);
+        assert_eq!(batcher.writes.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn test_write_batcher_without_interval_writes_every_consume() {
+        let batcher = WriteBatcher::new(0, 0);
+
+        for now in [1, 1, 2] {
+            assert!(batcher.record_consume("k", now, 1.0, 60).unwrap());
+        }
+    }
+
+    #[cfg(feature = "redis-tests")]
+    #[tokio::test]
+    async fn test_batched_writes_still_enforce_limit() {
+        let redis_url = std::env::var("REDIS_URL")
+            .unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+        let limiter = RateLimiter::new(Config {
+            redis_url,
+            min_redis_write_interval_ms: 60_000,
+            ..Config::default()
+        }).unwrap();
+        let key = format!("batch-{}", uuid::Uuid::new_v4());
+
+        let mut allowed = 0;
+        for _ in 0..20 {
+            if limiter.check_rate_limit_shared_with_params(&key, 10, 0.0).await.unwrap().0 {
+                allowed += 1;
+            }
+        }
+
+        assert_eq!(allowed, 10);
+        assert_eq!(limiter.get_stats().unwrap()["redis_writes"], 1);
+
+        // The batched consumption reaches Redis on flush
+        assert_eq!(limiter.flush_pending_writes().unwrap(), 1);
+        let stored = limiter.store.as_ref().unwrap()
+            .get_token_bucket(&limiter.redis_key(&key)).unwrap().unwrap();
+        assert_eq!(stored.tokens, 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_instances_share_state_through_store() {
+        let store = Arc::new(MemoryStore::new());
+        let a = RateLimiter::with_store(Config::default(), store.clone()).unwrap();
+        let b = RateLimiter::with_store(Config::default(), store.clone()).unwrap();
+
+        let mut allowed = 0;
+        for limiter in [&a, &b, &a, &b, &a, &b] {
+            if limiter.check_rate_limit_shared_with_params("shared", 4, 0.0).await.unwrap().0 {
+                allowed += 1;
+            }
+        }
+        assert_eq!(allowed, 4);
+
+        let stored = store.get_token_bucket(&a.redis_key("shared")).unwrap().unwrap();
+        assert_eq!(stored.tokens, 0.0);
+        assert!(a.is_redis_available());
+
+        // Resetting through one instance clears the shared bucket
+        b.reset("shared").unwrap();
+        assert!(store.is_empty().unwrap());
+        assert!(a.check_rate_limit_shared_with_params("shared", 4, 0.0).await.unwrap().0);
+    }
+
+    #[tokio::test]
+    async fn test_stored_bucket_idle_past_cap_refills_proportionally() {
+        let store = Arc::new(MemoryStore::new());
+        let config = Config { max_refill_elapsed_secs: 60, ..Config::default() };
+        let limiter = RateLimiter::with_store(config, store.clone()).unwrap();
+        let drained_at = |key: &str, idle_ms: u64| {
+            let mut bucket = TokenBucket::new(100, 1.0 / 3600.0);
+            bucket.tokens = 0.0;
+            bucket.last_refill = now_ms() - idle_ms;
+            store.set_token_bucket(&limiter.redis_key(key), &bucket, 3600).unwrap();
+        };
+
+        // 100 tokens at 1/hour, idle ten hours, well past the 60s cap: ten
+        // tokens, not a full bucket
+        drained_at("idle", 10 * 3_600_000);
+        let (allowed, remaining) = limiter.consume_tokens_shared("idle", 100, 1.0 / 3600.0, 1, None).await.unwrap();
+        assert!(allowed);
+        assert!((remaining - 9.0).abs() < 0.01, "{}", remaining);
+
+        // Idle longer than the 100 hours it takes to fill: full
+        drained_at("idler", 120 * 3_600_000);
+        let (_, remaining) = limiter.consume_tokens_shared("idler", 100, 1.0 / 3600.0, 1, None).await.unwrap();
+        assert!((remaining - 99.0).abs() < 0.01, "{}", remaining);
+    }
+
+    /// A [`MemoryStore`] that counts the operations reaching it
+    #[derive(Default)]
+    struct CountingStore {
+        inner: MemoryStore,
+        calls: AtomicU64,
+        /// Upcoming bucket writes to reject as lost races
+        lose_races: AtomicU64,
+        /// Quota charges, including zero ones
+        quota_charges: AtomicU64,
+    }
+
+    impl CountingStore {
+        fn count(&self) {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+        }
+
+        fn calls(&self) -> u64 {
+            self.calls.load(Ordering::SeqCst)
+        }
+    }
+
+    impl BucketStore for CountingStore {
+        fn get_token_bucket(&self, key: &str) -> Result<Option<TokenBucket>, ThrottlerError> {
+            self.count();
+            self.inner.get_token_bucket(key)
+        }
+
+        fn try_set_token_bucket(&self, key: &str, bucket: &TokenBucket, ttl: usize) -> Result<bool, ThrottlerError> {
+            self.count();
+            let lost = self.lose_races.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1));
+            if lost.is_ok() {
+                return Ok(false);
+            }
+            self.inner.try_set_token_bucket(key, bucket, ttl)
+        }
+
+        fn atomic_consume_tokens(&self, key: &str, tokens_to_consume: u32, rule: &RateLimitRule) -> Result<(bool, TokenBucket), ThrottlerError> {
+            self.count();
+            self.inner.atomic_consume_tokens(key, tokens_to_consume, rule)
+        }
+
+        fn transfer_tokens(
+            &self,
+            from: &str,
+            to: &str,
+            tokens: f64,
+            reserved: f64,
+            capacity: u64,
+            refill_rate: f64,
+        ) -> Result<Option<f64>, ThrottlerError> {
+            self.count();
+            self.inner.transfer_tokens(from, to, tokens, reserved, capacity, refill_rate)
+        }
+
+        fn delete_token_bucket(&self, key: &str) -> Result<(), ThrottlerError> {
+            self.count();
+            self.inner.delete_token_bucket(key)
+        }
+
+        fn ping(&self) -> Result<String, ThrottlerError> {
+            self.inner.ping()
+        }
+
+        fn scan_buckets(&self, prefix: &str, cursor: Option<&str>, count: usize) -> Result<BucketPage, ThrottlerError> {
+            self.count();
+            self.inner.scan_buckets(prefix, cursor, count)
+        }
+
+        fn acquire_slot(&self, key: &str, slot_id: &str, limit: u64, ttl_ms: u64) -> Result<Option<u64>, ThrottlerError> {
+            self.count();
+            self.inner.acquire_slot(key, slot_id, limit, ttl_ms)
+        }
+
+        fn release_slot(&self, key: &str, slot_id: &str) -> Result<bool, ThrottlerError> {
+            self.count();
+            self.inner.release_slot(key, slot_id)
+        }
+
+        fn consume_quota(&self, key: &str, cost: u64, limit: u64, reset_at_ms: u64) -> Result<(bool, u64), ThrottlerError> {
+            self.count();
+            self.quota_charges.fetch_add(1, Ordering::SeqCst);
+            self.inner.consume_quota(key, cost, limit, reset_at_ms)
+        }
+
+        fn quota_used(&self, key: &str, reset_at_ms: u64) -> Result<u64, ThrottlerError> {
+            self.count();
+            self.inner.quota_used(key, reset_at_ms)
+        }
+    }
+
+    fn hybrid_config(burst: u64) -> Config {
+        Config {
+            hybrid_local_burst: burst,
+            hybrid_sync_interval_ms: 60_000,
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_bursts_are_served_without_redis() {
+        let store = Arc::new(CountingStore::default());
+        let limiter = RateLimiter::with_store(hybrid_config(5), store.clone()).unwrap();
+
+        // The first consume leases 5 tokens with one read and one write
+        let (allowed, remaining) = limiter.check_rate_limit_shared_with_params("hot", 100, 0.0).await.unwrap();
+        assert!(allowed);
+        assert_eq!(remaining, 99);
+        assert_eq!(store.calls(), 2);
+        let stored = store.get_token_bucket(&limiter.redis_key("hot")).unwrap().unwrap();
+        assert_eq!(stored.tokens, 95.0);
+        let calls = store.calls();
+
+        // The rest of the lease is spent locally
+        for _ in 0..4 {
+            assert!(limiter.check_rate_limit_shared_with_params("hot", 100, 0.0).await.unwrap().0);
+        }
+        assert_eq!(store.calls(), calls);
+
+        // An exhausted lease syncs again
+        assert!(limiter.check_rate_limit_shared_with_params("hot", 100, 0.0).await.unwrap().0);
+        assert_eq!(store.calls(), calls + 2);
+    }
+
+    #[tokio::test]
+    async fn test_cleanup_returns_stale_leases_to_redis() {
+        let store = Arc::new(MemoryStore::new());
+        let config = Config { hybrid_sync_interval_ms: 1, ..hybrid_config(5) };
+        let limiter = RateLimiter::with_store(config, store.clone()).unwrap();
+        assert!(limiter.check_rate_limit_shared_with_params("idle-lease", 100, 0.0).await.unwrap().0);
+        let redis_key = limiter.redis_key("idle-lease");
+        assert_eq!(store.get_token_bucket(&redis_key).unwrap().unwrap().tokens, 95.0);
+
+        // The key is not seen again: its 4 unspent tokens go back
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        limiter.cleanup_expired_buckets(60_000).unwrap();
+        assert_eq!(store.get_token_bucket(&redis_key).unwrap().unwrap().tokens, 99.0);
+        assert_eq!(limiter.leases.held(&redis_key).unwrap(), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_over_admission_is_bounded() {
+        let capacity = 10;
+        let burst = 3;
+        let store = Arc::new(MemoryStore::new());
+        let a = RateLimiter::with_store(hybrid_config(burst), store.clone()).unwrap();
+        let b = RateLimiter::with_store(hybrid_config(burst), store.clone()).unwrap();
+        let check = |limiter: &RateLimiter| {
+            let limiter = limiter.clone();
+            async move { limiter.check_rate_limit_shared_with_params("k", capacity, 0.0).await.unwrap().0 }
+        };
+
+        // Both instances lease, then the Redis bucket refills behind them
+        assert!(check(&a).await && check(&b).await);
+        let redis_key = a.redis_key("k");
+        store.set_token_bucket(&redis_key, &TokenBucket::new(capacity, 0.0), 3600).unwrap();
+
+        let mut admitted = 2;
+        for _ in 0..20 {
+            for limiter in [&a, &b] {
+                if check(limiter).await {
+                    admitted += 1;
+                }
+            }
+        }
+
+        // Nothing beyond the refilled bucket plus both leases gets through
+        assert!(admitted > capacity);
+        assert!(admitted <= capacity + 2 * burst, "admitted {}", admitted);
+        assert_eq!(store.get_token_bucket(&redis_key).unwrap().unwrap().tokens, 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_lost_write_races_are_counted_and_logged() {
+        let store = Arc::new(CountingStore::default());
+        let config = Config { race_log_size: 2, ..Config::default() };
+        let limiter = RateLimiter::with_store(config, store.clone()).unwrap();
+
+        // The lost write is retried, so the request still succeeds
+        store.lose_races.store(1, Ordering::SeqCst);
+        assert!(limiter.check_rate_limit_shared("hot").await.unwrap().0);
+        assert_eq!(limiter.get_stats().unwrap()["write_races"], 1);
+
+        let races = limiter.recent_races().unwrap();
+        assert_eq!(races.len(), 1);
+        assert_eq!(races[0].key, "throttler:hot");
+        assert!(races[0].read_at_ms <= races[0].rejected_at_ms);
+
+        // The log keeps only the newest races; the count keeps them all
+        store.lose_races.store(3, Ordering::SeqCst);
+        assert!(limiter.check_rate_limit_shared("hotter").await.unwrap().0);
+        assert_eq!(limiter.get_stats().unwrap()["write_races"], 4);
+        let races = limiter.recent_races().unwrap();
+        assert_eq!(races.len(), 2);
+        assert!(races.iter().all(|race| race.key == "throttler:hotter"));
+    }
+
+    #[tokio::test]
+    async fn test_list_buckets_reports_remaining_from_store() {
+        let store = Arc::new(MemoryStore::new());
+        let limiter = RateLimiter::with_store(Config::default(), store.clone()).unwrap();
+        for (key, consumed) in [("b", 3), ("a", 1), ("c", 2)] {
+            for _ in 0..consumed {
+                limiter.check_rate_limit_shared_with_params(key, 10, 0.0).await.unwrap();
+            }
+        }
+
+        let first = limiter.list_buckets(2, None).await.unwrap();
+        let listed: Vec<_> = first.keys.iter()
+            .map(|summary| (summary.key.as_str(), summary.remaining, summary.capacity))
+            .collect();
+        assert_eq!(listed, vec![("a", 9, 10), ("b", 7, 10)]);
+        assert!(first.keys.iter().all(|summary| summary.last_activity_ms > 0));
+
+        let second = limiter.list_buckets(2, first.next_cursor).await.unwrap();
+        assert_eq!(second.keys.len(), 1);
+        assert_eq!((second.keys[0].key.as_str(), second.keys[0].remaining), ("c", 8));
+        assert!(second.next_cursor.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_peek_reads_store_without_consuming() {
+        let store = Arc::new(MemoryStore::new());
+        let limiter = RateLimiter::with_store(Config::default(), store.clone()).unwrap();
+
+        limiter.check_rate_limit_shared_with_params("k", 2, 0.0).await.unwrap();
+        assert_eq!(limiter.peek_rate_limit_shared_with_params("k", 2, 0.0).await.unwrap(), (true, 0));
+
+        limiter.check_rate_limit_shared_with_params("k", 2, 0.0).await.unwrap();
+        assert_eq!(limiter.peek_rate_limit_shared_with_params("k", 2, 0.0).await.unwrap(), (false, 0));
+        assert_eq!(store.get_token_bucket(&limiter.redis_key("k")).unwrap().unwrap().tokens, 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_peek_quota_reads_without_charging() {
+        let store = Arc::new(CountingStore::default());
+        let limiter = RateLimiter::with_store(Config::default(), store.clone()).unwrap();
+
+        assert_eq!(limiter.peek_quota("k", 5, QuotaPeriod::Monthly).await.unwrap().remaining, 5);
+        limiter.consume_quota("k", 2, 5, QuotaPeriod::Monthly).await.unwrap();
+        assert_eq!(limiter.peek_quota("k", 5, QuotaPeriod::Monthly).await.unwrap().remaining, 3);
+        assert_eq!(store.quota_charges.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reads_use_replica_and_writes_use_primary() {
+        let primary = Arc::new(MemoryStore::new());
+        let replica = Arc::new(MemoryStore::new());
+        let limiter = RateLimiter::with_store(Config::default(), primary.clone()).unwrap()
+            .with_replica(replica.clone());
+
+        limiter.check_rate_limit_shared_with_params("k", 4, 0.0).await.unwrap();
+        limiter.check_rate_limit_shared_with_params("k", 4, 0.0).await.unwrap();
+        assert_eq!(primary.get_token_bucket(&limiter.redis_key("k")).unwrap().unwrap().tokens, 2.0);
+        assert!(replica.is_empty().unwrap());
+
+        // The replica has not caught up, so the dry run sees a full bucket
+        assert_eq!(limiter.peek_rate_limit_shared_with_params("k", 4, 0.0).await.unwrap(), (true, 3));
+
+        let mut replicated = TokenBucket::new(4, 0.0);
+        replicated.tokens = 1.0;
+        replica.set_token_bucket(&limiter.redis_key("k"), &replicated, 60).unwrap();
+        assert_eq!(limiter.peek_rate_limit_shared_with_params("k", 4, 0.0).await.unwrap(), (true, 0));
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_replica_falls_back_to_primary() {
+        let primary = Arc::new(MemoryStore::new());
+        let replica = Arc::new(RedisClient::new("redis://127.0.0.1:1").unwrap());
+        let limiter = RateLimiter::with_store(Config::default(), primary).unwrap()
+            .with_replica(replica);
+
+        limiter.check_rate_limit_shared_with_params("k", 4, 0.0).await.unwrap();
+        assert_eq!(limiter.peek_rate_limit_shared_with_params("k", 4, 0.0).await.unwrap(), (true, 2));
+    }
+
+    #[cfg(feature = "redis-tests")]
+    #[tokio::test]
+    async fn test_replica_url_serves_reads() {
+        let redis_url = std::env::var("REDIS_URL")
+            .unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+        // A separate database stands in for a replica that has not caught up
+        let limiter = RateLimiter::new(Config {
+            redis_replica_url: format!("{}/1", redis_url.trim_end_matches('/')),
+            redis_url,
+            ..Config::default()
+        }).unwrap();
+        let key = format!("replica-{}", uuid::Uuid::new_v4());
+
+        limiter.check_rate_limit_shared_with_params(&key, 4, 0.0).await.unwrap();
+        let primary = limiter.store.as_ref().unwrap();
+        assert_eq!(primary.get_token_bucket(&limiter.redis_key(&key)).unwrap().unwrap().tokens, 3.0);
+
+        let replica = limiter.replica.as_ref().unwrap();
+        assert!(replica.get_token_bucket(&limiter.redis_key(&key)).unwrap().is_none());
+        assert_eq!(limiter.peek_rate_limit_shared_with_params(&key, 4, 0.0).await.unwrap(), (true, 3));
+    }
+
+    #[test]
+    fn test_store_sweeper_removes_only_stale_buckets() {
+        let store = Arc::new(MemoryStore::new());
+        let limiter = RateLimiter::with_store(Config::default(), store.clone()).unwrap();
+        let idle = TokenBucket { last_refill: now_ms() - 7_200_000, ..TokenBucket::new(5, 1.0) };
+        store.set_token_bucket(&limiter.redis_key("idle"), &idle, 86_400).unwrap();
+        store.set_token_bucket(&limiter.redis_key("busy"), &TokenBucket::new(5, 1.0), 86_400).unwrap();
+
+        assert_eq!(limiter.sweep_stale_store_buckets(3_600_000).unwrap(), 1);
+        assert!(store.get_token_bucket(&limiter.redis_key("idle")).unwrap().is_none());
+        assert!(store.get_token_bucket(&limiter.redis_key("busy")).unwrap().is_some());
+        assert_eq!(limiter.sweep_stale_store_buckets(3_600_000).unwrap(), 0);
+    }
+
+    #[cfg(feature = "redis-tests")]
+    #[tokio::test]
+    async fn test_redis_sweeper_removes_stale_buckets() {
+        let redis_url = std::env::var("REDIS_URL")
+            .unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+        let limiter = RateLimiter::new(Config { redis_url, ..Config::default() }).unwrap();
+        let stale = limiter.redis_key(&format!("sweep-stale-{}", uuid::Uuid::new_v4()));
+        let fresh = limiter.redis_key(&format!("sweep-fresh-{}", uuid::Uuid::new_v4()));
+
+        // A bucket idle for two hours whose TTL never ran out
+        let store = limiter.store.as_ref().unwrap();
+        let idle = TokenBucket { last_refill: now_ms() - 7_200_000, ..TokenBucket::new(5, 1.0) };
+        store.set_token_bucket(&stale, &idle, 86_400).unwrap();
+        store.set_token_bucket(&fresh, &TokenBucket::new(5, 1.0), 86_400).unwrap();
+
+        assert!(limiter.sweep_stale_store_buckets(3_600_000).unwrap() >= 1);
+        assert!(store.get_token_bucket(&stale).unwrap().is_none());
+        assert!(store.get_token_bucket(&fresh).unwrap().is_some());
+        store.delete_token_bucket(&fresh).unwrap();
+    }
+
+    #[test]
+    fn test_rule_change_applies_without_reset() {
+        let limiter = RateLimiter::new(Config::default()).unwrap();
+
+        for _ in 0..3 {
+            assert!(limiter.check_rate_limit_with_params("k", 10, 0.0).unwrap().0);
+        }
+
+        // Capacity lowered below the tokens left: clamped to the new ceiling
+        assert_eq!(limiter.check_rate_limit_with_params("k", 5, 0.0).unwrap(), (true, 4));
+
+        // Capacity raised: the token count carries over rather than refilling
+        assert_eq!(limiter.check_rate_limit_with_params("k", 100, 0.0).unwrap(), (true, 3));
+    }
+
+    #[test]
+    fn test_refill_rate_change_applies_without_reset() {
+        let limiter = RateLimiter::new(Config::default()).unwrap();
+
+        assert!(limiter.check_rate_limit_with_params("k", 1, 0.0).unwrap().0);
+        assert!(!limiter.check_rate_limit_with_params("k", 1, 0.0).unwrap().0);
+
+        // Under the old rule the bucket never refills; under the new one it does
+        assert!(!limiter.check_rate_limit_with_params("k", 1, 1.0).unwrap().0);
+        advance(&limiter, "k", 1_000);
+        assert!(limiter.check_rate_limit_with_params("k", 1, 1.0).unwrap().0);
+    }
+
+    #[test]
+    fn test_retry_after_is_clamped() {
+        let limiter = RateLimiter::new(Config { max_retry_after_secs: 120, ..Config::default() }).unwrap();
+
+        assert_eq!(limiter.retry_after_secs(0.0, 0.0, 1).unwrap(), 120);
+        assert_eq!(limiter.retry_after_secs(0.001, 0.0, 1).unwrap(), 120);
+        assert_eq!(limiter.retry_after_secs(0.1, 0.0, 1).unwrap(), 10);
+        assert_eq!(limiter.retry_after_secs(50.0, 0.0, 1).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_retry_after_waits_for_every_token_requested() {
+        let limiter = RateLimiter::new(Config::default()).unwrap();
+
+        // Ten tokens with two held at 1/s is eight seconds away, not one
+        assert_eq!(limiter.retry_after_secs(1.0, 2.0, 10).unwrap(), 8);
+        assert_eq!(limiter.retry_after_secs(1.0, 2.5, 10).unwrap(), 8);
+        assert_eq!(limiter.retry_after_secs(1.0, 0.0, 1).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_retry_after_ms_counts_from_the_tokens_held() {
+        let limiter = RateLimiter::new(Config { max_retry_after_secs: 120, ..Config::default() }).unwrap();
+
+        // Half a token short at 2/s is 250ms, where Retry-After says 1s
+        assert_eq!(limiter.retry_after_ms(2.0, 0.5, 1).unwrap(), 250);
+        assert_eq!(limiter.retry_after_ms(2.0, 0.0, 3).unwrap(), 1500);
+        assert_eq!(limiter.retry_after_ms(1000.0, 0.9999, 1).unwrap(), 1);
+        assert_eq!(limiter.retry_after_ms(0.0, 0.0, 1).unwrap(), 120_000);
+    }
+
+    #[tokio::test]
+    async fn test_peek_does_not_consume() {
+        let limiter = RateLimiter::new(Config { default_capacity: 2, ..Config::default() }).unwrap();
+
+        for _ in 0..5 {
+            assert_eq!(limiter.peek_rate_limit_shared("peek").await.unwrap(), (true, 1));
+        }
+        assert!(limiter.check_rate_limit_shared("peek").await.unwrap().0);
+        assert!(limiter.check_rate_limit_shared("peek").await.unwrap().0);
+        assert_eq!(limiter.peek_rate_limit_shared("peek").await.unwrap(), (false, 0));
+    }
+
+    #[tokio::test]
+    async fn test_shared_check_without_redis_uses_local() {
+        let limiter = RateLimiter::new(Config::default()).unwrap();
+        let (allowed, remaining) = limiter.check_rate_limit_shared("local").await.unwrap();
+        assert!(allowed);
+        assert_eq!(remaining, 99);
     }
 }
@@ -45,15 +45,141 @@
 //!                 (Lost update!)                          (Both correct)
 //! ```
 //!
+//! ## Clock Skew
+//!
+//! The Lua scripts read the Redis server's `TIME` instead of trusting each
+//! instance's clock. A stored `last_refill` more than `max_clock_skew_ms`
+//! ahead of the server clock is reset to the server time, and elapsed time
+//! is clamped to be non-negative, so a skewed writer can neither freeze nor
+//! inflate refill.
+//!
 //! ## Key Format
 //!
 //! Buckets are stored with the key format: `throttler:{key}`
+//!
+//! ## Sharding
+//!
+//! Given several URLs (`REDIS_URLS`), the client spreads buckets across
+//! independent Redis nodes without Redis Cluster. Each `throttler:{key}` is
+//! routed by a [`HashRing`], so a key always lands on the same node and
+//! adding a node remaps only the keys that move onto it. Batched operations
+//! are split per node, so the atomicity of [`RedisClient::atomic_consume_many`]
+//! holds per node rather than across the whole batch.
+//!
+//! ## Serialization Formats
+//!
+//! Buckets written through [`RedisClient::set_token_bucket`] are encoded with
+//! the configured [`SerializationFormat`]:
+//!
+//! | Format    | Encoding                         | Write path           |
+//! |-----------|----------------------------------|----------------------|
+//! | `json`    | Plain JSON object                | Lua (race-checked)   |
+//! | `msgpack` | `mp1:` marker + MessagePack map  | Plain `SET ... EX`   |
+//!
+//! Reads detect the encoding from the stored bytes rather than the local
+//! setting, so instances configured with different formats can share a
+//! Redis during a rollout. The Lua scripts read both encodings but only
+//! write JSON, so msgpack mode cannot use the race-checked write path, and
+//! [`RedisClient::atomic_consume_tokens`] always stores JSON regardless of
+//! the configured format.
+//!
+//! ## Script Errors
+//!
+//! A failing Lua script is reported with the script's name, the Redis
+//! error class (`WRONGTYPE` when a key holds a list or set, `NOSCRIPT`,
+//! `ERR` for a Lua runtime error, ...) and the script line Redis names, so
+//! e.g. `Atomic consume script failed with WRONGTYPE at line 17` points
+//! straight at the cause. `LOG_SCRIPT_SOURCE` also logs that line's source.
 
 use redis::{Client, Commands, Connection};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use crate::bucket_store::{disabled_rule_consume, BucketPage, BucketStore};
+use crate::config::Config;
+#[cfg(feature = "redis-tls")]
+use crate::config::TlsVersion;
+#[cfg(feature = "redis-tls")]
+use std::collections::HashSet;
+#[cfg(feature = "redis-tls")]
+use std::sync::Mutex;
 use crate::error::ThrottlerError;
+use crate::hash_ring::HashRing;
 use crate::token_bucket::TokenBucket;
 
+/// Marker prefixed to MessagePack-encoded buckets so readers can tell them
+/// apart from JSON (which always starts with `{`).
+const MSGPACK_MARKER: &[u8] = b"mp1:";
+
+/// Encoding used for token buckets stored in Redis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SerializationFormat {
+    /// JSON strings, readable by the Lua scripts (default)
+    #[default]
+    Json,
+    /// Marker-prefixed MessagePack, smaller and cheaper to encode
+    MsgPack,
+}
+
+impl FromStr for SerializationFormat {
+    type Err = ThrottlerError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(SerializationFormat::Json),
+            "msgpack" => Ok(SerializationFormat::MsgPack),
+            other => Err(ThrottlerError::ConfigError(format!(
+                "Invalid REDIS_SERIALIZATION value '{}'. Must be 'json' or 'msgpack'",
+                other
+            ))),
+        }
+    }
+}
+
+/// Encodes a token bucket in the given format.
+pub fn encode_bucket(bucket: &TokenBucket, format: SerializationFormat) -> Result<Vec<u8>, ThrottlerError> {
+    match format {
+        SerializationFormat::Json => serde_json::to_vec(bucket)
+            .map_err(|e| ThrottlerError::SerializationError(format!("Failed to serialize token bucket: {}", e))),
+        SerializationFormat::MsgPack => {
+            let body = rmp_serde::to_vec_named(bucket)
+                .map_err(|e| ThrottlerError::SerializationError(format!("Failed to serialize token bucket: {}", e)))?;
+            let mut data = Vec::with_capacity(MSGPACK_MARKER.len() + body.len());
+            data.extend_from_slice(MSGPACK_MARKER);
+            data.extend_from_slice(&body);
+            Ok(data)
+        }
+    }
+}
+
+/// Decodes a token bucket, detecting the format from the stored bytes.
+pub fn decode_bucket(data: &[u8]) -> Result<TokenBucket, ThrottlerError> {
+    match data.strip_prefix(MSGPACK_MARKER) {
+        Some(body) => rmp_serde::from_slice(body)
+            .map_err(|e| ThrottlerError::SerializationError(format!("Failed to deserialize token bucket: {}", e))),
+        None => serde_json::from_slice(data)
+            .map_err(|e| ThrottlerError::SerializationError(format!("Failed to deserialize token bucket: {}", e))),
+    }
+}
+
+/// Decodes a stored bucket and checks it can be used as is.
+///
+/// MessagePack carries NaN and infinite floats, and a hand-edited or
+/// foreign value may carry anything, so a bucket that decodes is still
+/// refused unless [`TokenBucket::is_consistent`]; refill math on such a
+/// bucket would poison it for good. The error describes why, for logging.
+pub fn decode_stored_bucket(data: &[u8]) -> Result<TokenBucket, String> {
+    let bucket = decode_bucket(data).map_err(|e| e.to_string())?;
+    if !bucket.is_consistent() {
+        return Err(format!(
+            "inconsistent fields (tokens {}, capacity {}, refill_rate {})",
+            bucket.tokens, bucket.capacity, bucket.refill_rate
+        ));
+    }
+    Ok(bucket)
+}
+
 /// Redis client wrapper for distributed token bucket storage.
 ///
 /// Provides methods for storing, retrieving, and atomically updating
@@ -85,61 +211,352 @@ use crate::token_bucket::TokenBucket;
 /// # }
 /// ```
 pub struct RedisClient {
-    /// The underlying Redis client
-    client: Client,
+    /// One client per Redis node (a single node unless sharding)
+    nodes: Vec<Client>,
+    /// Routes each key to its node
+    ring: HashRing,
+    /// Encoding used when writing buckets
+    format: SerializationFormat,
+    /// Socket timeout for connecting and for each command (None = no timeout)
+    op_timeout: Option<Duration>,
+    /// How far a stored `last_refill` may lead the Redis clock before it is distrusted
+    max_clock_skew_ms: u64,
+    /// Stored buckets found corrupt and replaced, see [`RedisClient::corrupt_buckets`]
+    corrupt_buckets: AtomicU64,
+    /// Replicas each write must reach before returning (0 = don't wait)
+    wait_replicas: usize,
+    /// How long `WAIT` may block for them, in ms
+    wait_timeout_ms: u64,
+    /// Log the source line a failing Lua script stopped at
+    log_script_source: bool,
+    /// Lowest TLS version a node may negotiate (None = connector default)
+    #[cfg(feature = "redis-tls")]
+    min_tls_version: Option<TlsVersion>,
+    /// Nodes whose TLS version has been checked, by address
+    #[cfg(feature = "redis-tls")]
+    tls_checked: Mutex<HashSet<String>>,
 }
 
 impl RedisClient {
     pub fn new(url: &str) -> Result<Self, ThrottlerError> {
-        let client = Client::open(url)
+        Self::sharded(&[url])
+    }
+
+    /// Creates a client spreading keys across independent Redis nodes by
+    /// consistent hashing of each key.
+    pub fn sharded<S: AsRef<str>>(urls: &[S]) -> Result<Self, ThrottlerError> {
+        if urls.is_empty() {
+            return Err(ThrottlerError::RedisError("No Redis URLs given".to_string()));
+        }
+
+        let nodes = urls.iter()
+            .map(|url| Client::open(url.as_ref()))
+            .collect::<Result<Vec<_>, _>>()
             .map_err(|e| ThrottlerError::RedisError(format!("Failed to create Redis client: {}", e)))?;
 
-        Ok(RedisClient { client })
+        Ok(RedisClient {
+            nodes,
+            ring: HashRing::new(urls),
+            format: SerializationFormat::default(),
+            op_timeout: None,
+            max_clock_skew_ms: 1000,
+            corrupt_buckets: AtomicU64::new(0),
+            wait_replicas: 0,
+            wait_timeout_ms: 0,
+            log_script_source: false,
+            #[cfg(feature = "redis-tls")]
+            min_tls_version: None,
+            #[cfg(feature = "redis-tls")]
+            tls_checked: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Creates a client using the Redis settings from the application config.
+    ///
+    /// Shards across `config.redis_urls` when set, otherwise uses `config.redis_url`.
+    pub fn from_config(config: &Config) -> Result<Self, ThrottlerError> {
+        Self::with_settings(&config.redis_nodes(), config)
+    }
+
+    /// Creates a client for `config.redis_replica_url`, if one is configured.
+    pub fn replica_from_config(config: &Config) -> Result<Option<Self>, ThrottlerError> {
+        if config.redis_replica_url.is_empty() {
+            return Ok(None);
+        }
+        Self::with_settings(&[config.redis_replica_url.as_str()], config).map(Some)
+    }
+
+    fn with_settings<S: AsRef<str>>(urls: &[S], config: &Config) -> Result<Self, ThrottlerError> {
+        let mut client = Self::sharded(urls)?;
+        client.format = config.redis_serialization;
+        if config.redis_op_timeout_ms > 0 {
+            client.op_timeout = Some(Duration::from_millis(config.redis_op_timeout_ms));
+        }
+        client.max_clock_skew_ms = config.max_clock_skew_ms;
+        client.wait_replicas = config.redis_wait_replicas;
+        client.wait_timeout_ms = config.redis_wait_timeout_ms;
+        client.log_script_source = config.log_script_source;
+        #[cfg(feature = "redis-tls")]
+        {
+            client.min_tls_version = config.redis_min_tls_version;
+        }
+        Ok(client)
     }
 
+    /// Connection to the first node
     pub fn get_connection(&self) -> Result<Connection, ThrottlerError> {
-        self.client.get_connection()
-            .map_err(|e| ThrottlerError::RedisError(format!("Failed to get Redis connection: {}", e)))
+        self.connect(&self.nodes[0])
+    }
+
+    /// Connection to the node `key` is routed to
+    pub fn connection_for(&self, key: &str) -> Result<Connection, ThrottlerError> {
+        self.connect(&self.nodes[self.node_for(key)])
+    }
+
+    /// Index of the node `key` is routed to
+    pub fn node_for(&self, key: &str) -> usize {
+        self.ring.node_for(key)
+    }
+
+    fn connect(&self, client: &Client) -> Result<Connection, ThrottlerError> {
+        #[cfg(feature = "redis-tls")]
+        self.check_tls_version(client)?;
+
+        let Some(timeout) = self.op_timeout else {
+            return client.get_connection()
+                .map_err(|e| ThrottlerError::RedisError(format!("Failed to get Redis connection: {}", e)));
+        };
+
+        let conn = client.get_connection_with_timeout(timeout)
+            .map_err(|e| ThrottlerError::RedisError(format!("Failed to get Redis connection: {}", e)))?;
+        conn.set_read_timeout(Some(timeout))
+            .and_then(|_| conn.set_write_timeout(Some(timeout)))
+            .map_err(|e| ThrottlerError::RedisError(format!("Failed to set Redis socket timeout: {}", e)))?;
+
+        Ok(conn)
+    }
+
+    /// Refuses a TLS node that cannot negotiate the minimum TLS version,
+    /// probing each node once; a failed probe is retried on the next connect
+    #[cfg(feature = "redis-tls")]
+    fn check_tls_version(&self, client: &Client) -> Result<(), ThrottlerError> {
+        let Some(min_version) = self.min_tls_version else {
+            return Ok(());
+        };
+        let redis::ConnectionAddr::TcpTls { host, port, .. } = &client.get_connection_info().addr else {
+            return Ok(());
+        };
+
+        let address = format!("{}:{}", host, port);
+        if self.tls_checked.lock().unwrap().contains(&address) {
+            return Ok(());
+        }
+        crate::redis_tls::check_min_version(host, *port, min_version, self.op_timeout)?;
+        self.tls_checked.lock().unwrap().insert(address);
+        Ok(())
+    }
+
+    /// The `WAIT` issued after writes, when replicas must acknowledge them
+    fn wait_command(&self) -> Option<redis::Cmd> {
+        if self.wait_replicas == 0 {
+            return None;
+        }
+        let mut cmd = redis::cmd("WAIT");
+        cmd.arg(self.wait_replicas).arg(self.wait_timeout_ms);
+        Some(cmd)
+    }
+
+    /// Blocks until the writes made on `conn` reach the configured number of
+    /// replicas or the `WAIT` times out.
+    ///
+    /// The write has already happened on the primary, so a shortfall is only
+    /// logged: the write may be lost if the primary fails over before it
+    /// replicates.
+    fn wait_for_replicas(&self, conn: &mut Connection) {
+        let Some(cmd) = self.wait_command() else {
+            return;
+        };
+
+        match cmd.query::<usize>(conn) {
+            Ok(acked) if acked >= self.wait_replicas => {}
+            Ok(acked) => tracing::warn!(
+                acked,
+                wanted = self.wait_replicas,
+                timeout_ms = self.wait_timeout_ms,
+                "Redis write not confirmed by enough replicas"
+            ),
+            Err(e) => tracing::warn!("Failed to confirm Redis replication: {}", e),
+        }
     }
 
     pub fn get_token_bucket(&self, key: &str) -> Result<Option<TokenBucket>, ThrottlerError> {
-        let mut conn = self.get_connection()?;
+        let mut conn = self.connection_for(key)?;
 
-        let data: Option<String> = conn.get(key)
+        let data: Option<Vec<u8>> = conn.get(key)
             .map_err(|e| ThrottlerError::RedisError(format!("Failed to get token bucket: {}", e)))?;
 
-        match data {
-            Some(ref json) => {
-                let bucket: TokenBucket = serde_json::from_str(json)
-                    .map_err(|e| ThrottlerError::SerializationError(format!("Failed to deserialize token bucket: {}", e)))?;
-                Ok(Some(bucket))
+        let Some(bytes) = data else {
+            return Ok(None);
+        };
+
+        // A bucket that cannot be trusted (e.g. written by an incompatible
+        // version) starts over rather than failing every request for the key
+        match decode_stored_bucket(&bytes) {
+            Ok(bucket) => Ok(Some(bucket)),
+            Err(reason) => {
+                self.note_corrupt_bucket(key, &reason);
+                Ok(None)
             }
-            None => Ok(None)
         }
     }
 
+    /// Lists buckets whose key starts with `prefix` using `SCAN`, reading
+    /// each page's buckets with one pipelined round of `GET`s.
+    ///
+    /// When sharding, nodes are scanned one after another; the cursor is
+    /// `{node}:{scan cursor}`. Keys that expire or turn out corrupt between
+    /// the `SCAN` and the `GET` are skipped.
+    pub fn scan_buckets(&self, prefix: &str, cursor: Option<&str>, count: usize) -> Result<BucketPage, ThrottlerError> {
+        let (mut node, mut scan_cursor) = match cursor {
+            None => (0, 0),
+            Some(cursor) => parse_scan_cursor(cursor)
+                .filter(|(node, _)| *node < self.nodes.len())
+                .ok_or_else(|| ThrottlerError::ValidationError(format!("Invalid cursor: {}", cursor)))?,
+        };
+        let pattern = format!("{}*", prefix);
+        let mut buckets = Vec::new();
+
+        while buckets.len() < count {
+            let mut conn = self.connect(&self.nodes[node])?;
+            let (next, keys): (u64, Vec<String>) = redis::cmd("SCAN")
+                .arg(scan_cursor)
+                .arg("MATCH")
+                .arg(&pattern)
+                .arg("COUNT")
+                .arg(count - buckets.len())
+                .query(&mut conn)
+                .map_err(|e| ThrottlerError::RedisError(format!("Failed to scan buckets: {}", e)))?;
+
+            if !keys.is_empty() {
+                let mut pipe = redis::pipe();
+                for key in &keys {
+                    pipe.get(key);
+                }
+                let values: Vec<Option<Vec<u8>>> = pipe.query(&mut conn)
+                    .map_err(|e| ThrottlerError::RedisError(format!("Failed to read scanned buckets: {}", e)))?;
+
+                for (key, value) in keys.into_iter().zip(values) {
+                    match value.map(|data| decode_stored_bucket(&data)) {
+                        Some(Ok(bucket)) => buckets.push((key, bucket)),
+                        Some(Err(reason)) => self.note_corrupt_bucket(&key, &reason),
+                        None => {}
+                    }
+                }
+            }
+
+            scan_cursor = next;
+            if scan_cursor == 0 {
+                node += 1;
+                if node == self.nodes.len() {
+                    return Ok(BucketPage { buckets, cursor: None });
+                }
+            }
+        }
+
+        Ok(BucketPage { buckets, cursor: Some(format!("{}:{}", node, scan_cursor)) })
+    }
+
+    /// Stored buckets found unreadable or inconsistent and replaced with
+    /// fresh ones since this client was created
+    pub fn corrupt_buckets(&self) -> u64 {
+        self.corrupt_buckets.load(Ordering::Relaxed)
+    }
+
+    fn note_corrupt_bucket(&self, key: &str, reason: &str) {
+        self.corrupt_buckets.fetch_add(1, Ordering::Relaxed);
+        tracing::warn!(key = %key, reason = %reason, "Ignoring corrupt bucket in Redis, starting it over");
+    }
+
+    /// Maps a failed Lua script call to a `RedisError` naming the script,
+    /// the Redis error class and the script line Redis blamed, if any, and
+    /// logs it (with that line's source when `log_script_source` is on).
+    fn script_error(&self, name: &str, source: &str, error: redis::RedisError) -> ThrottlerError {
+        let class = script_error_class(&error);
+        let line = script_error_line(&error);
+
+        let source_line = line
+            .filter(|_| self.log_script_source)
+            .and_then(|line| source.lines().nth(line.checked_sub(1)?))
+            .map(str::trim);
+        tracing::error!(
+            script = %name,
+            class = %class,
+            line = ?line,
+            source_line = ?source_line,
+            error = %error,
+            "Redis script failed"
+        );
+
+        let at = line.map(|line| format!(" at line {}", line)).unwrap_or_default();
+        ThrottlerError::RedisError(format!("{} script failed with {}{}: {}", name, class, at, error))
+    }
+
     pub fn set_token_bucket(&self, key: &str, bucket: &TokenBucket, ttl: usize) -> Result<(), ThrottlerError> {
-        let mut conn = self.get_connection()?;
-        
-        let json = serde_json::to_string(bucket)
-            .map_err(|e| ThrottlerError::SerializationError(format!("Failed to serialize token bucket: {}", e)))?;
-        
+        if !self.try_set_token_bucket(key, bucket, ttl)? {
+            return Err(ThrottlerError::RedisError("Token bucket update was rejected due to race condition".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Writes a bucket unless a concurrent writer stored a newer one first.
+    ///
+    /// Returns `Ok(false)` when the write was rejected as a race, so callers
+    /// can re-read the bucket and try again.
+    pub fn try_set_token_bucket(&self, key: &str, bucket: &TokenBucket, ttl: usize) -> Result<bool, ThrottlerError> {
+        let mut conn = self.connection_for(key)?;
+
+        let data = encode_bucket(bucket, self.format)?;
+
+        // The race-checked script decodes the new bucket with cjson, so msgpack uses a plain SET
+        if self.format == SerializationFormat::MsgPack {
+            let _: () = conn.set_ex(key, data, ttl as u64)
+                .map_err(|e| ThrottlerError::RedisError(format!("Failed to set token bucket: {}", e)))?;
+            self.wait_for_replicas(&mut conn);
+            return Ok(true);
+        }
+
         // Use Lua script to atomically update the bucket with proper race condition handling
         let script = r#"
             local key = KEYS[1]
             local new_data = ARGV[1]
             local ttl = tonumber(ARGV[2])
-            local current_time = tonumber(ARGV[3])
-            
+            local max_skew_ms = tonumber(ARGV[3])
+
+            -- Judge staleness by the Redis server clock, not the writer's
+            redis.replicate_commands()
+            local time = redis.call('TIME')
+            local current_time = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+            -- Buckets stored in msgpack mode carry the mp1: marker
+            local function decode(data)
+                if string.sub(data, 1, 4) == 'mp1:' then
+                    return pcall(cmsgpack.unpack, string.sub(data, 5))
+                end
+                return pcall(cjson.decode, data)
+            end
+
             local existing = redis.call('GET', key)
+            local ok, existing_bucket = false, nil
             if existing then
-                local existing_bucket = cjson.decode(existing)
+                ok, existing_bucket = decode(existing)
+            end
+            if ok and type(existing_bucket) == 'table' and type(existing_bucket.last_refill) == 'number' then
                 local new_bucket = cjson.decode(new_data)
                 
                 -- Only update if the new bucket has a more recent last_refill time
-                -- or if the existing bucket is older than expected
+                -- or if the existing bucket is older than the skew tolerance
                 if new_bucket.last_refill >= existing_bucket.last_refill or 
-                   (current_time - existing_bucket.last_refill) > 1 then
+                   (current_time - existing_bucket.last_refill) > max_skew_ms then
                     redis.call('SET', key, new_data)
                     redis.call('EXPIRE', key, ttl)
                     return 1
@@ -147,43 +564,338 @@ impl RedisClient {
                     return 0
                 end
             else
+                -- Missing, or unreadable and so not worth protecting
                 redis.call('SET', key, new_data)
                 redis.call('EXPIRE', key, ttl)
                 return 1
             end
         "#;
 
-        let current_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-
         let result: i32 = redis::Script::new(script)
             .key(key)
-            .arg(&json)
+            .arg(&data)
             .arg(ttl)
-            .arg(current_time)
+            .arg(self.max_clock_skew_ms)
             .invoke(&mut conn)
-            .map_err(|e| ThrottlerError::RedisError(format!("Failed to execute Redis script: {}", e)))?;
+            .map_err(|e| self.script_error("Bucket write", script, e))?;
 
-        if result == 0 {
-            return Err(ThrottlerError::RedisError("Token bucket update was rejected due to race condition".to_string()));
+        if result == 1 {
+            self.wait_for_replicas(&mut conn);
         }
+        Ok(result == 1)
+    }
 
-        Ok(())
+    /// Atomically moves tokens between two buckets, see
+    /// [`BucketStore::transfer_tokens`].
+    ///
+    /// Both keys must live on the same node (and, on Redis Cluster, in the
+    /// same slot, e.g. by sharing a `{hash-tag}`). Buckets of either
+    /// encoding are read, and both are stored back as JSON.
+    pub fn transfer_tokens(
+        &self,
+        from: &str,
+        to: &str,
+        tokens: f64,
+        reserved: f64,
+        capacity: u64,
+        refill_rate: f64,
+    ) -> Result<Option<f64>, ThrottlerError> {
+        let node = self.node_for(from);
+        if node != self.node_for(to) {
+            return Err(ThrottlerError::RedisError(format!(
+                "Cannot transfer tokens atomically: '{}' and '{}' are on different Redis nodes",
+                from, to
+            )));
+        }
+
+        let mut conn = self.connect(&self.nodes[node])?;
+
+        let script = r#"
+            local tokens = tonumber(ARGV[1])
+            local reserved = tonumber(ARGV[2])
+            local capacity = tonumber(ARGV[3])
+            local refill_rate = tonumber(ARGV[4])
+            local max_skew_ms = tonumber(ARGV[5])
+
+            redis.replicate_commands()
+            local time = redis.call('TIME')
+            local current_time = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+            -- Buckets stored in msgpack mode carry the mp1: marker
+            local function decode(data)
+                if string.sub(data, 1, 4) == 'mp1:' then
+                    return pcall(cmsgpack.unpack, string.sub(data, 5))
+                end
+                return pcall(cjson.decode, data)
+            end
+
+            -- A bucket refilled up to now at its own rate; missing or
+            -- unreadable ones start full, as a consume would create them
+            local function load(key)
+                local existing = redis.call('GET', key)
+                if existing then
+                    local ok, bucket = decode(existing)
+                    if ok and type(bucket) == 'table'
+                        and type(bucket.tokens) == 'number' and type(bucket.capacity) == 'number'
+                        and bucket.tokens >= 0 and bucket.tokens <= bucket.capacity
+                        and bucket.capacity < math.huge
+                        and type(bucket.refill_rate) == 'number'
+                        and bucket.refill_rate >= 0 and bucket.refill_rate < math.huge
+                        and type(bucket.last_refill) == 'number' then
+                        if bucket.last_refill - current_time > max_skew_ms then
+                            bucket.last_refill = current_time
+                        end
+                        local elapsed = math.max(0, current_time - bucket.last_refill)
+                        bucket.tokens = math.min(bucket.capacity, bucket.tokens + elapsed * bucket.refill_rate / 1000)
+                        bucket.last_refill = current_time
+                        return bucket
+                    end
+                end
+                return {
+                    tokens = capacity,
+                    capacity = capacity,
+                    refill_rate = refill_rate,
+                    last_refill = current_time
+                }
+            end
+
+            -- Kept until it would have refilled from empty
+            local function store(key, bucket)
+                local ttl_ms = 3600000
+                if bucket.refill_rate > 0 then
+                    ttl_ms = math.max(1, math.ceil(bucket.capacity / bucket.refill_rate)) * 1000
+                end
+                redis.call('SET', key, cjson.encode(bucket), 'PX', ttl_ms)
+            end
+
+            local source = load(KEYS[1])
+            if source.tokens - reserved < tokens then
+                return {0, '0'}
+            end
+            local destination = load(KEYS[2])
+
+            local moved = math.max(0, math.min(tokens, destination.capacity - destination.tokens))
+            source.tokens = source.tokens - moved
+            destination.tokens = destination.tokens + moved
+            store(KEYS[1], source)
+            store(KEYS[2], destination)
+
+            -- Integer replies would truncate a fractional amount
+            return {1, tostring(moved)}
+        "#;
+
+        let (completed, moved): (i64, String) = redis::Script::new(script)
+            .key(from)
+            .key(to)
+            .arg(tokens)
+            .arg(reserved)
+            .arg(capacity)
+            .arg(refill_rate)
+            .arg(self.max_clock_skew_ms)
+            .invoke(&mut conn)
+            .map_err(|e| self.script_error("Token transfer", script, e))?;
+
+        if completed != 1 {
+            return Ok(None);
+        }
+        self.wait_for_replicas(&mut conn);
+
+        moved.parse()
+            .map(Some)
+            .map_err(|_| ThrottlerError::RedisError("Invalid transfer amount from Redis".to_string()))
     }
 
     pub fn delete_token_bucket(&self, key: &str) -> Result<(), ThrottlerError> {
-        let mut conn = self.get_connection()?;
+        let mut conn = self.connection_for(key)?;
         
         let _: () = conn.del(key)
             .map_err(|e| ThrottlerError::RedisError(format!("Failed to delete token bucket: {}", e)))?;
+        self.wait_for_replicas(&mut conn);
         
         Ok(())
     }
 
+    /// Delete several token buckets with a single `DEL` per node
+    pub fn delete_token_buckets(&self, keys: &[String]) -> Result<(), ThrottlerError> {
+        for (node, indexes) in self.group_by_node(keys.iter().map(String::as_str)) {
+            let node_keys: Vec<&String> = indexes.iter().map(|&i| &keys[i]).collect();
+            let mut conn = self.connect(&self.nodes[node])?;
+
+            let _: () = conn.del(node_keys)
+                .map_err(|e| ThrottlerError::RedisError(format!("Failed to delete token buckets: {}", e)))?;
+            self.wait_for_replicas(&mut conn);
+        }
+
+        Ok(())
+    }
+
+    /// Takes a concurrency slot in the sorted set at `key`, scored by when
+    /// the slot lapses on the Redis clock. Lapsed slots are pruned first and
+    /// the set expires once its newest slot would.
+    pub fn acquire_slot(&self, key: &str, slot_id: &str, limit: u64, ttl_ms: u64) -> Result<Option<u64>, ThrottlerError> {
+        let mut conn = self.connection_for(key)?;
+
+        let script = r#"
+            local key = KEYS[1]
+            local slot_id = ARGV[1]
+            local limit = tonumber(ARGV[2])
+            local ttl_ms = tonumber(ARGV[3])
+
+            redis.replicate_commands()
+            local time = redis.call('TIME')
+            local current_time = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+
+            redis.call('ZREMRANGEBYSCORE', key, '-inf', current_time)
+            local held = redis.call('ZCARD', key)
+            if held >= limit then
+                return -1
+            end
+
+            redis.call('ZADD', key, current_time + ttl_ms, slot_id)
+            redis.call('PEXPIRE', key, ttl_ms)
+            return held + 1
+        "#;
+
+        let held: i64 = redis::Script::new(script)
+            .key(key)
+            .arg(slot_id)
+            .arg(limit)
+            .arg(ttl_ms)
+            .invoke(&mut conn)
+            .map_err(|e| self.script_error("Slot acquire", script, e))?;
+        if held < 0 {
+            return Ok(None);
+        }
+        self.wait_for_replicas(&mut conn);
+
+        Ok(Some(held as u64))
+    }
+
+    /// Removes a concurrency slot from the sorted set at `key`
+    pub fn release_slot(&self, key: &str, slot_id: &str) -> Result<bool, ThrottlerError> {
+        let mut conn = self.connection_for(key)?;
+
+        let removed: u64 = conn.zrem(key, slot_id)
+            .map_err(|e| ThrottlerError::RedisError(format!("Failed to release slot: {}", e)))?;
+        self.wait_for_replicas(&mut conn);
+
+        Ok(removed > 0)
+    }
+
+    /// Charges `cost` against the quota counter at `key`, which expires
+    /// when its period ends at `reset_at_ms`
+    pub fn consume_quota(&self, key: &str, cost: u64, limit: u64, reset_at_ms: u64) -> Result<(bool, u64), ThrottlerError> {
+        let mut conn = self.connection_for(key)?;
+
+        let script = r#"
+            local key = KEYS[1]
+            local cost = tonumber(ARGV[1])
+            local limit = tonumber(ARGV[2])
+            local reset_at = tonumber(ARGV[3])
+
+            local used = tonumber(redis.call('GET', key) or '0')
+            if used + cost > limit then
+                return {0, used}
+            end
+
+            used = redis.call('INCRBY', key, cost)
+            redis.call('PEXPIREAT', key, reset_at)
+            return {1, used}
+        "#;
+
+        let (consumed, used): (i64, u64) = redis::Script::new(script)
+            .key(key)
+            .arg(cost)
+            .arg(limit)
+            .arg(reset_at_ms)
+            .invoke(&mut conn)
+            .map_err(|e| self.script_error("Quota consume", script, e))?;
+        if consumed == 1 {
+            self.wait_for_replicas(&mut conn);
+        }
+
+        Ok((consumed == 1, used))
+    }
+
+    /// Reads the quota counter at `key` without charging it or moving its
+    /// expiry
+    pub fn quota_used(&self, key: &str) -> Result<u64, ThrottlerError> {
+        let mut conn = self.connection_for(key)?;
+
+        let (used, ttl_ms): (Option<u64>, i64) = redis::pipe()
+            .atomic()
+            .get(key)
+            .cmd("PTTL").arg(key)
+            .query(&mut conn)
+            .map_err(|e| ThrottlerError::RedisError(format!("Failed to read quota: {}", e)))?;
+
+        // -2: the counter is gone, its period over or never charged
+        Ok(if ttl_ms == -2 { 0 } else { used.unwrap_or(0) })
+    }
+
+    /// Adds per-client `(allowed, throttled)` request counts to the fleet
+    /// counter hashes `<hash_key>:allowed` and `<hash_key>:throttled`, in
+    /// one transaction so a failed flush adds nothing
+    pub fn add_request_counts(&self, hash_key: &str, counts: &HashMap<String, (u64, u64)>) -> Result<(), ThrottlerError> {
+        if counts.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.connection_for(hash_key)?;
+        let (allowed_key, throttled_key) = fleet_hash_keys(hash_key);
+
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for (client_id, (allowed, throttled)) in counts {
+            if *allowed > 0 {
+                pipe.hincr(&allowed_key, client_id, *allowed).ignore();
+            }
+            if *throttled > 0 {
+                pipe.hincr(&throttled_key, client_id, *throttled).ignore();
+            }
+        }
+        let _: () = pipe.query(&mut conn)
+            .map_err(|e| ThrottlerError::RedisError(format!("Failed to flush request counts: {}", e)))?;
+        self.wait_for_replicas(&mut conn);
+
+        Ok(())
+    }
+
+    /// Per-client `(allowed, throttled)` request counts flushed by every
+    /// instance to the fleet counter hashes at `hash_key`
+    pub fn request_counts(&self, hash_key: &str) -> Result<HashMap<String, (u64, u64)>, ThrottlerError> {
+        let mut conn = self.connection_for(hash_key)?;
+        let (allowed_key, throttled_key) = fleet_hash_keys(hash_key);
+
+        let (allowed, throttled): (HashMap<String, u64>, HashMap<String, u64>) = redis::pipe()
+            .hgetall(&allowed_key)
+            .hgetall(&throttled_key)
+            .query(&mut conn)
+            .map_err(|e| ThrottlerError::RedisError(format!("Failed to read request counts: {}", e)))?;
+
+        let mut counts: HashMap<String, (u64, u64)> = HashMap::new();
+        for (client_id, count) in allowed {
+            counts.entry(client_id).or_default().0 = count;
+        }
+        for (client_id, count) in throttled {
+            counts.entry(client_id).or_default().1 = count;
+        }
+        Ok(counts)
+    }
+
+    /// Positions of `keys` grouped by the node each is routed to, in node order
+    fn group_by_node<'a>(&self, keys: impl Iterator<Item = &'a str>) -> Vec<(usize, Vec<usize>)> {
+        let mut groups: Vec<Vec<usize>> = vec![Vec::new(); self.nodes.len()];
+        for (i, key) in keys.enumerate() {
+            groups[self.node_for(key)].push(i);
+        }
+        groups.into_iter()
+            .enumerate()
+            .filter(|(_, indexes)| !indexes.is_empty())
+            .collect()
+    }
+
     pub fn exists(&self, key: &str) -> Result<bool, ThrottlerError> {
-        let mut conn = self.get_connection()?;
+        let mut conn = self.connection_for(key)?;
         
         let exists: bool = conn.exists(key)
             .map_err(|e| ThrottlerError::RedisError(format!("Failed to check key existence: {}", e)))?;
@@ -191,106 +903,808 @@ impl RedisClient {
         Ok(exists)
     }
 
+    /// Pings every node; fails if any of them is unreachable
     pub fn ping(&self) -> Result<String, ThrottlerError> {
-        let mut conn = self.get_connection()?;
-        
-        let pong: String = redis::cmd("PING")
-            .query(&mut conn)
-            .map_err(|e| ThrottlerError::RedisError(format!("Redis ping failed: {}", e)))?;
-        
+        let mut pong = String::new();
+        for node in &self.nodes {
+            let mut conn = self.connect(node)?;
+
+            pong = redis::cmd("PING")
+                .query(&mut conn)
+                .map_err(|e| ThrottlerError::RedisError(format!("Redis ping failed: {}", e)))?;
+        }
+
         Ok(pong)
     }
 
     pub fn atomic_consume_tokens(&self, key: &str, tokens_to_consume: u32, rule: &crate::rate_limit_config::RateLimitRule) -> Result<(bool, TokenBucket), ThrottlerError> {
-        let mut conn = self.get_connection()?;
+        let requests = [(key.to_string(), tokens_to_consume)];
+        self.atomic_consume_many(&requests, rule)?
+            .pop()
+            .ok_or_else(|| ThrottlerError::RedisError("Invalid response from Redis script".to_string()))
+    }
+
+    /// Atomically consumes tokens from several buckets in one round trip.
+    ///
+    /// Each `(key, tokens)` pair is evaluated independently against `rule`:
+    /// one key being denied does not affect the others. Results are returned
+    /// in the same order as `requests`. Under a disabled rule every request
+    /// is allowed and Redis is not touched.
+    ///
+    /// All keys on one node are passed as `KEYS` to a single script, so on
+    /// Redis Cluster they must hash to the same slot (e.g. share a
+    /// `{hash-tag}`). When sharding, each node runs its own script.
+    pub fn atomic_consume_many(&self, requests: &[(String, u32)], rule: &crate::rate_limit_config::RateLimitRule) -> Result<Vec<(bool, TokenBucket)>, ThrottlerError> {
+        if !rule.enabled {
+            return Ok(requests.iter().map(|_| disabled_rule_consume(rule)).collect());
+        }
+        if self.nodes.len() == 1 {
+            return self.consume_on_node(&self.nodes[0], requests, rule);
+        }
+
+        let mut results: Vec<Option<(bool, TokenBucket)>> = vec![None; requests.len()];
+        for (node, indexes) in self.group_by_node(requests.iter().map(|(key, _)| key.as_str())) {
+            let node_requests: Vec<(String, u32)> = indexes.iter().map(|&i| requests[i].clone()).collect();
+            let node_results = self.consume_on_node(&self.nodes[node], &node_requests, rule)?;
+            for (i, result) in indexes.into_iter().zip(node_results) {
+                results[i] = Some(result);
+            }
+        }
+
+        results.into_iter()
+            .map(|result| result.ok_or_else(|| ThrottlerError::RedisError("Invalid response from Redis script".to_string())))
+            .collect()
+    }
+
+    /// Runs the consume script for `requests`, all routed to `node`
+    fn consume_on_node(&self, node: &Client, requests: &[(String, u32)], rule: &crate::rate_limit_config::RateLimitRule) -> Result<Vec<(bool, TokenBucket)>, ThrottlerError> {
+        if requests.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut conn = self.connect(node)?;
 
         let window_ms = rule.window_size.as_millis() as u64;
 
         let script = r#"
-            local key = KEYS[1]
-            local tokens_to_consume = tonumber(ARGV[1])
-            local capacity = tonumber(ARGV[2])
-            local refill_rate = tonumber(ARGV[3])
-            local window_ms = tonumber(ARGV[4])
-            local current_time = tonumber(ARGV[5])
+            local capacity = tonumber(ARGV[1])
+            local refill_per_ms = tonumber(ARGV[2])
+            local window_ms = tonumber(ARGV[3])
+            local max_skew_ms = tonumber(ARGV[4])
+            local results = {}
 
-            local existing = redis.call('GET', key)
-            local bucket
+            -- The Redis server clock is authoritative so that instances with
+            -- skewed clocks agree on elapsed time (effects replication makes
+            -- TIME safe to combine with writes on older servers)
+            redis.replicate_commands()
+            local time = redis.call('TIME')
+            local current_time = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
 
-            if existing then
-                bucket = cjson.decode(existing)
-
-                -- Calculate tokens to add based on time elapsed
-                local time_elapsed = current_time - bucket.last_refill
-                if time_elapsed > 0 then
-                    local tokens_to_add = math.floor(time_elapsed * refill_rate / window_ms)
-                    bucket.tokens = math.min(capacity, bucket.tokens + tokens_to_add)
-                    bucket.last_refill = current_time
+            -- Buckets stored in msgpack mode carry the mp1: marker
+            local function decode(data)
+                if string.sub(data, 1, 4) == 'mp1:' then
+                    return pcall(cmsgpack.unpack, string.sub(data, 5))
                 end
-            else
-                bucket = {
-                    tokens = capacity,
-                    capacity = capacity,
-                    refill_rate = refill_rate,
-                    window_ms = window_ms,
-                    last_refill = current_time
-                }
+                return pcall(cjson.decode, data)
             end
 
-            local success = false
-            if bucket.tokens >= tokens_to_consume then
-                bucket.tokens = bucket.tokens - tokens_to_consume
-                success = true
-            end
+            for i, key in ipairs(KEYS) do
+                local tokens_to_consume = tonumber(ARGV[4 + i])
+                local existing = redis.call('GET', key)
+                local bucket
+                local corrupt = 0
 
-            local bucket_json = cjson.encode(bucket)
-            redis.call('SET', key, bucket_json)
-            redis.call('EXPIRE', key, math.ceil(window_ms / 1000))
+                -- Anything unreadable or inconsistent is replaced by a fresh
+                -- bucket (and reported) rather than failing the script
+                if existing then
+                    local ok, decoded = decode(existing)
+                    -- (both decoders accept nan and inf, which fail these too)
+                    if ok and type(decoded) == 'table'
+                        and type(decoded.tokens) == 'number' and type(decoded.capacity) == 'number'
+                        and decoded.tokens >= 0 and decoded.tokens <= decoded.capacity
+                        and decoded.capacity < math.huge
+                        and type(decoded.refill_rate) == 'number'
+                        and decoded.refill_rate >= 0 and decoded.refill_rate < math.huge
+                        and type(decoded.last_refill) == 'number' then
+                        bucket = decoded
+                    else
+                        corrupt = 1
+                    end
+                end
 
-            return {success and 1 or 0, bucket_json}
-        "#;
+                if bucket then
+                    -- A stamp further ahead than the tolerance came from a fast
+                    -- clock; distrust it rather than freezing refill until then
+                    if bucket.last_refill - current_time > max_skew_ms then
+                        bucket.last_refill = current_time
+                    end
 
-        let current_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
+                    -- Refill for the ms elapsed (never negative), keeping
+                    -- fractions so short intervals are not rounded away
+                    local time_elapsed = math.max(0, current_time - bucket.last_refill)
+                    if time_elapsed > 0 then
+                        local tokens_to_add = time_elapsed * refill_per_ms
+                        bucket.tokens = math.min(capacity, bucket.tokens + tokens_to_add)
+                        bucket.last_refill = current_time
+                    end
+                else
+                    bucket = {
+                        tokens = capacity,
+                        capacity = capacity,
+                        refill_rate = refill_per_ms * 1000,
+                        window_ms = window_ms,
+                        last_refill = current_time
+                    }
+                end
 
-        let result: Vec<redis::Value> = redis::Script::new(script)
-            .key(key)
-            .arg(tokens_to_consume)
+                local success = false
+                if bucket.tokens >= tokens_to_consume then
+                    bucket.tokens = bucket.tokens - tokens_to_consume
+                    success = true
+                end
+
+                local bucket_json = cjson.encode(bucket)
+                redis.call('SET', key, bucket_json)
+                redis.call('PEXPIRE', key, window_ms)
+
+                table.insert(results, success and 1 or 0)
+                table.insert(results, bucket_json)
+                table.insert(results, corrupt)
+            end
+
+            return results
+        "#;
+
+        let consume = redis::Script::new(script);
+        let mut invocation = consume.prepare_invoke();
+        for (key, _) in requests {
+            invocation.key(key);
+        }
+        invocation
             .arg(rule.burst_capacity)
-            .arg(rule.requests_per_second)
+            .arg(rule.refill_rate_ms())
             .arg(window_ms)
-            .arg(current_time)
+            .arg(self.max_clock_skew_ms);
+        for (_, tokens) in requests {
+            invocation.arg(*tokens);
+        }
+
+        let result: Vec<redis::Value> = invocation
             .invoke(&mut conn)
-            .map_err(|e| ThrottlerError::RedisError(format!("Failed to execute atomic consume script: {}", e)))?;
+            .map_err(|e| self.script_error("Atomic consume", script, e))?;
+        self.wait_for_replicas(&mut conn);
 
-        if result.len() != 2 {
+        if result.len() != requests.len() * 3 {
             return Err(ThrottlerError::RedisError("Invalid response from Redis script".to_string()));
         }
 
-        let success = match &result[0] {
-            redis::Value::Int(val) => val == &1,
-            _ => return Err(ThrottlerError::RedisError("Invalid success value from Redis".to_string())),
-        };
-
-        let bucket_json = match &result[1] {
-            redis::Value::Data(data) => std::str::from_utf8(data.as_slice())
-                .map_err(|e| ThrottlerError::RedisError(format!("Invalid UTF-8 in bucket data: {}", e)))?,
-            redis::Value::Bulk(items) if !items.is_empty() => {
-                if let redis::Value::Data(data) = &items[0] {
-                    std::str::from_utf8(data.as_slice())
-                        .map_err(|e| ThrottlerError::RedisError(format!("Invalid UTF-8 in bucket data: {}", e)))?
-                } else {
-                    return Err(ThrottlerError::RedisError("Invalid bucket data format from Redis".to_string()));
+        result
+            .chunks(3)
+            .zip(requests)
+            .map(|(entry, (key, _))| {
+                let success = match &entry[0] {
+                    redis::Value::Int(val) => val == &1,
+                    _ => return Err(ThrottlerError::RedisError("Invalid success value from Redis".to_string())),
+                };
+                if entry[2] == redis::Value::Int(1) {
+                    self.note_corrupt_bucket(key, "unreadable or inconsistent in consume script");
                 }
+                Ok((success, bucket_from_value(&entry[1])?))
+            })
+            .collect()
+    }
+}
+
+impl BucketStore for RedisClient {
+    fn get_token_bucket(&self, key: &str) -> Result<Option<TokenBucket>, ThrottlerError> {
+        RedisClient::get_token_bucket(self, key)
+    }
+
+    fn try_set_token_bucket(&self, key: &str, bucket: &TokenBucket, ttl: usize) -> Result<bool, ThrottlerError> {
+        RedisClient::try_set_token_bucket(self, key, bucket, ttl)
+    }
+
+    fn atomic_consume_tokens(&self, key: &str, tokens_to_consume: u32, rule: &crate::rate_limit_config::RateLimitRule) -> Result<(bool, TokenBucket), ThrottlerError> {
+        RedisClient::atomic_consume_tokens(self, key, tokens_to_consume, rule)
+    }
+
+    fn transfer_tokens(
+        &self,
+        from: &str,
+        to: &str,
+        tokens: f64,
+        reserved: f64,
+        capacity: u64,
+        refill_rate: f64,
+    ) -> Result<Option<f64>, ThrottlerError> {
+        RedisClient::transfer_tokens(self, from, to, tokens, reserved, capacity, refill_rate)
+    }
+
+    fn delete_token_bucket(&self, key: &str) -> Result<(), ThrottlerError> {
+        RedisClient::delete_token_bucket(self, key)
+    }
+
+    fn delete_token_buckets(&self, keys: &[String]) -> Result<(), ThrottlerError> {
+        RedisClient::delete_token_buckets(self, keys)
+    }
+
+    fn ping(&self) -> Result<String, ThrottlerError> {
+        RedisClient::ping(self)
+    }
+
+    fn corrupt_buckets(&self) -> u64 {
+        RedisClient::corrupt_buckets(self)
+    }
+
+    fn scan_buckets(&self, prefix: &str, cursor: Option<&str>, count: usize) -> Result<BucketPage, ThrottlerError> {
+        RedisClient::scan_buckets(self, prefix, cursor, count)
+    }
+
+    fn acquire_slot(&self, key: &str, slot_id: &str, limit: u64, ttl_ms: u64) -> Result<Option<u64>, ThrottlerError> {
+        RedisClient::acquire_slot(self, key, slot_id, limit, ttl_ms)
+    }
+
+    fn release_slot(&self, key: &str, slot_id: &str) -> Result<bool, ThrottlerError> {
+        RedisClient::release_slot(self, key, slot_id)
+    }
+
+    fn consume_quota(&self, key: &str, cost: u64, limit: u64, reset_at_ms: u64) -> Result<(bool, u64), ThrottlerError> {
+        RedisClient::consume_quota(self, key, cost, limit, reset_at_ms)
+    }
+
+    fn quota_used(&self, key: &str, _reset_at_ms: u64) -> Result<u64, ThrottlerError> {
+        // The key already names its period
+        RedisClient::quota_used(self, key)
+    }
+}
+
+/// Splits a `{node}:{scan cursor}` cursor from [`RedisClient::scan_buckets`]
+fn parse_scan_cursor(cursor: &str) -> Option<(usize, u64)> {
+    let (node, scan_cursor) = cursor.split_once(':')?;
+    Some((node.parse().ok()?, scan_cursor.parse().ok()?))
+}
+
+/// The allowed and throttled counter hashes under `hash_key`; both live on
+/// `hash_key`'s node, so one transaction updates them together
+fn fleet_hash_keys(hash_key: &str) -> (String, String) {
+    (format!("{}:allowed", hash_key), format!("{}:throttled", hash_key))
+}
+
+/// Error replies a failing `redis.call` passes through a script; older
+/// servers wrap them in a generic `ERR Error running script ...`
+const SCRIPT_ERROR_CLASSES: [&str; 6] = ["WRONGTYPE", "NOSCRIPT", "OOM", "READONLY", "BUSY", "NOPERM"];
+
+/// The Redis error class behind a failed script call: the reply's code
+/// (`WRONGTYPE`, `NOSCRIPT`, ...), a class wrapped inside an `ERR`, `ERR`
+/// alone for Lua runtime errors, or the client-side category (e.g. an I/O
+/// error) when Redis never replied.
+fn script_error_class(error: &redis::RedisError) -> String {
+    match error.code() {
+        Some("ERR") => {
+            let detail = error.detail().unwrap_or_default();
+            SCRIPT_ERROR_CLASSES.iter()
+                .find(|class| detail.split(|c: char| !c.is_ascii_alphabetic()).any(|word| word == **class))
+                .map_or_else(|| "ERR".to_string(), |class| class.to_string())
+        }
+        Some(code) => code.to_string(),
+        None => error.category().to_string(),
+    }
+}
+
+/// The script line Redis blamed for an error (`... @user_script:12 ...`)
+fn script_error_line(error: &redis::RedisError) -> Option<usize> {
+    let detail = error.detail()?;
+    let (_, rest) = detail.split_once("user_script:")?;
+    let digits = rest.split(|c: char| !c.is_ascii_digit()).next()?;
+    digits.parse().ok()
+}
+
+/// Extracts a JSON-encoded bucket returned by a Lua script.
+fn bucket_from_value(value: &redis::Value) -> Result<TokenBucket, ThrottlerError> {
+    let bucket_json = match value {
+        redis::Value::Data(data) => std::str::from_utf8(data.as_slice())
+            .map_err(|e| ThrottlerError::RedisError(format!("Invalid UTF-8 in bucket data: {}", e)))?,
+        redis::Value::Bulk(items) if !items.is_empty() => {
+            if let redis::Value::Data(data) = &items[0] {
+                std::str::from_utf8(data.as_slice())
+                    .map_err(|e| ThrottlerError::RedisError(format!("Invalid UTF-8 in bucket data: {}", e)))?
+            } else {
+                return Err(ThrottlerError::RedisError("Invalid bucket data format from Redis".to_string()));
             }
-            _ => return Err(ThrottlerError::RedisError("Invalid bucket data from Redis".to_string())),
+        }
+        _ => return Err(ThrottlerError::RedisError("Invalid bucket data from Redis".to_string())),
+    };
+
+    serde_json::from_str(bucket_json)
+        .map_err(|e| ThrottlerError::SerializationError(format!("Failed to deserialize updated bucket: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The error `redis` raises for an error reply line from the server
+    fn server_error(reply: &str) -> redis::RedisError {
+        redis::parse_redis_value(format!("-{}\r\n", reply).as_bytes()).unwrap_err()
+    }
+
+    #[test]
+    fn test_script_errors_name_class_and_line() {
+        let client = RedisClient::new("redis://127.0.0.1:6379").unwrap();
+        let source = "\n            local a = 1\n            redis.call('GET', KEYS[1])\n";
+
+        // Redis 7 passes the class through
+        let error = server_error(
+            "WRONGTYPE Operation against a key holding the wrong kind of value script: 5ad3, on @user_script:3."
+        );
+        assert_eq!(script_error_class(&error), "WRONGTYPE");
+        assert_eq!(script_error_line(&error), Some(3));
+        let message = client.script_error("Atomic consume", source, error).to_string();
+        assert!(message.contains("Atomic consume script failed with WRONGTYPE at line 3"), "{}", message);
+
+        // Older servers wrap it in a generic ERR
+        let error = server_error(
+            "ERR Error running script (call to f_5ad3): @user_script:3: WRONGTYPE Operation against a key holding the wrong kind of value"
+        );
+        assert_eq!(script_error_class(&error), "WRONGTYPE");
+        assert_eq!(script_error_line(&error), Some(3));
+
+        // A Lua runtime error is an ERR of its own
+        let error = server_error("ERR user_script:2: attempt to compare nil with number script: 5ad3, on @user_script:2.");
+        assert_eq!(script_error_class(&error), "ERR");
+        assert_eq!(script_error_line(&error), Some(2));
+
+        let error = server_error("NOSCRIPT No matching script. Please use EVAL.");
+        assert_eq!(script_error_class(&error), "NOSCRIPT");
+        assert_eq!(script_error_line(&error), None);
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let bucket = TokenBucket::new(100, 2.5);
+        let data = encode_bucket(&bucket, SerializationFormat::Json).unwrap();
+        assert_eq!(data[0], b'{');
+
+        let decoded = decode_bucket(&data).unwrap();
+        assert_eq!(decoded.capacity, 100);
+        assert_eq!(decoded.refill_rate, 2.5);
+        assert_eq!(decoded.last_refill, bucket.last_refill);
+    }
+
+    #[test]
+    fn test_msgpack_round_trip() {
+        let mut bucket = TokenBucket::new(100, 2.5);
+        bucket.tokens = 42.25;
+        let data = encode_bucket(&bucket, SerializationFormat::MsgPack).unwrap();
+        assert!(data.starts_with(MSGPACK_MARKER));
+
+        let decoded = decode_bucket(&data).unwrap();
+        assert_eq!(decoded.capacity, 100);
+        assert_eq!(decoded.tokens, 42.25);
+        assert_eq!(decoded.last_refill, bucket.last_refill);
+    }
+
+    #[test]
+    fn test_msgpack_is_smaller_than_json() {
+        let bucket = TokenBucket::new(100, 10.0);
+        let json = encode_bucket(&bucket, SerializationFormat::Json).unwrap();
+        let msgpack = encode_bucket(&bucket, SerializationFormat::MsgPack).unwrap();
+        assert!(msgpack.len() < json.len());
+    }
+
+    #[test]
+    fn test_decode_rejects_garbage() {
+        assert!(decode_bucket(b"not a bucket").is_err());
+        assert!(decode_bucket(b"mp1:\xff\xff").is_err());
+    }
+
+    #[test]
+    fn test_stored_bucket_with_non_finite_fields_is_refused() {
+        let poisoned = [
+            TokenBucket { tokens: f64::NAN, ..TokenBucket::new(100, 10.0) },
+            TokenBucket { tokens: f64::INFINITY, ..TokenBucket::new(100, 10.0) },
+            TokenBucket::new(100, f64::NAN),
+            TokenBucket::new(100, f64::INFINITY),
+            TokenBucket::new(100, f64::NEG_INFINITY),
+        ];
+        for bucket in poisoned {
+            // MessagePack stores the non-finite value faithfully
+            let data = encode_bucket(&bucket, SerializationFormat::MsgPack).unwrap();
+            assert!(decode_bucket(&data).is_ok());
+            let reason = decode_stored_bucket(&data).unwrap_err();
+            assert!(reason.contains("inconsistent"), "{}", reason);
+        }
+
+        // JSON has no NaN; out-of-range numbers do not decode at all
+        let overflow = br#"{"tokens": 1e999, "capacity": 100, "refill_rate": 10, "last_refill": 0}"#;
+        assert!(decode_stored_bucket(overflow).is_err());
+
+        let healthy = encode_bucket(&TokenBucket::new(100, 10.0), SerializationFormat::MsgPack).unwrap();
+        assert_eq!(decode_stored_bucket(&healthy).unwrap().tokens, 100.0);
+    }
+
+    #[test]
+    fn test_parse_serialization_format() {
+        assert_eq!("json".parse::<SerializationFormat>().unwrap(), SerializationFormat::Json);
+        assert_eq!("MsgPack".parse::<SerializationFormat>().unwrap(), SerializationFormat::MsgPack);
+        assert!("xml".parse::<SerializationFormat>().is_err());
+    }
+
+    #[test]
+    fn test_parse_scan_cursor() {
+        assert_eq!(parse_scan_cursor("0:0"), Some((0, 0)));
+        assert_eq!(parse_scan_cursor("2:17408"), Some((2, 17408)));
+        assert_eq!(parse_scan_cursor("17408"), None);
+        assert_eq!(parse_scan_cursor("a:1"), None);
+    }
+
+    #[test]
+    fn test_sharded_client_routes_keys_across_nodes() {
+        let urls = ["redis://10.0.0.1:6379", "redis://10.0.0.2:6379", "redis://10.0.0.3:6379"];
+        let client = RedisClient::sharded(&urls).unwrap();
+        let again = RedisClient::sharded(&urls).unwrap();
+
+        let mut used = [false; 3];
+        for i in 0..100 {
+            let key = format!("throttler:user-{}", i);
+            assert_eq!(client.node_for(&key), again.node_for(&key));
+            used[client.node_for(&key)] = true;
+        }
+        assert_eq!(used, [true; 3]);
+
+        // One node: everything on it
+        let single = RedisClient::new(urls[0]).unwrap();
+        assert_eq!(single.node_for("throttler:user-1"), 0);
+        assert!(RedisClient::sharded::<&str>(&[]).is_err());
+    }
+
+    #[test]
+    fn test_wait_command_uses_configured_replicas() {
+        let config = Config {
+            redis_url: "redis://127.0.0.1:6379".to_string(),
+            redis_wait_replicas: 2,
+            redis_wait_timeout_ms: 150,
+            ..Config::default()
         };
+        let client = RedisClient::from_config(&config).unwrap();
+        let cmd = client.wait_command().unwrap();
+        assert_eq!(cmd.get_packed_command(), redis::cmd("WAIT").arg(2).arg(150).get_packed_command());
+
+        // Off by default
+        assert!(RedisClient::new("redis://127.0.0.1:6379").unwrap().wait_command().is_none());
+    }
+}
+
+/// Tests against a live Redis at `REDIS_URL` (default `redis://127.0.0.1:6379`).
+/// Run with `cargo test --features redis-tests`.
+#[cfg(all(test, feature = "redis-tests"))]
+mod redis_tests {
+    use super::*;
+    use crate::rate_limit_config::RateLimitRule;
+    use std::time::Duration;
+
+    fn test_client() -> RedisClient {
+        let url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+        RedisClient::new(&url).unwrap()
+    }
+
+    fn unique_key(name: &str) -> String {
+        format!("throttler:test:{}:{}", name, uuid::Uuid::new_v4())
+    }
 
-        let bucket: TokenBucket = serde_json::from_str(bucket_json)
-            .map_err(|e| ThrottlerError::SerializationError(format!("Failed to deserialize updated bucket: {}", e)))?;
+    /// Times the server has run `WAIT`, from `INFO commandstats`
+    fn wait_calls(client: &RedisClient) -> u64 {
+        let info: String = redis::cmd("INFO").arg("commandstats")
+            .query(&mut client.get_connection().unwrap())
+            .unwrap();
+        info.lines()
+            .find_map(|line| line.strip_prefix("cmdstat_wait:calls="))
+            .and_then(|rest| rest.split(',').next())
+            .and_then(|calls| calls.parse().ok())
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_transfer_script_moves_clamps_and_refuses() {
+        let client = test_client();
+        let (pool, member) = (unique_key("pool"), unique_key("member"));
+        client.set_token_bucket(&member, &TokenBucket { tokens: 2.0, ..TokenBucket::new(10, 0.0) }, 60).unwrap();
+
+        assert_eq!(client.transfer_tokens(&pool, &member, 4.0, 0.0, 10, 0.0).unwrap(), Some(4.0));
+        assert_eq!(client.get_token_bucket(&pool).unwrap().unwrap().tokens, 6.0);
+        assert_eq!(client.get_token_bucket(&member).unwrap().unwrap().tokens, 6.0);
+
+        // Reserved tokens are not the pool's to lend
+        assert_eq!(client.transfer_tokens(&pool, &member, 4.0, 3.0, 10, 0.0).unwrap(), None);
+
+        assert_eq!(client.transfer_tokens(&pool, &member, 6.0, 0.0, 10, 0.0).unwrap(), Some(4.0));
+        assert_eq!(client.get_token_bucket(&pool).unwrap().unwrap().tokens, 2.0);
+        assert_eq!(client.get_token_bucket(&member).unwrap().unwrap().tokens, 10.0);
+    }
+
+    #[test]
+    fn test_slot_script_caps_releases_and_expires() {
+        let client = test_client();
+        let key = unique_key("slots");
+
+        assert_eq!(client.acquire_slot(&key, "a", 2, 100).unwrap(), Some(1));
+        assert_eq!(client.acquire_slot(&key, "b", 2, 60_000).unwrap(), Some(2));
+        assert_eq!(client.acquire_slot(&key, "c", 2, 60_000).unwrap(), None);
+
+        // A lapsed slot no longer counts
+        std::thread::sleep(Duration::from_millis(150));
+        assert_eq!(client.acquire_slot(&key, "c", 2, 60_000).unwrap(), Some(2));
+
+        assert!(client.release_slot(&key, "b").unwrap());
+        assert!(!client.release_slot(&key, "b").unwrap());
+        assert_eq!(client.acquire_slot(&key, "d", 2, 60_000).unwrap(), Some(2));
+    }
+
+    #[test]
+    fn test_quota_script_caps_usage_until_the_period_ends() {
+        let client = test_client();
+        let key = unique_key("quota");
+        let now_ms = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as u64;
+        let reset_at_ms = now_ms + 200;
+
+        assert_eq!(client.consume_quota(&key, 2, 3, reset_at_ms).unwrap(), (true, 2));
+        assert_eq!(client.consume_quota(&key, 2, 3, reset_at_ms).unwrap(), (false, 2));
+        assert_eq!(client.consume_quota(&key, 1, 3, reset_at_ms).unwrap(), (true, 3));
 
-        Ok((success, bucket))
+        // Reads leave the counter and its expiry alone
+        assert_eq!(client.quota_used(&key).unwrap(), 3);
+
+        // The counter lapses with its period
+        std::thread::sleep(Duration::from_millis(300));
+        assert!(!client.exists(&key).unwrap());
+        assert_eq!(client.quota_used(&key).unwrap(), 0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_consume_on_wrong_type_key_names_the_cause() {
+        let client = test_client();
+        let key = unique_key("wrongtype");
+        let mut conn = client.connection_for(&key).unwrap();
+        let _: () = conn.rpush(&key, "not a bucket").unwrap();
+
+        let rule = RateLimitRule::new(1, 5, Duration::from_secs(60));
+        let message = client.atomic_consume_tokens(&key, 1, &rule).unwrap_err().to_string();
+        assert!(message.contains("Atomic consume script failed with WRONGTYPE at line"), "{}", message);
+
+        let _: () = conn.del(&key).unwrap();
+    }
+
+    #[test]
+    fn test_writes_wait_for_configured_replicas() {
+        let url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+        let client = RedisClient::from_config(&Config {
+            redis_url: url,
+            redis_wait_replicas: 1,
+            redis_wait_timeout_ms: 50,
+            ..Config::default()
+        }).unwrap();
+        let key = unique_key("wait");
+        let rule = RateLimitRule::new(1, 5, Duration::from_secs(60));
+
+        // Without replicas the WAIT times out; the writes still succeed
+        let before = wait_calls(&client);
+        assert!(client.atomic_consume_tokens(&key, 1, &rule).unwrap().0);
+        client.delete_token_bucket(&key).unwrap();
+        assert_eq!(wait_calls(&client), before + 2);
+
+        // Reads never wait
+        client.get_token_bucket(&key).unwrap();
+        assert_eq!(wait_calls(&client), before + 2);
+    }
+
+    #[test]
+    fn test_atomic_consume_many_returns_per_key_results() {
+        let client = test_client();
+        let rule = RateLimitRule::new(1, 5, Duration::from_secs(60));
+        let requests = vec![
+            (unique_key("a"), 3),
+            (unique_key("b"), 10),
+            (unique_key("c"), 5),
+        ];
+
+        let results = client.atomic_consume_many(&requests, &rule).unwrap();
+
+        assert_eq!(results.len(), 3);
+        assert!(results[0].0);
+        assert_eq!(results[0].1.tokens, 2.0);
+        // Denied key keeps its tokens and doesn't affect its neighbours
+        assert!(!results[1].0);
+        assert_eq!(results[1].1.tokens, 5.0);
+        assert!(results[2].0);
+        assert_eq!(results[2].1.tokens, 0.0);
+
+        for (key, _) in &requests {
+            client.delete_token_bucket(key).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_scripts_read_msgpack_buckets() {
+        let url = std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+        let msgpack = RedisClient::from_config(&Config {
+            redis_url: url,
+            redis_serialization: SerializationFormat::MsgPack,
+            ..Config::default()
+        }).unwrap();
+        let rule = RateLimitRule::new(1, 5, Duration::from_secs(60));
+        let drained = TokenBucket { tokens: 0.0, ..TokenBucket::new(5, 0.0) };
+
+        // An emptied bucket stays empty rather than being reset as corrupt
+        let key = unique_key("msgpack");
+        msgpack.set_token_bucket(&key, &drained, 60).unwrap();
+        let (allowed, bucket) = msgpack.atomic_consume_tokens(&key, 1, &rule).unwrap();
+        assert!(!allowed);
+        assert!(bucket.tokens < 1.0);
+        assert_eq!(msgpack.corrupt_buckets(), 0);
+
+        let (pool, member) = (unique_key("msgpack-pool"), unique_key("msgpack-member"));
+        msgpack.set_token_bucket(&pool, &drained, 60).unwrap();
+        assert_eq!(msgpack.transfer_tokens(&pool, &member, 1.0, 0.0, 5, 0.0).unwrap(), None);
+
+        for key in [key, pool, member] {
+            msgpack.delete_token_bucket(&key).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_try_set_reports_lost_race() {
+        let client = test_client();
+        let key = unique_key("race");
+        let newer = TokenBucket { capacity: 5, tokens: 4.0, refill_rate: 1.0, last_refill: server_time_ms(&client) };
+        let stale = TokenBucket { last_refill: newer.last_refill - 10, tokens: 3.0, ..newer.clone() };
+
+        // Two rapid writes: the older one must lose without erroring
+        assert!(client.try_set_token_bucket(&key, &newer, 60).unwrap());
+        assert!(!client.try_set_token_bucket(&key, &stale, 60).unwrap());
+        assert_eq!(client.get_token_bucket(&key).unwrap().unwrap().tokens, 4.0);
+
+        client.delete_token_bucket(&key).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_bucket_starts_over() {
+        let client = test_client();
+        let rule = RateLimitRule::new(1, 5, Duration::from_secs(60));
+        let garbage: [&[u8]; 3] = [
+            b"{not json",
+            br#"{"tokens": "lots", "capacity": 5, "refill_rate": 1, "last_refill": 0}"#,
+            br#"{"capacity": 5}"#,
+        ];
+
+        for (i, data) in garbage.into_iter().enumerate() {
+            let key = unique_key("corrupt");
+            let mut conn = client.get_connection().unwrap();
+            let _: () = conn.set_ex(&key, data, 60).unwrap();
+
+            // Reads treat it as missing rather than erroring
+            assert!(client.get_token_bucket(&key).unwrap().is_none());
+            assert_eq!(client.corrupt_buckets(), i as u64 * 2 + 1);
+
+            // Consuming replaces it with a fresh bucket from the rule
+            let (allowed, bucket) = client.atomic_consume_tokens(&key, 1, &rule).unwrap();
+            assert!(allowed);
+            assert_eq!(bucket.tokens, 4.0);
+            assert_eq!(client.corrupt_buckets(), i as u64 * 2 + 2);
+            assert_eq!(client.get_token_bucket(&key).unwrap().unwrap().tokens, 4.0);
+
+            client.delete_token_bucket(&key).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_scan_buckets_lists_every_key_once() {
+        let client = test_client();
+        let prefix = unique_key("scan");
+        let bucket = TokenBucket::new(5, 1.0);
+        let keys: Vec<String> = (0..25).map(|i| format!("{}:{}", prefix, i)).collect();
+        for key in &keys {
+            client.set_token_bucket(key, &bucket, 60).unwrap();
+        }
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = client.scan_buckets(&prefix, cursor.as_deref(), 10).unwrap();
+            seen.extend(page.buckets.into_iter().map(|(key, _)| key));
+            cursor = page.cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        seen.sort();
+        seen.dedup();
+        let mut expected = keys.clone();
+        expected.sort();
+        assert_eq!(seen, expected);
+
+        client.delete_token_buckets(&keys).unwrap();
+    }
+
+    #[test]
+    fn test_corrupt_bucket_is_overwritten() {
+        let client = test_client();
+        let key = unique_key("corrupt-write");
+        let mut conn = client.get_connection().unwrap();
+        let _: () = conn.set_ex(&key, "{not json", 60).unwrap();
+
+        let bucket = TokenBucket::new(5, 1.0);
+        assert!(client.try_set_token_bucket(&key, &bucket, 60).unwrap());
+        assert_eq!(client.get_token_bucket(&key).unwrap().unwrap().tokens, 5.0);
+
+        client.delete_token_bucket(&key).unwrap();
+    }
+
+    fn server_time_ms(client: &RedisClient) -> u64 {
+        let mut conn = client.get_connection().unwrap();
+        let (secs, micros): (u64, u64) = redis::cmd("TIME").query(&mut conn).unwrap();
+        secs * 1000 + micros / 1000
+    }
+
+    fn seed_bucket(client: &RedisClient, key: &str, tokens: f64, last_refill: u64) {
+        let bucket = TokenBucket { capacity: 5, tokens, refill_rate: 1.0, last_refill };
+        let mut conn = client.get_connection().unwrap();
+        let _: () = conn.set(key, serde_json::to_string(&bucket).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn test_fast_clock_stamp_is_clamped_to_server_time() {
+        let client = test_client();
+        let rule = RateLimitRule::new(1, 5, Duration::from_secs(60));
+        let key = unique_key("skew-ahead");
+        let now = server_time_ms(&client);
+
+        // An instance one hour ahead wrote the bucket
+        seed_bucket(&client, &key, 0.0, now + 3_600_000);
+        let (allowed, bucket) = client.atomic_consume_tokens(&key, 1, &rule).unwrap();
+
+        assert!(!allowed);
+        assert!(bucket.last_refill <= server_time_ms(&client));
+        assert!(bucket.last_refill >= now);
+
+        client.delete_token_bucket(&key).unwrap();
+    }
+
+    #[test]
+    fn test_sub_second_window_refill_and_ttl() {
+        let client = test_client();
+        // 10 tokens per second with a 200ms window
+        let rule = RateLimitRule::new(10, 2, Duration::from_millis(200));
+        let key = unique_key("sub-second");
+
+        let (_, bucket) = client.atomic_consume_tokens(&key, 2, &rule).unwrap();
+        assert_eq!(bucket.tokens, 0.0);
+
+        std::thread::sleep(Duration::from_millis(120));
+        let (allowed, bucket) = client.atomic_consume_tokens(&key, 1, &rule).unwrap();
+        assert!(allowed, "refilled only {} tokens", bucket.tokens);
+
+        let mut conn = client.get_connection().unwrap();
+        let ttl: i64 = redis::cmd("PTTL").arg(&key).query(&mut conn).unwrap();
+        assert!(ttl > 0 && ttl <= 200, "ttl was {}ms", ttl);
+
+        std::thread::sleep(Duration::from_millis(250));
+        assert!(!client.exists(&key).unwrap());
+    }
+
+    #[test]
+    fn test_slow_clock_stamp_refill_stays_bounded() {
+        let client = test_client();
+        let rule = RateLimitRule::new(1, 5, Duration::from_secs(60));
+        let key = unique_key("skew-behind");
+        let now = server_time_ms(&client);
+
+        // An instance one day behind wrote the bucket
+        seed_bucket(&client, &key, 0.0, now - 86_400_000);
+        let (allowed, bucket) = client.atomic_consume_tokens(&key, 1, &rule).unwrap();
+
+        assert!(allowed);
+        assert!(bucket.tokens <= 5.0);
+
+        client.delete_token_bucket(&key).unwrap();
+    }
+}
@@ -0,0 +1,134 @@
+//! Minimum TLS version checks for `rediss://` Redis nodes.
+//!
+//! The Redis client builds its own TLS connector and cannot be told which
+//! protocol versions to offer, so before a node is first used it is probed
+//! with a handshake offering only the versions `REDIS_MIN_TLS_VERSION`
+//! permits. A node that cannot complete it is refused. One that can is then
+//! connected to normally: the client prefers the newest version both sides
+//! support, so it negotiates at least what the probe did.
+
+use crate::config::TlsVersion;
+use crate::error::ThrottlerError;
+use rustls::version::{TLS12, TLS13};
+use rustls::{ClientConfig, ClientConnection, ProtocolVersion, RootCertStore, ServerName, SupportedProtocolVersion};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Protocol versions a handshake may offer under `min_version`
+pub fn permitted_versions(min_version: TlsVersion) -> Vec<&'static SupportedProtocolVersion> {
+    match min_version {
+        TlsVersion::Tls12 => vec![&TLS13, &TLS12],
+        TlsVersion::Tls13 => vec![&TLS13],
+    }
+}
+
+/// Completes a handshake with `host:port` offering only the permitted
+/// versions, failing if the server cannot agree to one of them.
+pub fn check_min_version(
+    host: &str,
+    port: u16,
+    min_version: TlsVersion,
+    timeout: Option<Duration>,
+) -> Result<(), ThrottlerError> {
+    let refused = |reason: String| ThrottlerError::RedisError(format!(
+        "Redis at {}:{} refused for minimum TLS version {:?}: {}",
+        host, port, min_version, reason
+    ));
+
+    let mut roots = RootCertStore::empty();
+    let native = rustls_native_certs::load_native_certs()
+        .map_err(|e| refused(format!("failed to load root certificates: {}", e)))?;
+    roots.add_parsable_certificates(&native.into_iter().map(|cert| cert.0).collect::<Vec<_>>());
+
+    let tls_config = ClientConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(&permitted_versions(min_version))
+        .map_err(|e| refused(e.to_string()))?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let server_name = ServerName::try_from(host)
+        .map_err(|e| refused(e.to_string()))?;
+    let mut tls = ClientConnection::new(Arc::new(tls_config), server_name)
+        .map_err(|e| refused(e.to_string()))?;
+
+    let mut socket = connect_tcp(host, port, timeout).map_err(|e| refused(e.to_string()))?;
+    while tls.is_handshaking() {
+        tls.complete_io(&mut socket).map_err(|e| refused(e.to_string()))?;
+    }
+
+    let negotiated = tls.protocol_version();
+    let meets_minimum = match min_version {
+        TlsVersion::Tls12 => matches!(negotiated, Some(ProtocolVersion::TLSv1_2 | ProtocolVersion::TLSv1_3)),
+        TlsVersion::Tls13 => negotiated == Some(ProtocolVersion::TLSv1_3),
+    };
+    if !meets_minimum {
+        return Err(refused(format!("negotiated {:?}", negotiated)));
+    }
+
+    Ok(())
+}
+
+fn connect_tcp(host: &str, port: u16, timeout: Option<Duration>) -> std::io::Result<TcpStream> {
+    let Some(timeout) = timeout else {
+        return TcpStream::connect((host, port));
+    };
+
+    let mut last_error = None;
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(socket) => {
+                socket.set_read_timeout(Some(timeout))?;
+                socket.set_write_timeout(Some(timeout))?;
+                return Ok(socket);
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "host resolved to no addresses",
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    /// A server that answers any ClientHello with a `protocol_version`
+    /// alert, as one limited to TLS 1.2 does when offered only TLS 1.3
+    fn spawn_tls12_only_server() -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            if let Ok((mut socket, _)) = listener.accept() {
+                let mut hello = [0u8; 4096];
+                let _ = socket.read(&mut hello);
+                let _ = socket.write_all(&[0x15, 0x03, 0x03, 0x00, 0x02, 0x02, 0x46]);
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn test_permitted_versions_start_at_minimum() {
+        assert_eq!(permitted_versions(TlsVersion::Tls12).len(), 2);
+        let only = permitted_versions(TlsVersion::Tls13);
+        assert_eq!(only.len(), 1);
+        assert_eq!(only[0].version, ProtocolVersion::TLSv1_3);
+    }
+
+    #[test]
+    fn test_server_below_minimum_is_refused() {
+        let port = spawn_tls12_only_server();
+        let err = check_min_version("localhost", port, TlsVersion::Tls13, Some(Duration::from_secs(2)))
+            .unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("Tls13"), "{}", message);
+        assert!(message.contains("ProtocolVersion"), "{}", message);
+    }
+}
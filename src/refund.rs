@@ -0,0 +1,148 @@
+//! # Refunds
+//!
+//! A check that consumed several tokens for work that then partly failed
+//! downstream can hand some of them back with
+//! `POST /rate-limit/:key/refund`. With `Config::refund_window_ms` set,
+//! every allowed check that consumed tokens answers with an
+//! `X-RateLimit-Refund-Id`; a refund names that id and how many tokens to
+//! return.
+//!
+//! ## Ledger
+//!
+//! The [`RefundLedger`] remembers, per check, how many of its tokens may
+//! still be refunded, for `refund_window_ms`. Refunding more than that, or
+//! against an unknown, lapsed or another key's check, is rejected, so a
+//! client can never get back more than it spent. Tokens go back to the
+//! key's bucket only, and never beyond its capacity; quota and route usage
+//! stand.
+//!
+//! The ledger is kept in the process, so a refund must reach the instance
+//! that served the check.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use crate::error::ThrottlerError;
+
+/// Tokens of one check that may still be refunded
+#[derive(Debug)]
+struct Refundable {
+    key: String,
+    tokens: u64,
+}
+
+#[derive(Debug, Default)]
+struct LedgerState {
+    /// Refundable checks by refund id
+    entries: HashMap<String, Refundable>,
+    /// `(expires_at, refund id)` in the order checks were recorded
+    expiry: VecDeque<(u64, String)>,
+}
+
+impl LedgerState {
+    /// Forgets checks whose refund window has passed
+    fn prune(&mut self, now_ms: u64) {
+        while let Some((expires_at, _)) = self.expiry.front() {
+            if *expires_at > now_ms {
+                break;
+            }
+            if let Some((_, refund_id)) = self.expiry.pop_front() {
+                self.entries.remove(&refund_id);
+            }
+        }
+    }
+}
+
+/// Short-lived record of the tokens recent checks consumed.
+#[derive(Debug, Default)]
+pub struct RefundLedger {
+    state: Mutex<LedgerState>,
+}
+
+impl RefundLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn lock(&self) -> Result<std::sync::MutexGuard<'_, LedgerState>, ThrottlerError> {
+        self.state.lock()
+            .map_err(|_| ThrottlerError::InternalError("Failed to acquire lock on refund ledger".to_string()))
+    }
+
+    /// Records that a check of `key` consumed `tokens`, refundable until
+    /// `now_ms + window_ms`, returning the check's refund id.
+    pub fn record(&self, key: &str, tokens: u64, window_ms: u64, now_ms: u64) -> Result<String, ThrottlerError> {
+        let refund_id = uuid::Uuid::new_v4().to_string();
+        let expires_at = now_ms.saturating_add(window_ms);
+
+        let mut state = self.lock()?;
+        state.prune(now_ms);
+        state.entries.insert(refund_id.clone(), Refundable { key: key.to_string(), tokens });
+        state.expiry.push_back((expires_at, refund_id.clone()));
+        Ok(refund_id)
+    }
+
+    /// Takes `tokens` off what check `refund_id` of `key` may still refund,
+    /// returning how many it may refund afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ValidationError`, changing nothing, if the check is
+    /// unknown, lapsed or another key's, or has fewer than `tokens` left.
+    pub fn claim(&self, refund_id: &str, key: &str, tokens: u64, now_ms: u64) -> Result<u64, ThrottlerError> {
+        let mut state = self.lock()?;
+        state.prune(now_ms);
+
+        let entry = state.entries.get_mut(refund_id)
+            .filter(|entry| entry.key == key)
+            .ok_or_else(|| ThrottlerError::ValidationError(
+                format!("Unknown or expired refund id for key {}: {}", key, refund_id)
+            ))?;
+        if tokens > entry.tokens {
+            return Err(ThrottlerError::ValidationError(format!(
+                "Cannot refund {} tokens; the check has {} refundable", tokens, entry.tokens
+            )));
+        }
+
+        // Spent entries stay until they lapse, so a failed refund can be
+        // put back with `restore`
+        entry.tokens -= tokens;
+        Ok(entry.tokens)
+    }
+
+    /// Puts back `tokens` claimed from check `refund_id` of `key` whose
+    /// refund could not be applied. Does nothing once the check has lapsed.
+    pub fn restore(&self, refund_id: &str, key: &str, tokens: u64) -> Result<(), ThrottlerError> {
+        let mut state = self.lock()?;
+        if let Some(entry) = state.entries.get_mut(refund_id).filter(|entry| entry.key == key) {
+            entry.tokens += tokens;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refunds_never_exceed_what_was_consumed() {
+        let ledger = RefundLedger::new();
+        let refund_id = ledger.record("k", 5, 1000, 0).unwrap();
+
+        assert_eq!(ledger.claim(&refund_id, "k", 3, 10).unwrap(), 2);
+        assert!(ledger.claim(&refund_id, "k", 3, 10).is_err());
+        assert!(ledger.claim(&refund_id, "other", 1, 10).is_err());
+        assert_eq!(ledger.claim(&refund_id, "k", 2, 10).unwrap(), 0);
+        assert!(ledger.claim(&refund_id, "k", 1, 10).is_err());
+
+        // A claim whose refund failed can be made again
+        ledger.restore(&refund_id, "k", 2).unwrap();
+        assert_eq!(ledger.claim(&refund_id, "k", 2, 10).unwrap(), 0);
+
+        // Lapsed checks can no longer be refunded
+        let refund_id = ledger.record("k", 5, 1000, 0).unwrap();
+        assert!(ledger.claim(&refund_id, "k", 1, 1000).is_err());
+        assert!(ledger.lock().unwrap().expiry.is_empty());
+    }
+}
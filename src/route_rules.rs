@@ -0,0 +1,119 @@
+//! # Route Limits
+//!
+//! Keys limit each client separately; a route rule limits every request to
+//! an endpoint of the protected service, whichever client sends it (e.g.
+//! `POST /upload` across all callers). Route rules are keyed by a
+//! `METHOD PATH` pattern:
+//!
+//! ```text
+//! POST /upload       exactly this method and path
+//! GET /reports/*     GET on any path starting with /reports/
+//! ```
+//!
+//! A check names the route it is for with `method` and `path`; the exact
+//! route's rule applies, else the longest matching wildcard pattern.
+//!
+//! ## Buckets
+//!
+//! Each route rule has one bucket shared by all clients, kept under
+//! [`ROUTE_KEY_PREFIX`] so it can never collide with a client key. The
+//! route is checked after the global limit and before the client's own
+//! bucket: a route denial spends none of the client's tokens, while a
+//! request the client's bucket denies has still spent its route tokens.
+
+use std::collections::HashMap;
+
+use crate::rate_limit_config::RateLimitRule;
+
+/// Prefix of the bucket key of each route rule, which is the prefix plus
+/// the rule's pattern
+pub const ROUTE_KEY_PREFIX: &str = "throttler-route:";
+
+/// Most characters in a route pattern
+pub const MAX_ROUTE_LEN: usize = 512;
+
+/// The route a request to `path` with `method` is checked under, e.g.
+/// `POST /upload`
+pub fn route_of(method: &str, path: &str) -> String {
+    format!("{} {}", method.to_ascii_uppercase(), path)
+}
+
+/// Bucket key of the rule with route pattern `pattern`
+pub fn route_bucket_key(pattern: &str) -> String {
+    format!("{}{}", ROUTE_KEY_PREFIX, pattern)
+}
+
+/// Check that a route pattern is an HTTP method and an absolute path,
+/// with at most a single trailing `*`.
+pub fn validate_route(pattern: &str) -> Result<(), String> {
+    if pattern.len() > MAX_ROUTE_LEN {
+        return Err(format!("Route must be at most {} characters", MAX_ROUTE_LEN));
+    }
+    let Some((method, path)) = pattern.split_once(' ') else {
+        return Err("Route must be a method and a path, e.g. `POST /upload`".to_string());
+    };
+    if method.is_empty() || !method.bytes().all(|b| b.is_ascii_uppercase()) {
+        return Err(format!("Route method must be an upper-case HTTP method, got `{}`", method));
+    }
+    if !path.starts_with('/') || path.chars().any(char::is_whitespace) {
+        return Err(format!("Route path must be an absolute path, got `{}`", path));
+    }
+    if path.trim_end_matches('*').contains('*') || path.ends_with("**") {
+        return Err("Only a single trailing '*' wildcard is supported".to_string());
+    }
+    Ok(())
+}
+
+/// Find the rule for a route: an exact pattern, else the longest wildcard
+/// pattern whose prefix the route starts with
+pub fn match_route<'a>(
+    route_rules: &'a HashMap<String, RateLimitRule>,
+    route: &str,
+) -> Option<(&'a String, &'a RateLimitRule)> {
+    if let Some(found) = route_rules.get_key_value(route) {
+        return Some(found);
+    }
+    route_rules
+        .iter()
+        .filter_map(|(pattern, rule)| Some((pattern, rule, pattern.strip_suffix('*')?)))
+        .filter(|(_, _, prefix)| route.starts_with(prefix))
+        .max_by_key(|(_, _, prefix)| prefix.len())
+        .map(|(pattern, rule, _)| (pattern, rule))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(rps: u32) -> RateLimitRule {
+        RateLimitRule::new(rps, rps, std::time::Duration::from_secs(60))
+    }
+
+    #[test]
+    fn test_exact_route_wins_over_longest_wildcard() {
+        let mut rules = HashMap::new();
+        rules.insert("GET /reports/*".to_string(), rule(1));
+        rules.insert("GET /reports/daily/*".to_string(), rule(2));
+        rules.insert("POST /upload".to_string(), rule(3));
+
+        let matched = |route: &str| match_route(&rules, route).map(|(pattern, _)| pattern.as_str());
+        assert_eq!(matched(&route_of("post", "/upload")), Some("POST /upload"));
+        assert_eq!(matched("GET /reports/daily/2024"), Some("GET /reports/daily/*"));
+        assert_eq!(matched("GET /reports/weekly"), Some("GET /reports/*"));
+        // Exact patterns do not match longer paths, nor other methods
+        assert_eq!(matched("POST /uploads"), None);
+        assert_eq!(matched("PUT /upload"), None);
+    }
+
+    #[test]
+    fn test_route_patterns_are_validated() {
+        assert!(validate_route("POST /upload").is_ok());
+        assert!(validate_route("GET /reports/*").is_ok());
+        assert!(validate_route("/upload").is_err());
+        assert!(validate_route("post /upload").is_err());
+        assert!(validate_route("POST upload").is_err());
+        assert!(validate_route("POST /a b").is_err());
+        assert!(validate_route("GET /*/reports").is_err());
+        assert!(validate_route("GET /reports/**").is_err());
+    }
+}
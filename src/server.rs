@@ -24,7 +24,24 @@
 //! │  ├── GET    /rate-limit/:key     → get_rate_limit           │
 //! │  ├── POST   /rate-limit/:key     → set_rate_limit           │
 //! │  ├── DELETE /rate-limit/:key     → delete_rate_limit        │
-//! │  └── POST   /rate-limit/:key/check → check_rate_limit       │
+//! │  ├── DELETE /rate-limit?keys=…   → delete_rate_limits       │
+//! │  ├── POST   /rate-limit/:key/check → check_rate_limit       │
+//! │  ├── HEAD   /rate-limit/:key/check → check_rate_limit_head  │
+//! │  ├── POST   /rate-limit/:key/commit → commit_rate_limit     │
+//! │  ├── POST   /rate-limit/:key/refund → refund_rate_limit     │
+//! │  ├── GET    /rate-limit/:key/explain → explain_rate_limit   │
+//! │  ├── POST   /rate-limit/:key/enable  → enable_rate_limit    │
+//! │  ├── POST   /rate-limit/:key/disable → disable_rate_limit   │
+//! │  ├── PUT    /route-limit         → set_route_limit          │
+//! │  ├── DELETE /route-limit?route=… → delete_route_limit       │
+//! │  ├── PUT    /pattern-limit       → set_pattern_limit        │
+//! │  ├── DELETE /pattern-limit?pattern=… → delete_pattern_limit │
+//! │  ├── POST   /nginx/limit         → nginx_limit              │
+//! │  ├── GET    /admin/state         → export_state             │
+//! │  ├── PUT    /admin/state         → import_state             │
+//! │  ├── GET    /admin/stats         → admin_stats              │
+//! │  ├── GET    /admin/keys/detailed → list_keys_detailed       │
+//! │  └── GET    /metrics             → metrics                  │
 //! │                                                             │
 //! └─────────────────────────────────────────────────────────────┘
 //! ```
@@ -36,6 +53,8 @@
 //! - `SIGTERM` - Container/orchestrator shutdown (Unix only)
 //!
 //! In-flight requests are allowed to complete before the server exits.
+//! Afterwards, any bucket state held only in local memory (local mode or a
+//! Redis outage) is flushed to Redis, bounded by `Config::shutdown_timeout_ms`.
 //!
 //! ## Example Usage
 //!
@@ -53,14 +72,28 @@
 
 use crate::config::Config;
 use crate::handlers::{
-    check_rate_limit, delete_rate_limit, get_rate_limit, set_rate_limit,
-    health_check, readiness_check, AppState, SharedState,
+    acquire_concurrency_slot, release_concurrency_slot,
+    check_rate_limit, check_rate_limit_head, commit_rate_limit, delete_rate_limit,
+    delete_rate_limits, disable_rate_limit, enable_rate_limit, explain_rate_limit, get_rate_limit,
+    delete_pattern_limit, delete_route_limit, nginx_limit, refund_rate_limit, set_pattern_limit,
+    set_rate_limit, set_route_limit,
+    admin_stats, export_state, health_check, import_state, list_keys_detailed, metrics,
+    readiness_check, AppState, SharedState,
+};
+use crate::config::ResponseHeaderPolicy;
+use crate::middleware::{
+    declared_length_limit_middleware, degraded_warning_middleware, lenient_content_type_middleware,
+    request_deadline_middleware, response_headers_middleware, strict_get_bodies_middleware,
+    verbose_errors_middleware,
 };
 use crate::rate_limiter::RateLimiter;
+use crate::throttler::Throttler;
 use crate::validation::RequestValidator;
-use axum::routing::{delete, get, post};
+use axum::routing::{delete, get, post, put};
 use axum::Router;
+use std::future::Future;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use tower::ServiceBuilder;
 use tower_http::cors::CorsLayer;
@@ -94,8 +127,25 @@ pub struct Server {
     app: Router,
     /// The address to bind the server to (e.g., "127.0.0.1:8080")
     bind_address: String,
+    /// Handle to the rate limiter for shutdown work
+    rate_limiter: RateLimiter,
+    /// Bound on shutdown work after the server stops accepting requests
+    shutdown_timeout: Duration,
+    /// State shared with the handlers, for background maintenance
+    state: SharedState,
 }
 
+/// How often rules past their `expires_at` are swept out
+const RULE_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often idle local buckets, lapsed concurrency slots and stale hybrid
+/// leases are cleaned up
+const LOCAL_CLEANUP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long a local bucket without a rule may sit idle before cleanup
+/// drops it (never before it would have refilled)
+const LOCAL_BUCKET_MAX_AGE: Duration = Duration::from_secs(3600);
+
 /// Creates the Axum router with all routes and middleware configured.
 ///
 /// This function is the primary entry point for building the application router.
@@ -136,12 +186,37 @@ pub fn create_app(config: Config) -> Result<Router, Box<dyn std::error::Error>>
     // Create rate limiter - connects to Redis if URL is configured
     let rate_limiter = RateLimiter::new(config)?;
 
+    create_router(rate_limiter).map(|(app, _)| app)
+}
+
+/// Builds the router around an existing rate limiter, returning it with
+/// the state its handlers share.
+fn create_router(rate_limiter: RateLimiter) -> Result<(Router, SharedState), Box<dyn std::error::Error>> {
+    let verbose_errors = rate_limiter.config().verbose_errors;
+    let lenient_content_type = rate_limiter.config().lenient_content_type;
+    let strict_get_bodies = rate_limiter.config().strict_get_bodies;
+    let honor_request_deadline = rate_limiter.config().honor_request_deadline;
+    let header_policy = rate_limiter.config().response_headers.clone();
+    let allowed_windows_ms = rate_limiter.config().allowed_windows_ms.clone();
+    let key_case = rate_limiter.config().key_case;
+    let key_slashes = rate_limiter.config().key_slashes;
+    let degraded_warning = rate_limiter.config().warn_on_degraded.then(|| rate_limiter.clone());
+    let (max_json_depth, max_json_fields) =
+        (rate_limiter.config().max_json_depth, rate_limiter.config().max_json_fields);
+    let throttler = Throttler::with_rate_limiter(rate_limiter.clone())?;
+
     // Create shared state wrapped in Arc<RwLock> for thread-safe access
     // - Arc: Allows multiple owners across async tasks
     // - RwLock: Allows concurrent reads, exclusive writes
     let state: SharedState = Arc::new(RwLock::new(AppState {
         rate_limiter,
-        validator: RequestValidator::new(),
+        validator: RequestValidator::new()
+            .with_allowed_windows_ms(allowed_windows_ms)
+            .with_json_limits(max_json_depth, max_json_fields)
+            .with_key_case(key_case)
+            .with_key_slashes(key_slashes),
+        metrics: throttler.metrics().clone(),
+        throttler,
     }));
 
     // Build the router with all routes and middleware
@@ -150,12 +225,29 @@ pub fn create_app(config: Config) -> Result<Router, Box<dyn std::error::Error>>
         .route("/rate-limit/:key", get(get_rate_limit))      // Get current limit status
         .route("/rate-limit/:key", post(set_rate_limit))     // Create/update limit config
         .route("/rate-limit/:key", delete(delete_rate_limit)) // Delete limit config
-        .route("/rate-limit/:key/check", post(check_rate_limit)) // Check and consume tokens
+        .route("/rate-limit/:key/check", post(check_rate_limit).head(check_rate_limit_head)) // Check and consume tokens
+        .route("/rate-limit/:key/commit", post(commit_rate_limit)) // Consume after a dry check
+        .route("/rate-limit/:key/refund", post(refund_rate_limit)) // Return part of a check's tokens
+        .route("/rate-limit/:key/explain", get(explain_rate_limit)) // Rule resolution trace
+        .route("/rate-limit", delete(delete_rate_limits))    // Delete many keys at once
+        .route("/rate-limit/:key/enable", post(enable_rate_limit))   // Resume limiting
+        .route("/rate-limit/:key/disable", post(disable_rate_limit)) // Pause limiting
+        .route("/route-limit", put(set_route_limit).delete(delete_route_limit)) // Per-route limits
+        .route("/pattern-limit", put(set_pattern_limit).delete(delete_pattern_limit)) // Key prefix limits
+        .route("/nginx/limit", post(nginx_limit))            // nginx limit_req compatibility
+        // Concurrency endpoints - requests in flight per key
+        .route("/concurrency/:key/acquire", post(acquire_concurrency_slot))
+        .route("/concurrency/:key/release", post(release_concurrency_slot))
+        // Admin endpoints - state migration between instances
+        .route("/admin/state", get(export_state).put(import_state))
+        .route("/admin/stats", get(admin_stats))    // Bucket counts and memory estimates
+        .route("/admin/keys/detailed", get(list_keys_detailed)) // Paginated buckets with remaining
         // Health and readiness endpoints - Kubernetes probes
         .route("/health", get(health_check))    // Liveness probe
         .route("/ready", get(readiness_check))  // Readiness probe (checks Redis)
+        .route("/metrics", get(metrics))        // Prometheus scrape endpoint
         // Attach shared state to all routes
-        .with_state(state)
+        .with_state(state.clone())
         // Apply middleware stack (executed in reverse order)
         .layer(
             ServiceBuilder::new()
@@ -163,7 +255,58 @@ pub fn create_app(config: Config) -> Result<Router, Box<dyn std::error::Error>>
                 .layer(CorsLayer::permissive())    // Allow all CORS origins
         );
 
-    Ok(app)
+    // Tell clients when limits are no longer shared across instances
+    let app = match degraded_warning {
+        Some(rate_limiter) => app.layer(axum::middleware::from_fn_with_state(rate_limiter, degraded_warning_middleware)),
+        None => app,
+    };
+
+    // Drop rate limit headers the deployment has opted out of (outside the
+    // warning layer, so its `Warning` is filtered too)
+    let app = if header_policy == ResponseHeaderPolicy::All {
+        app
+    } else {
+        app.layer(axum::middleware::from_fn_with_state(
+            Arc::new(header_policy),
+            response_headers_middleware,
+        ))
+    };
+
+    // Accept JSON bodies from clients that omit Content-Type
+    let app = if lenient_content_type {
+        app.layer(axum::middleware::from_fn(lenient_content_type_middleware))
+    } else {
+        app
+    };
+
+    // Refuse bodies on GET and DELETE requests
+    let app = if strict_get_bodies {
+        app.layer(axum::middleware::from_fn(strict_get_bodies_middleware))
+    } else {
+        app
+    };
+
+    // Refuse oversized bodies on their declared length, before
+    // `Expect: 100-continue` clients are told to send them
+    let app = app.layer(axum::middleware::from_fn(declared_length_limit_middleware));
+
+    // Give up on requests whose gateway has stopped waiting
+    let app = if honor_request_deadline {
+        app.layer(axum::middleware::from_fn(request_deadline_middleware))
+    } else {
+        app
+    };
+
+    // Test-only clock control via X-Test-Time
+    #[cfg(feature = "testing")]
+    let app = app.layer(axum::middleware::from_fn(crate::middleware::test_time_middleware));
+
+    // Expose internal error details only when configured (development)
+    if verbose_errors {
+        Ok((app.layer(axum::middleware::from_fn(verbose_errors_middleware)), state))
+    } else {
+        Ok((app, state))
+    }
 }
 
 impl Server {
@@ -198,8 +341,10 @@ impl Server {
     /// ```
     pub fn new(config: Config) -> Result<Self, Box<dyn std::error::Error>> {
         let bind_address = config.bind_address.clone();
-        let app = create_app(config)?;
-        Ok(Self { app, bind_address })
+        let shutdown_timeout = Duration::from_millis(config.shutdown_timeout_ms);
+        let rate_limiter = RateLimiter::new(config)?;
+        let (app, state) = create_router(rate_limiter.clone())?;
+        Ok(Self { app, bind_address, rate_limiter, shutdown_timeout, state })
     }
 
     /// Starts the HTTP server and runs until a shutdown signal is received.
@@ -209,6 +354,7 @@ impl Server {
     /// 2. Logs startup information
     /// 3. Serves requests until shutdown signal
     /// 4. Performs graceful shutdown (completes in-flight requests)
+    /// 5. Flushes locally-held bucket state to Redis
     ///
     /// # Shutdown Behavior
     ///
@@ -238,6 +384,17 @@ impl Server {
     /// }
     /// ```
     pub async fn run(self) -> Result<(), Box<dyn std::error::Error>> {
+        self.run_until(shutdown_signal()).await
+    }
+
+    /// Runs the server until `signal` completes, then performs the same
+    /// graceful shutdown as [`Server::run`].
+    ///
+    /// Useful for embedding the server or driving shutdown from tests.
+    pub async fn run_until<F>(self, signal: F) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
         // Bind to the configured address
         let listener = tokio::net::TcpListener::bind(&self.bind_address).await?;
 
@@ -246,13 +403,115 @@ impl Server {
         tracing::info!("Health check available at /health");
         tracing::info!("Readiness check available at /ready");
 
+        // Periodically persist batched Redis consumption
+        let write_interval_ms = self.rate_limiter.config().min_redis_write_interval_ms;
+        let pending_flusher = (write_interval_ms > 0).then(|| {
+            let rate_limiter = self.rate_limiter.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_millis(write_interval_ms));
+                loop {
+                    interval.tick().await;
+                    let limiter = rate_limiter.clone();
+                    let _ = tokio::task::spawn_blocking(move || {
+                        if let Err(e) = limiter.flush_pending_writes() {
+                            tracing::warn!("Failed to flush batched Redis writes: {}", e);
+                        }
+                    }).await;
+                }
+            })
+        });
+
+        // Periodically drop temporary rules that have lapsed
+        let state = self.state.clone();
+        let rule_sweeper = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(RULE_SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                state.read().await.throttler.sweep_expired_rules().await;
+            }
+        });
+
+        // Periodically drop idle local state and return stale leases
+        let rate_limiter = self.rate_limiter.clone();
+        let local_cleaner = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(LOCAL_CLEANUP_INTERVAL);
+            loop {
+                interval.tick().await;
+                let limiter = rate_limiter.clone();
+                let _ = tokio::task::spawn_blocking(move || {
+                    let max_age_ms = LOCAL_BUCKET_MAX_AGE.as_millis() as u64;
+                    if let Err(e) = limiter.cleanup_expired_buckets(max_age_ms) {
+                        tracing::warn!("Failed to clean up local rate limit state: {}", e);
+                    }
+                }).await;
+            }
+        });
+
+        // Periodically add request counts to the fleet totals in Redis
+        let metrics_interval_ms = self.rate_limiter.config().metrics_flush_interval_ms;
+        let metrics_flusher = (metrics_interval_ms > 0).then(|| {
+            let state = self.state.clone();
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_millis(metrics_interval_ms));
+                loop {
+                    interval.tick().await;
+                    if let Err(e) = state.read().await.throttler.flush_metrics().await {
+                        tracing::warn!("Failed to flush request metrics to Redis: {}", e);
+                    }
+                }
+            })
+        });
+
+        // Periodically delete Redis buckets that outlived their TTL's purpose
+        let sweep_interval_ms = self.rate_limiter.config().redis_sweep_interval_ms;
+        let redis_sweeper = (sweep_interval_ms > 0).then(|| {
+            let rate_limiter = self.rate_limiter.clone();
+            let max_age_ms = rate_limiter.config().redis_sweep_max_age_ms;
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(Duration::from_millis(sweep_interval_ms));
+                loop {
+                    interval.tick().await;
+                    let limiter = rate_limiter.clone();
+                    let _ = tokio::task::spawn_blocking(move || {
+                        match limiter.sweep_stale_store_buckets(max_age_ms) {
+                            Ok(0) => {}
+                            Ok(swept) => tracing::info!(swept, "Deleted stale Redis buckets"),
+                            Err(e) => tracing::warn!("Failed to sweep stale Redis buckets: {}", e),
+                        }
+                    }).await;
+                }
+            })
+        });
+
         // Run server with graceful shutdown support
         // - Handles incoming connections until shutdown signal
         // - Completes in-flight requests before exiting
         axum::serve(listener, self.app)
-            .with_graceful_shutdown(shutdown_signal())
+            .with_graceful_shutdown(signal)
             .await?;
 
+        if let Some(pending_flusher) = pending_flusher {
+            pending_flusher.abort();
+        }
+        rule_sweeper.abort();
+        local_cleaner.abort();
+        if let Some(redis_sweeper) = redis_sweeper {
+            redis_sweeper.abort();
+        }
+        if let Some(metrics_flusher) = metrics_flusher {
+            metrics_flusher.abort();
+            if let Err(e) = self.state.read().await.throttler.flush_metrics().await {
+                tracing::warn!("Failed to flush request metrics to Redis: {}", e);
+            }
+        }
+
+        // Persist state that only lives in local memory before exiting
+        match self.rate_limiter.flush_to_redis_within(self.shutdown_timeout).await {
+            Ok(0) => {}
+            Ok(flushed) => tracing::info!("Flushed {} local buckets to Redis", flushed),
+            Err(e) => tracing::warn!("Failed to flush local buckets to Redis: {}", e),
+        }
+
         Ok(())
     }
 }
@@ -302,3 +561,184 @@ async fn shutdown_signal() {
         },
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bucket_store::{BucketPage, BucketStore, MemoryStore};
+    use crate::error::ThrottlerError;
+    use crate::middleware::DEGRADED_WARNING;
+    use crate::rate_limit_config::RateLimitRule;
+    use crate::token_bucket::TokenBucket;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tower::ServiceExt;
+
+    fn test_config() -> Config {
+        Config {
+            bind_address: "127.0.0.1:0".to_string(),
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_until_returns_after_signal() {
+        let server = Server::new(test_config()).unwrap();
+        server.run_until(async {}).await.unwrap();
+    }
+
+    /// A [`MemoryStore`] that can be made to fail like an unreachable Redis
+    #[derive(Default)]
+    struct FlakyStore {
+        inner: MemoryStore,
+        down: AtomicBool,
+    }
+
+    impl FlakyStore {
+        fn up(&self) -> Result<(), ThrottlerError> {
+            if self.down.load(Ordering::SeqCst) {
+                return Err(ThrottlerError::RedisError("Connection refused".to_string()));
+            }
+            Ok(())
+        }
+    }
+
+    impl BucketStore for FlakyStore {
+        fn get_token_bucket(&self, key: &str) -> Result<Option<TokenBucket>, ThrottlerError> {
+            self.up()?;
+            self.inner.get_token_bucket(key)
+        }
+
+        fn try_set_token_bucket(&self, key: &str, bucket: &TokenBucket, ttl: usize) -> Result<bool, ThrottlerError> {
+            self.up()?;
+            self.inner.try_set_token_bucket(key, bucket, ttl)
+        }
+
+        fn atomic_consume_tokens(&self, key: &str, tokens: u32, rule: &RateLimitRule) -> Result<(bool, TokenBucket), ThrottlerError> {
+            self.up()?;
+            self.inner.atomic_consume_tokens(key, tokens, rule)
+        }
+
+        fn transfer_tokens(
+            &self,
+            from: &str,
+            to: &str,
+            tokens: f64,
+            reserved: f64,
+            capacity: u64,
+            refill_rate: f64,
+        ) -> Result<Option<f64>, ThrottlerError> {
+            self.up()?;
+            self.inner.transfer_tokens(from, to, tokens, reserved, capacity, refill_rate)
+        }
+
+        fn delete_token_bucket(&self, key: &str) -> Result<(), ThrottlerError> {
+            self.up()?;
+            self.inner.delete_token_bucket(key)
+        }
+
+        fn ping(&self) -> Result<String, ThrottlerError> {
+            self.up()?;
+            self.inner.ping()
+        }
+
+        fn scan_buckets(&self, prefix: &str, cursor: Option<&str>, count: usize) -> Result<BucketPage, ThrottlerError> {
+            self.up()?;
+            self.inner.scan_buckets(prefix, cursor, count)
+        }
+
+        fn acquire_slot(&self, key: &str, slot_id: &str, limit: u64, ttl_ms: u64) -> Result<Option<u64>, ThrottlerError> {
+            self.up()?;
+            self.inner.acquire_slot(key, slot_id, limit, ttl_ms)
+        }
+
+        fn release_slot(&self, key: &str, slot_id: &str) -> Result<bool, ThrottlerError> {
+            self.up()?;
+            self.inner.release_slot(key, slot_id)
+        }
+
+        fn consume_quota(&self, key: &str, cost: u64, limit: u64, reset_at_ms: u64) -> Result<(bool, u64), ThrottlerError> {
+            self.up()?;
+            self.inner.consume_quota(key, cost, limit, reset_at_ms)
+        }
+
+        fn quota_used(&self, key: &str, reset_at_ms: u64) -> Result<u64, ThrottlerError> {
+            self.up()?;
+            self.inner.quota_used(key, reset_at_ms)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_warning_header_while_redis_is_down() {
+        let store = Arc::new(FlakyStore::default());
+        let config = Config { warn_on_degraded: true, ..test_config() };
+        let (app, _) = create_router(RateLimiter::with_store(config, store.clone()).unwrap()).unwrap();
+
+        let check = || {
+            let request = axum::http::Request::builder()
+                .method("POST")
+                .uri("/rate-limit/client/check")
+                .header("content-type", "application/json")
+                .body(axum::body::Body::from(r#"{"tokens": 1}"#))
+                .unwrap();
+            app.clone().oneshot(request)
+        };
+
+        let response = check().await.unwrap();
+        assert!(response.headers().get("warning").is_none());
+
+        // Served from the local bucket, with a warning
+        store.down.store(true, Ordering::SeqCst);
+        let response = check().await.unwrap();
+        assert!(response.status().is_success());
+        assert_eq!(response.headers()["warning"], DEGRADED_WARNING);
+
+        // Once Redis answers again the warning goes away
+        store.down.store(false, Ordering::SeqCst);
+        let response = check().await.unwrap();
+        assert!(response.headers().get("warning").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_header_policy_can_drop_the_warning() {
+        let store = Arc::new(FlakyStore::default());
+        let config = Config {
+            warn_on_degraded: true,
+            response_headers: ResponseHeaderPolicy::Deny(vec!["warning".to_string()]),
+            ..test_config()
+        };
+        let (app, _) = create_router(RateLimiter::with_store(config, store.clone()).unwrap()).unwrap();
+
+        store.down.store(true, Ordering::SeqCst);
+        let request = axum::http::Request::builder()
+            .method("POST")
+            .uri("/rate-limit/client/check")
+            .header("content-type", "application/json")
+            .body(axum::body::Body::from(r#"{"tokens": 1}"#))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert!(response.status().is_success());
+        assert!(response.headers().get("warning").is_none());
+    }
+
+    #[cfg(feature = "redis-tests")]
+    #[tokio::test]
+    async fn test_shutdown_flushes_local_buckets_to_redis() {
+        use crate::redis::RedisClient;
+
+        let redis_url = std::env::var("REDIS_URL")
+            .unwrap_or_else(|_| "redis://127.0.0.1:6379".to_string());
+        let config = Config { redis_url: redis_url.clone(), ..test_config() };
+        let key = format!("shutdown-flush-{}", uuid::Uuid::new_v4());
+
+        let server = Server::new(config).unwrap();
+        let redis_key = server.rate_limiter.redis_key(&key);
+        // Populate local state as if Redis had been unreachable
+        server.rate_limiter.check_rate_limit(&key).unwrap();
+        server.run_until(async {}).await.unwrap();
+
+        let client = RedisClient::new(&redis_url).unwrap();
+        let bucket = client.get_token_bucket(&redis_key).unwrap().expect("bucket persisted");
+        assert_eq!(bucket.tokens.floor() as u64, 99);
+        client.delete_token_bucket(&redis_key).unwrap();
+    }
+}
@@ -22,7 +22,11 @@
 //! │  ├── should_throttle(key)     → Check if request is throttled  │
 //! │  ├── get_rate_limit_status()  → Get current limit status       │
 //! │  ├── set_rule(key, rule)      → Add/update rate limit rule     │
+//! │  ├── bulk_set_rules(rules)    → Add/update many rules at once  │
 //! │  ├── remove_rule(key)         → Remove rate limit rule         │
+//! │  ├── set_pattern_rule(p, rule)→ Rule for keys with a prefix    │
+//! │  ├── explain(key)             → Which rule applies, and why    │
+//! │  ├── set_enabled(key, bool)   → Pause/resume limiting for key  │
 //! │  ├── reset_rate_limit(key)    → Reset bucket to full capacity  │
 //! │  └── health_check()           → Get service health status      │
 //! │                                                                │
@@ -43,15 +47,64 @@
 //! - Rules are stored in `Arc<RwLock<HashMap>>` for concurrent access
 //! - Multiple readers can check rules simultaneously
 //! - Writers get exclusive access for rule modifications
+//!
+//! ## Rule Resolution
+//!
+//! A key is governed by its exact rule if it has one, otherwise by the
+//! longest matching prefix pattern (e.g. `tenant-*`), otherwise by the
+//! configured defaults (or denied, under `UnknownKeyPolicy::Deny`).
+//! [`Throttler::explain`] reports which of these applied.
+//!
+//! ## Rule Changes
+//!
+//! A check resolves its rule once and uses that copy throughout, so its
+//! capacity and refill rate always come from the same version of the rule.
+//! By default a change applies from the next check that starts after
+//! [`Throttler::set_rule`] returns; checks already in flight finish with the
+//! rule they resolved. With `RuleUpdateOrdering::Serialized`, rule changes
+//! instead wait for in-flight checks, so none still uses the old rule once
+//! the change returns.
+//!
+//! ## Enforcement Rollout
+//!
+//! `Config::enforcement_rollout_pct` lets limits be switched on gradually.
+//! Each key is placed in one of 100 slots by a SHA-256 hash of the key, so
+//! every instance agrees, and keys in slots below the percentage are
+//! enforced. The rest run in shadow: they are checked and consumed as
+//! usual and their would-be denials logged and counted, but let through.
+//! Raising the percentage only adds keys, so a key enforced at 20% stays
+//! enforced at 50%. The global limit protects the service and is always
+//! enforced.
+//!
+//! ## Rule Limit
+//!
+//! Rules are created through the admin API, so their number is capped by
+//! `Config::max_rules` to keep a misbehaving client from exhausting memory.
+//! Exact and pattern rules share the cap; updating an existing key or
+//! pattern never counts against it. A warning is
+//! logged once the store passes [`RULES_WARN_RATIO`] of the cap.
 
-use crate::config::Config;
+use crate::adaptive::AdaptiveCapacity;
+use crate::config::{Config, RemainingSemantics, RuleUpdateOrdering, UnknownKeyPolicy};
+use crate::config_validator::ConfigValidator;
 use crate::error::{ThrottlerError, ThrottlerResult};
-use crate::rate_limit_config::RateLimitRule;
-use crate::rate_limiter::RateLimiter;
+use crate::expiry_events::ExpiryWatcher;
+use crate::metrics::{MetricsCollector, ThrottleMetrics, FLEET_METRICS_KEY};
+use crate::quota::QuotaState;
+use crate::rate_limit_config::{PatternRules, RateLimitRule};
+use crate::rate_limiter::{now_ms, BucketSnapshot, RateLimiter};
+use crate::refund::RefundLedger;
 use crate::redis::RedisClient;
+use crate::route_rules::{match_route, route_bucket_key, validate_route};
+use crate::token_bucket::TokenBucket;
+use crate::validation::RequestValidator;
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Fraction of `Config::max_rules` beyond which a warning is logged
+pub const RULES_WARN_RATIO: f64 = 0.9;
 
 /// Main throttler service that orchestrates rate limiting operations.
 ///
@@ -79,12 +132,88 @@ use tokio::sync::RwLock;
 /// # }
 /// ```
 pub struct Throttler {
+    /// Application configuration shared with the rate limiter
+    config: Arc<Config>,
     /// Core rate limiting engine with token bucket implementation
     rate_limiter: RateLimiter,
     /// Per-key rate limit rules (allows custom limits per client/endpoint)
     rules: Arc<RwLock<HashMap<String, RateLimitRule>>>,
+    /// Rules for every key starting with a prefix, in registration order
+    pattern_rules: Arc<RwLock<PatternRules>>,
+    /// Rules shared by every request to a route, keyed by `METHOD PATH`
+    /// pattern (see [`crate::route_rules`])
+    route_rules: Arc<RwLock<HashMap<String, RateLimitRule>>>,
     /// Optional Redis client for distributed health checks
     redis_client: Option<Arc<RedisClient>>,
+    /// Per-key request counters, recorded by [`Throttler::process_request`]
+    metrics: MetricsCollector,
+    /// Per-key capacities learned from the metrics, when adaptive mode is on
+    adaptive: Option<AdaptiveCapacity>,
+    /// Redis expiry notifications, when `Config::expiry_events` is on
+    expiry_watcher: Option<ExpiryWatcher>,
+    /// Held shared by checks and exclusively by rule changes when
+    /// `Config::rule_update_ordering` is `Serialized`
+    rule_barrier: RwLock<()>,
+    /// Tokens recent checks consumed that may still be refunded
+    refunds: RefundLedger,
+}
+
+/// Which limit denied a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DenialScope {
+    /// The key's own bucket was exhausted
+    Key,
+    /// The service-wide `Config::global_rate_limit` was exhausted
+    Global,
+    /// The key's bucket had tokens, but its rule's quota for the current
+    /// period was spent
+    Quota,
+    /// The bucket shared by every request to the route was exhausted
+    Route,
+}
+
+impl DenialScope {
+    /// Name of the limit, as sent in `X-RateLimit-Scope` and
+    /// `X-RateLimit-Source`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DenialScope::Key => "key",
+            DenialScope::Global => "global",
+            DenialScope::Quota => "quota",
+            DenialScope::Route => "route",
+        }
+    }
+}
+
+/// Everything a handler needs to answer a check, from
+/// [`Throttler::process_request`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestOutcome {
+    /// Whether the request may proceed
+    pub allowed: bool,
+    /// The limit that denied the request, if any
+    pub denied_by: Option<DenialScope>,
+    /// Tokens left after the request, unfloored
+    pub remaining: f64,
+    /// Capacity of the bucket the request was checked against
+    pub limit: u64,
+    /// Seconds to wait before retrying, when denied
+    pub retry_after_secs: Option<u64>,
+    /// The same wait in milliseconds, from the bucket's exact state rather
+    /// than rounded up to whole seconds
+    pub retry_after_ms: Option<u64>,
+    /// Retries still advisable, when denied and `Config::retry_budget` is on
+    pub retry_budget: Option<u64>,
+    /// Fraction of the bucket in use after the request (0.0 = full, 1.0 = empty)
+    pub utilization: f64,
+    /// The key's limit would have denied the request, but the key is not
+    /// yet under enforcement (see `Config::enforcement_rollout_pct`)
+    pub shadow_denied: bool,
+    /// The key's quota after the request, when its rule sets one
+    pub quota: Option<QuotaState>,
+    /// Names this check in a refund of its tokens, when allowed and
+    /// `Config::refund_window_ms` is set (see [`crate::refund`])
+    pub refund_id: Option<String>,
 }
 
 impl Throttler {
@@ -116,29 +245,443 @@ impl Throttler {
     /// # }
     /// ```
     pub fn new(config: Config) -> ThrottlerResult<Self> {
+        // Create the core rate limiting engine
+        Self::with_rate_limiter(RateLimiter::new(config)?)
+    }
+
+    /// Creates a Throttler around an existing rate limiter, sharing its
+    /// buckets (e.g. with the HTTP handlers).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the Redis client cannot be created (when configured).
+    pub fn with_rate_limiter(rate_limiter: RateLimiter) -> ThrottlerResult<Self> {
+        let config = rate_limiter.config().clone();
+
         // Connect to Redis if URL is provided
-        let redis_client = if !config.redis_url.is_empty() {
-            Some(Arc::new(RedisClient::new(&config.redis_url)?))
+        let redis_client = if !config.redis_nodes().is_empty() {
+            Some(Arc::new(RedisClient::from_config(&config)?))
         } else {
             None
         };
 
-        // Create the core rate limiting engine
-        let rate_limiter = RateLimiter::new(config)?;
+        let expiry_watcher = if config.expiry_events && !config.redis_nodes().is_empty() {
+            Some(ExpiryWatcher::start(&config.redis_nodes())?)
+        } else {
+            None
+        };
+
+        let adaptive = config.adaptive_capacity.then(|| AdaptiveCapacity::new(
+            config.default_capacity,
+            config.adaptive_min_capacity,
+            config.adaptive_max_capacity,
+            config.adaptive_interval_ms,
+        ));
+
+        let rules = seed_rules(&config)?;
+
+        let mut metrics = MetricsCollector::with_sample_rate(config.metrics_sample_rate);
+        if config.metrics_flush_interval_ms > 0 && redis_client.is_some() {
+            metrics = metrics.with_fleet_key(FLEET_METRICS_KEY);
+        }
+        if !config.remaining_histogram_buckets.is_empty() {
+            metrics = metrics.with_remaining_histogram(config.remaining_histogram_buckets.clone());
+        }
 
         Ok(Self {
+            metrics,
+            adaptive,
+            config: Arc::new(config),
             rate_limiter,
-            rules: Arc::new(RwLock::new(HashMap::new())),
+            rules: Arc::new(RwLock::new(rules)),
+            pattern_rules: Arc::new(RwLock::new(PatternRules::default())),
+            route_rules: Arc::new(RwLock::new(HashMap::new())),
             redis_client,
+            expiry_watcher,
+            rule_barrier: RwLock::new(()),
+            refunds: RefundLedger::new(),
+        })
+    }
+
+    /// Per-key request counters fed by [`Self::process_request`]
+    pub fn metrics(&self) -> &MetricsCollector {
+        &self.metrics
+    }
+
+    /// Adds the request counts recorded since the last flush to the fleet
+    /// totals in Redis, returning how many keys had new counts. Does
+    /// nothing unless `Config::metrics_flush_interval_ms` is set and Redis
+    /// is configured (see [`crate::metrics`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if Redis could not be updated; the counts are kept
+    /// for the next flush.
+    pub async fn flush_metrics(&self) -> ThrottlerResult<usize> {
+        match &self.redis_client {
+            Some(client) => self.metrics.flush_to(client.clone()).await,
+            None => Ok(0),
+        }
+    }
+
+    /// Per-key request counts across every instance, including this one's
+    /// latest, or `None` when metrics are per-instance.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if Redis could not be read or updated.
+    pub async fn fleet_metrics(&self) -> ThrottlerResult<Option<HashMap<String, ThrottleMetrics>>> {
+        let Some(client) = &self.redis_client else {
+            return Ok(None);
+        };
+        self.metrics.flush_to(client.clone()).await?;
+        self.metrics.fleet_metrics(client.clone()).await
+    }
+
+    /// Receives the key of each bucket that expires in Redis, or `None`
+    /// unless `Config::expiry_events` is on and Redis is configured.
+    ///
+    /// With `Config::hash_keys` the keys are the hashed forms.
+    pub fn subscribe_expirations(&self) -> Option<broadcast::Receiver<String>> {
+        self.expiry_watcher.as_ref().map(ExpiryWatcher::subscribe)
+    }
+
+    /// Checks and consumes `tokens` for a key and records the result in
+    /// the metrics, returning everything needed to build the response.
+    ///
+    /// The request path otherwise resolves the rule, consumes and records
+    /// metrics as separate steps; this does each once, holding no lock
+    /// across them. A key whose rule is disabled is allowed without
+    /// consuming or being counted. The global limit is checked before the
+    /// key's bucket, so a global denial does not spend the key's tokens.
+    /// A key with a rule is limited by the rule's burst capacity and refill
+    /// rate; otherwise, with `Config::adaptive_capacity` on, its capacity is
+    /// the one [`AdaptiveCapacity`] has learned for it. A rule's quota is
+    /// charged once the bucket admits the request (see [`crate::quota`]).
+    ///
+    /// # Errors
+    ///
+    /// Returns `ThrottlerError::UnknownKey`, consuming nothing, if the key
+    /// has no rule and `Config::unknown_key_policy` is `Deny`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use throttler::throttler::Throttler;
+    /// # async fn example(throttler: &Throttler) -> Result<(), Box<dyn std::error::Error>> {
+    /// let outcome = throttler.process_request("client-123", 1).await?;
+    /// if !outcome.allowed {
+    ///     println!("Retry after {:?}s", outcome.retry_after_secs);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn process_request(&self, key: &str, tokens: u64) -> ThrottlerResult<RequestOutcome> {
+        self.process_request_on_route(key, tokens, None).await
+    }
+
+    /// The key's bucket sized by its rule as a check would size it, for
+    /// `?debug=true` checks (see [`RateLimiter::bucket_snapshot`]).
+    pub async fn bucket_snapshot(&self, key: &str) -> ThrottlerResult<BucketSnapshot> {
+        let rule = self.resolve_rule(key).await.map(|resolved| resolved.rule);
+        let (capacity, refill_rate) = self.bucket_params(key, rule.as_ref()).await?;
+        self.rate_limiter.bucket_snapshot(key, capacity, refill_rate).await
+    }
+
+    /// Capacity and refill rate of the key's bucket under `rule`.
+    ///
+    /// The key's rule sizes its bucket from the first request on, so a new
+    /// key gets the rule's burst rather than the default capacity.
+    async fn bucket_params(&self, key: &str, rule: Option<&RateLimitRule>) -> ThrottlerResult<(u64, f64)> {
+        match rule {
+            Some(rule) => Ok((rule.burst_capacity as u64, rule.refill_per_second())),
+            None => {
+                let limit = match &self.adaptive {
+                    Some(adaptive) => adaptive.capacity_for(key, &self.metrics).await?,
+                    None => self.config.default_capacity,
+                };
+                Ok((limit, self.config.default_refill_rate))
+            }
+        }
+    }
+
+    /// Returns `tokens` consumed by the check `refund_id` names to the
+    /// key's bucket, up to its capacity, returning the tokens it then
+    /// holds. See [`crate::refund`].
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ValidationError` if refunds are disabled, or the check is
+    /// unknown, lapsed or another key's, or has fewer than `tokens` left to
+    /// refund. If the bucket cannot be credited (e.g. `StoreUnavailable`),
+    /// the tokens stay refundable.
+    pub async fn refund(&self, key: &str, refund_id: &str, tokens: u64) -> ThrottlerResult<f64> {
+        if self.config.refund_window_ms == 0 {
+            return Err(ThrottlerError::ValidationError(
+                "Refunds are disabled; set REFUND_WINDOW_MS to enable them".to_string()
+            ));
+        }
+        // Claim up front so concurrent refunds cannot both spend the same
+        // tokens, and put the claim back if the bucket is not credited
+        self.refunds.claim(refund_id, key, tokens, now_ms())?;
+        let refunded = self.refund_claimed(key, tokens).await;
+        if refunded.is_err() {
+            self.refunds.restore(refund_id, key, tokens)?;
+        }
+        refunded
+    }
+
+    /// Credits `tokens` already claimed from the refund ledger to the key's
+    /// bucket, sized by its current rule
+    async fn refund_claimed(&self, key: &str, tokens: u64) -> ThrottlerResult<f64> {
+        let rule = self.resolve_rule(key).await.map(|resolved| resolved.rule);
+        let (limit, refill_rate) = self.bucket_params(key, rule.as_ref()).await?;
+        let window_ms = rule.as_ref().map(RateLimitRule::window_ms);
+        self.rate_limiter.refund_tokens(key, limit, refill_rate, tokens, window_ms).await
+    }
+
+    /// [`Self::process_request`] for a request to `route` (`METHOD PATH`,
+    /// see [`crate::route_rules::route_of`]).
+    ///
+    /// When a route rule matches, its bucket is charged after the global
+    /// limit and before the key's: a route denial spends none of the key's
+    /// tokens and is not counted in the key's metrics.
+    pub async fn process_request_on_route(
+        &self,
+        key: &str,
+        tokens: u64,
+        route: Option<&str>,
+    ) -> ThrottlerResult<RequestOutcome> {
+        self.decide(key, tokens, route, true).await
+    }
+
+    /// Reports what [`Self::process_request_on_route`] would decide, for a
+    /// dry check: the same rule, limits and sizing, but nothing is consumed
+    /// (not the key's, route's or global tokens, nor its quota) and nothing
+    /// is recorded in the metrics.
+    ///
+    /// # Errors
+    ///
+    /// As [`Self::process_request`].
+    pub async fn peek_request_on_route(
+        &self,
+        key: &str,
+        tokens: u64,
+        route: Option<&str>,
+    ) -> ThrottlerResult<RequestOutcome> {
+        self.decide(key, tokens, route, false).await
+    }
+
+    /// Charges `tokens` to a bucket, or with `consume` off only reports
+    /// whether it could. `window_ms` is the governing rule's window, which
+    /// bounds how long the bucket is kept idle.
+    async fn charge(
+        &self,
+        key: &str,
+        capacity: u64,
+        refill_rate: f64,
+        window_ms: Option<u64>,
+        tokens: u64,
+        consume: bool,
+    ) -> ThrottlerResult<(bool, f64)> {
+        if consume {
+            self.rate_limiter.consume_tokens_shared(key, capacity, refill_rate, tokens, window_ms).await
+        } else {
+            self.rate_limiter.peek_tokens_shared(key, capacity, refill_rate, tokens).await
+        }
+    }
+
+    async fn decide(
+        &self,
+        key: &str,
+        tokens: u64,
+        route: Option<&str>,
+        consume: bool,
+    ) -> ThrottlerResult<RequestOutcome> {
+        let _barrier = self.check_barrier().await;
+        let rule = self.resolve_rule(key).await.map(|resolved| resolved.rule);
+        if rule.is_none() && self.config.unknown_key_policy == UnknownKeyPolicy::Deny {
+            return Err(ThrottlerError::UnknownKey(key.to_string()));
+        }
+        let (limit, refill_rate) = self.bucket_params(key, rule.as_ref()).await?;
+
+        // Limiting paused for this key: allow without consuming
+        if rule.as_ref().is_some_and(|rule| !rule.enabled) {
+            return Ok(RequestOutcome {
+                allowed: true,
+                denied_by: None,
+                remaining: limit as f64,
+                limit,
+                retry_after_secs: None,
+                retry_after_ms: None,
+                retry_budget: None,
+                utilization: 0.0,
+                shadow_denied: false,
+                quota: None,
+                refund_id: None,
+            });
+        }
+
+        let global_allowed = if consume {
+            self.rate_limiter.check_global_limit()?
+        } else {
+            self.rate_limiter.peek_global_limit()?
+        };
+        if !global_allowed {
+            return Ok(RequestOutcome {
+                allowed: false,
+                denied_by: Some(DenialScope::Global),
+                remaining: 0.0,
+                limit,
+                retry_after_secs: Some(1),
+                retry_after_ms: Some(1000),
+                retry_budget: None,
+                utilization: 1.0,
+                shadow_denied: false,
+                quota: None,
+                refund_id: None,
+            });
+        }
+
+        let route_rule = match route {
+            Some(route) => self.resolve_route_rule(route).await,
+            None => None,
+        };
+        if let Some((pattern, route_rule)) = route_rule.filter(|(_, rule)| rule.enabled) {
+            let route_limit = route_rule.burst_capacity as u64;
+            let route_refill = route_rule.refill_per_second();
+            let (route_allowed, route_remaining) = self
+                .charge(&route_bucket_key(&pattern), route_limit, route_refill, Some(route_rule.window_ms()), tokens, consume)
+                .await?;
+            if !route_allowed {
+                return Ok(RequestOutcome {
+                    allowed: false,
+                    denied_by: Some(DenialScope::Route),
+                    remaining: route_remaining,
+                    limit: route_limit,
+                    retry_after_secs: Some(self.rate_limiter.retry_after_secs(route_refill, route_remaining, tokens)?),
+                    retry_after_ms: Some(self.rate_limiter.retry_after_ms(route_refill, route_remaining, tokens)?),
+                    retry_budget: None,
+                    utilization: utilization(route_remaining, route_limit, route_refill)?,
+                    shadow_denied: false,
+                    quota: None,
+                    refund_id: None,
+                });
+            }
+        }
+
+        let window_ms = rule.as_ref().map(RateLimitRule::window_ms);
+        let (rate_allowed, remaining) = self.charge(key, limit, refill_rate, window_ms, tokens, consume).await?;
+
+        // Only requests the rate limit admits are charged to the quota; a
+        // zero charge still reports what is left of it, and a dry run only reads it
+        let (quota_allowed, quota) = match rule.as_ref().and_then(|rule| rule.quota.map(|quota| (quota, rule.quota_period))) {
+            Some((quota, period)) if consume => {
+                let cost = if rate_allowed { tokens } else { 0 };
+                let (consumed, state) = self.rate_limiter.consume_quota(key, cost, quota, period).await?;
+                (consumed, Some(state))
+            }
+            Some((quota, period)) => {
+                let mut state = self.rate_limiter.peek_quota(key, quota, period).await?;
+                let fits = state.remaining >= tokens;
+                if rate_allowed && fits {
+                    state.remaining -= tokens;
+                }
+                (fits, Some(state))
+            }
+            None => (true, None),
+        };
+        let allowed = rate_allowed && quota_allowed;
+        if consume {
+            self.metrics.record_request(key, allowed).await;
+            self.metrics.record_remaining(remaining);
+        }
+
+        let (retry_after_secs, retry_after_ms, retry_budget) = if allowed {
+            (None, None, None)
+        } else if let Some(quota) = quota.filter(|_| rate_allowed) {
+            // Nothing to gain from retrying before the quota resets
+            let max_retry_after_secs = self.config.max_retry_after_secs.max(1);
+            let until_reset_ms = quota.reset_at_ms.saturating_sub(now_ms());
+            let retry_after = until_reset_ms.div_ceil(1000).clamp(1, max_retry_after_secs);
+            let retry_after_ms = until_reset_ms.clamp(1, max_retry_after_secs * 1000);
+            (Some(retry_after), Some(retry_after_ms), None)
+        } else {
+            let budget = if self.config.retry_budget {
+                Some(self.rate_limiter.retry_budget(key, refill_rate)?)
+            } else {
+                None
+            };
+            let retry_after = self.rate_limiter.retry_after_secs(refill_rate, remaining, tokens)?;
+            let retry_after_ms = self.rate_limiter.retry_after_ms(refill_rate, remaining, tokens)?;
+            (Some(retry_after), Some(retry_after_ms), budget)
+        };
+
+        // Utilization reflects the bucket after this request, whichever
+        // way `remaining` is reported
+        let tokens_after = match self.config.remaining_semantics {
+            RemainingSemantics::Before if rate_allowed => remaining - tokens as f64,
+            _ => remaining,
+        };
+
+        let utilization = utilization(tokens_after, limit, refill_rate)?;
+
+        // Not yet under enforcement: the denial is only recorded
+        if !allowed && !enforced(key, self.config.enforcement_rollout_pct) {
+            if consume {
+                tracing::info!(key = %key, "Shadow denial: key not yet under enforcement");
+            }
+            return Ok(RequestOutcome {
+                allowed: true,
+                denied_by: None,
+                remaining,
+                limit,
+                retry_after_secs: None,
+                retry_after_ms: None,
+                retry_budget: None,
+                utilization,
+                shadow_denied: true,
+                quota,
+                refund_id: None,
+            });
+        }
+
+        let denied_by = match (rate_allowed, quota_allowed) {
+            (false, _) => Some(DenialScope::Key),
+            (true, false) => Some(DenialScope::Quota),
+            (true, true) => None,
+        };
+
+        // The tokens an allowed check spent may be partly handed back
+        let refund_id = if consume && allowed && tokens > 0 && self.config.refund_window_ms > 0 {
+            Some(self.refunds.record(key, tokens, self.config.refund_window_ms, now_ms())?)
+        } else {
+            None
+        };
+
+        Ok(RequestOutcome {
+            allowed,
+            denied_by,
+            remaining,
+            limit,
+            retry_after_secs,
+            retry_after_ms,
+            retry_budget,
+            utilization,
+            shadow_denied: false,
+            quota,
+            refund_id,
         })
     }
 
     /// Checks if a request should be throttled (rate limit exceeded).
     ///
     /// This method:
-    /// 1. Checks if a specific rule exists for the key
-    /// 2. If rule exists and is disabled, allows the request
-    /// 3. Otherwise, checks the rate limiter for token availability
+    /// 1. Resolves the key's rule: exact match, then prefix pattern
+    /// 2. If no rule exists and unknown keys are denied, rejects the request
+    /// 3. If rule exists and is disabled, allows the request
+    /// 4. Otherwise, checks the rate limiter for token availability, using
+    ///    the rule's burst capacity and refill rate when one exists
     ///
     /// # Arguments
     ///
@@ -149,6 +692,11 @@ impl Throttler {
     /// - `Ok(true)` - Request should be blocked (rate limit exceeded)
     /// - `Ok(false)` - Request should be allowed
     ///
+    /// # Errors
+    ///
+    /// Returns `ThrottlerError::UnknownKey` if the key has no rule and
+    /// `Config::unknown_key_policy` is `Deny`.
+    ///
     /// # Example
     ///
     /// ```rust,no_run
@@ -161,18 +709,22 @@ impl Throttler {
     /// # }
     /// ```
     pub async fn should_throttle(&self, key: &str) -> ThrottlerResult<bool> {
-        let rules = self.rules.read().await;
+        let _barrier = self.check_barrier().await;
+
+        // Exact rule first, then the longest matching prefix pattern
+        let resolved = self.resolve_rule(key).await;
 
-        // Check if there's a specific rule for this key
-        if let Some(rule) = rules.get(key) {
+        let (allowed, _remaining) = match resolved.map(|resolved| resolved.rule) {
             // If rate limiting is disabled for this key, allow the request
-            if !rule.enabled {
-                return Ok(false);
+            Some(rule) if !rule.enabled => return Ok(false),
+            // Rule burst capacity and steady refill rate apply
+            Some(rule) => self.rate_limiter.check_rate_limit_with_rule(key, &rule)?,
+            None if self.config.unknown_key_policy == UnknownKeyPolicy::Deny => {
+                return Err(ThrottlerError::UnknownKey(key.to_string()));
             }
-        }
-
-        // Check the rate limiter - returns (allowed, remaining)
-        let (allowed, _remaining) = self.rate_limiter.check_rate_limit(key)?;
+            // Check the rate limiter with defaults - returns (allowed, remaining)
+            None => self.rate_limiter.check_rate_limit(key)?,
+        };
 
         // Return true if request should be throttled (not allowed)
         Ok(!allowed)
@@ -181,10 +733,14 @@ impl Throttler {
     /// Gets the current rate limit status for a key.
     ///
     /// Returns information about:
-    /// - The configured limit
+    /// - The capacity of the key's bucket
     /// - Remaining tokens in the bucket
     /// - Whether rate limiting is enabled for this key
     ///
+    /// The bucket is sized by the governing rule (exact or pattern) or the
+    /// defaults, exactly as [`Self::process_request`] sizes it, so status
+    /// and checks agree.
+    ///
     /// # Arguments
     ///
     /// * `key` - The rate limit key
@@ -193,19 +749,35 @@ impl Throttler {
     ///
     /// A `RateLimitStatus` with current limit information.
     pub async fn get_rate_limit_status(&self, key: &str) -> ThrottlerResult<RateLimitStatus> {
-        let rules = self.rules.read().await;
-        let rule = rules.get(key).cloned().unwrap_or_default();
+        // Exact rule, pattern or default: sized just as a check sizes it
+        let rule = self.resolve_rule(key).await.map(|resolved| resolved.rule);
+        let (limit, refill_per_sec) = self.bucket_params(key, rule.as_ref()).await?;
+        let rule = rule.unwrap_or_default();
 
-        let remaining = self.rate_limiter.get_remaining_tokens(key)?;
+        let remaining = self.rate_limiter
+            .get_remaining_tokens_shared_with_params(key, limit, refill_per_sec)
+            .await?;
+        let utilization = utilization(remaining as f64, limit, refill_per_sec)?;
 
         Ok(RateLimitStatus {
             key: key.to_string(),
-            limit: rule.requests_per_second,
-            remaining: remaining as u32,
+            limit,
+            remaining,
             enabled: rule.enabled,
+            metadata: rule.metadata,
+            utilization,
+            expires_at: rule.expires_at,
+            algorithm: self.algorithm().to_string(),
+            refill_per_sec,
         })
     }
 
+    /// Name of the algorithm enforcing limits, for every key with or
+    /// without a rule of its own (see [`RateLimiter::algorithm_name`])
+    pub fn algorithm(&self) -> &str {
+        self.rate_limiter.algorithm_name()
+    }
+
     /// Adds or updates a rate limit rule for a specific key.
     ///
     /// Rules allow custom rate limits per client or endpoint, overriding
@@ -218,13 +790,315 @@ impl Throttler {
     ///
     /// # Errors
     ///
-    /// Returns an error if rule validation fails.
+    /// Returns an error if rule validation fails or a new key would exceed
+    /// `Config::max_rules`.
     pub async fn set_rule(&self, key: String, rule: RateLimitRule) -> ThrottlerResult<()> {
+        self.set_rule_if(key, rule, None).await.map(|_| ())
+    }
+
+    /// Like [`Self::set_rule`], but with `expected_version` the rule is only
+    /// replaced if its stored version still matches, so concurrent admin
+    /// clients cannot silently overwrite each other's changes.
+    ///
+    /// The stored rule's `version` is set to one past the version it
+    /// replaces (1 for a new rule), and returned.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ThrottlerError::VersionConflict` if the stored version (or
+    /// the absence of a rule) does not match `expected_version`, and
+    /// otherwise the errors of [`Self::set_rule`].
+    pub async fn set_rule_if(
+        &self,
+        key: String,
+        mut rule: RateLimitRule,
+        expected_version: Option<u64>,
+    ) -> ThrottlerResult<u64> {
         // Validate the rule before storing
-        rule.validate().map_err(ThrottlerError::ValidationError)?;
+        validate_rule(&rule, &self.config).map_err(ThrottlerError::ValidationError)?;
+        let _barrier = self.rule_change_barrier().await;
 
         let mut rules = self.rules.write().await;
+        let current = rules.get(&key)
+            .filter(|rule| !rule.is_expired(now_ms()))
+            .map(|rule| rule.version);
+        if expected_version.is_some_and(|expected| current != Some(expected)) {
+            return Err(ThrottlerError::VersionConflict { key, current });
+        }
+
+        let patterns = self.pattern_rules.read().await;
+        let routes = self.route_rules.read().await;
+        let added = usize::from(!rules.contains_key(&key));
+        self.check_rule_capacity(total_rules(&rules, &patterns, &routes), added)?;
+        let version = next_version(rules.get(&key));
+        rule.version = version;
         rules.insert(key, rule);
+        Ok(version)
+    }
+
+    /// Adds or updates a rule for every key starting with a prefix.
+    ///
+    /// Patterns are a literal prefix optionally followed by a single trailing
+    /// `*` (e.g. `tenant-*`). Exact key rules take precedence, then the
+    /// longest matching pattern, then the earliest registered.
+    /// Updating a pattern keeps its place in the registration order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the pattern or rule is invalid, or a new pattern
+    /// would exceed `Config::max_rules`.
+    pub async fn set_pattern_rule(&self, pattern: String, rule: RateLimitRule) -> ThrottlerResult<()> {
+        validate_rule(&rule, &self.config).map_err(ThrottlerError::ValidationError)?;
+        let _barrier = self.rule_change_barrier().await;
+
+        let rules = self.rules.read().await;
+        let mut patterns = self.pattern_rules.write().await;
+        let routes = self.route_rules.read().await;
+        let added = usize::from(!patterns.contains(&pattern));
+        self.check_rule_capacity(total_rules(&rules, &patterns, &routes), added)?;
+        patterns.set(pattern, rule).map_err(ThrottlerError::ValidationError)
+    }
+
+    /// Removes a prefix pattern rule, returning it if it existed.
+    pub async fn remove_pattern_rule(&self, pattern: &str) -> Option<RateLimitRule> {
+        let _barrier = self.rule_change_barrier().await;
+        let mut patterns = self.pattern_rules.write().await;
+        patterns.remove(pattern)
+    }
+
+    /// Adds or updates the rule for a route pattern such as `POST /upload`
+    /// or `GET /reports/*`, limiting all requests to it together.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the route or rule is invalid, or a new route
+    /// would exceed `Config::max_rules`.
+    pub async fn set_route_rule(&self, route: String, rule: RateLimitRule) -> ThrottlerResult<()> {
+        validate_route(&route).map_err(ThrottlerError::ValidationError)?;
+        validate_rule(&rule, &self.config).map_err(ThrottlerError::ValidationError)?;
+        let _barrier = self.rule_change_barrier().await;
+
+        let rules = self.rules.read().await;
+        let patterns = self.pattern_rules.read().await;
+        let mut routes = self.route_rules.write().await;
+        let added = usize::from(!routes.contains_key(&route));
+        self.check_rule_capacity(total_rules(&rules, &patterns, &routes), added)?;
+        routes.insert(route, rule);
+        Ok(())
+    }
+
+    /// Removes a route rule and resets its bucket, returning the rule if
+    /// it existed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the route's bucket could not be reset.
+    pub async fn remove_route_rule(&self, route: &str) -> ThrottlerResult<Option<RateLimitRule>> {
+        let _barrier = self.rule_change_barrier().await;
+        let removed = self.route_rules.write().await.remove(route);
+        if removed.is_some() {
+            self.rate_limiter.reset(&route_bucket_key(route))?;
+        }
+        Ok(removed)
+    }
+
+    /// Finds the rule for a route and the pattern it was set under: the
+    /// exact route's rule, else the longest matching wildcard pattern.
+    ///
+    /// Expired rules are skipped and removed on the way.
+    pub async fn resolve_route_rule(&self, route: &str) -> Option<(String, RateLimitRule)> {
+        let now = now_ms();
+        loop {
+            let routes = self.route_rules.read().await;
+            let (pattern, rule) = match_route(&routes, route)?;
+            if !rule.is_expired(now) {
+                return Some((pattern.clone(), rule.clone()));
+            }
+
+            let pattern = pattern.clone();
+            drop(routes);
+            remove_if_expired(&self.route_rules, &pattern, now).await;
+        }
+    }
+
+    /// Finds the rule governing a key: its exact rule, else the longest
+    /// matching prefix pattern (the earliest registered among equally long
+    /// ones). `None` means the defaults apply.
+    ///
+    /// Expired rules are skipped and removed on the way.
+    pub async fn resolve_rule(&self, key: &str) -> Option<ResolvedRule> {
+        let now = now_ms();
+        let rules = self.rules.read().await;
+        match rules.get(key) {
+            Some(rule) if !rule.is_expired(now) => {
+                return Some(ResolvedRule {
+                    rule: rule.clone(),
+                    source: RuleSource::Exact,
+                    matched: key.to_string(),
+                });
+            }
+            Some(_) => {
+                drop(rules);
+                remove_if_expired(&self.rules, key, now).await;
+            }
+            None => drop(rules),
+        }
+
+        loop {
+            let patterns = self.pattern_rules.read().await;
+            let (pattern, rule) = patterns.matching(key)?;
+            if !rule.is_expired(now) {
+                return Some(ResolvedRule {
+                    rule: rule.clone(),
+                    source: RuleSource::Pattern,
+                    matched: pattern.clone(),
+                });
+            }
+
+            // Drop the lapsed pattern and look for the next longest match
+            let pattern = pattern.clone();
+            drop(patterns);
+            let mut patterns = self.pattern_rules.write().await;
+            if patterns.get(&pattern).is_some_and(|rule| rule.is_expired(now)) {
+                patterns.remove(&pattern);
+                tracing::debug!(pattern = %pattern, "Removed expired pattern rule");
+            }
+        }
+    }
+
+    /// Removes every key, pattern and route rule that has expired, returning
+    /// how many were removed.
+    ///
+    /// Lookups already ignore expired rules; this reclaims the ones that
+    /// are never looked up again.
+    pub async fn sweep_expired_rules(&self) -> usize {
+        let now = now_ms();
+        let mut swept = 0;
+        for store in [&self.rules, &self.route_rules] {
+            let mut rules = store.write().await;
+            let before = rules.len();
+            rules.retain(|_, rule| !rule.is_expired(now));
+            swept += before - rules.len();
+        }
+        let mut patterns = self.pattern_rules.write().await;
+        let before = patterns.len();
+        patterns.retain(|rule| !rule.is_expired(now));
+        swept += before - patterns.len();
+        drop(patterns);
+        if swept > 0 {
+            tracing::info!(swept, "Removed expired rate limit rules");
+        }
+        swept
+    }
+
+    /// Reports the effective limits for a key and how they were resolved.
+    ///
+    /// A debugging aid: does not consume tokens.
+    pub async fn explain(&self, key: &str) -> RuleExplanation {
+        let Some(resolved) = self.resolve_rule(key).await else {
+            if self.config.unknown_key_policy == UnknownKeyPolicy::Deny {
+                return RuleExplanation {
+                    key: key.to_string(),
+                    source: RuleSource::Denied,
+                    matched: None,
+                    explanation: "no rule matched and unknown keys are denied".to_string(),
+                    rule: None,
+                };
+            }
+            return RuleExplanation {
+                key: key.to_string(),
+                source: RuleSource::Default,
+                matched: None,
+                explanation: "no rule matched; fell back to default".to_string(),
+                rule: Some(EffectiveRule {
+                    capacity: self.config.default_capacity,
+                    refill_per_second: self.config.default_refill_rate,
+                    enabled: true,
+                }),
+            };
+        };
+
+        let explanation = match resolved.source {
+            RuleSource::Pattern => format!("matched prefix pattern `{}`", resolved.matched),
+            _ => "matched exact key rule".to_string(),
+        };
+        RuleExplanation {
+            key: key.to_string(),
+            source: resolved.source,
+            matched: Some(resolved.matched),
+            explanation,
+            rule: Some(EffectiveRule {
+                capacity: resolved.rule.burst_capacity as u64,
+                refill_per_second: resolved.rule.refill_per_second(),
+                enabled: resolved.rule.enabled,
+            }),
+        }
+    }
+
+    /// Adds or updates several rules at once.
+    ///
+    /// All rules are validated, and the cap checked for all new keys, before
+    /// any is stored; on error nothing changes.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any rule is invalid or the new keys would exceed
+    /// `Config::max_rules`.
+    pub async fn bulk_set_rules(&self, new_rules: HashMap<String, RateLimitRule>) -> ThrottlerResult<()> {
+        for (key, rule) in &new_rules {
+            validate_rule(rule, &self.config).map_err(|e| {
+                ThrottlerError::ValidationError(format!("Rule for key {}: {}", key, e))
+            })?;
+        }
+
+        let _barrier = self.rule_change_barrier().await;
+        let mut rules = self.rules.write().await;
+        let patterns = self.pattern_rules.read().await;
+        let routes = self.route_rules.read().await;
+        let added = new_rules.keys().filter(|key| !rules.contains_key(*key)).count();
+        self.check_rule_capacity(total_rules(&rules, &patterns, &routes), added)?;
+        for (key, mut rule) in new_rules {
+            rule.version = next_version(rules.get(&key));
+            rules.insert(key, rule);
+        }
+        Ok(())
+    }
+
+    /// Taken for the whole of a check: with `RuleUpdateOrdering::Serialized`
+    /// it keeps rule changes out until the check has consumed
+    async fn check_barrier(&self) -> Option<RwLockReadGuard<'_, ()>> {
+        match self.config.rule_update_ordering {
+            RuleUpdateOrdering::NextConsume => None,
+            RuleUpdateOrdering::Serialized => Some(self.rule_barrier.read().await),
+        }
+    }
+
+    /// Taken by rule changes: with `RuleUpdateOrdering::Serialized` it waits
+    /// for in-flight checks to finish
+    async fn rule_change_barrier(&self) -> Option<RwLockWriteGuard<'_, ()>> {
+        match self.config.rule_update_ordering {
+            RuleUpdateOrdering::NextConsume => None,
+            RuleUpdateOrdering::Serialized => Some(self.rule_barrier.write().await),
+        }
+    }
+
+    /// Rejects adding `added` rules to a store holding `current`, and warns
+    /// when the store is getting close to the cap.
+    fn check_rule_capacity(&self, current: usize, added: usize) -> ThrottlerResult<()> {
+        let max_rules = self.config.max_rules;
+        if max_rules == 0 || added == 0 {
+            return Ok(());
+        }
+
+        let total = current + added;
+        if total > max_rules {
+            return Err(ThrottlerError::ValidationError(format!(
+                "Rule limit of {} reached; remove unused rules first", max_rules
+            )));
+        }
+        if total as f64 >= max_rules as f64 * RULES_WARN_RATIO {
+            tracing::warn!(rules = total, max_rules, "Rule store approaching its limit");
+        }
         Ok(())
     }
 
@@ -240,18 +1114,58 @@ impl Throttler {
     ///
     /// The removed rule, if it existed.
     pub async fn remove_rule(&self, key: &str) -> ThrottlerResult<Option<RateLimitRule>> {
+        let _barrier = self.rule_change_barrier().await;
         let mut rules = self.rules.write().await;
         Ok(rules.remove(key))
     }
 
+    /// Enables or disables limiting for a key that already has a rule.
+    ///
+    /// A disabled rule lets every request for the key through until it is
+    /// enabled again; the rest of the rule is left unchanged.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ThrottlerError::RuleNotFound` if the key has no rule.
+    pub async fn set_enabled(&self, key: &str, enabled: bool) -> ThrottlerResult<()> {
+        let _barrier = self.rule_change_barrier().await;
+        let mut rules = self.rules.write().await;
+        let rule = rules.get_mut(key)
+            .ok_or_else(|| ThrottlerError::RuleNotFound(key.to_string()))?;
+        rule.enabled = enabled;
+        rule.version += 1;
+        Ok(())
+    }
+
+    /// Gets the rule configured for a key, if any.
+    pub async fn get_rule(&self, key: &str) -> Option<RateLimitRule> {
+        let rules = self.rules.read().await;
+        rules.get(key).filter(|rule| !rule.is_expired(now_ms())).cloned()
+    }
+
     /// Gets all configured rate limit rules.
     ///
     /// # Returns
     ///
     /// A clone of the rules map.
     pub async fn get_all_rules(&self) -> ThrottlerResult<HashMap<String, RateLimitRule>> {
+        let now = now_ms();
         let rules = self.rules.read().await;
-        Ok(rules.clone())
+        Ok(rules.iter()
+            .filter(|(_, rule)| !rule.is_expired(now))
+            .map(|(key, rule)| (key.clone(), rule.clone()))
+            .collect())
+    }
+
+    /// Gets the metadata labels of every unexpired rule that has any, keyed
+    /// by rate limit key (used to label exported metrics).
+    pub async fn get_metadata_labels(&self) -> HashMap<String, HashMap<String, String>> {
+        let now = now_ms();
+        let rules = self.rules.read().await;
+        rules.iter()
+            .filter(|(_, rule)| !rule.metadata.is_empty() && !rule.is_expired(now))
+            .map(|(key, rule)| (key.clone(), rule.metadata.clone()))
+            .collect()
     }
 
     /// Resets the rate limit bucket for a specific key.
@@ -266,6 +1180,9 @@ impl Throttler {
     ///
     /// * `key` - The rate limit key to reset
     pub async fn reset_rate_limit(&self, key: &str) -> ThrottlerResult<()> {
+        if let Some(adaptive) = &self.adaptive {
+            adaptive.forget(key)?;
+        }
         self.rate_limiter.reset(key)
     }
 
@@ -299,12 +1216,156 @@ impl Throttler {
 pub struct RateLimitStatus {
     /// The rate limit key
     pub key: String,
-    /// Maximum requests allowed per window
-    pub limit: u32,
-    /// Remaining requests in current window
-    pub remaining: u32,
+    /// Capacity of the key's bucket
+    pub limit: u64,
+    /// Whole tokens left in the key's bucket
+    pub remaining: u64,
     /// Whether rate limiting is enabled for this key
     pub enabled: bool,
+    /// Operator-defined labels attached to the key's rule
+    pub metadata: HashMap<String, String>,
+    /// Fraction of the bucket in use (0.0 = full, 1.0 = empty)
+    pub utilization: f64,
+    /// When the key's temporary rule lapses (ms since UNIX epoch)
+    pub expires_at: Option<u64>,
+    /// Name of the algorithm in effect for the key, e.g. `token_bucket`
+    pub algorithm: String,
+    /// Tokens per second the key's bucket refills at, after converting the
+    /// governing rule's `rate_unit` (or the configured default refill rate)
+    pub refill_per_sec: f64,
+}
+
+/// Key, pattern and route rules together, as counted against
+/// `Config::max_rules`. Callers lock the stores in this order.
+fn total_rules(
+    rules: &HashMap<String, RateLimitRule>,
+    patterns: &PatternRules,
+    routes: &HashMap<String, RateLimitRule>,
+) -> usize {
+    rules.len() + patterns.len() + routes.len()
+}
+
+/// Removes `key` from `rules` if its rule has expired by `now`, re-checking
+/// under the write lock in case it was replaced meanwhile
+async fn remove_if_expired(rules: &RwLock<HashMap<String, RateLimitRule>>, key: &str, now: u64) {
+    let mut rules = rules.write().await;
+    if rules.get(key).is_some_and(|rule| rule.is_expired(now)) {
+        rules.remove(key);
+        tracing::debug!(key = %key, "Removed expired rate limit rule");
+    }
+}
+
+/// Validates a rule, and rejects one that refills its whole capacity in
+/// under `Config::min_full_refill_ms`.
+fn validate_rule(rule: &RateLimitRule, config: &Config) -> Result<(), String> {
+    rule.validate()?;
+    ConfigValidator::validate_full_refill_interval(
+        rule.burst_capacity as u64,
+        rule.refill_per_second(),
+        config.min_full_refill_ms,
+    )
+    .map_err(|e| match e {
+        ThrottlerError::ValidationError(msg) => msg,
+        other => other.to_string(),
+    })
+}
+
+/// The rules of `Config::seed_rules`, keyed as requests will name them.
+///
+/// Every key and rule is validated as if set through the API, so a bad
+/// entry stops startup rather than being skipped.
+fn seed_rules(config: &Config) -> ThrottlerResult<HashMap<String, RateLimitRule>> {
+    let validator = RequestValidator::new()
+        .with_key_case(config.key_case)
+        .with_key_slashes(config.key_slashes);
+    let mut rules = HashMap::new();
+    for (key, rule) in &config.seed_rules {
+        validator.validate_key(key)?;
+        validate_rule(rule, config).map_err(|e| {
+            ThrottlerError::ValidationError(format!("Seeded rule for key {}: {}", key, e))
+        })?;
+        let rule = RateLimitRule { version: 1, ..rule.clone() };
+        rules.insert(validator.normalize_key(key.clone()), rule);
+    }
+
+    if config.max_rules > 0 && rules.len() > config.max_rules {
+        return Err(ThrottlerError::ValidationError(format!(
+            "{} seeded rules exceed the rule limit of {}", rules.len(), config.max_rules
+        )));
+    }
+    Ok(rules)
+}
+
+/// Version for a rule replacing `existing` (1 for a new rule)
+fn next_version(existing: Option<&RateLimitRule>) -> u64 {
+    existing.map_or(1, |rule| rule.version + 1)
+}
+
+/// Whether `key` falls within an enforcement rollout of `rollout_pct`
+/// percent: its slot, from a SHA-256 hash of the key, is below it
+pub fn enforced(key: &str, rollout_pct: u8) -> bool {
+    let digest = Sha256::digest(key.as_bytes());
+    let hash = u64::from_be_bytes(digest[..8].try_into().expect("SHA-256 digest has 8 bytes"));
+    hash % 100 < rollout_pct as u64
+}
+
+/// Utilization of a bucket of `capacity` holding `tokens`, per
+/// [`TokenBucket::utilization`]
+fn utilization(tokens: f64, capacity: u64, refill_rate: f64) -> ThrottlerResult<f64> {
+    let mut bucket = TokenBucket::new(capacity, refill_rate);
+    bucket.tokens = tokens.clamp(0.0, capacity as f64);
+    Ok(bucket.utilization()?.clamp(0.0, 1.0))
+}
+
+/// Where the rule governing a key came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleSource {
+    /// A rule configured for exactly this key
+    Exact,
+    /// The longest prefix pattern matching the key
+    Pattern,
+    /// No rule matched; the configured defaults apply
+    Default,
+    /// No rule matched and unknown keys are denied
+    Denied,
+}
+
+/// A rule together with how it was found for a key.
+#[derive(Debug, Clone)]
+pub struct ResolvedRule {
+    /// The governing rule
+    pub rule: RateLimitRule,
+    /// `Exact` or `Pattern`
+    pub source: RuleSource,
+    /// The rule key or pattern that matched
+    pub matched: String,
+}
+
+/// Limits in effect for a key, normalized across rules and defaults.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct EffectiveRule {
+    /// Burst capacity
+    pub capacity: u64,
+    /// Sustained refill rate in tokens per second
+    pub refill_per_second: f64,
+    /// Whether limiting is active
+    pub enabled: bool,
+}
+
+/// Resolution trace returned by [`Throttler::explain`].
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RuleExplanation {
+    /// The rate limit key
+    pub key: String,
+    /// Which kind of rule applied
+    pub source: RuleSource,
+    /// The rule key or pattern that matched, if any
+    pub matched: Option<String>,
+    /// Human-readable resolution path
+    pub explanation: String,
+    /// Effective limits; absent when the key is denied outright
+    pub rule: Option<EffectiveRule>,
 }
 
 /// Service health status information.
@@ -317,3 +1378,796 @@ pub struct HealthStatus {
     /// Whether Redis is connected and responsive
     pub redis_connected: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quota::QuotaPeriod;
+    use crate::rate_limit_config::RateUnit;
+
+    fn deny_unknown_config() -> Config {
+        Config {
+            unknown_key_policy: UnknownKeyPolicy::Deny,
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_deny_mode_allows_known_key() {
+        let throttler = Throttler::new(deny_unknown_config()).unwrap();
+        throttler.set_rule("known".to_string(), RateLimitRule::default()).await.unwrap();
+
+        assert!(!throttler.should_throttle("known").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_deny_mode_rejects_unknown_key() {
+        let throttler = Throttler::new(deny_unknown_config()).unwrap();
+
+        let err = throttler.should_throttle("stranger").await.unwrap_err();
+        assert!(matches!(err, ThrottlerError::UnknownKey(ref key) if key == "stranger"));
+    }
+
+    #[tokio::test]
+    async fn test_deny_mode_rejects_unknown_key_on_process_request() {
+        let throttler = Throttler::new(deny_unknown_config()).unwrap();
+
+        let err = throttler.process_request("stranger", 1).await.unwrap_err();
+        assert!(matches!(err, ThrottlerError::UnknownKey(ref key) if key == "stranger"));
+        assert!(throttler.metrics().get_client_metrics("stranger").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_seeded_rule_applies_from_the_first_request() {
+        let throttler = Throttler::new(Config {
+            seed_rules: vec![("seeded".to_string(), RateLimitRule::new(1, 2, std::time::Duration::from_secs(60)))],
+            ..deny_unknown_config()
+        }).unwrap();
+
+        assert!(throttler.get_rule("seeded").await.is_some());
+        assert!(!throttler.should_throttle("seeded").await.unwrap());
+        assert!(!throttler.should_throttle("seeded").await.unwrap());
+        assert!(throttler.should_throttle("seeded").await.unwrap());
+    }
+
+    #[test]
+    fn test_invalid_seeded_rule_fails_startup() {
+        let seeded = |key: &str, rule| Config {
+            seed_rules: vec![
+                ("good".to_string(), RateLimitRule::default()),
+                (key.to_string(), rule),
+            ],
+            ..Config::default()
+        };
+
+        let Err(err) = Throttler::new(seeded("bad", RateLimitRule::new(0, 5, std::time::Duration::from_secs(60)))) else {
+            panic!("zero-rate rule was accepted");
+        };
+        assert!(err.to_string().contains("bad"), "{}", err);
+        assert!(Throttler::new(seeded("bad key!", RateLimitRule::default())).is_err());
+        assert!(Throttler::new(seeded("fine", RateLimitRule::default())).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rules_refilling_too_fast_are_rejected() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+
+        // A million tokens a second refills a burst of 10 in 10µs
+        let absurd = RateLimitRule::new(1_000_000, 10, std::time::Duration::from_secs(60));
+        let err = throttler.set_rule("absurd".to_string(), absurd.clone()).await.unwrap_err();
+        assert!(err.to_string().contains("minimum"), "{}", err);
+        assert!(throttler.set_pattern_rule("absurd-*".to_string(), absurd).await.is_err());
+
+        let sane = RateLimitRule::new(100, 200, std::time::Duration::from_secs(60));
+        assert!(throttler.set_rule("sane".to_string(), sane).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_route_rule_limits_all_clients_together() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+        let upload = RateLimitRule::new(1, 2, std::time::Duration::from_secs(60));
+        throttler.set_route_rule("POST /upload".to_string(), upload).await.unwrap();
+
+        // Two clients share the route's burst of 2
+        let on_route = |key: &'static str| throttler.process_request_on_route(key, 1, Some("POST /upload"));
+        assert!(on_route("alice").await.unwrap().allowed);
+        assert!(on_route("bob").await.unwrap().allowed);
+        let outcome = on_route("carol").await.unwrap();
+        assert_eq!(outcome.denied_by, Some(DenialScope::Route));
+        assert_eq!(outcome.limit, 2);
+
+        // Other routes, and checks naming no route, are unaffected
+        assert!(throttler.process_request_on_route("carol", 1, Some("GET /upload")).await.unwrap().allowed);
+        assert!(throttler.process_request("carol", 1).await.unwrap().allowed);
+
+        // Removing the rule lifts the limit
+        assert!(throttler.remove_route_rule("POST /upload").await.unwrap().is_some());
+        assert!(on_route("carol").await.unwrap().allowed);
+    }
+
+    #[tokio::test]
+    async fn test_status_reports_effective_refill_rate() {
+        let throttler = Throttler::new(Config { default_refill_rate: 2.5, ..Config::default() }).unwrap();
+        let hourly = RateLimitRule::new(3600, 100, std::time::Duration::from_secs(3600))
+            .with_rate_unit(RateUnit::PerHour);
+        throttler.set_rule("hourly".to_string(), hourly).await.unwrap();
+        let windowed = RateLimitRule::new(60, 30, std::time::Duration::from_secs(60))
+            .with_rate_unit(RateUnit::PerWindow);
+        throttler.set_pattern_rule("windowed-*".to_string(), windowed).await.unwrap();
+
+        // 3600 per hour and 60 per 60s window are both 1 token per second
+        for key in ["hourly", "windowed-7"] {
+            let status = throttler.get_rate_limit_status(key).await.unwrap();
+            assert!((status.refill_per_sec - 1.0).abs() < 1e-9, "{}: {}", key, status.refill_per_sec);
+        }
+        let status = throttler.get_rate_limit_status("defaulted").await.unwrap();
+        assert_eq!(status.refill_per_sec, 2.5);
+    }
+
+    #[tokio::test]
+    async fn test_status_is_sized_like_a_check() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+        let hourly = RateLimitRule::new(3600, 10, std::time::Duration::from_secs(3600))
+            .with_rate_unit(RateUnit::PerHour);
+        throttler.set_pattern_rule("hourly-*".to_string(), hourly).await.unwrap();
+
+        // An unseen key reports its rule's burst, not the default capacity
+        let status = throttler.get_rate_limit_status("hourly-1").await.unwrap();
+        assert_eq!((status.limit, status.remaining), (10, 10));
+        assert_eq!(status.utilization, 0.0);
+
+        let outcome = throttler.process_request("hourly-1", 4).await.unwrap();
+        let status = throttler.get_rate_limit_status("hourly-1").await.unwrap();
+        assert_eq!(status.limit, outcome.limit);
+        assert_eq!(status.remaining, outcome.remaining.floor() as u64);
+        assert!((status.utilization - 0.4).abs() < 0.01, "{}", status.utilization);
+
+        let status = throttler.get_rate_limit_status("defaulted").await.unwrap();
+        assert_eq!(status.limit, Config::default().default_capacity);
+    }
+
+    #[tokio::test]
+    async fn test_conditional_rule_updates_check_the_version() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+        let rule = RateLimitRule::default();
+
+        // Expecting a version of a rule that does not exist conflicts
+        let err = throttler.set_rule_if("k".to_string(), rule.clone(), Some(1)).await.unwrap_err();
+        assert!(matches!(err, ThrottlerError::VersionConflict { current: None, .. }));
+
+        assert_eq!(throttler.set_rule_if("k".to_string(), rule.clone(), None).await.unwrap(), 1);
+        assert_eq!(throttler.set_rule_if("k".to_string(), rule.clone(), Some(1)).await.unwrap(), 2);
+
+        // A client still holding version 1 cannot overwrite version 2
+        let err = throttler.set_rule_if("k".to_string(), rule.clone(), Some(1)).await.unwrap_err();
+        assert!(matches!(err, ThrottlerError::VersionConflict { current: Some(2), .. }));
+
+        // Other changes bump the version too
+        throttler.set_enabled("k", false).await.unwrap();
+        assert_eq!(throttler.get_rule("k").await.unwrap().version, 3);
+    }
+
+    #[tokio::test]
+    async fn test_status_reports_algorithm() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+        throttler.set_rule("ruled".to_string(), RateLimitRule::default()).await.unwrap();
+
+        for key in ["ruled", "defaulted"] {
+            let status = throttler.get_rate_limit_status(key).await.unwrap();
+            assert_eq!(status.algorithm, "token_bucket");
+        }
+        let status = serde_json::to_value(throttler.get_rate_limit_status("ruled").await.unwrap()).unwrap();
+        assert_eq!(status["algorithm"], "token_bucket");
+    }
+
+    #[tokio::test]
+    async fn test_process_request_outcome() {
+        let throttler = Throttler::new(Config {
+            default_capacity: 3,
+            default_refill_rate: 0.5,
+            ..Config::default()
+        }).unwrap();
+
+        let outcome = throttler.process_request("client", 2).await.unwrap();
+        assert!(outcome.allowed);
+        assert_eq!(outcome.limit, 3);
+        assert!((outcome.remaining - 1.0).abs() < 0.01);
+        assert_eq!(outcome.retry_after_secs, None);
+
+        // More than is left: denied, nothing consumed, and told to wait
+        // for both tokens rather than one
+        let outcome = throttler.process_request("client", 2).await.unwrap();
+        assert!(!outcome.allowed);
+        assert_eq!(outcome.denied_by, Some(DenialScope::Key));
+        assert_eq!(outcome.retry_after_secs, Some(4));
+        assert_eq!(outcome.retry_budget, None);
+
+        assert!(throttler.process_request("client", 1).await.unwrap().allowed);
+    }
+
+    #[tokio::test]
+    async fn test_peek_sizes_like_a_real_check_and_consumes_nothing() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+        let rule = RateLimitRule::new(1, 3, std::time::Duration::from_secs(60))
+            .with_quota(10, QuotaPeriod::Monthly);
+        throttler.set_rule("weighted".to_string(), rule).await.unwrap();
+
+        for _ in 0..3 {
+            let outcome = throttler.peek_request_on_route("weighted", 2, None).await.unwrap();
+            assert!(outcome.allowed);
+            assert_eq!(outcome.limit, 3);
+            assert!((outcome.remaining - 1.0).abs() < 0.01);
+            assert_eq!(outcome.quota.unwrap().remaining, 8);
+            assert_eq!(outcome.refund_id, None);
+        }
+        assert!(throttler.metrics().get_client_metrics("weighted").await.is_none());
+
+        // More tokens than the rule's burst would be denied
+        let outcome = throttler.peek_request_on_route("weighted", 4, None).await.unwrap();
+        assert_eq!(outcome.denied_by, Some(DenialScope::Key));
+        assert!(throttler.process_request("weighted", 3).await.unwrap().allowed);
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_quota_denies_while_bucket_has_tokens() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+        let rule = RateLimitRule::new(100, 100, std::time::Duration::from_secs(60))
+            .with_quota(3, QuotaPeriod::Monthly);
+        throttler.set_rule("metered".to_string(), rule).await.unwrap();
+
+        let outcome = throttler.process_request("metered", 2).await.unwrap();
+        assert!(outcome.allowed);
+        let quota = outcome.quota.unwrap();
+        assert_eq!(quota.remaining, 1);
+        assert_eq!(quota.reset_at_ms, QuotaPeriod::Monthly.reset_at(now_ms()));
+
+        // The bucket still holds plenty, but the quota does not
+        let outcome = throttler.process_request("metered", 2).await.unwrap();
+        assert!(!outcome.allowed);
+        assert_eq!(outcome.denied_by, Some(DenialScope::Quota));
+        assert!(outcome.remaining > 90.0);
+        assert_eq!(outcome.quota.unwrap().remaining, 1);
+        assert!(outcome.retry_after_secs.unwrap() >= 1);
+
+        assert!(throttler.process_request("metered", 1).await.unwrap().allowed);
+        assert_eq!(throttler.process_request("metered", 1).await.unwrap().denied_by, Some(DenialScope::Quota));
+    }
+
+    #[tokio::test]
+    async fn test_utilization_tracks_drain() {
+        let throttler = Throttler::new(Config {
+            default_capacity: 10,
+            default_refill_rate: 0.01,
+            ..Config::default()
+        }).unwrap();
+
+        let status = throttler.get_rate_limit_status("draining").await.unwrap();
+        assert_eq!(status.utilization, 0.0);
+
+        let mut last = 0.0;
+        for _ in 0..10 {
+            let outcome = throttler.process_request("draining", 1).await.unwrap();
+            assert!(outcome.utilization > last);
+            last = outcome.utilization;
+        }
+        assert!(last > 0.99, "utilization was {}", last);
+
+        let status = throttler.get_rate_limit_status("draining").await.unwrap();
+        assert!(status.utilization > 0.99);
+    }
+
+    #[tokio::test]
+    async fn test_utilization_is_post_request_with_before_semantics() {
+        let throttler = Throttler::new(Config {
+            default_capacity: 4,
+            remaining_semantics: RemainingSemantics::Before,
+            ..Config::default()
+        }).unwrap();
+
+        let outcome = throttler.process_request("before", 1).await.unwrap();
+        assert!((outcome.remaining - 4.0).abs() < 0.01);
+        assert!((outcome.utilization - 0.25).abs() < 0.01);
+    }
+
+    fn adaptive_config(default_capacity: u64, refill_rate: f64) -> Config {
+        Config {
+            default_capacity,
+            default_refill_rate: refill_rate,
+            adaptive_capacity: true,
+            adaptive_min_capacity: default_capacity / 2,
+            adaptive_max_capacity: default_capacity * 2,
+            adaptive_interval_ms: 0,
+            ..Config::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_capacity_grows_for_steady_client() {
+        let throttler = Throttler::new(adaptive_config(100, 10.0)).unwrap();
+
+        let mut limit = 0;
+        for _ in 0..60 {
+            let outcome = throttler.process_request("steady", 1).await.unwrap();
+            assert!(outcome.allowed);
+            limit = outcome.limit;
+        }
+        assert!(limit > 100, "limit was {}", limit);
+        assert!(limit <= 200);
+    }
+
+    #[tokio::test]
+    async fn test_adaptive_capacity_shrinks_for_throttled_client() {
+        let throttler = Throttler::new(adaptive_config(10, 0.01)).unwrap();
+
+        let mut limit = 0;
+        for _ in 0..200 {
+            limit = throttler.process_request("abuser", 1).await.unwrap().limit;
+        }
+        assert_eq!(limit, 5);
+
+        // Without adaptive mode the default capacity always applies
+        let throttler = Throttler::new(Config { default_capacity: 10, ..Config::default() }).unwrap();
+        for _ in 0..20 {
+            assert_eq!(throttler.process_request("abuser", 1).await.unwrap().limit, 10);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_process_request_records_metrics_once() {
+        let throttler = Throttler::new(Config {
+            default_capacity: 2,
+            default_refill_rate: 0.001,
+            retry_budget: true,
+            ..Config::default()
+        }).unwrap();
+
+        for _ in 0..3 {
+            throttler.process_request("counted", 1).await.unwrap();
+        }
+        let metrics = throttler.metrics().get_client_metrics("counted").await.unwrap();
+        assert_eq!(metrics.total_requests, 3);
+        assert_eq!(metrics.allowed_requests, 2);
+        assert_eq!(metrics.throttled_requests, 1);
+
+        let rule = RateLimitRule { enabled: false, ..RateLimitRule::default() };
+        throttler.set_rule("paused".to_string(), rule).await.unwrap();
+        assert!(throttler.process_request("paused", 1).await.unwrap().allowed);
+        assert!(throttler.metrics().get_client_metrics("paused").await.is_none());
+    }
+
+    #[test]
+    fn test_enforcement_rollout_is_stable_per_key() {
+        let keys: Vec<String> = (0..1000).map(|i| format!("client-{}", i)).collect();
+
+        assert!(keys.iter().all(|key| !enforced(key, 0)));
+        assert!(keys.iter().all(|key| enforced(key, 100)));
+
+        let at_30: Vec<bool> = keys.iter().map(|key| enforced(key, 30)).collect();
+        assert_eq!(at_30, keys.iter().map(|key| enforced(key, 30)).collect::<Vec<_>>());
+        let share = at_30.iter().filter(|&&on| on).count();
+        assert!((200..400).contains(&share), "{} of 1000 enforced at 30%", share);
+
+        // Ramping up only adds keys
+        for (key, was_enforced) in keys.iter().zip(at_30) {
+            if was_enforced {
+                assert!(enforced(key, 60), "{} dropped out at 60%", key);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unenforced_keys_are_shadowed() {
+        let throttler = |enforcement_rollout_pct| Throttler::new(Config {
+            default_capacity: 1,
+            default_refill_rate: 0.001,
+            enforcement_rollout_pct,
+            ..Config::default()
+        }).unwrap();
+
+        let shadowed = throttler(0);
+        assert!(shadowed.process_request("ramping", 1).await.unwrap().allowed);
+        let outcome = shadowed.process_request("ramping", 1).await.unwrap();
+        assert!(outcome.allowed && outcome.shadow_denied);
+        assert_eq!(outcome.denied_by, None);
+        assert_eq!(shadowed.metrics().get_client_metrics("ramping").await.unwrap().throttled_requests, 1);
+
+        let enforcing = throttler(100);
+        assert!(enforcing.process_request("ramping", 1).await.unwrap().allowed);
+        let outcome = enforcing.process_request("ramping", 1).await.unwrap();
+        assert!(!outcome.allowed && !outcome.shadow_denied);
+        assert_eq!(outcome.denied_by, Some(DenialScope::Key));
+    }
+
+    #[tokio::test]
+    async fn test_status_includes_rule_metadata() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+        let metadata = HashMap::from([("plan".to_string(), "gold".to_string())]);
+        throttler.set_rule("client".to_string(), RateLimitRule::default().with_metadata(metadata.clone()))
+            .await.unwrap();
+
+        let status = throttler.get_rate_limit_status("client").await.unwrap();
+        assert_eq!(status.metadata, metadata);
+        assert_eq!(throttler.get_metadata_labels().await["client"], metadata);
+
+        // A lapsed rule no longer labels anything
+        let lapsed = RateLimitRule::default().with_metadata(metadata).with_expires_at(now_ms() - 1);
+        throttler.set_rule("former".to_string(), lapsed).await.unwrap();
+        assert!(!throttler.get_metadata_labels().await.contains_key("former"));
+    }
+
+    #[tokio::test]
+    async fn test_set_rule_rejects_oversized_metadata() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+        let metadata = HashMap::from([("plan".to_string(), "x".repeat(1000))]);
+
+        let result = throttler.set_rule("client".to_string(), RateLimitRule::default().with_metadata(metadata)).await;
+        assert!(matches!(result, Err(ThrottlerError::ValidationError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_rule_burst_capacity_is_enforced() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+        let rule = RateLimitRule::new(1, 3, std::time::Duration::from_secs(60));
+        throttler.set_rule("bursty".to_string(), rule).await.unwrap();
+
+        for _ in 0..3 {
+            assert!(!throttler.should_throttle("bursty").await.unwrap());
+        }
+        assert!(throttler.should_throttle("bursty").await.unwrap());
+    }
+
+    fn capped_throttler(max_rules: usize) -> Throttler {
+        Throttler::new(Config { max_rules, ..Config::default() }).unwrap()
+    }
+
+    fn any_rule() -> RateLimitRule {
+        RateLimitRule::new(10, 10, std::time::Duration::from_secs(60))
+    }
+
+    #[tokio::test]
+    async fn test_set_rule_enforces_max_rules() {
+        let throttler = capped_throttler(3);
+        for i in 0..3 {
+            throttler.set_rule(format!("key-{}", i), any_rule()).await.unwrap();
+        }
+
+        let err = throttler.set_rule("key-3".to_string(), any_rule()).await.unwrap_err();
+        assert!(matches!(err, ThrottlerError::ValidationError(_)));
+
+        // Updating an existing key is still allowed at the cap
+        throttler.set_rule("key-0".to_string(), any_rule()).await.unwrap();
+
+        // Removing a rule frees a slot
+        throttler.remove_rule("key-1").await.unwrap();
+        throttler.set_rule("key-3".to_string(), any_rule()).await.unwrap();
+        assert_eq!(throttler.get_all_rules().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_bulk_set_rules_is_all_or_nothing() {
+        let throttler = capped_throttler(3);
+        throttler.set_rule("existing".to_string(), any_rule()).await.unwrap();
+
+        let too_many: HashMap<_, _> = (0..3).map(|i| (format!("bulk-{}", i), any_rule())).collect();
+        assert!(throttler.bulk_set_rules(too_many).await.is_err());
+        assert_eq!(throttler.get_all_rules().await.unwrap().len(), 1);
+
+        let fits: HashMap<_, _> = [("existing", any_rule()), ("bulk-0", any_rule()), ("bulk-1", any_rule())]
+            .into_iter()
+            .map(|(key, rule)| (key.to_string(), rule))
+            .collect();
+        throttler.bulk_set_rules(fits).await.unwrap();
+        assert_eq!(throttler.get_all_rules().await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_every_kind_of_rule_counts_toward_max_rules() {
+        let throttler = capped_throttler(3);
+        throttler.set_pattern_rule("tenant-*".to_string(), any_rule()).await.unwrap();
+        throttler.set_route_rule("POST /upload".to_string(), any_rule()).await.unwrap();
+        throttler.set_rule("key-0".to_string(), any_rule()).await.unwrap();
+
+        let bulk: HashMap<_, _> = [("key-1".to_string(), any_rule())].into_iter().collect();
+        assert!(throttler.bulk_set_rules(bulk).await.is_err());
+        assert!(throttler.set_rule("key-1".to_string(), any_rule()).await.is_err());
+        assert!(throttler.set_pattern_rule("other-*".to_string(), any_rule()).await.is_err());
+        assert!(throttler.set_route_rule("GET /reports".to_string(), any_rule()).await.is_err());
+        assert_eq!(throttler.get_all_rules().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_explain_exact_match() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+        throttler.set_pattern_rule("tenant-*".to_string(), any_rule()).await.unwrap();
+        throttler.set_rule("tenant-vip".to_string(), RateLimitRule::new(50, 100, std::time::Duration::from_secs(60))).await.unwrap();
+
+        let explained = throttler.explain("tenant-vip").await;
+        assert_eq!(explained.source, RuleSource::Exact);
+        assert_eq!(explained.matched.as_deref(), Some("tenant-vip"));
+        assert_eq!(explained.rule.unwrap().capacity, 100);
+    }
+
+    #[tokio::test]
+    async fn test_explain_prefix_match() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+        throttler.set_pattern_rule("tenant-*".to_string(), any_rule()).await.unwrap();
+        throttler.set_pattern_rule("tenant-acme-*".to_string(), RateLimitRule::new(1, 2, std::time::Duration::from_secs(60))).await.unwrap();
+
+        let explained = throttler.explain("tenant-acme-42").await;
+        assert_eq!(explained.source, RuleSource::Pattern);
+        assert_eq!(explained.explanation, "matched prefix pattern `tenant-acme-*`");
+        assert_eq!(explained.rule.unwrap().capacity, 2);
+
+        // The pattern is what should_throttle applies
+        assert!(!throttler.should_throttle("tenant-acme-42").await.unwrap());
+        assert!(!throttler.should_throttle("tenant-acme-42").await.unwrap());
+        assert!(throttler.should_throttle("tenant-acme-42").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_explain_default_fallback() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+        throttler.set_pattern_rule("tenant-*".to_string(), any_rule()).await.unwrap();
+
+        let explained = throttler.explain("other-key").await;
+        assert_eq!(explained.source, RuleSource::Default);
+        assert_eq!(explained.explanation, "no rule matched; fell back to default");
+        assert!(explained.matched.is_none());
+        assert_eq!(explained.rule.unwrap().capacity, Config::default().default_capacity);
+
+        let denying = Throttler::new(deny_unknown_config()).unwrap();
+        let explained = denying.explain("other-key").await;
+        assert_eq!(explained.source, RuleSource::Denied);
+        assert!(explained.rule.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_disable_and_enable_toggle_throttling() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+        let rule = RateLimitRule::new(1, 1, std::time::Duration::from_secs(60));
+        throttler.set_rule("trusted".to_string(), rule).await.unwrap();
+
+        assert!(!throttler.should_throttle("trusted").await.unwrap());
+        assert!(throttler.should_throttle("trusted").await.unwrap());
+
+        throttler.set_enabled("trusted", false).await.unwrap();
+        for _ in 0..5 {
+            assert!(!throttler.should_throttle("trusted").await.unwrap());
+        }
+
+        throttler.set_enabled("trusted", true).await.unwrap();
+        assert!(throttler.should_throttle("trusted").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_set_enabled_without_rule_is_not_found() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+
+        let err = throttler.set_enabled("missing", false).await.unwrap_err();
+        assert!(matches!(err, ThrottlerError::RuleNotFound(_)));
+    }
+
+    #[tokio::test]
+    async fn test_temporary_rule_applies_until_expiry() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+        let now = now_ms();
+        throttler.set_rule("live".to_string(), any_rule().with_expires_at(now + 60_000)).await.unwrap();
+        throttler.set_rule("lapsed".to_string(), any_rule().with_expires_at(now - 1)).await.unwrap();
+
+        let live = throttler.resolve_rule("live").await.unwrap();
+        assert_eq!(live.source, RuleSource::Exact);
+        assert_eq!(throttler.get_rule("live").await.unwrap().expires_at, Some(now + 60_000));
+
+        // An expired rule reverts the key to the defaults and is removed
+        assert!(throttler.resolve_rule("lapsed").await.is_none());
+        assert!(!throttler.rules.read().await.contains_key("lapsed"));
+        assert_eq!(throttler.explain("lapsed").await.source, RuleSource::Default);
+    }
+
+    #[tokio::test]
+    async fn test_expired_pattern_falls_back_to_shorter_match() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+        throttler.set_pattern_rule("tenant-*".to_string(), any_rule()).await.unwrap();
+        throttler.set_pattern_rule("tenant-acme-*".to_string(), any_rule().with_expires_at(now_ms() - 1))
+            .await
+            .unwrap();
+
+        let resolved = throttler.resolve_rule("tenant-acme-1").await.unwrap();
+        assert_eq!(resolved.matched, "tenant-*");
+    }
+
+    #[tokio::test]
+    async fn test_overlapping_patterns_resolve_deterministically() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+        throttler.set_pattern_rule("tenant-".to_string(), any_rule()).await.unwrap();
+        throttler.set_pattern_rule("tenant-*".to_string(), any_rule()).await.unwrap();
+        throttler.set_pattern_rule("tenant-acme*".to_string(), any_rule()).await.unwrap();
+
+        assert_eq!(throttler.resolve_rule("tenant-acme-1").await.unwrap().matched, "tenant-acme*");
+        assert_eq!(throttler.resolve_rule("tenant-globex").await.unwrap().matched, "tenant-");
+
+        throttler.remove_pattern_rule("tenant-").await;
+        assert_eq!(throttler.resolve_rule("tenant-globex").await.unwrap().matched, "tenant-*");
+    }
+
+    #[tokio::test]
+    async fn test_sweep_removes_only_expired_rules() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+        let now = now_ms();
+        throttler.set_rule("permanent".to_string(), any_rule()).await.unwrap();
+        throttler.set_rule("lapsed".to_string(), any_rule().with_expires_at(now - 1)).await.unwrap();
+        throttler.set_pattern_rule("old-*".to_string(), any_rule().with_expires_at(now - 1)).await.unwrap();
+
+        assert_eq!(throttler.sweep_expired_rules().await, 2);
+        assert_eq!(throttler.sweep_expired_rules().await, 0);
+        assert!(throttler.get_rule("permanent").await.is_some());
+        assert!(throttler.pattern_rules.read().await.is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_rule_updates_never_tear_capacity_and_refill() {
+        let throttler = Arc::new(Throttler::new(Config::default()).unwrap());
+        let small = RateLimitRule::new(5, 10, std::time::Duration::from_secs(60));
+        let large = RateLimitRule::new(50, 100, std::time::Duration::from_secs(60));
+        throttler.set_rule("contended".to_string(), small.clone()).await.unwrap();
+
+        let writer = {
+            let throttler = throttler.clone();
+            tokio::spawn(async move {
+                for i in 0..200 {
+                    let rule = if i % 2 == 0 { large.clone() } else { small.clone() };
+                    throttler.set_rule("contended".to_string(), rule).await.unwrap();
+                    tokio::task::yield_now().await;
+                }
+            })
+        };
+
+        let consumers: Vec<_> = (0..4).map(|_| {
+            let throttler = throttler.clone();
+            tokio::spawn(async move {
+                for _ in 0..200 {
+                    throttler.should_throttle("contended").await.unwrap();
+                    let state = throttler.rate_limiter.export_state().unwrap();
+                    let bucket = &state.buckets["contended"];
+                    let pair = (bucket.capacity, bucket.refill_rate);
+                    assert!(pair == (10, 5.0) || pair == (100, 50.0), "torn bucket: {:?}", pair);
+                    assert!(bucket.tokens <= bucket.capacity as f64);
+                }
+            })
+        }).collect();
+
+        writer.await.unwrap();
+        for consumer in consumers {
+            consumer.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_serialized_rule_change_waits_for_in_flight_checks() {
+        let throttler = Arc::new(Throttler::new(Config {
+            rule_update_ordering: RuleUpdateOrdering::Serialized,
+            ..Config::default()
+        }).unwrap());
+
+        // Stand in for a check that is mid-consume
+        let in_flight = throttler.check_barrier().await;
+        assert!(in_flight.is_some());
+
+        let change = {
+            let throttler = throttler.clone();
+            tokio::spawn(async move { throttler.set_rule("key".to_string(), any_rule()).await })
+        };
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        assert!(!change.is_finished());
+        assert!(throttler.get_rule("key").await.is_none());
+
+        drop(in_flight);
+        change.await.unwrap().unwrap();
+        assert!(throttler.get_rule("key").await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_next_consume_ordering_takes_no_barrier() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+        assert!(throttler.check_barrier().await.is_none());
+        assert!(throttler.rule_change_barrier().await.is_none());
+    }
+
+    /// A [`MemoryStore`] whose bucket reads fail while it is marked down
+    struct OutageStore {
+        inner: crate::bucket_store::MemoryStore,
+        down: std::sync::atomic::AtomicBool,
+    }
+
+    impl crate::bucket_store::BucketStore for OutageStore {
+        fn get_token_bucket(&self, key: &str) -> ThrottlerResult<Option<TokenBucket>> {
+            if self.down.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err(ThrottlerError::RedisError("connection refused".to_string()));
+            }
+            self.inner.get_token_bucket(key)
+        }
+
+        fn try_set_token_bucket(&self, key: &str, bucket: &TokenBucket, ttl: usize) -> ThrottlerResult<bool> {
+            self.inner.try_set_token_bucket(key, bucket, ttl)
+        }
+
+        fn atomic_consume_tokens(&self, key: &str, tokens_to_consume: u32, rule: &RateLimitRule) -> ThrottlerResult<(bool, TokenBucket)> {
+            self.inner.atomic_consume_tokens(key, tokens_to_consume, rule)
+        }
+
+        fn transfer_tokens(
+            &self,
+            from: &str,
+            to: &str,
+            tokens: f64,
+            reserved: f64,
+            capacity: u64,
+            refill_rate: f64,
+        ) -> ThrottlerResult<Option<f64>> {
+            self.inner.transfer_tokens(from, to, tokens, reserved, capacity, refill_rate)
+        }
+
+        fn delete_token_bucket(&self, key: &str) -> ThrottlerResult<()> {
+            self.inner.delete_token_bucket(key)
+        }
+
+        fn ping(&self) -> ThrottlerResult<String> {
+            self.inner.ping()
+        }
+
+        fn scan_buckets(&self, prefix: &str, cursor: Option<&str>, count: usize) -> ThrottlerResult<crate::bucket_store::BucketPage> {
+            self.inner.scan_buckets(prefix, cursor, count)
+        }
+
+        fn acquire_slot(&self, key: &str, slot_id: &str, limit: u64, ttl_ms: u64) -> ThrottlerResult<Option<u64>> {
+            self.inner.acquire_slot(key, slot_id, limit, ttl_ms)
+        }
+
+        fn release_slot(&self, key: &str, slot_id: &str) -> ThrottlerResult<bool> {
+            self.inner.release_slot(key, slot_id)
+        }
+
+        fn consume_quota(&self, key: &str, cost: u64, limit: u64, reset_at_ms: u64) -> ThrottlerResult<(bool, u64)> {
+            self.inner.consume_quota(key, cost, limit, reset_at_ms)
+        }
+
+        fn quota_used(&self, key: &str, reset_at_ms: u64) -> ThrottlerResult<u64> {
+            self.inner.quota_used(key, reset_at_ms)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_refund_keeps_the_tokens_refundable() {
+        use std::sync::atomic::Ordering;
+
+        let config = Config {
+            consistency_mode: crate::config::ConsistencyMode::Strict,
+            refund_window_ms: 60_000,
+            ..Config::default()
+        };
+        let store = Arc::new(OutageStore {
+            inner: crate::bucket_store::MemoryStore::new(),
+            down: Default::default(),
+        });
+        let limiter = RateLimiter::with_store(config, store.clone()).unwrap();
+        let throttler = Throttler::with_rate_limiter(limiter).unwrap();
+        let refund_id = throttler.process_request("batch", 5).await.unwrap().refund_id.unwrap();
+
+        store.down.store(true, Ordering::SeqCst);
+        assert!(matches!(
+            throttler.refund("batch", &refund_id, 5).await,
+            Err(ThrottlerError::StoreUnavailable(_))
+        ));
+
+        // Once the store is back, all five are still there to refund
+        store.down.store(false, Ordering::SeqCst);
+        assert!(throttler.refund("batch", &refund_id, 5).await.is_ok());
+        assert!(throttler.refund("batch", &refund_id, 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_default_policy_allows_unknown_key() {
+        let throttler = Throttler::new(Config::default()).unwrap();
+        assert!(!throttler.should_throttle("stranger").await.unwrap());
+    }
+}
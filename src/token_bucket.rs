@@ -45,7 +45,10 @@
 //! ## Edge Case Handling
 //!
 //! The implementation handles several edge cases:
-//! - **Overflow prevention**: Elapsed time capped at 1 hour max
+//! - **Long idle periods**: Refill is always proportional to the time
+//!   elapsed, with the tokens added capped by capacity, so a slow bucket
+//!   idle long enough returns to full; the elapsed cap
+//!   (`Config::max_refill_elapsed_secs`) only guards the arithmetic
 //! - **NaN/Infinity protection**: Validates floating point arithmetic
 //! - **Precision**: Uses f64 for fractional token accumulation
 //! - **Time skew**: Saturating subtraction prevents underflow
@@ -54,6 +57,14 @@ use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use serde::{Deserialize, Serialize};
 use crate::error::ThrottlerError;
 
+/// Longest wait [`TokenBucket::time_until_tokens`] reports (24 hours)
+pub const MAX_WAIT_SECS: u64 = 86_400;
+
+/// Elapsed time [`TokenBucket::refill`] counts at most, unless the bucket
+/// needs longer to fill (1 hour); the default of
+/// `Config::max_refill_elapsed_secs`
+pub const DEFAULT_MAX_REFILL_ELAPSED_SECS: u64 = 3600;
+
 /// A token bucket for rate limiting with time-based refill.
 ///
 /// The token bucket algorithm allows controlled bursts while maintaining
@@ -187,28 +198,38 @@ impl TokenBucket {
     ///
     /// # Edge Cases Handled
     ///
-    /// - **Overflow**: Elapsed time capped at 1 hour
+    /// - **Long idle periods**: Tokens added are capped by capacity, not
+    ///   elapsed time (see [`Self::refill_within`])
     /// - **Precision**: Ignores durations < 1ms
     /// - **NaN/Infinity**: Validates arithmetic results
     pub fn refill(&mut self) -> Result<(), ThrottlerError> {
+        self.refill_within(DEFAULT_MAX_REFILL_ELAPSED_SECS)
+    }
+
+    /// Like [`Self::refill`], with the elapsed cap in seconds.
+    ///
+    /// Elapsed time is counted up to `max_elapsed_secs`, or up to the time
+    /// an empty bucket takes to fill if that is longer, so the cap bounds
+    /// the arithmetic (e.g. after a clock jump) without ever leaving a slow
+    /// bucket short: one idle long enough always returns to full, and never
+    /// sooner than its refill rate allows.
+    pub fn refill_within(&mut self, max_elapsed_secs: u64) -> Result<(), ThrottlerError> {
         let now = Self::now_ms();
         let elapsed_ms = now.saturating_sub(self.last_refill);
 
-        // Cap elapsed time to prevent overflow (1 hour max)
-        // This handles cases where system clock jumps or bucket is very stale
-        let safe_elapsed_ms = elapsed_ms.min(3600_000);
-        let seconds_elapsed = safe_elapsed_ms as f64 / 1000.0;
-
         // Avoid floating point precision issues with very small durations
-        if seconds_elapsed < 0.001 {
+        if elapsed_ms < 1 {
             return Ok(());
         }
 
-        let tokens_to_add = self.refill_rate * seconds_elapsed;
+        let capacity = self.capacity as f64;
+        let time_to_full = if self.refill_rate > 0.0 { capacity / self.refill_rate } else { 0.0 };
+        let seconds_elapsed = (elapsed_ms as f64 / 1000.0).min((max_elapsed_secs as f64).max(time_to_full));
+        let tokens_to_add = (self.refill_rate * seconds_elapsed).min(capacity);
 
         // Ensure we don't exceed capacity and handle potential NaN/infinity
         if tokens_to_add.is_finite() && tokens_to_add > 0.0 {
-            self.tokens = (self.tokens + tokens_to_add).min(self.capacity as f64);
+            self.tokens = (self.tokens + tokens_to_add).min(capacity);
         }
 
         self.last_refill = now;
@@ -254,6 +275,12 @@ impl TokenBucket {
     /// assert!(wait >= Duration::from_secs(1)); // At least 1 second
     /// ```
     pub fn time_until_tokens(&mut self, tokens: u64) -> Result<Duration, ThrottlerError> {
+        self.time_until_tokens_capped(tokens, Duration::from_secs(MAX_WAIT_SECS))
+    }
+
+    /// Like [`Self::time_until_tokens`], but never reports more than
+    /// `max_wait`. A bucket that never refills reports `max_wait` itself.
+    pub fn time_until_tokens_capped(&mut self, tokens: u64, max_wait: Duration) -> Result<Duration, ThrottlerError> {
         self.refill()?;
 
         let tokens_f64 = tokens as f64;
@@ -263,16 +290,15 @@ impl TokenBucket {
 
         let tokens_needed = tokens_f64 - self.tokens;
 
-        // Handle edge case where refill_rate is zero or very small
+        // A bucket that never refills would wait forever; report the cap
         if self.refill_rate <= 0.0 {
-            return Ok(Duration::from_secs(u64::MAX));
+            return Ok(max_wait);
         }
 
         let seconds_needed = tokens_needed / self.refill_rate;
 
-        // Cap the wait time to prevent overflow (24 hours max)
-        let max_wait_seconds = 86400.0;
-        let safe_seconds = seconds_needed.min(max_wait_seconds);
+        // Cap the wait time to prevent overflow
+        let safe_seconds = seconds_needed.min(max_wait.as_secs_f64());
 
         Ok(Duration::from_secs_f64(safe_seconds))
     }
@@ -303,6 +329,17 @@ impl TokenBucket {
         self.refill()?;
         Ok(1.0 - (self.tokens / self.capacity as f64))
     }
+
+    /// Whether the fields describe a usable bucket: finite, non-negative
+    /// tokens no greater than capacity, and a finite non-negative refill
+    /// rate. Stored buckets failing this are treated as corrupt.
+    pub fn is_consistent(&self) -> bool {
+        self.tokens.is_finite()
+            && self.tokens >= 0.0
+            && self.tokens <= self.capacity as f64
+            && self.refill_rate.is_finite()
+            && self.refill_rate >= 0.0
+    }
 }
 
 #[cfg(test)]
@@ -316,6 +353,22 @@ mod tests {
         assert_eq!(bucket.tokens, 100.0);
     }
 
+    #[test]
+    fn test_is_consistent() {
+        assert!(TokenBucket::new(100, 10.0).is_consistent());
+
+        let corrupt = [
+            TokenBucket { tokens: f64::NAN, ..TokenBucket::new(100, 10.0) },
+            TokenBucket { tokens: -1.0, ..TokenBucket::new(100, 10.0) },
+            TokenBucket { tokens: 101.0, ..TokenBucket::new(100, 10.0) },
+            TokenBucket::new(100, f64::INFINITY),
+            TokenBucket::new(100, -0.5),
+        ];
+        for bucket in corrupt {
+            assert!(!bucket.is_consistent(), "{:?}", bucket);
+        }
+    }
+
     #[test]
     fn test_consume_tokens() {
         let mut bucket = TokenBucket::new(100, 10.0);
@@ -330,6 +383,57 @@ mod tests {
         assert_eq!(bucket.tokens, 10.0);
     }
 
+    #[test]
+    fn test_zero_refill_wait_is_clamped_to_cap() {
+        let mut bucket = TokenBucket::new(10, 0.0);
+        bucket.tokens = 0.0;
+
+        let cap = Duration::from_secs(300);
+        assert_eq!(bucket.time_until_tokens_capped(1, cap).unwrap(), cap);
+        assert_eq!(bucket.time_until_tokens(1).unwrap(), Duration::from_secs(MAX_WAIT_SECS));
+    }
+
+    #[test]
+    fn test_short_wait_passes_through_unclamped() {
+        let mut bucket = TokenBucket::new(10, 2.0);
+        bucket.tokens = 0.0;
+
+        let wait = bucket.time_until_tokens_capped(4, Duration::from_secs(300)).unwrap();
+        assert!(wait > Duration::from_millis(1900) && wait <= Duration::from_secs(2));
+
+        let long = bucket.time_until_tokens_capped(10, Duration::from_secs(3)).unwrap();
+        assert_eq!(long, Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_slow_bucket_idle_past_cap_refills_proportionally_then_to_full() {
+        // 100 a day: an hour's idle is worth about 4 tokens, not a full bucket
+        let mut bucket = TokenBucket::new(100, 100.0 / 86_400.0);
+        bucket.tokens = 0.0;
+        bucket.last_refill = TokenBucket::now_ms() - 2 * 3_600_000;
+        bucket.refill_within(3600).unwrap();
+        assert!((bucket.tokens - 100.0 / 12.0).abs() < 0.01, "{}", bucket.tokens);
+
+        // Half a day past the one hour cap: half the bucket
+        bucket.tokens = 0.0;
+        bucket.last_refill = TokenBucket::now_ms() - 43_200_000;
+        bucket.refill_within(3600).unwrap();
+        assert!((bucket.tokens - 50.0).abs() < 0.01, "{}", bucket.tokens);
+
+        // Idle longer than it takes to fill: full, and no more
+        bucket.tokens = 0.0;
+        bucket.last_refill = TokenBucket::now_ms() - 3 * 86_400_000;
+        bucket.refill_within(3600).unwrap();
+        assert_eq!(bucket.tokens, 100.0);
+
+        // A bucket that never refills stays empty however long it idles
+        let mut bucket = TokenBucket::new(10, 0.0);
+        bucket.tokens = 0.0;
+        bucket.last_refill = TokenBucket::now_ms() - 3 * 86_400_000;
+        bucket.refill().unwrap();
+        assert_eq!(bucket.tokens, 0.0);
+    }
+
     #[test]
     fn test_reset() {
         let mut bucket = TokenBucket::new(100, 10.0);
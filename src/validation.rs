@@ -1,7 +1,17 @@
-use crate::error::{ThrottlerError, Result};
+use crate::config::{KeyCase, KeySlashes};
+use crate::error::{LimitConstraint, ThrottlerError, Result};
 use regex::Regex;
 use std::collections::HashMap;
 
+/// Longest accepted tenant id
+pub const MAX_TENANT_ID_LENGTH: usize = 64;
+
+/// Default deepest nesting of objects and arrays accepted in a JSON body
+pub const DEFAULT_MAX_JSON_DEPTH: usize = 8;
+
+/// Default most object fields (across all levels) accepted in a JSON body
+pub const DEFAULT_MAX_JSON_FIELDS: usize = 64;
+
 #[derive(Debug, Clone)]
 pub struct RequestValidator {
     key_pattern: Regex,
@@ -9,6 +19,15 @@ pub struct RequestValidator {
     max_requests_per_window: u64,
     min_window_ms: u64,
     max_window_ms: u64,
+    /// When non-empty, the only window sizes accepted
+    allowed_windows_ms: Vec<u64>,
+    max_json_depth: usize,
+    max_json_fields: usize,
+    /// Case keys are canonicalized to by [`RequestValidator::normalize_key`]
+    key_case: KeyCase,
+    /// Whether a `/` in a key is rejected or replaced by
+    /// [`RequestValidator::normalize_key`]
+    key_slashes: KeySlashes,
 }
 
 impl Default for RequestValidator {
@@ -19,6 +38,11 @@ impl Default for RequestValidator {
             max_requests_per_window: 10000,
             min_window_ms: 1000,     // 1 second minimum
             max_window_ms: 3600000,  // 1 hour maximum
+            allowed_windows_ms: Vec::new(),
+            max_json_depth: DEFAULT_MAX_JSON_DEPTH,
+            max_json_fields: DEFAULT_MAX_JSON_FIELDS,
+            key_case: KeyCase::Sensitive,
+            key_slashes: KeySlashes::Reject,
         }
     }
 }
@@ -28,6 +52,45 @@ impl RequestValidator {
         Self::default()
     }
 
+    /// Restricts windows to fixed tiers; an empty list keeps the range check
+    pub fn with_allowed_windows_ms(mut self, windows_ms: Vec<u64>) -> Self {
+        self.allowed_windows_ms = windows_ms;
+        self
+    }
+
+    /// Bounds the nesting depth and field count of JSON request bodies
+    pub fn with_json_limits(mut self, max_depth: usize, max_fields: usize) -> Self {
+        self.max_json_depth = max_depth;
+        self.max_json_fields = max_fields;
+        self
+    }
+
+    /// Canonicalizes key case before lookup
+    pub fn with_key_case(mut self, key_case: KeyCase) -> Self {
+        self.key_case = key_case;
+        self
+    }
+
+    /// Accepts keys containing `/`, replacing it, instead of rejecting them
+    pub fn with_key_slashes(mut self, key_slashes: KeySlashes) -> Self {
+        self.key_slashes = key_slashes;
+        self
+    }
+
+    /// The canonical form of a validated key, so case variants of one key
+    /// resolve to the same bucket and rule when case is not significant
+    pub fn normalize_key(&self, key: String) -> String {
+        let key = match self.key_slashes {
+            KeySlashes::Replace if key.contains('/') => key.replace('/', "_"),
+            _ => key,
+        };
+        match self.key_case {
+            KeyCase::Sensitive => key,
+            KeyCase::Lower => key.to_ascii_lowercase(),
+            KeyCase::Upper => key.to_ascii_uppercase(),
+        }
+    }
+
     pub fn validate_key(&self, key: &str) -> Result<()> {
         if key.is_empty() {
             return Err(ThrottlerError::InvalidKey("Key cannot be empty".to_string()));
@@ -39,6 +102,16 @@ impl RequestValidator {
             ));
         }
 
+        // Path keys arrive decoded, so `a%2Fb` is `a/b` by now
+        if key.contains('/') {
+            if self.key_slashes == KeySlashes::Reject {
+                return Err(ThrottlerError::InvalidKey(
+                    "Key cannot contain '/', including URL-encoded as %2F".to_string()
+                ));
+            }
+            return self.validate_key(&key.replace('/', "_"));
+        }
+
         if !self.key_pattern.is_match(key) {
             return Err(ThrottlerError::InvalidKey(
                 "Key contains invalid characters. Only alphanumeric, underscore, dot, and dash allowed".to_string()
@@ -48,29 +121,69 @@ impl RequestValidator {
         Ok(())
     }
 
-    pub fn validate_rate_limit(&self, requests: u64, window_ms: u64) -> Result<()> {
-        if requests == 0 {
+    /// Validates a tenant id: 1-64 characters of `[a-zA-Z0-9_-]`.
+    pub fn validate_tenant_id(&self, tenant: &str) -> Result<()> {
+        if tenant.is_empty() || tenant.len() > MAX_TENANT_ID_LENGTH {
             return Err(ThrottlerError::ValidationError(
-                "Requests per window must be greater than 0".to_string()
+                format!("Tenant id must be between 1 and {} characters", MAX_TENANT_ID_LENGTH)
             ));
         }
 
-        if requests > self.max_requests_per_window {
+        if !tenant.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-') {
             return Err(ThrottlerError::ValidationError(
-                format!("Requests per window exceeds maximum of {}", self.max_requests_per_window)
+                "Tenant id contains invalid characters. Only alphanumeric, underscore, and dash allowed".to_string()
             ));
         }
 
+        Ok(())
+    }
+
+    /// Checks a rule's request count and window against the bounds,
+    /// reporting the first broken one as a [`ThrottlerError::LimitOutOfRange`]
+    pub fn validate_rate_limit(&self, requests: u64, window_ms: u64) -> Result<()> {
+        let out_of_range = |constraint, message: String| {
+            Err(ThrottlerError::LimitOutOfRange { constraint, message })
+        };
+
+        if requests == 0 {
+            return out_of_range(
+                LimitConstraint::ZeroRequests,
+                "Requests per window must be greater than 0".to_string(),
+            );
+        }
+
+        if requests > self.max_requests_per_window {
+            return out_of_range(
+                LimitConstraint::MaxRequests,
+                format!("Requests per window exceeds maximum of {}", self.max_requests_per_window),
+            );
+        }
+
+        if !self.allowed_windows_ms.is_empty() {
+            if !self.allowed_windows_ms.contains(&window_ms) {
+                let allowed: Vec<String> = self.allowed_windows_ms.iter()
+                    .map(|window| window.to_string())
+                    .collect();
+                return out_of_range(
+                    LimitConstraint::WindowNotAllowed,
+                    format!("Window duration must be one of: {} (ms)", allowed.join(", ")),
+                );
+            }
+            return Ok(());
+        }
+
         if window_ms < self.min_window_ms {
-            return Err(ThrottlerError::ValidationError(
-                format!("Window duration must be at least {}ms", self.min_window_ms)
-            ));
+            return out_of_range(
+                LimitConstraint::MinWindow,
+                format!("Window duration must be at least {}ms", self.min_window_ms),
+            );
         }
 
         if window_ms > self.max_window_ms {
-            return Err(ThrottlerError::ValidationError(
-                format!("Window duration cannot exceed {}ms", self.max_window_ms)
-            ));
+            return out_of_range(
+                LimitConstraint::MaxWindow,
+                format!("Window duration cannot exceed {}ms", self.max_window_ms),
+            );
         }
 
         Ok(())
@@ -118,6 +231,54 @@ impl RequestValidator {
         
         Ok(())
     }
+
+    /// Rejects pathological JSON bodies before they are parsed.
+    ///
+    /// A single pass over the raw bytes tracks how deeply objects and arrays
+    /// nest and counts object fields, ignoring anything inside strings. The
+    /// body is not otherwise checked; malformed JSON is left to the parser.
+    pub fn validate_json_shape(&self, body: &[u8]) -> Result<()> {
+        let mut depth = 0usize;
+        let mut fields = 0usize;
+        let mut in_string = false;
+        let mut escaped = false;
+
+        for &byte in body {
+            if in_string {
+                match byte {
+                    _ if escaped => escaped = false,
+                    b'\\' => escaped = true,
+                    b'"' => in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+
+            match byte {
+                b'"' => in_string = true,
+                b'{' | b'[' => {
+                    depth += 1;
+                    if depth > self.max_json_depth {
+                        return Err(ThrottlerError::ValidationError(format!(
+                            "JSON body nests deeper than {} levels", self.max_json_depth
+                        )));
+                    }
+                }
+                b'}' | b']' => depth = depth.saturating_sub(1),
+                b':' => {
+                    fields += 1;
+                    if fields > self.max_json_fields {
+                        return Err(ThrottlerError::ValidationError(format!(
+                            "JSON body has more than {} fields", self.max_json_fields
+                        )));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -139,12 +300,50 @@ mod tests {
         assert!(validator.validate_key(&"a".repeat(300)).is_err());
     }
 
+    #[test]
+    fn test_slashes_rejected_or_replaced() {
+        let validator = RequestValidator::new();
+        let err = validator.validate_key("a/b").unwrap_err();
+        assert!(err.to_string().contains("%2F"), "{}", err);
+
+        let validator = RequestValidator::new().with_key_slashes(KeySlashes::Replace);
+        assert!(validator.validate_key("a/b").is_ok());
+        assert!(validator.validate_key("a/b c").is_err());
+        assert_eq!(validator.normalize_key("a/b".to_string()), "a_b");
+    }
+
+    #[test]
+    fn test_tenant_id_format() {
+        let validator = RequestValidator::new();
+        assert!(validator.validate_tenant_id("tenant-a").is_ok());
+        assert!(validator.validate_tenant_id("acme_42").is_ok());
+        assert!(validator.validate_tenant_id("").is_err());
+        assert!(validator.validate_tenant_id("a:b").is_err());
+        assert!(validator.validate_tenant_id("a.b").is_err());
+        assert!(validator.validate_tenant_id(&"t".repeat(65)).is_err());
+    }
+
     #[test]
     fn test_valid_rate_limit() {
         let validator = RequestValidator::new();
         assert!(validator.validate_rate_limit(100, 60000).is_ok());
     }
 
+    #[test]
+    fn test_allowed_window_tiers() {
+        let validator = RequestValidator::new()
+            .with_allowed_windows_ms(vec![60_000, 3_600_000, 86_400_000]);
+
+        assert!(validator.validate_rate_limit(100, 60_000).is_ok());
+        assert!(validator.validate_rate_limit(100, 86_400_000).is_ok());
+
+        let err = validator.validate_rate_limit(100, 30_000).unwrap_err();
+        assert!(err.to_string().contains("60000, 3600000, 86400000"));
+
+        // Request limits still apply
+        assert!(validator.validate_rate_limit(0, 60_000).is_err());
+    }
+
     #[test]
     fn test_invalid_rate_limit() {
         let validator = RequestValidator::new();
@@ -152,4 +351,66 @@ mod tests {
         assert!(validator.validate_rate_limit(100, 500).is_err());
         assert!(validator.validate_rate_limit(20000, 60000).is_err());
     }
+
+    #[test]
+    fn test_each_limit_violation_has_its_own_code() {
+        let constraint = |validator: &RequestValidator, requests, window_ms| {
+            match validator.validate_rate_limit(requests, window_ms) {
+                Err(ThrottlerError::LimitOutOfRange { constraint, .. }) => constraint,
+                other => panic!("expected LimitOutOfRange, got {:?}", other),
+            }
+        };
+
+        let validator = RequestValidator::new();
+        assert_eq!(constraint(&validator, 0, 60_000), LimitConstraint::ZeroRequests);
+        assert_eq!(constraint(&validator, 20_000, 60_000), LimitConstraint::MaxRequests);
+        assert_eq!(constraint(&validator, 100, 500), LimitConstraint::MinWindow);
+        assert_eq!(constraint(&validator, 100, 90_000_000), LimitConstraint::MaxWindow);
+
+        let tiered = RequestValidator::new().with_allowed_windows_ms(vec![60_000]);
+        assert_eq!(constraint(&tiered, 100, 30_000), LimitConstraint::WindowNotAllowed);
+
+        let codes = [
+            LimitConstraint::ZeroRequests,
+            LimitConstraint::MaxRequests,
+            LimitConstraint::MinWindow,
+            LimitConstraint::MaxWindow,
+            LimitConstraint::WindowNotAllowed,
+        ].map(|constraint| constraint.code());
+        let distinct: std::collections::HashSet<_> = codes.iter().collect();
+        assert_eq!(distinct.len(), codes.len());
+    }
+
+    #[test]
+    fn test_normalize_key_case() {
+        let key = || "User-123".to_string();
+        assert_eq!(RequestValidator::new().normalize_key(key()), "User-123");
+        assert_eq!(RequestValidator::new().with_key_case(KeyCase::Lower).normalize_key(key()), "user-123");
+        assert_eq!(RequestValidator::new().with_key_case(KeyCase::Upper).normalize_key(key()), "USER-123");
+    }
+
+    #[test]
+    fn test_json_shape_within_limits() {
+        let validator = RequestValidator::new().with_json_limits(3, 4);
+        let body = br#"{"requests": 10, "window_ms": 1000, "metadata": {"team": "a{[:"}}"#;
+        assert!(validator.validate_json_shape(body).is_ok());
+    }
+
+    #[test]
+    fn test_json_shape_rejects_deep_nesting() {
+        let validator = RequestValidator::new().with_json_limits(3, 100);
+        assert!(validator.validate_json_shape(br#"{"a": [[1]]}"#).is_ok());
+
+        let err = validator.validate_json_shape(br#"{"a": [[[1]]]}"#).unwrap_err();
+        assert!(err.to_string().contains("deeper than 3 levels"));
+    }
+
+    #[test]
+    fn test_json_shape_rejects_excess_fields() {
+        let validator = RequestValidator::new().with_json_limits(8, 2);
+        assert!(validator.validate_json_shape(br#"{"a": 1, "b": "x:\"y:"}"#).is_ok());
+
+        let err = validator.validate_json_shape(br#"{"a": 1, "b": {"c": 2}}"#).unwrap_err();
+        assert!(err.to_string().contains("more than 2 fields"));
+    }
 }
@@ -6,9 +6,10 @@ use axum::{
 use http_body_util::BodyExt;
 use tower::ServiceExt;
 use throttler::{
-    config::Config,
+    config::{CheckResponseMode, Config, ConsistencyMode, KeyCase, KeySlashes, ResponseHeaderPolicy, UnknownKeyPolicy},
+    rate_limit_config::{RateLimitRule, RateUnit},
     server::create_app,
-    token_bucket::TokenBucket,
+    token_bucket::{TokenBucket, MAX_WAIT_SECS},
 };
 
 /// Helper function to convert response body to bytes
@@ -24,7 +25,7 @@ async fn test_token_bucket_edge_cases() {
     assert!(bucket.try_consume(5).unwrap());
 
     let wait_time = bucket.time_until_tokens(10).unwrap();
-    assert_eq!(wait_time, Duration::from_secs(u64::MAX));
+    assert_eq!(wait_time, Duration::from_secs(MAX_WAIT_SECS));
 
     // Test very small time differences
     let mut bucket = TokenBucket::new(100, 10.0);
@@ -175,3 +176,1983 @@ async fn test_token_bucket_serialization() {
     assert_eq!(bucket.capacity, deserialized.capacity);
     assert_eq!(bucket.refill_rate, deserialized.refill_rate);
 }
+
+/// Helper to send a rate limit check for a key
+async fn check_key(app: &axum::Router, key: &str) -> axum::response::Response {
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/rate-limit/{}/check", key))
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"tokens": 1}"#))
+        .unwrap();
+
+    app.clone().oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_head_check_consumes_and_returns_headers_only() {
+    let app = create_app(Config {
+        default_capacity: 2,
+        default_refill_rate: 0.01,
+        ..Config::default()
+    }).unwrap();
+    let head = || {
+        Request::builder()
+            .method("HEAD")
+            .uri("/rate-limit/probed/check")
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(head()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["X-RateLimit-Limit"], "2");
+    assert_eq!(response.headers()["X-RateLimit-Remaining"], "1");
+    assert!(body_to_bytes(response.into_body()).await.is_empty());
+
+    // One token per HEAD: a POST takes the last, and the next HEAD is denied
+    assert_eq!(check_key(&app, "probed").await.status(), StatusCode::OK);
+    let response = app.clone().oneshot(head()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["X-RateLimit-Remaining"], "0");
+    assert!(response.headers().contains_key("Retry-After"));
+    assert!(body_to_bytes(response.into_body()).await.is_empty());
+}
+
+#[tokio::test]
+async fn test_invalid_tokens_are_described() {
+    let app = create_app(Config::default()).unwrap();
+
+    for (tokens, shown) in [("-1", "-1"), ("1.5", "1.5"), (r#""5""#, r#""5""#)] {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/rate-limit/bad-tokens/check")
+            .header("content-type", "application/json")
+            .body(Body::from(format!(r#"{{"tokens": {}}}"#, tokens)))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = body_to_bytes(response.into_body()).await;
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "validation_error");
+        let message = body["message"].as_str().unwrap();
+        assert!(message.contains("tokens: must be a non-negative integer"), "{}", message);
+        assert!(message.contains(&format!("got {}", shown)), "{}", message);
+    }
+
+    // Nothing was consumed by the rejected requests
+    let response = check_key(&app, "bad-tokens").await;
+    assert_eq!(response.headers()["X-RateLimit-Remaining"], "99");
+}
+
+#[tokio::test]
+async fn test_strict_consistency_returns_503_when_redis_down() {
+    let config = |consistency_mode| Config {
+        redis_url: "redis://127.0.0.1:1".to_string(),
+        consistency_mode,
+        ..Config::default()
+    };
+
+    let app = create_app(config(ConsistencyMode::Strict)).unwrap();
+    let response = check_key(&app, "strict").await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()["Retry-After"], "1");
+    let body = body_to_bytes(response.into_body()).await;
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["error"], "store_unavailable");
+
+    // Lenient (the default) answers from the local bucket instead
+    let app = create_app(config(ConsistencyMode::Lenient)).unwrap();
+    let response = check_key(&app, "lenient").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["X-RateLimit-Remaining"], "99");
+}
+
+#[tokio::test]
+async fn test_pathological_rule_bodies_rejected() {
+    let app = create_app(Config {
+        max_json_depth: 4,
+        max_json_fields: 20,
+        ..Config::default()
+    })
+    .unwrap();
+
+    let nested = format!(
+        r#"{{"requests": 100, "window_ms": 60000, "metadata": {}1{}}}"#,
+        "[".repeat(50),
+        "]".repeat(50)
+    );
+    let fields: Vec<String> = (0..100).map(|i| format!(r#""f{}": {}"#, i, i)).collect();
+    let wide = format!(r#"{{"requests": 100, "window_ms": 60000, {}}}"#, fields.join(", "));
+
+    for (body, reason) in [(nested, "deeper than 4 levels"), (wide, "more than 20 fields")] {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/rate-limit/pathological")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let body = body_to_bytes(response.into_body()).await;
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "validation_error");
+        assert!(body["message"].as_str().unwrap().contains(reason));
+    }
+
+    // A well-formed rule still fits
+    let request = Request::builder()
+        .method("POST")
+        .uri("/rate-limit/pathological")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"requests": 100, "window_ms": 60000, "metadata": {"team": "a"}}"#))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_server_timing_header() {
+    let app = create_app(Config {
+        emit_server_timing: true,
+        ..Config::default()
+    }).unwrap();
+
+    let response = check_key(&app, "timed").await;
+    let timing = response.headers()["Server-Timing"].to_str().unwrap().to_string();
+    let phases: Vec<&str> = timing.split(", ")
+        .map(|phase| phase.split(';').next().unwrap())
+        .collect();
+    assert_eq!(phases, vec!["validate", "store", "serialize"]);
+    assert!(timing.contains(r#"store;desc="local";dur="#));
+    for phase in timing.split(", ") {
+        let dur = phase.rsplit("dur=").next().unwrap();
+        assert!(dur.parse::<f64>().unwrap() >= 0.0, "bad duration in {}", timing);
+    }
+
+    // Off by default
+    let app = create_app(Config::default()).unwrap();
+    let response = check_key(&app, "timed").await;
+    assert!(!response.headers().contains_key("Server-Timing"));
+}
+
+#[tokio::test]
+async fn test_detailed_key_listing() {
+    let app = create_app(Config {
+        default_capacity: 10,
+        default_refill_rate: 0.0,
+        ..Config::default()
+    }).unwrap();
+    for (key, checks) in [("listed-a", 1), ("listed-b", 4), ("listed-c", 2)] {
+        for _ in 0..checks {
+            check_key(&app, key).await;
+        }
+    }
+
+    let list = |query: String| {
+        let app = app.clone();
+        async move {
+            let request = Request::builder()
+                .uri(format!("/admin/keys/detailed{}", query))
+                .body(Body::empty())
+                .unwrap();
+            let response = app.oneshot(request).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = body_to_bytes(response.into_body()).await;
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        }
+    };
+
+    let page = list("?limit=2".to_string()).await;
+    assert_eq!(page["keys"].as_array().unwrap().len(), 2);
+    assert_eq!(page["keys"][0]["key"], "listed-a");
+    assert_eq!(page["keys"][0]["remaining"], 9);
+    assert_eq!(page["keys"][0]["capacity"], 10);
+    assert!(page["keys"][0]["last_activity_ms"].as_u64().unwrap() > 0);
+    assert_eq!(page["keys"][1]["key"], "listed-b");
+    assert_eq!(page["keys"][1]["remaining"], 6);
+
+    let cursor = page["next_cursor"].as_str().unwrap();
+    let page = list(format!("?limit=2&cursor={}", cursor)).await;
+    assert_eq!(page["keys"].as_array().unwrap().len(), 1);
+    assert_eq!(page["keys"][0]["key"], "listed-c");
+    assert_eq!(page["keys"][0]["remaining"], 8);
+    assert!(page["next_cursor"].is_null());
+}
+
+#[tokio::test]
+async fn test_per_key_denial_returns_429() {
+    let config = Config {
+        default_capacity: 1,
+        default_refill_rate: 1.0,
+        ..Config::default()
+    };
+    let app = create_app(config).unwrap();
+
+    assert_eq!(check_key(&app, "client-a").await.status(), StatusCode::OK);
+
+    let response = check_key(&app, "client-a").await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["X-RateLimit-Scope"], "key");
+    assert!(response.headers().contains_key("Retry-After"));
+}
+
+#[tokio::test]
+async fn test_unknown_key_denied_on_check_when_policy_is_deny() {
+    let app = create_app(Config {
+        unknown_key_policy: UnknownKeyPolicy::Deny,
+        seed_rules: vec![("known".to_string(), RateLimitRule::new(10, 10, Duration::from_secs(60)))],
+        ..Config::default()
+    }).unwrap();
+
+    assert_eq!(check_key(&app, "known").await.status(), StatusCode::OK);
+    assert_eq!(check_key(&app, "unruled").await.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_global_denial_returns_503() {
+    let config = Config {
+        global_rate_limit: 1,
+        default_capacity: 7,
+        ..Config::default()
+    };
+    let app = create_app(config).unwrap();
+
+    assert_eq!(check_key(&app, "client-a").await.status(), StatusCode::OK);
+
+    // A different key still hits the shared global limit
+    let response = check_key(&app, "client-b").await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.headers()["X-RateLimit-Scope"], "global");
+    assert!(response.headers().contains_key("Retry-After"));
+
+    // The body still describes the key's own limit
+    let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    assert_eq!(body["allowed"], false);
+    assert_eq!(body["limit"], 7);
+}
+
+#[tokio::test]
+async fn test_metadata_labels_in_status_and_metrics() {
+    let app = create_app(Config::default()).unwrap();
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/rate-limit/acme-client")
+        .header("content-type", "application/json")
+        .body(Body::from(
+            r#"{"requests": 100, "window_ms": 60000, "metadata": {"tenant": "acme", "plan": "gold"}}"#,
+        ))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let request = Request::builder()
+        .uri("/rate-limit/acme-client")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = body_to_bytes(response.into_body()).await;
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["metadata"]["tenant"], "acme");
+    assert_eq!(body["metadata"]["plan"], "gold");
+
+    check_key(&app, "acme-client").await;
+
+    let request = Request::builder()
+        .uri("/metrics")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = String::from_utf8(body_to_bytes(response.into_body()).await).unwrap();
+    assert!(body.contains(
+        r#"throttler_requests_total{key="acme-client",plan="gold",tenant="acme",result="allowed"} 1"#
+    ));
+}
+
+#[tokio::test]
+async fn test_remaining_tokens_histogram_in_metrics() {
+    let config = Config {
+        default_capacity: 3,
+        default_refill_rate: 0.01,
+        remaining_histogram_buckets: vec![0.5, 1.5, 2.5],
+        ..Config::default()
+    };
+    let app = create_app(config).unwrap();
+
+    // Leaves 2, 1 and 0 tokens, then is denied with none left
+    for _ in 0..4 {
+        check_key(&app, "planner").await;
+    }
+
+    let request = Request::builder()
+        .uri("/metrics")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = String::from_utf8(body_to_bytes(response.into_body()).await).unwrap();
+    assert!(body.contains("throttler_remaining_tokens_bucket{le=\"0.5\"} 2\n"), "{}", body);
+    assert!(body.contains("throttler_remaining_tokens_bucket{le=\"1.5\"} 3\n"), "{}", body);
+    assert!(body.contains("throttler_remaining_tokens_bucket{le=\"2.5\"} 4\n"), "{}", body);
+    assert!(body.contains("throttler_remaining_tokens_count 4\n"), "{}", body);
+}
+
+#[tokio::test]
+async fn test_oversized_metadata_rejected() {
+    let app = create_app(Config::default()).unwrap();
+    let long_value = "x".repeat(1000);
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/rate-limit/acme-client")
+        .header("content-type", "application/json")
+        .body(Body::from(format!(
+            r#"{{"requests": 100, "window_ms": 60000, "metadata": {{"tenant": "{}"}}}}"#,
+            long_value
+        )))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+/// Drains a key, then advances the limiter's clock with `X-Test-Time` to
+/// observe refill without sleeping. Run with `--features testing`.
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_refill_via_test_time_header() {
+    let config = Config {
+        default_capacity: 1,
+        default_refill_rate: 1.0,
+        ..Config::default()
+    };
+    let app = create_app(config).unwrap();
+
+    assert_eq!(check_key(&app, "refill-key").await.status(), StatusCode::OK);
+    assert_eq!(check_key(&app, "refill-key").await.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/rate-limit/refill-key/check")
+        .header("content-type", "application/json")
+        .header("X-Test-Time", "2000")
+        .body(Body::from(r#"{"tokens": 1}"#))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+/// A rule set with `expires_in_secs` governs the key until it lapses, then
+/// the key reverts to the defaults. Run with `--features testing`.
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_temporary_rule_reverts_after_expiry() {
+    let app = create_app(Config::default()).unwrap();
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/rate-limit/promo-key")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"requests": 500, "window_ms": 60000, "expires_in_secs": 60}"#))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let explain = |offset_ms: &'static str| {
+        Request::builder()
+            .uri("/rate-limit/promo-key/explain")
+            .header("X-Test-Time", offset_ms)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(explain("0")).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    assert_eq!(body["source"], "exact");
+    assert_eq!(body["rule"]["capacity"], 500);
+
+    let response = app.clone().oneshot(explain("61000")).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    assert_eq!(body["source"], "default");
+}
+
+#[tokio::test]
+async fn test_zero_expiry_rejected() {
+    let app = create_app(Config::default()).unwrap();
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/rate-limit/promo-key")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"requests": 500, "window_ms": 60000, "expires_in_secs": 0}"#))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_limit_violations_carry_distinct_codes() {
+    let app = create_app(Config::default()).unwrap();
+
+    for (body, code) in [
+        (r#"{"requests": 0, "window_ms": 60000}"#, "requests_zero"),
+        (r#"{"requests": 20000, "window_ms": 60000}"#, "requests_above_max"),
+        (r#"{"requests": 10, "window_ms": 10}"#, "window_below_min"),
+        (r#"{"requests": 10, "window_ms": 90000000}"#, "window_above_max"),
+    ] {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/rate-limit/coded-key")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+        assert_eq!(body["error"], "validation_error");
+        assert_eq!(body["code"], code);
+    }
+}
+
+#[tokio::test]
+async fn test_case_variant_keys_share_bucket_when_lowercased() {
+    let config = |key_case| Config {
+        default_capacity: 1,
+        default_refill_rate: 0.01,
+        key_case,
+        ..Config::default()
+    };
+
+    let app = create_app(config(KeyCase::Lower)).unwrap();
+    assert_eq!(check_key(&app, "User123").await.status(), StatusCode::OK);
+    assert_eq!(check_key(&app, "user123").await.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let app = create_app(config(KeyCase::Sensitive)).unwrap();
+    assert_eq!(check_key(&app, "User123").await.status(), StatusCode::OK);
+    assert_eq!(check_key(&app, "user123").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_encoded_slash_in_key_reaches_check_handler() {
+    // Rejected by the check handler with a clear reason, not a 404
+    let app = create_app(Config::default()).unwrap();
+    let response = check_key(&app, "a%2Fb").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = String::from_utf8(body_to_bytes(response.into_body()).await).unwrap();
+    assert!(body.contains("%2F"), "{}", body);
+
+    // Or limited under the key with `_` in its place
+    let config = Config {
+        default_capacity: 1,
+        default_refill_rate: 0.01,
+        key_slashes: KeySlashes::Replace,
+        ..Config::default()
+    };
+    let app = create_app(config).unwrap();
+    assert_eq!(check_key(&app, "a%2Fb").await.status(), StatusCode::OK);
+    assert_eq!(check_key(&app, "a_b").await.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn test_denial_status_is_configurable() {
+    let config = |deny_status_code| Config {
+        default_capacity: 1,
+        default_refill_rate: 0.01,
+        deny_status_code,
+        ..Config::default()
+    };
+
+    for (code, status) in [(429, StatusCode::TOO_MANY_REQUESTS), (200, StatusCode::OK)] {
+        let app = create_app(config(code)).unwrap();
+        assert_eq!(check_key(&app, "denied-client").await.status(), StatusCode::OK);
+
+        let response = check_key(&app, "denied-client").await;
+        assert_eq!(response.status(), status);
+        assert_eq!(response.headers()["X-RateLimit-Limit"], "1");
+        assert_eq!(response.headers()["X-RateLimit-Remaining"], "0");
+        assert_eq!(response.headers()["X-RateLimit-Scope"], "key");
+        assert!(response.headers().contains_key("Retry-After"));
+
+        let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+        assert_eq!(body["allowed"], false);
+    }
+}
+
+#[tokio::test]
+async fn test_check_response_mode_trims_allowed_bodies() {
+    let config = |check_response_mode| Config {
+        default_capacity: 2,
+        default_refill_rate: 0.01,
+        check_response_mode,
+        ..Config::default()
+    };
+
+    for (mode, expected) in [
+        (CheckResponseMode::Full, r#"{"allowed":true,"remaining":1,"limit":2}"#),
+        (CheckResponseMode::Minimal, "{}"),
+        (CheckResponseMode::HeadersOnly, ""),
+    ] {
+        let app = create_app(config(mode)).unwrap();
+
+        let response = check_key(&app, "lean-client").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["X-RateLimit-Limit"], "2");
+        assert_eq!(response.headers()["X-RateLimit-Remaining"], "1");
+        assert_eq!(String::from_utf8(body_to_bytes(response.into_body()).await).unwrap(), expected, "{:?}", mode);
+
+        // Denials keep the full body whatever the mode
+        check_key(&app, "lean-client").await;
+        let response = check_key(&app, "lean-client").await;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+        assert_eq!(body["allowed"], false);
+    }
+}
+
+#[tokio::test]
+async fn test_debug_state_only_when_allowed() {
+    let debug_check = |app: &axum::Router| {
+        let request = Request::builder()
+            .method("POST")
+            .uri("/rate-limit/debug-client/check?debug=true")
+            .header("content-type", "application/json")
+            .body(Body::from(r#"{"tokens": 1}"#))
+            .unwrap();
+        app.clone().oneshot(request)
+    };
+    let config = |allow_debug| Config { default_capacity: 10, allow_debug, ..Config::default() };
+
+    let app = create_app(config(false)).unwrap();
+    let response = debug_check(&app).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    assert_eq!(body["allowed"], true);
+    assert!(body.get("_debug").is_none());
+
+    let app = create_app(config(true)).unwrap();
+    let response = debug_check(&app).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["X-RateLimit-Remaining"], "9");
+    let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    assert_eq!(body["remaining"], 9);
+
+    let debug = &body["_debug"];
+    assert_eq!(debug["tokens_before"], 10.0);
+    let tokens_after = debug["tokens_after"].as_f64().unwrap();
+    assert!((9.0..9.5).contains(&tokens_after), "{}", tokens_after);
+    assert!(debug["refilled"].is_number());
+    assert!(debug["elapsed_ms"].is_u64());
+
+    // The next check sees the bucket this one left behind
+    let response = debug_check(&app).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    assert!(body["_debug"]["tokens_before"].as_f64().unwrap() < 10.0);
+}
+
+#[tokio::test]
+async fn test_debug_state_is_sized_by_the_keys_rule() {
+    let app = create_app(Config { default_capacity: 10, allow_debug: true, ..Config::default() }).unwrap();
+    let request = Request::builder()
+        .method("POST")
+        .uri("/rate-limit/debug-client")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"requests": 3, "window_ms": 60000}"#))
+        .unwrap();
+    assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/rate-limit/debug-client/check?debug=true")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"tokens": 1}"#))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    assert_eq!(body["limit"], 3);
+    assert_eq!(body["_debug"]["tokens_before"], 3.0);
+    let tokens_after = body["_debug"]["tokens_after"].as_f64().unwrap();
+    assert!((2.0..2.5).contains(&tokens_after), "{}", tokens_after);
+}
+
+#[tokio::test]
+async fn test_seeded_rule_in_effect_without_api_call() {
+    let app = create_app(Config {
+        seed_rules: vec![(
+            "partner-key".to_string(),
+            RateLimitRule::new(500, 500, Duration::from_secs(60)).with_rate_unit(RateUnit::PerWindow),
+        )],
+        ..Config::default()
+    })
+    .unwrap();
+
+    let request = Request::builder()
+        .uri("/rate-limit/partner-key/explain")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    assert_eq!(body["source"], "exact");
+    assert_eq!(body["rule"]["capacity"], 500);
+}
+
+#[tokio::test]
+async fn test_invalid_seeded_rule_fails_fast() {
+    let result = create_app(Config {
+        seed_rules: vec![("partner-key".to_string(), RateLimitRule::new(0, 500, Duration::from_secs(60)))],
+        ..Config::default()
+    });
+
+    assert!(result.is_err());
+}
+
+#[tokio::test]
+async fn test_status_reports_algorithm() {
+    let app = create_app(Config::default()).unwrap();
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/rate-limit/ruled-key")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"requests": 50, "window_ms": 60000}"#))
+        .unwrap();
+    assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+
+    for key in ["ruled-key", "default-key"] {
+        let request = Request::builder()
+            .uri(format!("/rate-limit/{}", key))
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+        assert_eq!(body["algorithm"], "token_bucket");
+    }
+}
+
+/// A body that records whether it was ever read
+struct WatchedBody {
+    read: std::sync::Arc<std::sync::atomic::AtomicBool>,
+    data: Option<axum::body::Bytes>,
+}
+
+impl axum::body::HttpBody for WatchedBody {
+    type Data = axum::body::Bytes;
+    type Error = std::convert::Infallible;
+
+    fn poll_frame(
+        mut self: std::pin::Pin<&mut Self>,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<hyper::body::Frame<Self::Data>, Self::Error>>> {
+        self.read.store(true, std::sync::atomic::Ordering::SeqCst);
+        std::task::Poll::Ready(self.data.take().map(|data| Ok(hyper::body::Frame::data(data))))
+    }
+}
+
+#[tokio::test]
+async fn test_expect_continue_over_limit_rejected_before_body_is_read() {
+    let app = create_app(Config::default()).unwrap();
+
+    let send = |declared_length: usize, data: &'static str| {
+        let read = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let body = WatchedBody { read: read.clone(), data: Some(axum::body::Bytes::from_static(data.as_bytes())) };
+        let request = Request::builder()
+            .method("POST")
+            .uri("/rate-limit/upload-key")
+            .header("content-type", "application/json")
+            .header("content-length", declared_length)
+            .header("expect", "100-continue")
+            .body(Body::new(body))
+            .unwrap();
+        (app.clone().oneshot(request), read)
+    };
+
+    // hyper only sends `100 Continue` once the body is read, so an unread
+    // body means the client was never asked for it
+    let (response, read) = send(10 * 1024 * 1024, "{}");
+    assert_eq!(response.await.unwrap().status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert!(!read.load(std::sync::atomic::Ordering::SeqCst));
+
+    let data = r#"{"requests": 100, "window_ms": 60000}"#;
+    let (response, read) = send(data.len(), data);
+    assert_eq!(response.await.unwrap().status(), StatusCode::OK);
+    assert!(read.load(std::sync::atomic::Ordering::SeqCst));
+}
+
+async fn check_with_deadline(app: &axum::Router, key: &str, deadline: &str) -> axum::response::Response {
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/rate-limit/{}/check", key))
+        .header("content-type", "application/json")
+        .header("X-Request-Deadline", deadline)
+        .body(Body::from(r#"{"tokens": 1}"#))
+        .unwrap();
+
+    app.clone().oneshot(request).await.unwrap()
+}
+
+fn epoch_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[tokio::test]
+async fn test_expired_request_deadline_short_circuits() {
+    let app = create_app(Config {
+        default_capacity: 1,
+        default_refill_rate: 0.01,
+        honor_request_deadline: true,
+        ..Config::default()
+    })
+    .unwrap();
+
+    let response = check_with_deadline(&app, "late-client", &(epoch_ms() - 1000).to_string()).await;
+    assert_eq!(response.status(), StatusCode::GATEWAY_TIMEOUT);
+    let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    assert_eq!(body["error"], "deadline_exceeded");
+
+    // The abandoned request did not spend the only token
+    assert_eq!(check_key(&app, "late-client").await.status(), StatusCode::OK);
+
+    let response = check_with_deadline(&app, "late-client", "soon").await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_generous_request_deadline_proceeds() {
+    let config = |honor_request_deadline| Config {
+        default_capacity: 1,
+        default_refill_rate: 0.01,
+        honor_request_deadline,
+        ..Config::default()
+    };
+
+    let app = create_app(config(true)).unwrap();
+    let deadline = (epoch_ms() + 60_000).to_string();
+    assert_eq!(check_with_deadline(&app, "timely-client", &deadline).await.status(), StatusCode::OK);
+    assert_eq!(check_with_deadline(&app, "timely-client", &deadline).await.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // Without the setting the header is ignored
+    let app = create_app(config(false)).unwrap();
+    assert_eq!(check_with_deadline(&app, "timely-client", "1").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_get_with_body_rejected_only_when_strict() {
+    let get_with_body = |app: axum::Router, body: &'static str| async move {
+        let request = Request::builder()
+            .method("GET")
+            .uri("/rate-limit/body-client")
+            .body(Body::from(body))
+            .unwrap();
+        app.oneshot(request).await.unwrap().status()
+    };
+
+    let app = create_app(Config { strict_get_bodies: true, ..Config::default() }).unwrap();
+    assert_eq!(get_with_body(app.clone(), r#"{"tokens": 1}"#).await, StatusCode::BAD_REQUEST);
+    assert_eq!(get_with_body(app.clone(), "").await, StatusCode::OK);
+
+    let request = Request::builder()
+        .method("DELETE")
+        .uri("/rate-limit/body-client")
+        .body(Body::from("x"))
+        .unwrap();
+    assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::BAD_REQUEST);
+
+    // Bodies on other methods are unaffected
+    assert_eq!(check_key(&app, "body-client").await.status(), StatusCode::OK);
+
+    // By default the body is ignored
+    let app = create_app(Config::default()).unwrap();
+    assert_eq!(get_with_body(app, r#"{"tokens": 1}"#).await, StatusCode::OK);
+}
+
+fn lenient_app() -> axum::Router {
+    create_app(Config {
+        default_capacity: 5,
+        lenient_content_type: true,
+        ..Config::default()
+    })
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_bodyless_check_defaults_to_one_token_when_lenient() {
+    let app = lenient_app();
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/rate-limit/bare-client/check")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    assert_eq!(body["remaining"], 4);
+}
+
+#[tokio::test]
+async fn test_json_without_content_type_accepted_when_lenient() {
+    let app = lenient_app();
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/rate-limit/bare-client/check")
+        .body(Body::from(r#"{"tokens": 3}"#))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    assert_eq!(body["remaining"], 2);
+
+    // Bodies that are not JSON are still refused
+    let request = Request::builder()
+        .method("POST")
+        .uri("/rate-limit/bare-client/check")
+        .body(Body::from("tokens=3"))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+#[tokio::test]
+async fn test_missing_content_type_rejected_by_default() {
+    let app = create_app(Config::default()).unwrap();
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/rate-limit/bare-client/check")
+        .body(Body::from(r#"{"tokens": 1}"#))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+#[tokio::test]
+async fn test_multi_key_delete_resets_all() {
+    let config = Config {
+        default_capacity: 1,
+        default_refill_rate: 1.0,
+        ..Config::default()
+    };
+    let app = create_app(config).unwrap();
+    for key in ["batch-a", "batch-b"] {
+        check_key(&app, key).await;
+        assert_eq!(check_key(&app, key).await.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    let request = Request::builder()
+        .method("DELETE")
+        .uri("/rate-limit?keys=batch-a,batch-b")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = body_to_bytes(response.into_body()).await;
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["results"]["batch-a"], true);
+    assert_eq!(body["results"]["batch-b"], true);
+
+    assert_eq!(check_key(&app, "batch-a").await.status(), StatusCode::OK);
+    assert_eq!(check_key(&app, "batch-b").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_multi_key_delete_with_invalid_key_rejected() {
+    let config = Config {
+        default_capacity: 1,
+        default_refill_rate: 1.0,
+        ..Config::default()
+    };
+    let app = create_app(config).unwrap();
+    check_key(&app, "batch-a").await;
+
+    let request = Request::builder()
+        .method("DELETE")
+        .uri("/rate-limit?keys=batch-a,bad%20key")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // Nothing was reset
+    assert_eq!(check_key(&app, "batch-a").await.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn test_utilization_header_and_status() {
+    let app = create_app(Config {
+        default_capacity: 4,
+        default_refill_rate: 0.01,
+        emit_utilization: true,
+        ..Config::default()
+    }).unwrap();
+
+    let response = check_key(&app, "util").await;
+    assert_eq!(response.headers()["X-RateLimit-Utilization"], "0.25");
+    for _ in 0..3 {
+        check_key(&app, "util").await;
+    }
+
+    let request = Request::builder()
+        .uri("/rate-limit/util")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body = body_to_bytes(response.into_body()).await;
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(body["utilization"].as_f64().unwrap() > 0.99);
+
+    // Off by default
+    let app = create_app(Config::default()).unwrap();
+    let response = check_key(&app, "util").await;
+    assert!(!response.headers().contains_key("X-RateLimit-Utilization"));
+}
+
+#[tokio::test]
+async fn test_admin_stats_reports_buckets_and_memory() {
+    let app = create_app(Config::default()).unwrap();
+    for key in ["stats-a", "stats-b", "stats-c"] {
+        check_key(&app, key).await;
+    }
+    for _ in 0..4 {
+        check_key(&app, "stats-busy-key").await;
+    }
+
+    let request = Request::builder()
+        .uri("/admin/stats?top=2")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_to_bytes(response.into_body()).await;
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(body["local_buckets"], 4);
+    let per_bucket = body["bytes_per_bucket"].as_u64().unwrap();
+    let memory = body["estimated_memory_bytes"].as_u64().unwrap();
+    assert_eq!(memory, 4 * per_bucket + 3 * 7 + 14);
+
+    assert_eq!(body["largest_keys"].as_array().unwrap().len(), 2);
+    assert_eq!(body["largest_keys"][0]["key"], "stats-busy-key");
+    assert_eq!(body["most_active_keys"][0]["key"], "stats-busy-key");
+    assert_eq!(body["most_active_keys"][0]["total_requests"], 4);
+}
+
+#[tokio::test]
+async fn test_state_export_import_round_trip() {
+    let source = create_app(Config::default()).unwrap();
+    for _ in 0..5 {
+        check_key(&source, "migrating").await;
+    }
+
+    let request = Request::builder()
+        .uri("/admin/state")
+        .body(Body::empty())
+        .unwrap();
+    let response = source.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let snapshot = body_to_bytes(response.into_body()).await;
+
+    let target = create_app(Config::default()).unwrap();
+    let request = Request::builder()
+        .method("PUT")
+        .uri("/admin/state")
+        .header("content-type", "application/json")
+        .body(Body::from(snapshot))
+        .unwrap();
+    let response = target.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let request = Request::builder()
+        .uri("/rate-limit/migrating")
+        .body(Body::empty())
+        .unwrap();
+    let response = target.oneshot(request).await.unwrap();
+    let body = body_to_bytes(response.into_body()).await;
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let remaining = body["remaining"].as_u64().unwrap();
+    assert!((95..=96).contains(&remaining), "remaining was {}", remaining);
+}
+
+#[tokio::test]
+async fn test_retry_budget_header() {
+    let config = Config {
+        default_capacity: 1,
+        default_refill_rate: 2.0,
+        retry_budget: true,
+        ..Config::default()
+    };
+    let app = create_app(config).unwrap();
+    check_key(&app, "retrying").await;
+
+    // Refill is imminent: a few retries are still worthwhile
+    let response = check_key(&app, "retrying").await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["X-RateLimit-Retry-Budget"], "1");
+
+    // Sustained retrying exhausts the budget
+    let mut response = check_key(&app, "retrying").await;
+    for _ in 0..3 {
+        response = check_key(&app, "retrying").await;
+    }
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["X-RateLimit-Retry-Budget"], "0");
+}
+
+#[tokio::test]
+async fn test_retry_after_ms_is_finer_than_retry_after() {
+    let config = Config {
+        default_capacity: 1,
+        default_refill_rate: 4.0,
+        emit_retry_after_ms: true,
+        ..Config::default()
+    };
+    let app = create_app(config).unwrap();
+    assert!(!check_key(&app, "precise").await.headers().contains_key("X-RateLimit-Retry-After-Ms"));
+
+    // The next token is at most 250ms away, which Retry-After rounds up to 1s
+    let response = check_key(&app, "precise").await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(header_u64(&response, "Retry-After"), 1);
+    let retry_after_ms = header_u64(&response, "X-RateLimit-Retry-After-Ms");
+    assert!((1..=250).contains(&retry_after_ms), "waited {}ms", retry_after_ms);
+
+    // Off by default
+    let app = create_app(Config { default_capacity: 1, ..Config::default() }).unwrap();
+    check_key(&app, "precise").await;
+    assert!(!check_key(&app, "precise").await.headers().contains_key("X-RateLimit-Retry-After-Ms"));
+}
+
+#[tokio::test]
+async fn test_retry_budget_header_off_by_default() {
+    let config = Config {
+        default_capacity: 1,
+        ..Config::default()
+    };
+    let app = create_app(config).unwrap();
+    check_key(&app, "retrying").await;
+
+    let response = check_key(&app, "retrying").await;
+    assert!(!response.headers().contains_key("X-RateLimit-Retry-Budget"));
+}
+
+#[tokio::test]
+async fn test_response_header_denylist_applies_to_success_and_denial() {
+    let config = Config {
+        default_capacity: 1,
+        response_headers: ResponseHeaderPolicy::Deny(vec!["X-RateLimit-Remaining".to_string()]),
+        ..Config::default()
+    };
+    let app = create_app(config).unwrap();
+
+    let response = check_key(&app, "headers").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key("X-RateLimit-Remaining"));
+    assert!(response.headers().contains_key("X-RateLimit-Limit"));
+
+    let response = check_key(&app, "headers").await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(!response.headers().contains_key("X-RateLimit-Remaining"));
+    assert!(response.headers().contains_key("Retry-After"));
+}
+
+#[tokio::test]
+async fn test_response_header_allowlist() {
+    let config = Config {
+        default_capacity: 1,
+        response_headers: ResponseHeaderPolicy::Allow(vec!["Retry-After".to_string()]),
+        ..Config::default()
+    };
+    let app = create_app(config).unwrap();
+
+    let response = check_key(&app, "headers").await;
+    assert!(!response.headers().contains_key("X-RateLimit-Limit"));
+    assert!(!response.headers().contains_key("X-RateLimit-Remaining"));
+
+    let response = check_key(&app, "headers").await;
+    assert!(response.headers().contains_key("Retry-After"));
+    assert!(!response.headers().contains_key("X-RateLimit-Scope"));
+}
+
+#[tokio::test]
+async fn test_response_header_policy_covers_quota_headers() {
+    let config = Config {
+        response_headers: ResponseHeaderPolicy::Deny(vec!["X-Quota-Remaining".to_string(), "X-Quota-Reset".to_string()]),
+        ..Config::default()
+    };
+    let app = create_app(config).unwrap();
+    set_quota(&app, "quota-headers", 5).await;
+
+    let response = check_key(&app, "quota-headers").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(!response.headers().contains_key("X-Quota-Remaining"));
+    assert!(!response.headers().contains_key("X-Quota-Reset"));
+    assert!(response.headers().contains_key("X-RateLimit-Remaining"));
+}
+
+/// Helper to POST to an enable/disable endpoint
+async fn toggle(app: &axum::Router, key: &str, action: &str) -> StatusCode {
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/rate-limit/{}/{}", key, action))
+        .body(Body::empty())
+        .unwrap();
+    app.clone().oneshot(request).await.unwrap().status()
+}
+
+#[tokio::test]
+async fn test_disable_and_enable_endpoints() {
+    let config = Config {
+        default_capacity: 1,
+        default_refill_rate: 1.0,
+        ..Config::default()
+    };
+    let app = create_app(config).unwrap();
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/rate-limit/trusted")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"requests": 10, "window_ms": 60000}"#))
+        .unwrap();
+    app.clone().oneshot(request).await.unwrap();
+
+    // The rule's burst of 10, not the default capacity, applies
+    for _ in 0..10 {
+        assert_eq!(check_key(&app, "trusted").await.status(), StatusCode::OK);
+    }
+    assert_eq!(check_key(&app, "trusted").await.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    assert_eq!(toggle(&app, "trusted", "disable").await, StatusCode::OK);
+    assert_eq!(check_key(&app, "trusted").await.status(), StatusCode::OK);
+
+    assert_eq!(toggle(&app, "trusted", "enable").await, StatusCode::OK);
+    assert_eq!(check_key(&app, "trusted").await.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn test_first_check_uses_rule_burst() {
+    let app = create_app(Config { default_capacity: 100, default_refill_rate: 0.01, ..Config::default() }).unwrap();
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/rate-limit/new-partner")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"requests": 3, "window_ms": 60000}"#))
+        .unwrap();
+    assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+
+    // No bucket exists yet; the very first check is sized by the rule
+    let response = check_key(&app, "new-partner").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["X-RateLimit-Limit"], "3");
+    assert_eq!(response.headers()["X-RateLimit-Remaining"], "2");
+
+    check_key(&app, "new-partner").await;
+    check_key(&app, "new-partner").await;
+    assert_eq!(check_key(&app, "new-partner").await.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn test_toggle_without_rule_is_404() {
+    let app = create_app(Config::default()).unwrap();
+
+    assert_eq!(toggle(&app, "no-rule", "disable").await, StatusCode::NOT_FOUND);
+    assert_eq!(toggle(&app, "no-rule", "enable").await, StatusCode::NOT_FOUND);
+}
+
+/// Helper to POST an nginx-style limit_req check
+async fn nginx_check(app: &axum::Router, body: &str) -> axum::response::Response {
+    let request = Request::builder()
+        .method("POST")
+        .uri("/nginx/limit")
+        .header("content-type", "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    app.clone().oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_nginx_burst_allows_excess_requests() {
+    let app = create_app(Config::default()).unwrap();
+    let body = r#"{"zone": "api", "rate": "1r/m", "burst": 2, "key": "203.0.113.7"}"#;
+
+    // One request at the rate plus two burst requests pass immediately
+    for expected_remaining in [2, 1, 0] {
+        let response = nginx_check(&app, body).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers().get("X-RateLimit-Remaining").unwrap(),
+            &expected_remaining.to_string()
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_nginx_rejection_uses_limit_req_status() {
+    let app = create_app(Config::default()).unwrap();
+    let body = r#"{"zone": "login", "rate": "1r/m", "key": "203.0.113.7"}"#;
+
+    assert_eq!(nginx_check(&app, body).await.status(), StatusCode::OK);
+    assert_eq!(nginx_check(&app, body).await.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    let closing = r#"{"zone": "login", "rate": "1r/m", "key": "203.0.113.7", "limit_req_status": 444}"#;
+    assert_eq!(nginx_check(&app, closing).await.status(), StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn test_nginx_zones_do_not_share_buckets_with_checked_keys() {
+    let app = create_app(Config::default()).unwrap();
+    let body = r#"{"zone": "api", "rate": "1r/m", "key": "client"}"#;
+    assert_eq!(nginx_check(&app, body).await.status(), StatusCode::OK);
+    assert_eq!(nginx_check(&app, body).await.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+    // The old dotted bucket name is just another key
+    assert_eq!(header_u64(&check_key(&app, "nginx.api.client").await, "X-RateLimit-Remaining"), 99);
+}
+
+#[tokio::test]
+async fn test_nginx_rejects_unusable_zone_parameters() {
+    let app = create_app(Config::default()).unwrap();
+    let huge_burst = r#"{"zone": "api", "rate": "1r/s", "burst": 18446744073709551615, "key": "client"}"#;
+    assert_eq!(nginx_check(&app, huge_burst).await.status(), StatusCode::BAD_REQUEST);
+
+    // A mistyped field is a JSON 400 like any other body
+    let response = nginx_check(&app, r#"{"zone": "api", "rate": "1r/s", "burst": "five", "key": "client"}"#).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    assert!(body["message"].as_str().unwrap().contains("burst"));
+}
+
+/// Helper to GET the resolution trace for a key
+async fn explain(app: &axum::Router, key: &str) -> serde_json::Value {
+    let request = Request::builder()
+        .uri(format!("/rate-limit/{}/explain", key))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap()
+}
+
+#[tokio::test]
+async fn test_explain_exact_rule_and_default() {
+    let app = create_app(Config::default()).unwrap();
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/rate-limit/configured")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"requests": 30, "window_ms": 60000}"#))
+        .unwrap();
+    app.clone().oneshot(request).await.unwrap();
+
+    let exact = explain(&app, "configured").await;
+    assert_eq!(exact["source"], "exact");
+    assert_eq!(exact["matched"], "configured");
+    assert_eq!(exact["rule"]["capacity"], 30);
+
+    let fallback = explain(&app, "unconfigured").await;
+    assert_eq!(fallback["source"], "default");
+    assert_eq!(fallback["explanation"], "no rule matched; fell back to default");
+}
+
+/// Helper to POST to a check-then-commit endpoint and read `remaining`
+async fn post_remaining(app: &axum::Router, uri: &str) -> (StatusCode, u64) {
+    let request = Request::builder()
+        .method("POST")
+        .uri(uri)
+        .header("content-type", "application/json")
+        .body(Body::from("{}"))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    let body: serde_json::Value =
+        serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    (status, body["remaining"].as_u64().unwrap())
+}
+
+#[tokio::test]
+async fn test_dry_check_does_not_decrement() {
+    let app = create_app(Config { default_capacity: 5, ..Config::default() }).unwrap();
+
+    for _ in 0..3 {
+        let (status, remaining) = post_remaining(&app, "/rate-limit/gateway/check?dry=true").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(remaining, 4);
+    }
+
+    let (_, remaining) = post_remaining(&app, "/rate-limit/gateway/check").await;
+    assert_eq!(remaining, 4);
+}
+
+#[tokio::test]
+async fn test_commit_decrements() {
+    let app = create_app(Config { default_capacity: 5, default_refill_rate: 0.001, ..Config::default() }).unwrap();
+
+    assert_eq!(post_remaining(&app, "/rate-limit/gateway/check?dry=true").await.1, 4);
+    assert_eq!(post_remaining(&app, "/rate-limit/gateway/commit").await, (StatusCode::OK, 4));
+    assert_eq!(post_remaining(&app, "/rate-limit/gateway/check?dry=true").await.1, 3);
+}
+
+#[tokio::test]
+async fn test_dry_check_and_commit_follow_the_keys_rule() {
+    let app = create_app(Config {
+        seed_rules: vec![("ruled".to_string(), RateLimitRule::new(1, 10, Duration::from_secs(60)))],
+        ..Config::default()
+    }).unwrap();
+    let post = |uri: &'static str, body: &'static str| {
+        let app = app.clone();
+        async move {
+            let request = Request::builder()
+                .method("POST")
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body))
+                .unwrap();
+            app.oneshot(request).await.unwrap()
+        }
+    };
+
+    let response = post("/rate-limit/ruled/check?dry=true", r#"{"tokens": 4}"#).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["X-RateLimit-Limit"], "10");
+    assert_eq!(response.headers()["X-RateLimit-Remaining"], "6");
+
+    let response = post("/rate-limit/ruled/commit", r#"{"tokens": 4}"#).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["X-RateLimit-Limit"], "10");
+    let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    assert_eq!(body["limit"], 10);
+    assert_eq!(body["remaining"], 6);
+
+    // The commit left the bucket at the rule's size, not the defaults
+    let response = post("/rate-limit/ruled/check?dry=true", r#"{"tokens": 7}"#).await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["X-RateLimit-Limit"], "10");
+}
+
+#[tokio::test]
+async fn test_fractional_remaining_uses_configured_precision() {
+    let config = Config { remaining_precision: 2, ..Config::default() };
+    assert_eq!(config.format_remaining(4.5), "4.50");
+    assert_eq!(config.format_remaining(2.3), "2.30");
+    assert_eq!(config.format_remaining(0.129), "0.12");
+    assert_eq!(Config::default().format_remaining(4.5), "4");
+
+    let app = create_app(config).unwrap();
+
+    // Seed a bucket holding a fractional token count that does not refill
+    let state = r#"{"exported_at": 0, "buckets": {"fractional": {
+        "tokens": 5.5, "capacity": 10, "refill_rate": 0.0, "last_refill": 0}}}"#;
+    let request = Request::builder()
+        .method("PUT")
+        .uri("/admin/state")
+        .header("content-type", "application/json")
+        .body(Body::from(state))
+        .unwrap();
+    assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+
+    let response = check_key(&app, "fractional").await;
+    let header = response.headers().get("X-RateLimit-Remaining").unwrap().to_str().unwrap();
+    assert_eq!(header, "4.50");
+    assert_eq!(header.parse::<f64>().unwrap(), 4.5);
+
+    let body: serde_json::Value =
+        serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    assert_eq!(body["remaining"], 4);
+}
+
+async fn check_as_tenant(app: &axum::Router, tenant: Option<&str>, key: &str) -> StatusCode {
+    let mut request = Request::builder()
+        .method("POST")
+        .uri(format!("/rate-limit/{}/check", key))
+        .header("content-type", "application/json");
+    if let Some(tenant) = tenant {
+        request = request.header("X-Tenant-Id", tenant);
+    }
+    let request = request.body(Body::from("{}")).unwrap();
+    app.clone().oneshot(request).await.unwrap().status()
+}
+
+#[tokio::test]
+async fn test_tenants_have_isolated_buckets() {
+    let config = Config {
+        default_capacity: 1,
+        default_refill_rate: 0.001,
+        tenant_isolation: true,
+        ..Config::default()
+    };
+    let app = create_app(config).unwrap();
+
+    assert_eq!(check_as_tenant(&app, Some("tenant-a"), "foo").await, StatusCode::OK);
+    assert_eq!(check_as_tenant(&app, Some("tenant-a"), "foo").await, StatusCode::TOO_MANY_REQUESTS);
+
+    // Same key, different tenant: its own bucket
+    assert_eq!(check_as_tenant(&app, Some("tenant-b"), "foo").await, StatusCode::OK);
+
+    // Another tenant's space cannot be named directly
+    assert_eq!(check_as_tenant(&app, Some("tenant-b"), "tenant-a:foo").await, StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_tenant_header_is_required_and_validated() {
+    let app = create_app(Config { tenant_isolation: true, ..Config::default() }).unwrap();
+
+    assert_eq!(check_as_tenant(&app, None, "foo").await, StatusCode::BAD_REQUEST);
+    assert_eq!(check_as_tenant(&app, Some("bad:tenant"), "foo").await, StatusCode::BAD_REQUEST);
+    assert_eq!(check_as_tenant(&app, Some(""), "foo").await, StatusCode::BAD_REQUEST);
+
+    // Without isolation the header is ignored
+    let app = create_app(Config::default()).unwrap();
+    assert_eq!(check_as_tenant(&app, None, "foo").await, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_admin_endpoints_need_the_operator_token_under_tenant_isolation() {
+    let admin = |app: &axum::Router, method: &str, uri: &str, token: Option<&str>| {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .header("X-Tenant-Id", "tenant-a");
+        if let Some(token) = token {
+            request = request.header("X-Admin-Token", token);
+        }
+        let body = if method == "PUT" { r#"{"exported_at": 0, "buckets": {}}"# } else { "" };
+        let request = request.body(Body::from(body)).unwrap();
+        let app = app.clone();
+        async move { app.oneshot(request).await.unwrap().status() }
+    };
+    let endpoints = [
+        ("GET", "/admin/state"),
+        ("PUT", "/admin/state"),
+        ("GET", "/admin/stats"),
+        ("GET", "/admin/keys/detailed"),
+    ];
+
+    let app = create_app(Config {
+        tenant_isolation: true,
+        admin_token: "s3cret".to_string(),
+        ..Config::default()
+    }).unwrap();
+    for (method, uri) in endpoints {
+        assert_eq!(admin(&app, method, uri, None).await, StatusCode::FORBIDDEN, "{} {}", method, uri);
+        assert_eq!(admin(&app, method, uri, Some("guess")).await, StatusCode::FORBIDDEN, "{} {}", method, uri);
+        assert_eq!(admin(&app, method, uri, Some("s3cret")).await, StatusCode::OK, "{} {}", method, uri);
+    }
+
+    // No token configured: refused whatever is sent
+    let app = create_app(Config { tenant_isolation: true, ..Config::default() }).unwrap();
+    for (method, uri) in endpoints {
+        assert_eq!(admin(&app, method, uri, Some("")).await, StatusCode::FORBIDDEN, "{} {}", method, uri);
+    }
+
+    // Without isolation the endpoints stay open
+    let app = create_app(Config::default()).unwrap();
+    for (method, uri) in endpoints {
+        assert_eq!(admin(&app, method, uri, None).await, StatusCode::OK, "{} {}", method, uri);
+    }
+}
+
+async fn get_remaining(app: &axum::Router, key: &str) -> u64 {
+    let request = Request::builder()
+        .uri(format!("/rate-limit/{}", key))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let body: serde_json::Value =
+        serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    body["remaining"].as_u64().unwrap()
+}
+
+#[tokio::test]
+async fn test_get_and_check_agree_on_fresh_key() {
+    let app = create_app(Config {
+        default_capacity: 20,
+        default_refill_rate: 0.001,
+        ..Config::default()
+    }).unwrap();
+
+    // Reading a fresh key reports a full bucket without creating one
+    let before = get_remaining(&app, "fresh").await;
+    assert_eq!(before, 20);
+    assert_eq!(get_remaining(&app, "fresh").await, before);
+
+    let response = check_key(&app, "fresh").await;
+    let body: serde_json::Value =
+        serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    assert_eq!(body["remaining"].as_u64().unwrap(), before - 1);
+    assert_eq!(get_remaining(&app, "fresh").await, before - 1);
+}
+
+#[tokio::test]
+async fn test_window_must_match_allowed_tier() {
+    let app = create_app(Config {
+        allowed_windows_ms: vec![60_000, 3_600_000],
+        ..Config::default()
+    }).unwrap();
+
+    let set_rule = |window_ms: u64| {
+        Request::builder()
+            .method("POST")
+            .uri("/rate-limit/tiered")
+            .header("content-type", "application/json")
+            .body(Body::from(format!(r#"{{"requests": 10, "window_ms": {}}}"#, window_ms)))
+            .unwrap()
+    };
+
+    let response = app.clone().oneshot(set_rule(60_000)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = app.clone().oneshot(set_rule(90_000)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = String::from_utf8(body_to_bytes(response.into_body()).await).unwrap();
+    assert!(body.contains("60000, 3600000"), "{}", body);
+}
+
+async fn acquire_slot(app: &axum::Router, key: &str) -> (StatusCode, serde_json::Value) {
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/concurrency/{}/acquire", key))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    let status = response.status();
+    (status, serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap())
+}
+
+async fn release_slot(app: &axum::Router, key: &str, slot_id: &str) -> bool {
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/concurrency/{}/release", key))
+        .header("content-type", "application/json")
+        .body(Body::from(serde_json::json!({ "slot_id": slot_id }).to_string()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    body["released"].as_bool().unwrap()
+}
+
+fn concurrency_app(slot_ttl_ms: u64) -> axum::Router {
+    create_app(Config { concurrency_slot_ttl_ms: slot_ttl_ms, ..Config::default() }).unwrap()
+}
+
+async fn set_concurrency_limit(app: &axum::Router, key: &str, limit: u32) {
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/rate-limit/{}", key))
+        .header("content-type", "application/json")
+        .body(Body::from(format!(
+            r#"{{"requests": 100, "window_ms": 60000, "concurrency_limit": {}}}"#, limit
+        )))
+        .unwrap();
+    assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_concurrency_slots_acquire_to_limit_and_release() {
+    let app = concurrency_app(30_000);
+    set_concurrency_limit(&app, "worker", 2).await;
+
+    let (status, first) = acquire_slot(&app, "worker").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!((first["in_flight"].as_u64(), first["limit"].as_u64()), (Some(1), Some(2)));
+    let (status, _) = acquire_slot(&app, "worker").await;
+    assert_eq!(status, StatusCode::OK);
+
+    // At the limit further requests are turned away
+    let (status, body) = acquire_slot(&app, "worker").await;
+    assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(body["acquired"], false);
+    assert!(body.get("slot_id").is_none());
+
+    // Releasing frees a slot, once
+    let slot_id = first["slot_id"].as_str().unwrap();
+    assert!(release_slot(&app, "worker", slot_id).await);
+    assert!(!release_slot(&app, "worker", slot_id).await);
+    assert_eq!(acquire_slot(&app, "worker").await.0, StatusCode::OK);
+    assert_eq!(acquire_slot(&app, "worker").await.0, StatusCode::TOO_MANY_REQUESTS);
+
+    // Keys without a cap are unbounded
+    for _ in 0..5 {
+        assert_eq!(acquire_slot(&app, "uncapped").await.0, StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn test_leaked_concurrency_slots_lapse() {
+    let app = concurrency_app(100);
+    set_concurrency_limit(&app, "crashy", 1).await;
+
+    // A client that never releases only blocks the key until its slot lapses
+    assert_eq!(acquire_slot(&app, "crashy").await.0, StatusCode::OK);
+    assert_eq!(acquire_slot(&app, "crashy").await.0, StatusCode::TOO_MANY_REQUESTS);
+
+    tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+    assert_eq!(acquire_slot(&app, "crashy").await.0, StatusCode::OK);
+}
+
+async fn set_quota(app: &axum::Router, key: &str, quota: u64) {
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/rate-limit/{}", key))
+        .header("content-type", "application/json")
+        .body(Body::from(format!(
+            r#"{{"requests": 100, "window_ms": 60000, "quota": {}, "quota_period": "monthly"}}"#, quota
+        )))
+        .unwrap();
+    assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+}
+
+fn header_u64(response: &axum::response::Response, name: &str) -> u64 {
+    response.headers()[name].to_str().unwrap().parse().unwrap()
+}
+
+#[tokio::test]
+async fn test_rate_ok_but_quota_exhausted() {
+    let app = create_app(Config::default()).unwrap();
+    set_quota(&app, "monthly-plan", 2).await;
+
+    let response = check_key(&app, "monthly-plan").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header_u64(&response, "X-Quota-Remaining"), 1);
+    let reset = header_u64(&response, "X-Quota-Reset");
+    assert!(check_key(&app, "monthly-plan").await.status().is_success());
+
+    // Plenty left in the bucket, but the month's quota is spent
+    let response = check_key(&app, "monthly-plan").await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["X-RateLimit-Scope"], "quota");
+    assert_eq!(header_u64(&response, "X-Quota-Remaining"), 0);
+    assert_eq!(header_u64(&response, "X-Quota-Reset"), reset);
+    assert!(header_u64(&response, "X-RateLimit-Remaining") >= 97);
+
+    // Keys without a quota send no quota headers
+    let response = check_key(&app, "unmetered").await;
+    assert!(response.headers().get("X-Quota-Remaining").is_none());
+}
+
+/// Spends a monthly quota, then moves the limiter's clock past the start of
+/// the next month with `X-Test-Time`. Run with `--features testing`.
+#[cfg(feature = "testing")]
+#[tokio::test]
+async fn test_quota_resets_on_calendar_boundary() {
+    let app = create_app(Config::default()).unwrap();
+    set_quota(&app, "calendar-plan", 1).await;
+
+    let response = check_key(&app, "calendar-plan").await;
+    let reset_ms = header_u64(&response, "X-Quota-Reset") * 1000;
+    assert_eq!(check_key(&app, "calendar-plan").await.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    let check_at = |offset_ms: u64| {
+        Request::builder()
+            .method("POST")
+            .uri("/rate-limit/calendar-plan/check")
+            .header("content-type", "application/json")
+            .header("X-Test-Time", offset_ms.to_string())
+            .body(Body::from(r#"{"tokens": 1}"#))
+            .unwrap()
+    };
+    let now_ms = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64;
+
+    // Just before the boundary the quota is still spent
+    let before = reset_ms.saturating_sub(now_ms + 60_000);
+    let response = app.clone().oneshot(check_at(before)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // From the boundary on the new month starts afresh
+    let after = reset_ms - now_ms + 1000;
+    let response = app.clone().oneshot(check_at(after)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(header_u64(&response, "X-Quota-Remaining"), 0);
+    assert!(header_u64(&response, "X-Quota-Reset") * 1000 > reset_ms);
+}
+
+#[tokio::test]
+async fn test_status_reports_refill_converted_from_window() {
+    let app = create_app(Config::default()).unwrap();
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/rate-limit/per-minute")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"requests": 120, "window_ms": 60000}"#))
+        .unwrap();
+    assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+
+    let status = |key: &str| {
+        Request::builder().uri(format!("/rate-limit/{}", key)).body(Body::empty()).unwrap()
+    };
+
+    // 120 requests per minute refill at 2 tokens per second
+    let response = app.clone().oneshot(status("per-minute")).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    assert_eq!(body["refill_per_sec"], 2.0);
+
+    let response = app.clone().oneshot(status("unconfigured")).await.unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    assert_eq!(body["refill_per_sec"], Config::default().default_refill_rate);
+}
+
+async fn set_rule_if_match(app: &axum::Router, key: &str, requests: u64, if_match: Option<&str>) -> axum::response::Response {
+    let mut request = Request::builder()
+        .method("POST")
+        .uri(format!("/rate-limit/{}", key))
+        .header("content-type", "application/json");
+    if let Some(if_match) = if_match {
+        request = request.header("If-Match", if_match);
+    }
+    let body = format!(r#"{{"requests": {}, "window_ms": 60000}}"#, requests);
+    app.clone().oneshot(request.body(Body::from(body)).unwrap()).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_conditional_rule_update_rejects_stale_version() {
+    let app = create_app(Config::default()).unwrap();
+
+    let response = set_rule_if_match(&app, "shared-rule", 100, None).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers()["etag"].to_str().unwrap().to_string();
+    assert_eq!(etag, "\"1\"");
+
+    // The status read hands out the same version
+    let request = Request::builder().uri("/rate-limit/shared-rule").body(Body::empty()).unwrap();
+    assert_eq!(app.clone().oneshot(request).await.unwrap().headers()["etag"], etag.as_str());
+
+    // First writer with the current version wins...
+    let response = set_rule_if_match(&app, "shared-rule", 200, Some(&etag)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["etag"], "\"2\"");
+
+    // ...and a second writer still holding version 1 is refused
+    let response = set_rule_if_match(&app, "shared-rule", 300, Some(&etag)).await;
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    assert_eq!(body["error"], "version_conflict");
+    assert_eq!(body["current_version"], 2);
+
+    let response = set_rule_if_match(&app, "shared-rule", 300, Some("not-a-version")).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+async fn check_route(app: &axum::Router, key: &str, method: &str, path: &str) -> axum::response::Response {
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/rate-limit/{}/check", key))
+        .header("content-type", "application/json")
+        .body(Body::from(format!(r#"{{"tokens": 1, "method": "{}", "path": "{}"}}"#, method, path)))
+        .unwrap();
+
+    app.clone().oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_route_limit_throttles_aggregate_traffic() {
+    let app = create_app(Config::default()).unwrap();
+    let request = Request::builder()
+        .method("PUT")
+        .uri("/route-limit")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"route": "POST /upload", "requests": 3, "window_ms": 60000}"#))
+        .unwrap();
+    assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+
+    // Three different clients use up the route between them
+    for key in ["client-a", "client-b", "client-c"] {
+        assert_eq!(check_route(&app, key, "POST", "/upload").await.status(), StatusCode::OK);
+    }
+    let response = check_route(&app, "client-d", "POST", "/upload").await;
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(response.headers()["X-RateLimit-Scope"], "route");
+    assert_eq!(header_u64(&response, "X-RateLimit-Limit"), 3);
+    assert!(response.headers().contains_key("Retry-After"));
+
+    // The clients' own buckets are untouched elsewhere
+    assert_eq!(check_route(&app, "client-d", "GET", "/upload").await.status(), StatusCode::OK);
+    assert_eq!(header_u64(&check_key(&app, "client-a").await, "X-RateLimit-Remaining"), 98);
+
+    let request = Request::builder()
+        .method("DELETE")
+        .uri("/route-limit?route=POST%20/upload")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+    assert_eq!(check_route(&app, "client-d", "POST", "/upload").await.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_limit_source_names_each_denying_constraint() {
+    let source = |response: &axum::response::Response| {
+        response.headers()["X-RateLimit-Source"].to_str().unwrap().to_string()
+    };
+
+    let app = create_app(Config { default_capacity: 1, emit_limit_source: true, ..Config::default() }).unwrap();
+    assert!(!check_key(&app, "bursty").await.headers().contains_key("X-RateLimit-Source"));
+    assert_eq!(source(&check_key(&app, "bursty").await), "key");
+
+    let app = create_app(Config { global_rate_limit: 1, emit_limit_source: true, ..Config::default() }).unwrap();
+    check_key(&app, "client-a").await;
+    let response = check_key(&app, "client-b").await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(source(&response), "global");
+
+    let app = create_app(Config { emit_limit_source: true, ..Config::default() }).unwrap();
+    set_quota(&app, "monthly-plan", 1).await;
+    check_key(&app, "monthly-plan").await;
+    assert_eq!(source(&check_key(&app, "monthly-plan").await), "quota");
+
+    let request = Request::builder()
+        .method("PUT")
+        .uri("/route-limit")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"route": "POST /upload", "requests": 1, "window_ms": 60000}"#))
+        .unwrap();
+    assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+    check_route(&app, "client-a", "POST", "/upload").await;
+    assert_eq!(source(&check_route(&app, "client-b", "POST", "/upload").await), "route");
+
+    set_concurrency_limit(&app, "worker", 1).await;
+    assert_eq!(acquire_slot(&app, "worker").await.0, StatusCode::OK);
+    let request = Request::builder()
+        .method("POST")
+        .uri("/concurrency/worker/acquire")
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(source(&response), "concurrency");
+
+    // Off by default
+    let app = create_app(Config { default_capacity: 1, ..Config::default() }).unwrap();
+    check_key(&app, "bursty").await;
+    assert!(!check_key(&app, "bursty").await.headers().contains_key("X-RateLimit-Source"));
+}
+
+async fn refund(app: &axum::Router, key: &str, refund_id: &str, tokens: u64) -> axum::response::Response {
+    let request = Request::builder()
+        .method("POST")
+        .uri(format!("/rate-limit/{}/refund", key))
+        .header("content-type", "application/json")
+        .body(Body::from(serde_json::json!({ "refund_id": refund_id, "tokens": tokens }).to_string()))
+        .unwrap();
+    app.clone().oneshot(request).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_partial_refund_returns_tokens_but_never_more_than_consumed() {
+    let config = Config {
+        default_capacity: 10,
+        default_refill_rate: 0.01,
+        refund_window_ms: 60_000,
+        ..Config::default()
+    };
+    let app = create_app(config).unwrap();
+
+    let request = Request::builder()
+        .method("POST")
+        .uri("/rate-limit/batch-job/check")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"tokens": 6}"#))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(header_u64(&response, "X-RateLimit-Remaining"), 4);
+    let refund_id = response.headers()["X-RateLimit-Refund-Id"].to_str().unwrap().to_string();
+
+    // Two of the six units of work failed downstream
+    let response = refund(&app, "batch-job", &refund_id, 2).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+    assert_eq!((body["refunded"].as_u64(), body["remaining"].as_u64()), (Some(2), Some(6)));
+
+    // Only four of the check's tokens are left to refund, and only to its key
+    let response = refund(&app, "batch-job", &refund_id, 5).await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(refund(&app, "other-job", &refund_id, 1).await.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(refund(&app, "batch-job", "made-up", 1).await.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(header_u64(&check_key(&app, "batch-job").await, "X-RateLimit-Remaining"), 5);
+
+    // Off by default
+    let app = create_app(Config::default()).unwrap();
+    assert!(!check_key(&app, "batch-job").await.headers().contains_key("X-RateLimit-Refund-Id"));
+    assert_eq!(refund(&app, "batch-job", &refund_id, 1).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_pattern_limit_resolves_overlaps_in_registration_order() {
+    let app = create_app(Config::default()).unwrap();
+    let set_pattern = |body: &'static str| {
+        Request::builder()
+            .method("PUT")
+            .uri("/pattern-limit")
+            .header("content-type", "application/json")
+            .body(Body::from(body))
+            .unwrap()
+    };
+    let explain = || async {
+        let request = Request::builder()
+            .uri("/rate-limit/tenant-acme/explain")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body_to_bytes(response.into_body()).await).unwrap();
+        body
+    };
+
+    let response = app.clone().oneshot(set_pattern(r#"{"pattern": "tenant-", "requests": 5, "window_ms": 60000}"#)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = app.clone().oneshot(set_pattern(r#"{"pattern": "tenant-*", "requests": 7, "window_ms": 60000}"#)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = explain().await;
+    assert_eq!(body["source"], "pattern");
+    assert_eq!(body["matched"], "tenant-");
+    assert_eq!(body["rule"]["capacity"], 5);
+
+    let request = Request::builder()
+        .method("DELETE")
+        .uri("/pattern-limit?pattern=tenant-")
+        .body(Body::empty())
+        .unwrap();
+    assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::OK);
+    assert_eq!(explain().await["matched"], "tenant-*");
+
+    // Wildcards other than a single trailing `*` are rejected
+    let response = app.clone().oneshot(set_pattern(r#"{"pattern": "*", "requests": 5, "window_ms": 60000}"#)).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_route_limit_rejects_bad_routes() {
+    let app = create_app(Config::default()).unwrap();
+    let request = Request::builder()
+        .method("PUT")
+        .uri("/route-limit")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"route": "/upload", "requests": 3, "window_ms": 60000}"#))
+        .unwrap();
+    assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::BAD_REQUEST);
+
+    // A check naming a path needs the method too
+    let request = Request::builder()
+        .method("POST")
+        .uri("/rate-limit/client-a/check")
+        .header("content-type", "application/json")
+        .body(Body::from(r#"{"path": "/upload"}"#))
+        .unwrap();
+    assert_eq!(app.clone().oneshot(request).await.unwrap().status(), StatusCode::BAD_REQUEST);
+}